@@ -0,0 +1,281 @@
+//! Addresses of the shared public key of a key on the chains that guardians sign for.
+//!
+//! Only the key info is needed, so any guardian that took part in key generation can answer.
+use crate::command::{ JsonCommand, MsgContext };
+use crate::signing::ecdsa::orchestrate::derive_child_key;
+use crate::signing::schnorr_secp256k1::{ x_only, SigningKey, TaprootTweak };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Result };
+use bech32::{ ToBase32, Variant };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Point, Secp256k1 };
+use curv::BigInt;
+use ripemd160::Ripemd160;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use sha3::Keccak256;
+use shared::ecdsa::Sum;
+use shared::key_info::Key;
+
+const DEFAULT_BITCOIN_HRP: &str = "bc";
+const DEFAULT_COSMOS_HRP: &str = "cosmos";
+/// Generic Substrate prefix
+const DEFAULT_SS58_PREFIX: u16 = 42;
+const TRON_VERSION: u8 = 0x41;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum AddressCommand {
+    KeyAddresses {
+        key_id: String,
+        #[serde(default)]
+        options: AddressOptions,
+    },
+}
+
+impl JsonCommand for AddressCommand {
+    type Response = Vec<ChainAddress>;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        match self {
+            AddressCommand::KeyAddresses { key_id, options } => {
+                let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+                    anyhow!("Key info is not found - key_id: {}", &key_id)
+                )?;
+                match (&key_info.kind, &options.derivation_path) {
+                    (Key::ECDSA { .. }, Some(derivation_path)) => {
+                        let child = derive_child_key(&key_id, derivation_path)?;
+                        secp256k1_addresses(&child.public_key, &options)
+                    }
+                    (_, Some(_)) => bail!("Key derivation is only supported for ECDSA keys"),
+                    (kind, None) => derive_addresses(kind, &options),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AddressOptions {
+    /// Human readable part of Bitcoin addresses, `bc` unless set, `tb` for testnets
+    #[serde(default)]
+    pub bitcoin_hrp: Option<String>,
+    /// Human readable part of Cosmos SDK addresses, `cosmos` unless set
+    #[serde(default)]
+    pub cosmos_hrp: Option<String>,
+    /// SS58 network prefix of Substrate addresses, 42 unless set
+    #[serde(default)]
+    pub ss58_prefix: Option<u16>,
+    /// BIP32 path of a non-hardened child key such as m/0/17, only supported for ECDSA keys
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Chain {
+    /// EIP-55 checksummed address, valid on every EVM chain
+    Ethereum,
+    BitcoinP2wpkh,
+    /// BIP86 key path only output
+    BitcoinP2tr,
+    Cosmos,
+    Tron,
+    Solana,
+    Substrate,
+    /// Public key of a beacon chain validator, which is how the consensus layer refers to it
+    EthereumValidator,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ChainAddress {
+    pub chain: Chain,
+    pub address: String,
+}
+
+/// Addresses of every chain the public key of the key can be used on
+pub fn derive_addresses(key: &Key, options: &AddressOptions) -> Result<Vec<ChainAddress>> {
+    match key {
+        Key::ECDSA { y_sum, .. } => secp256k1_addresses(&point_from_sum(y_sum)?, options),
+        Key::EDDSA { y_sum } => {
+            let public_key = public_key_bytes(y_sum)?;
+            Ok(
+                vec![
+                    ChainAddress {
+                        chain: Chain::Solana,
+                        address: bs58::encode(public_key).into_string(),
+                    },
+                    ChainAddress {
+                        chain: Chain::Substrate,
+                        address: ss58_address(&public_key, ss58_prefix(options))?,
+                    }
+                ]
+            )
+        }
+        Key::Sr25519 { pk } => {
+            let public_key = public_key_bytes(pk)?;
+            Ok(
+                vec![ChainAddress {
+                    chain: Chain::Substrate,
+                    address: ss58_address(&public_key, ss58_prefix(options))?,
+                }]
+            )
+        }
+        Key::Bls12381 { pk } =>
+            Ok(
+                vec![ChainAddress {
+                    chain: Chain::EthereumValidator,
+                    address: format!("0x{}", pk),
+                }]
+            ),
+    }
+}
+
+fn secp256k1_addresses(
+    public_key: &Point<Secp256k1>,
+    options: &AddressOptions
+) -> Result<Vec<ChainAddress>> {
+    let bitcoin_hrp = options.bitcoin_hrp.as_deref().unwrap_or(DEFAULT_BITCOIN_HRP);
+    let cosmos_hrp = options.cosmos_hrp.as_deref().unwrap_or(DEFAULT_COSMOS_HRP);
+    let key_hash = hash160(&public_key.to_bytes(true));
+    let taproot_key = SigningKey::new(public_key, Some(&TaprootTweak::default()))?.public_key;
+
+    Ok(
+        vec![
+            ChainAddress {
+                chain: Chain::Ethereum,
+                address: ethereum_address(public_key),
+            },
+            ChainAddress {
+                chain: Chain::BitcoinP2wpkh,
+                address: segwit_address(bitcoin_hrp, 0, &key_hash)?,
+            },
+            ChainAddress {
+                chain: Chain::BitcoinP2tr,
+                address: segwit_address(bitcoin_hrp, 1, &x_only(&taproot_key))?,
+            },
+            ChainAddress {
+                chain: Chain::Cosmos,
+                address: bech32::encode(cosmos_hrp, key_hash.to_base32(), Variant::Bech32)?,
+            },
+            ChainAddress {
+                chain: Chain::Tron,
+                address: tron_address(public_key),
+            }
+        ]
+    )
+}
+
+pub(crate) fn point_from_sum(y_sum: &Sum) -> Result<Point<Secp256k1>> {
+    let x = BigInt::from_hex(&y_sum.x).map_err(|_| anyhow!("Invalid public key x coordinate"))?;
+    let y = BigInt::from_hex(&y_sum.y).map_err(|_| anyhow!("Invalid public key y coordinate"))?;
+    Ok(Point::<Secp256k1>::from_coords(&x, &y)?)
+}
+
+fn public_key_bytes(public_key: &str) -> Result<[u8; 32]> {
+    hex::decode(public_key)?
+        .try_into()
+        .map_err(|_| anyhow!("Public keys of EdDSA and Sr25519 keys are 32 bytes long"))
+}
+
+fn ss58_prefix(options: &AddressOptions) -> u16 {
+    options.ss58_prefix.unwrap_or(DEFAULT_SS58_PREFIX)
+}
+
+fn eth_address_bytes(public_key: &Point<Secp256k1>) -> [u8; 20] {
+    let hash = Keccak256::digest(&public_key.to_bytes(false)[1..]);
+    let mut address = [0; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// EIP-55 mixed case checksum encoding
+fn ethereum_address(public_key: &Point<Secp256k1>) -> String {
+    let address = hex::encode(eth_address_bytes(public_key));
+    let hash = Keccak256::digest(address.as_bytes());
+    let checksummed: String = address
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+fn tron_address(public_key: &Point<Secp256k1>) -> String {
+    let mut payload = vec![TRON_VERSION];
+    payload.extend_from_slice(&eth_address_bytes(public_key));
+    bs58::encode(payload).with_check().into_string()
+}
+
+/// BIP173 address for witness version 0 and BIP350 for later versions
+fn segwit_address(hrp: &str, version: u8, program: &[u8]) -> Result<String> {
+    let mut data = vec![bech32::u5::try_from_u8(version)?];
+    data.extend(program.to_base32());
+    let variant = if version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+    Ok(bech32::encode(hrp, data, variant)?)
+}
+
+fn hash160(data: &[u8]) -> Vec<u8> {
+    Ripemd160::digest(&Sha256::digest(data)).to_vec()
+}
+
+/// SS58 address with the one byte prefix encoding below 64 and the two byte one up to 16383
+fn ss58_address(public_key: &[u8; 32], prefix: u16) -> Result<String> {
+    let mut payload = match prefix {
+        0..=63 => vec![prefix as u8],
+        64..=16383 =>
+            vec![
+                (((prefix & 0b1111_1100) as u8) >> 2) | 0b0100_0000,
+                ((prefix >> 8) as u8) | (((prefix & 0b11) as u8) << 6)
+            ],
+        _ => bail!("SS58 prefix {} is out of range", prefix),
+    };
+    payload.extend_from_slice(public_key);
+    let mut hasher = blake2b_simd::Params::new().hash_length(64).to_state();
+    hasher.update(b"SS58PRE");
+    hasher.update(&payload);
+    let checksum = hasher.finalize();
+    payload.extend_from_slice(&checksum.as_bytes()[..2]);
+    Ok(bs58::encode(payload).into_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secp256k1_addresses_of_generator() {
+        let addresses = secp256k1_addresses(
+            &Point::generator().to_point(),
+            &AddressOptions::default()
+        ).unwrap();
+        let address = |chain| {
+            addresses
+                .iter()
+                .find(|address| address.chain == chain)
+                .map(|address| address.address.clone())
+                .unwrap()
+        };
+        assert_eq!(address(Chain::Ethereum), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+        assert_eq!(address(Chain::BitcoinP2wpkh), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        assert!(address(Chain::BitcoinP2tr).starts_with("bc1p"));
+        assert_eq!(address(Chain::Cosmos), "cosmos1w508d6qejxtdg4y5r3zarvary0c5xw7k6ah60c");
+        assert_eq!(address(Chain::Tron), "TMVQGm1qAQYVdetCeGRRkTWYYrLXuHK2HC");
+    }
+
+    #[test]
+    fn ss58_address_of_alice() {
+        let alice = public_key_bytes(
+            "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"
+        ).unwrap();
+        assert_eq!(
+            ss58_address(&alice, 42).unwrap(),
+            "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"
+        );
+        assert!(ss58_address(&alice, 16384).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Identity keys of users.
+//!
+//! A user registers an Ed25519 or P-256 public key for a key when it is generated, or on
+//! recovery. Once registered, its signatures authorize the requests of the user for that key, so
+//! guardians only need to hold public material instead of the shared access key.
+use crate::auth::e2e_decrypt;
+use crate::node::NodeIdentity;
+use crate::signing::request_auth::CanonicalEncoder;
+use crate::storage::fs::WriteOpts;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Result };
+use p256::ecdsa::signature::Verifier;
+use serde::{ Deserialize, Serialize };
+use std::convert::TryFrom;
+use tracing::{ error, info };
+
+const IDENTITY_KEY_METADATA: &str = "identity_key";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityKeyKind {
+    Ed25519,
+    P256,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ClientIdentityKey {
+    pub kind: IdentityKeyKind,
+    /// Base64 encoded, 32 bytes for Ed25519 and a SEC1 encoded point for P-256
+    pub public_key: String,
+}
+
+impl ClientIdentityKey {
+    /// Verify a base64 encoded signature over `message`: 64 bytes for Ed25519 and r || s of an
+    /// ECDSA signature over SHA-256 for P-256, as produced by WebCrypto
+    pub fn verify(&self, message: &[u8], signature: &str) -> Result<()> {
+        let public_key = base64::decode(&self.public_key)?;
+        let signature = base64::decode(signature)?;
+        match self.kind {
+            IdentityKeyKind::Ed25519 => {
+                let public_key = ed25519_dalek::PublicKey
+                    ::from_bytes(&public_key)
+                    .map_err(|_| anyhow!("Invalid Ed25519 identity key"))?;
+                let signature = ed25519_dalek::Signature
+                    ::try_from(&signature[..])
+                    .map_err(|_| anyhow!("Invalid Ed25519 signature"))?;
+                public_key
+                    .verify_strict(message, &signature)
+                    .map_err(|_| anyhow!("Identity signature verification failed"))
+            }
+            IdentityKeyKind::P256 => {
+                let public_key = p256::ecdsa::VerifyingKey
+                    ::from_sec1_bytes(&public_key)
+                    .map_err(|_| anyhow!("Invalid P-256 identity key"))?;
+                let signature = p256::ecdsa::Signature
+                    ::try_from(&signature[..])
+                    .map_err(|_| anyhow!("Invalid P-256 signature"))?;
+                public_key
+                    .verify(message, &signature)
+                    .map_err(|_| anyhow!("Identity signature verification failed"))
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let public_key = base64::decode(&self.public_key)?;
+        let valid = match self.kind {
+            IdentityKeyKind::Ed25519 => ed25519_dalek::PublicKey::from_bytes(&public_key).is_ok(),
+            IdentityKeyKind::P256 => {
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key).is_ok()
+            }
+        };
+        if !valid {
+            bail!("Invalid {:?} identity key", self.kind);
+        }
+        Ok(())
+    }
+
+    /// Identity key registered by the user for a key, if any
+    pub fn load(key_id: &str, email: &str) -> Option<Self> {
+        let saved = KeyMetadataStore::get(key_id, IDENTITY_KEY_METADATA, email).ok()?;
+        serde_json::from_str(&saved).ok()
+    }
+
+    /// Register the identity key of a user for the key being generated. Keys that already have
+    /// credentials are refused, so a keygen request can't take over the authorization of an
+    /// existing key.
+    pub fn register(&self, key_id: &str, email: &str) -> Result<()> {
+        self.validate()?;
+        if has_credentials(key_id, email) {
+            bail!("Key {} already has client credentials", key_id);
+        }
+        self.save(key_id, email, &WriteOpts::CreateNewOnly)
+    }
+
+    /// Replace the identity key of a user for a key once they have recovered their account.
+    /// A user in recovery has lost their identity key, so the recovery challenge they answered
+    /// authorizes the replacement. A signature of the current identity key over the
+    /// replacement is optional, but has to be valid when the client sends one.
+    pub fn replace(
+        &self,
+        key_id: &str,
+        email: &str,
+        recovery_challenge: &str,
+        authorization: Option<&str>
+    ) -> Result<()> {
+        self.validate()?;
+        let stored_challenge = KeyMetadataStore::get_user_level("challenge", email)?;
+        if recovery_challenge != stored_challenge {
+            bail!("Replacement is not authorized by the recovery challenge");
+        }
+        if let (Some(current), Some(signature)) = (Self::load(key_id, email), authorization) {
+            current.verify(&self.replacement_encoding(key_id, email), signature)?;
+        }
+        self.save(key_id, email, &WriteOpts::Modify)
+    }
+
+    /// Message the current identity key signs to authorize its replacement by this one
+    pub fn replacement_encoding(&self, key_id: &str, email: &str) -> Vec<u8> {
+        CanonicalEncoder::new("identity_key_replacement")
+            .str("key_id", key_id)
+            .str("email", email)
+            .str("kind", &serde_json::to_string(&self.kind).unwrap_or_default())
+            .str("public_key", &self.public_key)
+            .finish()
+    }
+
+    fn save(&self, key_id: &str, email: &str, write_opts: &WriteOpts) -> Result<()> {
+        KeyMetadataStore::save(
+            &serde_json::to_string(self)?,
+            key_id,
+            IDENTITY_KEY_METADATA,
+            email,
+            write_opts
+        )
+    }
+}
+
+/// Whether a key already has an identity key or was generated on this node. The access key is
+/// kept per user, so it doesn't tell whether the key exists.
+fn has_credentials(key_id: &str, email: &str) -> bool {
+    KeyMetadataStore::get(key_id, IDENTITY_KEY_METADATA, email).is_ok() ||
+        KeyInfoStore::get_key_info(key_id).is_ok()
+}
+
+/// Store how a user authorizes its requests once a key is generated: the identity key when the
+/// client registers one, the access key encrypted to this node otherwise
+pub fn save_client_credentials(
+    key_id: &str,
+    email: &str,
+    identity_key: Option<&ClientIdentityKey>,
+    client_e2e_public_key: &str,
+    encrypted_signing_key: &str
+) -> Result<()> {
+    match identity_key {
+        Some(identity_key) => {
+            identity_key.register(key_id, email)?;
+            info!("Registered client identity key for email: {}", email);
+        }
+        None => {
+            if has_credentials(key_id, email) {
+                bail!("Key {} already has client credentials", key_id);
+            }
+            let node = NodeIdentity::load()?;
+            let decrypted_signing_key = e2e_decrypt(
+                encrypted_signing_key,
+                &node.e2e_private_key,
+                client_e2e_public_key
+            ).map_err(|err| anyhow!("Failed to decrypt signing key: {}", err))?;
+            let node_signing_key = String::from_utf8(decrypted_signing_key)?;
+            if
+                let Err(e) = KeyMetadataStore::save(
+                    &node_signing_key,
+                    key_id,
+                    "access",
+                    email,
+                    &WriteOpts::Modify
+                )
+            {
+                error!("Failed to save access key file: {}", e);
+            }
+        }
+    }
+
+    if
+        let Err(e) = KeyMetadataStore::save_user_level(
+            client_e2e_public_key,
+            "e2e_key",
+            email,
+            &WriteOpts::Modify
+        )
+    {
+        error!("Failed to save client's e2e public key: {}", e);
+    } else {
+        info!("Saved client e2e public key for email: {}", email);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer as _;
+    use p256::ecdsa::signature::Signer as _;
+
+    #[test]
+    fn identity_signatures_verify_only_for_the_signed_message() {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&[7u8; 32]).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let keypair = ed25519_dalek::Keypair { secret, public };
+        let identity = ClientIdentityKey {
+            kind: IdentityKeyKind::Ed25519,
+            public_key: base64::encode(public.as_bytes()),
+        };
+        let signature = base64::encode(keypair.sign(b"request").to_bytes());
+        identity.verify(b"request", &signature).unwrap();
+        assert!(identity.verify(b"other request", &signature).is_err());
+
+        let signing_key = p256::ecdsa::SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let identity = ClientIdentityKey {
+            kind: IdentityKeyKind::P256,
+            public_key: base64::encode(signing_key.verifying_key().to_encoded_point(true)),
+        };
+        let signature: p256::ecdsa::Signature = signing_key.sign(b"request");
+        let signature = base64::encode(signature);
+        identity.verify(b"request", &signature).unwrap();
+        assert!(identity.verify(b"other request", &signature).is_err());
+    }
+}
@@ -1,10 +1,28 @@
+use crate::address::AddressCommand;
+use crate::decryption::DecryptCommand;
 use crate::eject::{ EjectKeysCommand, EjectSharesCommand };
 use crate::keygen::key_import::{ KeyImportCommand, KeyImportShareCommand };
 use crate::keygen::sr25519::KeyGenCommand as Sr25519KeyGenCommand;
 use crate::keygen::KeyGenCommand;
-use crate::recovery::{ GetPaillierKeysCommand, RecoveryCommand };
+use crate::recovery::migration::{ ConfirmKeyshareHandoverCommand, RetireKeyshareCommand };
+use crate::recovery::refresh_session::CompleteKeyShareRefreshCommand;
+use crate::recovery::reshare::{
+    CompleteReshareCommand,
+    ProvePaillierKeyCommand,
+    ReceiveResharePackagesCommand,
+    ShredKeyshareCommand,
+};
+use crate::recovery::{
+    GetPaillierKeysCommand,
+    KeyShareRefreshCommand,
+    MigrationCommand,
+    RecoveryCommand,
+    ReshareCommand,
+};
+use crate::signing::adaptor::ExtractAdaptorSecretCommand;
 use crate::signing::sr25519::KeySignCommand as Sr25519KeySignCommand;
-use crate::signing::SigningCommand;
+use crate::signing::policy::UpdateSigningPolicyCommand;
+use crate::signing::{ DerivePublicKeyCommand, PresignCommand, SigningCommand };
 use crate::storage::keyshare_index_info::{ get_all_keyshare_indices, KeyshareIndex };
 use crate::App;
 use anyhow::{ anyhow, bail, Result };
@@ -83,7 +101,14 @@ fn process_request<T>(request: T, ctx: MsgContext) -> Result<String> where T: As
             (match tagged_cmd {
                 TaggedCommandType::OrchestrateKeyGen(cmd) => cmd.execute(ctx),
                 TaggedCommandType::OrchestrateSigning(cmd) => cmd.execute(ctx),
+                TaggedCommandType::OrchestratePresign(cmd) => cmd.execute(ctx),
                 TaggedCommandType::OrchestrateRecovery(cmd) => cmd.execute(ctx),
+                TaggedCommandType::OrchestrateKeyShareRefresh(cmd) => cmd.execute(ctx),
+                TaggedCommandType::OrchestrateReshare(cmd) => cmd.execute(ctx),
+                TaggedCommandType::OrchestrateMigration(cmd) => cmd.execute(ctx),
+                TaggedCommandType::DerivePublicKey(cmd) => cmd.execute(ctx),
+                TaggedCommandType::OrchestrateDecrypt(cmd) => cmd.execute(ctx),
+                TaggedCommandType::ExtractAdaptorSecret(cmd) => cmd.execute(ctx),
             })?,
         Err(_e) =>
             (match serde_json::from_slice::<CommandType>(&command)? {
@@ -93,12 +118,21 @@ fn process_request<T>(request: T, ctx: MsgContext) -> Result<String> where T: As
                 CommandType::UpdatePaillierKeys(cmd) => cmd.execute(ctx),
                 CommandType::UpdateSinglePaillierKey(cmd) => cmd.execute(ctx),
                 CommandType::Parameterless(cmd) => cmd.execute(ctx),
+                CommandType::Addresses(cmd) => cmd.execute(ctx),
                 CommandType::EjectShares(cmd) => cmd.execute(ctx),
                 CommandType::EjectKeys(cmd) => cmd.execute(ctx),
                 CommandType::Sr25519KeyGen(cmd) => cmd.execute(ctx),
                 CommandType::Sr25519KeySign(cmd) => cmd.execute(ctx),
+                CommandType::UpdateSigningPolicy(cmd) => cmd.execute(ctx),
                 CommandType::UpdateKeyInfo(cmd) => cmd.execute(ctx),
                 CommandType::GetPaillierKeys(cmd) => cmd.execute(ctx),
+                CommandType::ReceiveResharePackages(cmd) => cmd.execute(ctx),
+                CommandType::CompleteReshare(cmd) => cmd.execute(ctx),
+                CommandType::ProvePaillierKey(cmd) => cmd.execute(ctx),
+                CommandType::CompleteKeyShareRefresh(cmd) => cmd.execute(ctx),
+                CommandType::RetireKeyshare(cmd) => cmd.execute(ctx),
+                CommandType::ConfirmKeyshareHandover(cmd) => cmd.execute(ctx),
+                CommandType::ShredKeyshare(cmd) => cmd.execute(ctx),
             })?,
     };
 
@@ -112,14 +146,23 @@ pub enum CommandType {
     KeyImportShare(KeyImportShareCommand),
     Sr25519KeyGen(Sr25519KeyGenCommand),
     Sr25519KeySign(Sr25519KeySignCommand),
+    UpdateSigningPolicy(UpdateSigningPolicyCommand),
     KeyshareRecovery(ReceiveRecoveryPackages),
     UpdatePaillierKeys(UpdatePaillierKeysCommand),
     UpdateSinglePaillierKey(UpdateSinglePaillierKeyCommand),
     Parameterless(ParameterlessCommand),
+    Addresses(AddressCommand),
     EjectShares(EjectSharesCommand),
     EjectKeys(EjectKeysCommand),
     UpdateKeyInfo(UpdateKeyInfoCommand),
     GetPaillierKeys(GetPaillierKeysCommand),
+    ReceiveResharePackages(ReceiveResharePackagesCommand),
+    CompleteReshare(CompleteReshareCommand),
+    ProvePaillierKey(ProvePaillierKeyCommand),
+    CompleteKeyShareRefresh(CompleteKeyShareRefreshCommand),
+    RetireKeyshare(RetireKeyshareCommand),
+    ConfirmKeyshareHandover(ConfirmKeyshareHandoverCommand),
+    ShredKeyshare(ShredKeyshareCommand),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -127,7 +170,14 @@ pub enum CommandType {
 pub enum TaggedCommandType {
     OrchestrateKeyGen(KeyGenCommand),
     OrchestrateSigning(SigningCommand),
+    OrchestratePresign(PresignCommand),
     OrchestrateRecovery(RecoveryCommand),
+    OrchestrateKeyShareRefresh(KeyShareRefreshCommand),
+    OrchestrateReshare(ReshareCommand),
+    OrchestrateMigration(MigrationCommand),
+    DerivePublicKey(DerivePublicKeyCommand),
+    OrchestrateDecrypt(DecryptCommand),
+    ExtractAdaptorSecret(ExtractAdaptorSecretCommand),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
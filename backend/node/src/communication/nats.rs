@@ -46,7 +46,7 @@ pub struct NatsBaseMessenger<R> {
     pub session: NatsBaseSession,
     nc: Connection,
     subs: RoundSubscriber,
-    rounds: PhantomData<*const R>,
+    rounds: PhantomData<fn() -> R>,
 }
 
 impl<R> NatsBaseMessenger<R> where R: AllRounds {
@@ -90,7 +90,7 @@ pub struct NatsPeerMessenger<R> {
     nc: Connection,
     subs: RoundSubscriber,
     session: NatsPeerSession,
-    rounds: PhantomData<*const R>,
+    rounds: PhantomData<fn() -> R>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
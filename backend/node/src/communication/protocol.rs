@@ -11,10 +11,16 @@ pub trait AllRounds {
 #[derive(macroDisplay)]
 pub enum Topic {
     KeyGenEdDSA,
+    KeyGenSr25519,
+    KeyGenBls12381,
     EphemeralKeyGenEdDSA,
     KeySignEdDSA,
     KeyShareRecovery,
+    KeyShareRefresh,
+    KeyShareReshare,
     KeySignSr25519,
+    KeySignSchnorrSecp256k1,
+    KeySignAdaptor,
 }
 
 pub struct KeyGenAllRounds;
@@ -37,6 +43,33 @@ pub enum KeyGenP2PRound {
     ShareSecret,
 }
 
+/// Round that follows the EdDSA key generation rounds when an Sr25519 key is created
+pub struct KeyGenSr25519AllRounds;
+
+impl AllRounds for KeyGenSr25519AllRounds {
+    type BroadcastRound = KeyGenSr25519BroadcastRound;
+    type P2PRound = KeySignP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyGenSr25519BroadcastRound {
+    PublicShare,
+}
+
+/// Joint Feldman key generation of BLS12-381 keys
+pub struct KeyGenBls12381AllRounds;
+
+impl AllRounds for KeyGenBls12381AllRounds {
+    type BroadcastRound = KeyGenBls12381BroadcastRound;
+    type P2PRound = KeyGenP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyGenBls12381BroadcastRound {
+    Commitments,
+    Result,
+}
+
 pub struct KeySignEdDSAAllRounds;
 
 impl AllRounds for KeySignEdDSAAllRounds {
@@ -71,6 +104,39 @@ pub enum KeyShareRegenP2PRound {
     ExchangePartShares,
 }
 
+pub struct KeyShareRefreshAllRounds;
+
+impl AllRounds for KeyShareRefreshAllRounds {
+    type BroadcastRound = KeyShareRefreshBroadcastRound;
+    type P2PRound = KeyShareRefreshP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyShareRefreshBroadcastRound {
+    ZeroShareCommitments,
+    ValidationResult,
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyShareRefreshP2PRound {
+    ExchangeZeroShares,
+}
+
+pub struct KeyShareReshareAllRounds;
+
+impl AllRounds for KeyShareReshareAllRounds {
+    type BroadcastRound = KeyShareReshareBroadcastRound;
+    type P2PRound = KeyShareReshareP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyShareReshareBroadcastRound {
+    DeliverResharePackage,
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeyShareReshareP2PRound {}
+
 #[derive(macroDisplay, EnumIter)]
 pub enum SrMusig25519BroadcastRound {
     Reveal,
@@ -85,3 +151,46 @@ impl AllRounds for KeySignSr25519AllRounds {
     type BroadcastRound = SrMusig25519BroadcastRound;
     type P2PRound = KeySignP2PRound;
 }
+
+pub struct KeySignSchnorrAllRounds;
+
+impl AllRounds for KeySignSchnorrAllRounds {
+    type BroadcastRound = KeySignSchnorrBroadcastRound;
+    type P2PRound = KeySignSchnorrP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeySignSchnorrBroadcastRound {
+    NonceCommitments,
+    SignatureShare,
+    Result,
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeySignSchnorrP2PRound {}
+
+/// FROST rounds of EdDSA signing, which run under the EdDSA signing topic
+pub struct KeySignFrostEdDSAAllRounds;
+
+impl AllRounds for KeySignFrostEdDSAAllRounds {
+    type BroadcastRound = KeySignSchnorrBroadcastRound;
+    type P2PRound = KeySignSchnorrP2PRound;
+}
+
+/// FROST rounds of adaptor pre-signatures on secp256k1 and Ed25519
+pub struct KeySignAdaptorAllRounds;
+
+impl AllRounds for KeySignAdaptorAllRounds {
+    type BroadcastRound = KeySignAdaptorBroadcastRound;
+    type P2PRound = KeySignAdaptorP2PRound;
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeySignAdaptorBroadcastRound {
+    NonceCommitments,
+    PreSignatureShare,
+    Result,
+}
+
+#[derive(macroDisplay, EnumIter)]
+pub enum KeySignAdaptorP2PRound {}
@@ -57,4 +57,12 @@ impl ConfigProvider for ConfigGridlock {
     fn get_gridlock_directory() -> PathBuf {
         PathBuf::from(get_storage_dir().to_string())
     }
+
+    fn allow_legacy_request_auth() -> bool {
+        matches!(std::env::var("ALLOW_LEGACY_REQUEST_AUTH").as_deref(), Ok("true"))
+    }
+
+    fn policy_admin_key() -> Option<String> {
+        std::env::var("POLICY_ADMIN_KEY").ok().filter(|key| !key.is_empty())
+    }
 }
@@ -60,4 +60,12 @@ impl ConfigProvider for ConfigMobile {
         let path = unsafe { STORAGE_PATH.clone().unwrap() };
         path
     }
+
+    fn allow_legacy_request_auth() -> bool {
+        false
+    }
+
+    fn policy_admin_key() -> Option<String> {
+        None
+    }
 }
@@ -7,6 +7,10 @@ pub trait ConfigProvider {
     fn get_key_storage_path(key_id: &str, index: usize) -> PathBuf;
     fn get_key_info_storage_path(key_id: &str) -> PathBuf;
     fn get_gridlock_directory() -> PathBuf;
+    /// Whether signing requests from clients that only MAC `timestamp + email` are accepted
+    fn allow_legacy_request_auth() -> bool;
+    /// Identity key of the administrator allowed to set signing policies, as `kind:base64`
+    fn policy_admin_key() -> Option<String>;
 }
 
 cfg_if! {
@@ -0,0 +1,396 @@
+//! Threshold ECDH decryption with the keyshares of a key.
+//!
+//! Senders encrypt to the public key `Y` of a key with an ephemeral key pair `(e, R = e * G)`,
+//! keying AES-GCM with the ECDH secret `e * Y` the same way as [crate::encryption]: the x
+//! coordinate for secp256k1 keys, the X25519 secret for EdDSA keys after converting `Y` to its
+//! Montgomery form. Each guardian returns `x_i * R` with a DLEQ proof that it used the same
+//! `x_i` as its public share `x_i * G`, encrypted to the e2e key of the requesting client. The
+//! orchestrator only relays the encrypted shares, the client opens them, checks the proofs,
+//! checks that the public shares interpolate to `Y` and interpolates `x * R`, so neither the key
+//! nor the shared secret is ever seen by anyone else.
+pub mod orchestrate;
+pub mod session;
+
+use crate::address::point_from_sum;
+use crate::auth::{ e2e_decrypt, e2e_encrypt };
+use crate::command::{ JsonCommand, MsgContext };
+use crate::decryption::session::NewDecryptMessage;
+use crate::encryption::{ aes_decrypt, AES_KEY_BYTES_LEN };
+use crate::recovery::calculator::RecoveryCalculator;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::proofs::sigma_ec_ddh::{
+    ECDDHProof,
+    ECDDHStatement,
+    ECDDHWitness,
+};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use serde::{ Deserialize, Serialize };
+use sha2::Sha256;
+use shared::key_info::{ Key, KeyInfo, NodeId };
+use shared::recovery::EncryptedData;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DecryptCommand {
+    pub key_id: String,
+    pub session_id: String,
+    pub party_nodes: Vec<NodeId>,
+    /// Hex encoded ephemeral public key of the sender, SEC1 for secp256k1 keys and an X25519
+    /// public key for EdDSA keys
+    pub ephemeral_public_key: String,
+    /// Request of the client for every party node, in the order of `party_nodes`
+    pub requests: Vec<NewDecryptMessage>,
+}
+
+impl JsonCommand for DecryptCommand {
+    type Response = DecryptResponse;
+
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        orchestrate::orchestrate(self, ctx)
+    }
+}
+
+/// Encrypted decryption shares of the guardians, which only the client can combine with
+/// [decrypt_with_shares]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DecryptResponse {
+    pub shares: Vec<EncryptedDecryptionShare>,
+}
+
+/// Decryption share of a guardian, sealed to the e2e key of the requesting client
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EncryptedDecryptionShare {
+    pub party_index: usize,
+    /// e2e public key of the guardian, base64 encoded
+    pub node_e2e_public_key: String,
+    pub encrypted_share: String,
+}
+
+impl EncryptedDecryptionShare {
+    pub fn seal<E: Curve>(
+        share: &DecryptionShare<E>,
+        client_e2e_public_key: &str,
+        node_e2e_public_key: &str,
+        node_e2e_private_key: &str
+    ) -> Result<Self> {
+        Ok(Self {
+            party_index: share.party_index,
+            node_e2e_public_key: node_e2e_public_key.to_string(),
+            encrypted_share: e2e_encrypt(
+                &serde_json::to_vec(share)?,
+                client_e2e_public_key,
+                node_e2e_private_key
+            )?,
+        })
+    }
+
+    pub fn open<E: Curve>(&self, client_e2e_private_key: &str) -> Result<DecryptionShare<E>> {
+        let share = e2e_decrypt(
+            &self.encrypted_share,
+            client_e2e_private_key,
+            &self.node_e2e_public_key
+        )?;
+        let share = serde_json::from_slice::<DecryptionShare<E>>(&share)?;
+        if share.party_index != self.party_index {
+            bail!("Decryption share of party {} is sealed as another party", share.party_index);
+        }
+        Ok(share)
+    }
+}
+
+/// Contribution of one guardian to a decryption
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct DecryptionShare<E: Curve> {
+    pub party_index: usize,
+    /// `x_i * G`, computed from the VSS commitments of the keyshare
+    pub public_share: Point<E>,
+    /// `x_i * R`
+    pub share: Point<E>,
+    pub proof: ECDDHProof<E, Sha256>,
+}
+
+impl<E: Curve> DecryptionShare<E> {
+    pub fn new(
+        x_i: &Scalar<E>,
+        party_index: usize,
+        vss_scheme_vec: &[VerifiableSS<E>],
+        ephemeral: &Point<E>
+    ) -> Self {
+        let public_share = vss_scheme_vec
+            .iter()
+            .fold(Point::zero(), |acc, vss| acc + vss.get_point_commitment(party_index as u16));
+        let share = ephemeral * x_i;
+        let statement = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: public_share.clone(),
+            g2: ephemeral.clone(),
+            h2: share.clone(),
+        };
+        let proof = ECDDHProof::prove(&(ECDDHWitness { x: x_i.clone() }), &statement);
+        Self {
+            party_index,
+            public_share,
+            share,
+            proof,
+        }
+    }
+
+    fn verify(&self, ephemeral: &Point<E>) -> Result<()> {
+        let statement = ECDDHStatement {
+            g1: Point::generator().to_point(),
+            h1: self.public_share.clone(),
+            g2: ephemeral.clone(),
+            h2: self.share.clone(),
+        };
+        self.proof.verify(&statement).map_err(|_| {
+            anyhow!("Decryption share of party {} has an invalid proof", self.party_index)
+        })
+    }
+}
+
+/// Verify the shares of more than `threshold` parties and interpolate `x * R` from them
+pub fn combine_shares<E: Curve>(
+    shares: &[DecryptionShare<E>],
+    public_key: &Point<E>,
+    ephemeral: &Point<E>,
+    threshold: usize
+) -> Result<Point<E>> {
+    if shares.len() <= threshold {
+        bail!("Decryption needs {} shares, but only {} were received", threshold + 1, shares.len());
+    }
+    let indices = shares
+        .iter()
+        .map(|share| share.party_index)
+        .collect::<Vec<_>>();
+
+    let mut public_key_sum = Point::<E>::zero();
+    let mut shared_secret = Point::<E>::zero();
+    for share in shares {
+        share.verify(ephemeral)?;
+        let lambda = RecoveryCalculator::<E>::lagrange_coefficient_at_zero(
+            share.party_index,
+            &indices
+        )?;
+        public_key_sum = public_key_sum + &share.public_share * &lambda;
+        shared_secret = shared_secret + &share.share * &lambda;
+    }
+    // Binds the proven public shares to the key, a party can't make up a share of its own
+    if &public_key_sum != public_key {
+        bail!("Public shares of the parties don't interpolate to the public key");
+    }
+    if shared_secret.is_zero() {
+        bail!("Ephemeral public key results in an invalid shared secret");
+    }
+    Ok(shared_secret)
+}
+
+/// Client side of a decryption: opens the shares of the guardians with the e2e key of the
+/// client, verifies and combines them and decrypts the ciphertext
+pub fn decrypt_with_shares(
+    key_info: &KeyInfo,
+    ephemeral_public_key: &str,
+    ciphertext: &EncryptedData,
+    shares: &[EncryptedDecryptionShare],
+    client_e2e_private_key: &str
+) -> Result<Vec<u8>> {
+    let key = match &key_info.kind {
+        Key::ECDSA { y_sum, .. } => {
+            let ephemeral = secp256k1_ephemeral_key(ephemeral_public_key)?;
+            let shares = shares
+                .iter()
+                .map(|share| share.open(client_e2e_private_key))
+                .collect::<Result<Vec<_>>>()?;
+            let public_key = point_from_sum(y_sum)?;
+            let shared_secret = combine_shares(
+                &shares,
+                &public_key,
+                &ephemeral,
+                key_info.threshold
+            )?;
+            secp256k1_aes_key(&shared_secret)?
+        }
+        Key::EDDSA { y_sum } => {
+            let ephemeral = x25519_ephemeral_key(ephemeral_public_key)?;
+            let shares = shares
+                .iter()
+                .map(|share| share.open(client_e2e_private_key))
+                .collect::<Result<Vec<_>>>()?;
+            let public_key = Point::<Ed25519>
+                ::from_bytes(&hex::decode(y_sum)?)
+                .map_err(|_| anyhow!("Invalid EdDSA public key"))?;
+            let shared_secret = combine_shares(
+                &shares,
+                &public_key,
+                &ephemeral,
+                key_info.threshold
+            )?;
+            x25519_aes_key(&shared_secret)?
+        }
+        Key::Sr25519 { .. } => bail!("Decryption is not supported for Sr25519 keys"),
+        Key::Bls12381 { .. } => bail!("Decryption is not supported for BLS12-381 keys"),
+    };
+    aes_decrypt(ciphertext, &key)
+}
+
+pub fn secp256k1_ephemeral_key(ephemeral_public_key: &str) -> Result<Point<Secp256k1>> {
+    let bytes = hex::decode(ephemeral_public_key)?;
+    Point::from_bytes(&bytes).map_err(|_| anyhow!("Invalid secp256k1 ephemeral public key"))
+}
+
+/// Edwards point of an X25519 public key, whose sign doesn't change the X25519 shared secret
+pub fn x25519_ephemeral_key(ephemeral_public_key: &str) -> Result<Point<Ed25519>> {
+    let bytes: [u8; 32] = hex
+        ::decode(ephemeral_public_key)?
+        .try_into()
+        .map_err(|_| anyhow!("X25519 public keys are 32 bytes long"))?;
+    let edwards = MontgomeryPoint(bytes)
+        .to_edwards(0)
+        .ok_or_else(|| anyhow!("Invalid X25519 ephemeral public key"))?;
+    Point::from_bytes(edwards.compress().as_bytes()).map_err(|_|
+        anyhow!("X25519 ephemeral public key is not in the prime order subgroup")
+    )
+}
+
+/// AES key of the x coordinate, as [crate::encryption::encryption_key_for_aes] derives it
+pub fn secp256k1_aes_key(shared_secret: &Point<Secp256k1>) -> Result<Vec<u8>> {
+    let x = shared_secret.x_coord().ok_or_else(|| anyhow!("Shared secret is the identity"))?;
+    let bytes = x.to_bytes();
+    if bytes.len() > AES_KEY_BYTES_LEN {
+        bail!("encryption key length is too long");
+    }
+    let mut key = vec![0u8; AES_KEY_BYTES_LEN - bytes.len()];
+    key.extend_from_slice(&bytes);
+    Ok(key)
+}
+
+/// X25519 shared secret, the Montgomery u coordinate of the shared point
+pub fn x25519_aes_key(shared_secret: &Point<Ed25519>) -> Result<Vec<u8>> {
+    let point = CompressedEdwardsY::from_slice(&shared_secret.to_bytes(true))
+        .decompress()
+        .ok_or_else(|| anyhow!("Invalid shared secret"))?;
+    Ok(point.to_montgomery().to_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::{ aes_encrypt, encryption_key_for_aes };
+    use shared::ecdsa::Sum;
+    use sodiumoxide::crypto::box_;
+
+    #[test]
+    fn secp256k1_threshold_decryption() {
+        let secret = Scalar::<Secp256k1>::random();
+        let public_key = Point::generator() * &secret;
+        let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+
+        let ephemeral_secret = Scalar::<Secp256k1>::random();
+        let ephemeral = Point::generator() * &ephemeral_secret;
+        let key = encryption_key_for_aes(&public_key, &ephemeral_secret).unwrap();
+        let ciphertext = aes_encrypt(b"wallet data", &key).unwrap();
+
+        let vss_scheme_vec = vec![vss];
+        let mut decryption_shares = [1, 3]
+            .iter()
+            .map(|&index| {
+                DecryptionShare::new(&shares[index - 1], index, &vss_scheme_vec, &ephemeral)
+            })
+            .collect::<Vec<_>>();
+        let shared_secret = combine_shares(&decryption_shares, &public_key, &ephemeral, 1).unwrap();
+        let key = secp256k1_aes_key(&shared_secret).unwrap();
+        assert_eq!(aes_decrypt(&ciphertext, &key).unwrap(), b"wallet data");
+
+        decryption_shares[1].share = &decryption_shares[1].share + &ephemeral;
+        assert!(combine_shares(&decryption_shares, &public_key, &ephemeral, 1).is_err());
+        assert!(combine_shares(&decryption_shares[..1], &public_key, &ephemeral, 1).is_err());
+    }
+
+    #[test]
+    fn x25519_threshold_decryption() {
+        let secret = Scalar::<Ed25519>::random();
+        let public_key = Point::generator() * &secret;
+        let (vss, shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+
+        // Sender side X25519 with the Montgomery form of the Ed25519 public key
+        let ephemeral_secret = curve25519_dalek::scalar::Scalar::from_bytes_mod_order([7; 32]);
+        let ephemeral = hex::encode(
+            (&curve25519_dalek::constants::X25519_BASEPOINT * &ephemeral_secret).to_bytes()
+        );
+        let wallet = CompressedEdwardsY::from_slice(&public_key.to_bytes(true))
+            .decompress()
+            .unwrap()
+            .to_montgomery();
+        let sender_secret = (&wallet * &ephemeral_secret).to_bytes();
+
+        let ephemeral = x25519_ephemeral_key(&ephemeral).unwrap();
+        let vss_scheme_vec = vec![vss];
+        let decryption_shares = [2, 3]
+            .iter()
+            .map(|&index| {
+                DecryptionShare::new(&shares[index - 1], index, &vss_scheme_vec, &ephemeral)
+            })
+            .collect::<Vec<_>>();
+        let shared_secret = combine_shares(&decryption_shares, &public_key, &ephemeral, 1).unwrap();
+        assert_eq!(x25519_aes_key(&shared_secret).unwrap(), sender_secret.to_vec());
+    }
+
+    #[test]
+    fn only_the_client_opens_the_decryption_shares() {
+        let secret = Scalar::<Secp256k1>::random();
+        let public_key = Point::generator() * &secret;
+        let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+
+        let ephemeral_secret = Scalar::<Secp256k1>::random();
+        let ephemeral = Point::generator() * &ephemeral_secret;
+        let key = encryption_key_for_aes(&public_key, &ephemeral_secret).unwrap();
+        let ciphertext = aes_encrypt(b"wallet data", &key).unwrap();
+
+        let (client_public, client_private) = box_::gen_keypair();
+        let (client_public, client_private) = (
+            base64::encode(client_public.0),
+            base64::encode(client_private.0),
+        );
+        let vss_scheme_vec = vec![vss];
+        let sealed = [1, 2]
+            .iter()
+            .map(|&index| {
+                let (node_public, node_private) = box_::gen_keypair();
+                EncryptedDecryptionShare::seal(
+                    &DecryptionShare::new(&shares[index - 1], index, &vss_scheme_vec, &ephemeral),
+                    &client_public,
+                    &base64::encode(node_public.0),
+                    &base64::encode(node_private.0)
+                ).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let key_info = KeyInfo {
+            kind: Key::ECDSA {
+                y_sum: Sum {
+                    x: public_key.x_coord().unwrap().to_hex(),
+                    y: public_key.y_coord().unwrap().to_hex(),
+                },
+                chain_code: None,
+            },
+            node_pool: Vec::new(),
+            threshold: 1,
+        };
+        let ephemeral = hex::encode(&*ephemeral.to_bytes(true));
+        let plaintext = decrypt_with_shares(
+            &key_info,
+            &ephemeral,
+            &ciphertext,
+            &sealed,
+            &client_private
+        ).unwrap();
+        assert_eq!(plaintext, b"wallet data");
+
+        let (_, other_private) = box_::gen_keypair();
+        let other_private = base64::encode(other_private.0);
+        assert!(sealed[0].open::<Secp256k1>(&other_private).is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::command::MsgContext;
+use crate::decryption::{ DecryptCommand, DecryptResponse, EncryptedDecryptionShare };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
+use std::time::Duration;
+use tracing::{ info, instrument };
+
+const SHARE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: DecryptCommand, ctx: MsgContext) -> Result<DecryptResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+    let key_id = cmd.key_id.clone();
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", &key_id)
+    )?;
+    let party_count = cmd.party_nodes.len();
+    if party_count <= key_info.threshold {
+        bail!("Not enough nodes in party");
+    }
+    if cmd.requests.len() != party_count {
+        bail!("Expected a client request for each of the {} party nodes", party_count);
+    }
+    let client_e2e_public_key = &cmd.requests[0].credentials.client_e2e_public_key;
+    for (node, request) in cmd.party_nodes.iter().zip(cmd.requests.iter()) {
+        if
+            request.key_id != key_id ||
+            request.session_id != session_id ||
+            request.ephemeral_public_key != cmd.ephemeral_public_key ||
+            &request.credentials.client_e2e_public_key != client_e2e_public_key
+        {
+            bail!("Client request for node {} doesn't match the decryption command", node);
+        }
+    }
+
+    let result_key = format!("network.gridlock.nodes.Decrypt.{}.Result", &session_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    for (node, request) in cmd.party_nodes.iter().zip(cmd.requests.iter()) {
+        let new_key = format!("network.gridlock.nodes.Decrypt.new.{}", node);
+        nc.publish(&new_key, &serde_json::to_string(request)?)?;
+    }
+    nc.flush()?;
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub
+            .next_timeout(SHARE_TIMEOUT)
+            .context("Decryption share received from every party")?;
+        results.push(res.data);
+    }
+    info!("Decryption shares received");
+
+    let shares = results
+        .iter()
+        .map(|data| serde_json::from_slice::<EncryptedDecryptionShare>(data))
+        .collect::<Result<Vec<_>, _>>()?;
+    info!("Encrypted decryption shares relayed to the client");
+
+    Ok(DecryptResponse { shares })
+}
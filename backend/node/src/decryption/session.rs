@@ -0,0 +1,139 @@
+use crate::decryption::{
+    secp256k1_ephemeral_key,
+    x25519_ephemeral_key,
+    DecryptionShare,
+    EncryptedDecryptionShare,
+};
+use crate::node::NodeIdentity;
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::storage::{ KeyInfoStore, KeyshareAccessor, ECDSA, EDDSA };
+use crate::App;
+use anyhow::{ bail, Result };
+use serde::{ Deserialize, Serialize };
+use shared::key_info::Key;
+use std::thread;
+use tracing::{ error, info, instrument };
+
+/// Request of a client to decrypt data with a key, sent to every party node
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewDecryptMessage {
+    pub key_id: String,
+    pub session_id: String,
+    pub ephemeral_public_key: String,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+}
+
+impl AuthenticatedRequest for NewDecryptMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("decrypt")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .str("ephemeral_public_key", &self.ephemeral_public_key)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
+}
+
+#[instrument(skip_all)]
+fn decrypt_session(conn: nats::Connection, request: NewDecryptMessage, email: String) {
+    let session_id = request.session_id.clone();
+    match decrypt_session_inner(conn, request, email) {
+        Ok(()) => info!("Decryption share published for session id: {}", session_id),
+        Err(err) => error!("Error in decryption: session id: {}, error: {}", session_id, err),
+    }
+}
+
+fn decrypt_session_inner(
+    conn: nats::Connection,
+    request: NewDecryptMessage,
+    email: String
+) -> Result<()> {
+    let key_id = &request.key_id;
+    let node = NodeIdentity::load()?;
+    let client_e2e_public_key = &request.credentials.client_e2e_public_key;
+
+    // Shares are sealed to the client, anyone else on the network could otherwise combine them
+    let share = match KeyInfoStore::get_key_info(key_id)?.kind {
+        Key::ECDSA { .. } => {
+            let keyshare = KeyshareAccessor::<ECDSA>::read_only_with_email(key_id, &email)?.key;
+            let ephemeral = secp256k1_ephemeral_key(&request.ephemeral_public_key)?;
+            EncryptedDecryptionShare::seal(
+                &DecryptionShare::new(
+                    &keyshare.x_i,
+                    keyshare.party_index,
+                    &keyshare.vss_scheme_vec,
+                    &ephemeral
+                ),
+                client_e2e_public_key,
+                &node.e2e_public_key,
+                &node.e2e_private_key
+            )?
+        }
+        Key::EDDSA { .. } => {
+            let keyshare = KeyshareAccessor::<EDDSA>::read_only_with_email(key_id, &email)?.key;
+            let ephemeral = x25519_ephemeral_key(&request.ephemeral_public_key)?;
+            EncryptedDecryptionShare::seal(
+                &DecryptionShare::new(
+                    &keyshare.x_i,
+                    keyshare.party_index,
+                    &keyshare.vss_scheme_vec,
+                    &ephemeral
+                ),
+                client_e2e_public_key,
+                &node.e2e_public_key,
+                &node.e2e_private_key
+            )?
+        }
+        Key::Sr25519 { .. } => bail!("Decryption is not supported for Sr25519 keys"),
+        Key::Bls12381 { .. } => bail!("Decryption is not supported for BLS12-381 keys"),
+    };
+    info!("Computed decryption share for key {}", key_id);
+
+    conn.publish(
+        &format!("network.gridlock.nodes.Decrypt.{}.Result", request.session_id),
+        serde_json::to_vec(&share)?
+    )?;
+    Ok(())
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let request = match serde_json::from_slice::<NewDecryptMessage>(&message.data[..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    // Decryption shares are as sensitive as signatures, so they need the same authorization
+    let email = match authorize_session_request(&request) {
+        Some(email) => email,
+        None => return,
+    };
+
+    let thread_name = format!("decrypt_session_{}", request.session_id);
+    let nc = app.nc.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(thread_name)
+            .spawn(move || decrypt_session(nc, request, email))
+    {
+        Ok(_) => info!("Started decryption thread"),
+        Err(err) => error!("Failed to spawn thread for decryption: {}", err),
+    };
+}
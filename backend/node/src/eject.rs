@@ -8,12 +8,18 @@ use tracing::{ error, info };
 use crate::command::{ JsonCommand, MsgContext };
 use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
 
-const THRESHOLD: usize = 3;
-
 #[derive(Deserialize, Serialize, Debug)]
 pub struct EjectInfo {
     pub key_id: String,
     pub share_info: EjectShareInfo,
+    /// Threshold as stored in the keyshare, threshold + 1 shares are needed to reconstruct the key
+    #[serde(default = "legacy_threshold")]
+    pub threshold: usize,
+}
+
+/// Eject info created before the threshold was included belongs to 3-of-5 keys
+fn legacy_threshold() -> usize {
+    2
 }
 
 #[derive(Serialize, Debug, PartialEq)]
@@ -88,16 +94,20 @@ fn retrieve_eject_info_from_key_ids(key_ids: &[String]) -> Result<Vec<EjectInfo>
         .iter()
         .filter_map(|key_id| {
             if let Ok(ka) = KeyshareAccessor::<ECDSA>::read_only(key_id) {
+                let threshold = ka.key.threshold;
                 let share_info = EjectShareInfo::from(ka.key);
                 Some(EjectInfo {
                     key_id: key_id.to_string(),
                     share_info,
+                    threshold,
                 })
             } else if let Ok(ka) = KeyshareAccessor::<EDDSA>::read_only(key_id) {
+                let threshold = ka.key.threshold;
                 let share_info = EjectShareInfo::from(ka.key);
                 Some(EjectInfo {
                     key_id: key_id.to_string(),
                     share_info,
+                    threshold,
                 })
             } else {
                 None
@@ -115,7 +125,8 @@ fn combine_keyshares(
         .iter()
         .filter_map(|key_id| {
             let shares = collect_shares_by_key_id_from_supplied_keyshares(key_id, eject_info_vec);
-            match reconstruct_key_from_collected_eject_info(&shares) {
+            let threshold = threshold_of_key(key_id, eject_info_vec);
+            match reconstruct_key_from_collected_eject_info(&shares, threshold) {
                 Ok(key) =>
                     Some(KeyReconstructionResult {
                         key_id: key_id.clone(),
@@ -130,8 +141,11 @@ fn combine_keyshares(
         .collect()
 }
 
-fn reconstruct_key_from_collected_eject_info(eject_infos: &[EjectShareInfo]) -> Result<String> {
-    if eject_infos.len() < THRESHOLD {
+fn reconstruct_key_from_collected_eject_info(
+    eject_infos: &[EjectShareInfo],
+    threshold: usize
+) -> Result<String> {
+    if eject_infos.len() <= threshold {
         bail!("Not enough keyshares found to reconstruct private key");
     }
 
@@ -152,10 +166,20 @@ fn reconstruct_key_from_collected_eject_info(eject_infos: &[EjectShareInfo]) ->
     });
 
     let res = (if
-        let Some(reconstructed_key) = reconstruct_key::<Secp256k1>(&indices, &secp_scalars)
+        let Some(reconstructed_key) = reconstruct_key::<Secp256k1>(
+            &indices,
+            &secp_scalars,
+            threshold
+        )
     {
         serde_json::to_string(&reconstructed_key)
-    } else if let Some(reconstructed_key) = reconstruct_key::<Ed25519>(&indices, &ed25519_scalars) {
+    } else if
+        let Some(reconstructed_key) = reconstruct_key::<Ed25519>(
+            &indices,
+            &ed25519_scalars,
+            threshold
+        )
+    {
         serde_json::to_string(&reconstructed_key)
     } else {
         bail!(
@@ -174,7 +198,7 @@ fn collect_shares_by_key_id_from_supplied_keyshares(
         .enumerate()
         .filter_map(|(set_index, eject_info_set)| {
             match eject_info_set.iter().find(|x| x.key_id == key_id) {
-                Some(EjectInfo { key_id: _, share_info }) => Some(share_info.clone()),
+                Some(EjectInfo { share_info, .. }) => Some(share_info.clone()),
                 None => {
                     info!("No eject info for key id {} in set {}", key_id, set_index);
                     None
@@ -184,8 +208,25 @@ fn collect_shares_by_key_id_from_supplied_keyshares(
         .collect::<Vec<EjectShareInfo>>()
 }
 
-fn reconstruct_key<C>(indices: &[usize], shares: &[Scalar<C>]) -> Option<Scalar<C>> where C: Curve {
-    if shares.len() != indices.len() || shares.len() < THRESHOLD {
+/// The highest threshold claimed for the key, so that supplied eject info can't lower it
+fn threshold_of_key(key_id: &str, eject_info_vec: &[Vec<EjectInfo>]) -> usize {
+    eject_info_vec
+        .iter()
+        .flatten()
+        .filter(|x| x.key_id == key_id)
+        .map(|x| x.threshold)
+        .max()
+        .unwrap_or_else(legacy_threshold)
+}
+
+fn reconstruct_key<C>(
+    indices: &[usize],
+    shares: &[Scalar<C>],
+    threshold: usize
+) -> Option<Scalar<C>>
+    where C: Curve
+{
+    if shares.len() != indices.len() || shares.len() <= threshold {
         return None;
     }
 
@@ -155,6 +155,18 @@ pub fn decrypt_with_shared_secret(
     Ok(decrypted)
 }
 
+/// Base64 signature of `message` by the networking key of a node
+pub fn sign_with_nkey(private_key: &str, message: &[u8]) -> Result<String> {
+    let key_pair = KeyPair::from_seed(private_key)?;
+    Ok(base64::encode(key_pair.sign(message)?))
+}
+
+pub fn verify_nkey_signature(public_key: &str, message: &[u8], signature: &str) -> Result<()> {
+    let key_pair = KeyPair::from_public_key(public_key)?;
+    key_pair.verify(message, &base64::decode(signature)?)?;
+    Ok(())
+}
+
 fn create_shared_secret(private_key: &SecretKey, public_key: &PublicKey) -> Result<Vec<u8>> {
     let compressed_y = CompressedEdwardsY::from_slice(&public_key.to_bytes()[..]);
     let pub_key_point = match compressed_y.decompress() {
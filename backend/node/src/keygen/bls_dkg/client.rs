@@ -0,0 +1,134 @@
+use crate::communication::nats::PeerMessenger;
+use crate::communication::protocol::{ AllRounds, KeyGenBls12381AllRounds };
+use crate::encryption::{ aes_decrypt, aes_encrypt };
+use crate::keygen::bls_dkg::{
+    share_encryption_key,
+    DealerCommitment,
+    KeyGenResult,
+    SecretKnowledgeProof,
+};
+use crate::keygen::sr25519_dkg::combine_vss;
+use crate::keygen::ShareParams;
+use crate::storage::Bls12381;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Bls12_381_1, Scalar };
+use curv::BigInt;
+
+pub struct BlsKeyGenClient<C> {
+    pub peer_messenger: C,
+    pub session_id: String,
+    pub share_params: ShareParams,
+    pub all_party_indices: Vec<usize>,
+}
+
+impl<C> BlsKeyGenClient<C> where C: PeerMessenger<KeyGenBls12381AllRounds> {
+    pub fn create_shared_key(&self) -> Result<Bls12381> {
+        let threshold = self.share_params.threshold;
+        let party_index = self.share_params.party_index;
+        let indices = self.all_party_indices
+            .iter()
+            .map(|&index| index as u16)
+            .collect::<Vec<_>>();
+
+        let secret = Scalar::<Bls12_381_1>::random();
+        let (vss, shares) = VerifiableSS::share_at_indices(
+            threshold as u16,
+            self.share_params.party_count as u16,
+            &secret,
+            &indices
+        );
+
+        let commitments = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeyGenBls12381AllRounds as AllRounds>::BroadcastRound::Commitments,
+            DealerCommitment {
+                vss,
+                dlog_proof: SecretKnowledgeProof::prove(&secret, &self.session_id, party_index),
+            }
+        )?;
+        if commitments.len() != self.all_party_indices.len() {
+            bail!("Commitments were not received from every party");
+        }
+        for (commitment, index) in commitments.iter().zip(self.all_party_indices.iter()) {
+            commitment.verify(threshold, &self.session_id, *index)?;
+        }
+
+        let received_shares = self.exchange_secret_shares(&secret, &shares, &commitments)?;
+
+        let mut x_i = Scalar::<Bls12_381_1>::zero();
+        for ((share, commitment), index) in received_shares
+            .iter()
+            .zip(commitments.iter())
+            .zip(self.all_party_indices.iter()) {
+            if commitment.vss.validate_share(share, party_index as u16).is_err() {
+                bail!("Share dealt by party {} does not match its commitments", index);
+            }
+            x_i = x_i + share;
+        }
+
+        let vss_scheme = combine_vss(
+            &commitments
+                .into_iter()
+                .map(|commitment| commitment.vss)
+                .collect::<Vec<_>>()
+        )?;
+
+        Ok(Bls12381 {
+            threshold,
+            party_index,
+            x_i,
+            public_key: vss_scheme.commitments[0].clone(),
+            vss_scheme,
+        })
+    }
+
+    pub fn publish_result(&self, result: KeyGenResult) -> Result<()> {
+        let _ = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeyGenBls12381AllRounds as AllRounds>::BroadcastRound::Result,
+            result
+        )?;
+        Ok(())
+    }
+
+    /// Sends the share of every other party encrypted to it, returning the shares dealt to this
+    /// party in the order of the party indices
+    fn exchange_secret_shares(
+        &self,
+        secret: &Scalar<Bls12_381_1>,
+        shares: &[Scalar<Bls12_381_1>],
+        commitments: &[DealerCommitment]
+    ) -> Result<Vec<Scalar<Bls12_381_1>>> {
+        let enc_vec = commitments
+            .iter()
+            .map(|commitment| share_encryption_key(&commitment.vss.commitments[0], secret))
+            .collect::<Vec<_>>();
+
+        let mut outgoing_messages = Vec::new();
+        for (i, party_index) in self.all_party_indices.iter().enumerate() {
+            if *party_index != self.share_params.party_index {
+                let plaintext = shares[i].to_bigint().to_bytes();
+                outgoing_messages.push(aes_encrypt(&plaintext, &enc_vec[i])?);
+            }
+        }
+        let msg_vec = self.peer_messenger.send_p2p_and_collect_messages(
+            &<KeyGenBls12381AllRounds as AllRounds>::P2PRound::ShareSecret,
+            outgoing_messages
+        )?;
+
+        let mut encrypted_data = msg_vec.into_iter();
+        let mut party_shares = Vec::new();
+        for (i, party_index) in self.all_party_indices.iter().enumerate() {
+            if *party_index != self.share_params.party_index {
+                let encrypted = encrypted_data
+                    .next()
+                    .ok_or_else(|| anyhow!("Share of party {} was not received", party_index))?;
+                let plaintext = aes_decrypt(&encrypted, &enc_vec[i])?;
+                party_shares.push(Scalar::from(&BigInt::from_bytes(&plaintext)));
+            } else {
+                party_shares.push(shares[i].clone());
+            }
+        }
+        Ok(party_shares)
+    }
+}
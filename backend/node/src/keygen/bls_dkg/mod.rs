@@ -0,0 +1,113 @@
+//! Key generation of BLS12-381 keys on G1.
+//!
+//! Every party deals a Feldman VSS of a random secret with a proof of knowledge of it, as in the
+//! key generation of FROST, and sends the shares encrypted to each other party. A keyshare is the
+//! sum of the shares a party received, the public key the sum of the dealt secrets on G1.
+pub mod client;
+pub mod orchestrate;
+pub mod session;
+
+use anyhow::{ bail, Result };
+use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Bls12_381_1, Point, Scalar };
+use curv::BigInt;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct KeyGenResult {
+    /// Compressed G1 public key, hex encoded
+    pub pk: String,
+}
+
+/// Broadcast of a dealer, committing to its polynomial
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DealerCommitment {
+    pub vss: VerifiableSS<Bls12_381_1>,
+    /// Proof of knowledge of the dealt secret, which keeps a dealer from cancelling the others
+    pub dlog_proof: SecretKnowledgeProof,
+}
+
+impl DealerCommitment {
+    pub fn verify(&self, threshold: usize, session_id: &str, party_index: usize) -> Result<()> {
+        if
+            self.vss.parameters.threshold as usize != threshold ||
+            self.vss.commitments.len() != threshold + 1
+        {
+            bail!("VSS of party {} was created with a different threshold", party_index);
+        }
+        self.dlog_proof.verify(&self.vss.commitments[0], session_id, party_index)
+    }
+}
+
+/// Schnorr proof of knowledge of a dealt secret. The challenge commits to the session and the
+/// dealer, so a proof can't be replayed by another party or in another session.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SecretKnowledgeProof {
+    commitment: Point<Bls12_381_1>,
+    response: Scalar<Bls12_381_1>,
+}
+
+impl SecretKnowledgeProof {
+    pub fn prove(secret: &Scalar<Bls12_381_1>, session_id: &str, party_index: usize) -> Self {
+        let nonce = Scalar::<Bls12_381_1>::random();
+        let commitment = Point::generator() * &nonce;
+        let public_key = Point::generator() * secret;
+        let challenge = proof_challenge(session_id, party_index, &public_key, &commitment);
+        Self {
+            commitment,
+            response: nonce + challenge * secret,
+        }
+    }
+
+    pub fn verify(
+        &self,
+        public_key: &Point<Bls12_381_1>,
+        session_id: &str,
+        party_index: usize
+    ) -> Result<()> {
+        let challenge = proof_challenge(session_id, party_index, public_key, &self.commitment);
+        if Point::generator() * &self.response != &self.commitment + public_key * &challenge {
+            bail!("Party {} could not prove knowledge of its secret", party_index);
+        }
+        Ok(())
+    }
+}
+
+fn proof_challenge(
+    session_id: &str,
+    party_index: usize,
+    public_key: &Point<Bls12_381_1>,
+    commitment: &Point<Bls12_381_1>
+) -> Scalar<Bls12_381_1> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"gridlock/bls12381/dealer-secret");
+    hasher.update((session_id.len() as u32).to_be_bytes());
+    hasher.update(session_id.as_bytes());
+    hasher.update((party_index as u32).to_be_bytes());
+    hasher.update(&*public_key.to_bytes(true));
+    hasher.update(&*commitment.to_bytes(true));
+    Scalar::<Bls12_381_1>::from_bigint(&BigInt::from_bytes(&hasher.finalize()))
+}
+
+/// AES key of the shares a party sends to another, from the secrets both of them dealt
+pub fn share_encryption_key(
+    other_commitment: &Point<Bls12_381_1>,
+    local_secret: &Scalar<Bls12_381_1>
+) -> Vec<u8> {
+    let shared_point = other_commitment * local_secret;
+    Sha256::digest(&*shared_point.to_bytes(true)).to_vec()
+}
+
+#[test]
+fn secret_knowledge_proof_is_bound_to_session_and_dealer() {
+    let secret = Scalar::<Bls12_381_1>::random();
+    let public_key = Point::generator() * &secret;
+    let proof = SecretKnowledgeProof::prove(&secret, "session", 2);
+
+    assert!(proof.verify(&public_key, "session", 2).is_ok());
+    assert!(proof.verify(&public_key, "other session", 2).is_err());
+    assert!(proof.verify(&public_key, "session", 3).is_err());
+    assert!(proof.verify(&(Point::generator() * Scalar::random()), "session", 2).is_err());
+}
@@ -0,0 +1,102 @@
+use crate::command::MsgContext;
+use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
+use crate::keygen::bls_dkg::KeyGenResult;
+use crate::keygen::eddsa::session::NewKeyGenSession;
+use crate::keygen::{ KeyGenCommand, KeyGenResponse };
+use anyhow::{ bail, Context, Result };
+use shared::key_info::{ Key, KeyInfo, Node, NodeInfo, UpdateKeyInfoCommand };
+use tracing::{ error, info, instrument };
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+
+    let threshold = cmd.keyshare_threshold()?;
+    let party_nodes = cmd.party_nodes;
+    let key_id = cmd.key_id;
+
+    let party_count = party_nodes.len();
+
+    let join_key = format!("network.gridlock.nodes.KeyGenBls12381.{}.Join", &key_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!("network.gridlock.nodes.KeyGenBls12381.{}.Result", &key_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    for (i, node_id) in party_nodes.iter().enumerate() {
+        let key_gen_new = format!("network.gridlock.nodes.KeyGenBls12381.new.{node_id}");
+        let key_gen_new_data = serde_json::to_string(
+            &(NewKeyGenSession {
+                key_id: key_id.to_owned(),
+                threshold,
+                share_indices: vec![i + 1],
+            })
+        )?;
+        nc.publish(&key_gen_new, &key_gen_new_data)?;
+    }
+
+    let mut msg_vec = Vec::new();
+    for _ in 0..party_count {
+        let next = join_sub.next().context("Get next join message")?;
+        msg_vec.push(next);
+    }
+
+    let mut node_pool = Vec::new();
+    let mut indices = Vec::new();
+    for m in msg_vec.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        let node_id = confirmation.node_id.clone().try_into()?;
+        node_pool.push(NodeInfo {
+            node_id: confirmation.node_id,
+            networking_public_key: confirmation.networking_public_key,
+            kind: {
+                if app.node.node_id == node_id { Node::Owner } else { Node::Guardian }
+            },
+            share_index: confirmation.party_index,
+        });
+        indices.push(confirmation.party_index);
+    }
+    indices.sort();
+    let join_resp = JoinResponse {
+        party_count: indices.len(),
+        all_party_indices: indices,
+    };
+    for m in msg_vec.iter() {
+        if let Err(err) = m.respond(serde_json::to_string(&join_resp)?) {
+            error!("Error: {}", err);
+        }
+    }
+    nc.flush()?;
+    info!("Parties joined to BLS12-381 key generation");
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub.next().context("Key generation result received from every party")?;
+        results.push(serde_json::from_slice::<BroadcastMessage<KeyGenResult>>(&res.data)?.message);
+    }
+    if results.iter().any(|result| result != &results[0]) {
+        bail!("Parties do not agree on the BLS12-381 public key");
+    }
+    let result = results.remove(0);
+
+    let key_info = KeyInfo {
+        kind: Key::Bls12381 { pk: result.pk.clone() },
+        node_pool: node_pool.clone(),
+        threshold,
+    };
+
+    for node in node_pool {
+        nc.publish(
+            &format!("network.gridlock.nodes.Message.new.{}", node.node_id),
+            &serde_json::to_string(
+                &(UpdateKeyInfoCommand {
+                    key_id: key_id.to_string(),
+                    key_info: key_info.clone(),
+                })
+            )?
+        )?;
+    }
+
+    Ok(KeyGenResponse::Bls12381(result))
+}
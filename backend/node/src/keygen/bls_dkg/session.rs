@@ -0,0 +1,157 @@
+use crate::client_identity::save_client_credentials;
+use crate::communication::nats::{
+    BaseMessenger,
+    NatsBaseMessenger,
+    NatsBaseSession,
+    NatsPeerMessenger,
+};
+use crate::communication::protocol::{ KeyGenBls12381AllRounds, Topic };
+use crate::keygen::bls_dkg::client::BlsKeyGenClient;
+use crate::keygen::bls_dkg::KeyGenResult;
+use crate::keygen::eddsa::session::{ NewKeyGenMessage, NewKeyGenSession };
+use crate::keygen::ShareParams;
+use crate::node::NodeIdentity;
+use crate::signing::bls::to_g1_affine;
+use crate::storage::KeyshareSaver;
+use crate::App;
+use anyhow::bail;
+use std::thread;
+use tracing::{ error, info, instrument };
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let parsed_message = match serde_json::from_slice::<NewKeyGenMessage>(&message.data[..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    let recovery_email = parsed_message.email.clone();
+    if
+        let Err(err) = save_client_credentials(
+            &parsed_message.key_id,
+            &recovery_email,
+            parsed_message.identity_key.as_ref(),
+            &parsed_message.client_e2e_public_key,
+            &parsed_message.encrypted_signing_key
+        )
+    {
+        error!("Failed to save client credentials: {}", err);
+        return;
+    }
+
+    let session = NewKeyGenSession {
+        key_id: parsed_message.key_id,
+        share_indices: parsed_message.share_indices,
+        threshold: parsed_message.threshold,
+    };
+
+    for (thread_index, party_index) in session.share_indices.clone().iter().enumerate() {
+        let key = session.key_id.clone();
+        let nc = app.nc.clone();
+        let session = session.clone();
+        let party_index = *party_index;
+
+        let mut keyshare_saver = KeyshareSaver::new_creator(&key).with_email(&recovery_email);
+        if thread_index > 0 {
+            keyshare_saver = KeyshareSaver::new_encryptor(&key, thread_index).with_email(
+                &recovery_email
+            );
+        }
+
+        match
+            thread::Builder
+                ::new()
+                .name(format!("bls12381_key_gen_session_{}_{}", key, thread_index))
+                .spawn(move ||
+                    keygen_session(nc, session, party_index, thread_index, keyshare_saver)
+                )
+        {
+            Ok(_) => info!("Spawned a thread to handle BLS12-381 key gen"),
+            Err(_) => error!("Failed to spawn thread for BLS12-381 keygen session {}", key),
+        };
+    }
+}
+
+#[instrument(skip_all)]
+fn keygen_session(
+    conn: nats::Connection,
+    session: NewKeyGenSession,
+    party_index: usize,
+    thread_index: usize,
+    keysaver: KeyshareSaver
+) -> anyhow::Result<()> {
+    let session_id = session.key_id.clone();
+    match keygen_session_inner(conn, session, party_index, thread_index, keysaver) {
+        Ok(_) => {
+            info!("BLS12-381 key generation completed sucessfully, key id: {}", session_id);
+        }
+        Err(err) => error!("Error in key generation: session id: {}, error: {}", session_id, err),
+    }
+    Ok(())
+}
+
+fn keygen_session_inner(
+    conn: nats::Connection,
+    session: NewKeyGenSession,
+    party_index: usize,
+    thread_index: usize,
+    keysaver: KeyshareSaver
+) -> anyhow::Result<()> {
+    let node = NodeIdentity::load()?;
+    let key_id = session.key_id.clone();
+
+    let nats_session = NatsBaseSession {
+        session_id: key_id.clone(),
+        thread_index,
+        node_id: node.node_id.to_string(),
+        public_key: node.networking_public_key,
+        party_index,
+    };
+
+    let messenger = NatsBaseMessenger::<KeyGenBls12381AllRounds>::new(
+        Topic::KeyGenBls12381,
+        conn,
+        nats_session
+    )?;
+    let join_response = messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+
+    let party_count = join_response.party_count;
+    let mut all_party_indices = join_response.all_party_indices;
+    all_party_indices.sort();
+
+    let peer_messenger = NatsPeerMessenger::from(
+        messenger,
+        party_count,
+        all_party_indices.clone()
+    )?;
+
+    let keygen_client = BlsKeyGenClient {
+        peer_messenger,
+        session_id: key_id.clone(),
+        share_params: ShareParams {
+            threshold: session.threshold,
+            party_count,
+            party_index,
+        },
+        all_party_indices,
+    };
+
+    let keyshare = keygen_client.create_shared_key()?;
+    let pk = hex::encode(to_g1_affine(&keyshare.public_key)?.to_compressed());
+    info!("Verified the shares dealt by all parties");
+
+    match keysaver.save_key(&keyshare) {
+        Ok(()) => {
+            info!("Saved new key to file: {}", &key_id);
+        }
+        Err(err) => {
+            bail!("Unable to save key to file: {}", err);
+        }
+    }
+
+    keygen_client.publish_result(KeyGenResult { pk })?;
+
+    Ok(())
+}
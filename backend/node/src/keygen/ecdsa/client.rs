@@ -3,6 +3,8 @@ use crate::encryption::{ aes_decrypt, aes_encrypt, AES_KEY_BYTES_LEN };
 use crate::keygen::ecdsa::KeyGenMessage;
 use crate::keygen::ecdsa::{ KeyGenContext, NewKeyGenSession };
 use crate::security::check_for_small_primes;
+use crate::security::paillier_proofs::{ proof_context, PaillierKeyProof };
+use crate::signing::ecdsa::derivation::chain_code_from_blind_factors;
 use crate::storage::KeyshareSaver;
 use crate::storage::ECDSA;
 use anyhow::{ anyhow, bail };
@@ -20,6 +22,7 @@ use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2020::party_i::{
 };
 use nats::Subscription;
 use paillier::EncryptionKey;
+use serde::{ Deserialize, Serialize };
 use sha2::Sha256;
 use shared::recovery::EncryptedData;
 use zk_paillier::zkproofs::DLogStatement;
@@ -37,12 +40,10 @@ use zk_paillier::zkproofs::DLogStatement;
  * As such, the -1 is needed to convert from a) to b).
  */
 
-//set to one below threshold, see note.
-
-pub const THRESHOLD: usize = 2;
-
 pub struct KeygenClient {
     party_count: usize,
+    //one below the number of parties needed to sign, see note.
+    threshold: usize,
     party_id: usize,
     private_keys: Keys,
     commit_vec: Vec<KeyGenBroadcastMessage1>,
@@ -51,10 +52,12 @@ pub struct KeygenClient {
     pub y_sum: Point<Secp256k1>,
     vss_scheme_vec: Vec<VerifiableSS<Secp256k1>>,
     dlog_proof_vec: Vec<DLogProof<Secp256k1, Sha256>>,
+    pub chain_code: String,
 }
 
 pub struct SessionJoinParams {
     pub parties: usize,
+    pub threshold: usize,
     pub party_id: usize,
     pub session_start: Subscription,
     pub all_round_subs: AllRoundSubscriptions,
@@ -79,6 +82,13 @@ struct Phase1Part1Data {
     pub decom_i: KeyGenDecommitMessage1,
 }
 
+/// Round two message: the decommitment together with the proofs about the sender's Paillier key
+#[derive(Clone, Serialize, Deserialize)]
+struct Phase1Round2Message {
+    decom: KeyGenDecommitMessage1,
+    paillier_key_proof: PaillierKeyProof,
+}
+
 struct Phase1Part2Data {
     pub h1_h2_n_tilde_vec: Vec<DLogStatement>,
 }
@@ -116,6 +126,7 @@ impl KeygenClient {
             &context,
             &phase1_part1_data.decom_i,
             &phase1_part1_data.keys,
+            &commit_vec,
             all_round_subs.round2
         )?;
 
@@ -129,6 +140,13 @@ impl KeygenClient {
             &commit_vec
         )?;
 
+        // Blinding factors are only used once phase 2 has verified them against the commitments
+        let blind_factors = decom_vec
+            .iter()
+            .map(|decom| decom.blind_factor.clone())
+            .collect::<Vec<BigInt>>();
+        let chain_code = chain_code_from_blind_factors(&blind_factors);
+
         Self::phase2_send_shares(&context, &enc_key_vec, &phase2_part1_data.secret_shares)?;
 
         let party_shares = Self::phase2_receive_shares(
@@ -162,6 +180,7 @@ impl KeygenClient {
 
         Ok(Self {
             party_count: context.share_params.party_count,
+            threshold: context.share_params.threshold,
             party_id: context.share_params.party_index,
             private_keys: phase1_part1_data.keys,
             commit_vec,
@@ -170,6 +189,7 @@ impl KeygenClient {
             y_sum: phase2_part1_data.y_sum,
             vss_scheme_vec,
             dlog_proof_vec,
+            chain_code,
         })
     }
     fn phase1_round1(
@@ -197,15 +217,36 @@ impl KeygenClient {
         params: &KeyGenContext,
         decom_i: &KeyGenDecommitMessage1,
         party_keys: &Keys,
+        commit_vec: &[KeyGenBroadcastMessage1],
         round2: RoundSubscription
     ) -> anyhow::Result<(Vec<KeyGenDecommitMessage1>, Vec<Point<Secp256k1>>, Vec<Vec<u8>>)> {
         let mut point_vec: Vec<Point<Secp256k1>> = Vec::new();
         let mut enc_keys: Vec<Vec<u8>> = Vec::new();
         let mut decom_vec = Vec::new();
+        let own_index = params.share_params.party_index;
+
+        // Prove our Paillier modulus is sound to every other party, each against its own
+        // ring-Pedersen parameters
+        let verifiers: Vec<(usize, &DLogStatement)> = commit_vec
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != own_index - 1)
+            .map(|(i, commit)| (i + 1, &commit.dlog_statement))
+            .collect();
+        let paillier_key_proof = PaillierKeyProof::new(
+            &party_keys.dk,
+            &verifiers,
+            &proof_context(params.key_id, own_index)
+        )?;
 
         let message = KeyGenMessage {
-            sender_id: params.share_params.party_index - 1,
-            msg: serde_json::to_string(decom_i).unwrap(),
+            sender_id: own_index - 1,
+            msg: serde_json::to_string(
+                &(Phase1Round2Message {
+                    decom: decom_i.clone(),
+                    paillier_key_proof,
+                })
+            )?,
         };
 
         params.nc.publish(&round2.subject, serde_json::to_string(&message).unwrap()).unwrap();
@@ -215,10 +256,22 @@ impl KeygenClient {
         )?;
 
         for (index, phase2) in msg_vec.into_iter().enumerate() {
-            let phase2 = serde_json::from_str::<KeyGenDecommitMessage1>(&phase2.msg).unwrap();
+            let Phase1Round2Message { decom: phase2, paillier_key_proof } = serde_json::from_str::<
+                Phase1Round2Message
+            >(&phase2.msg)?;
             decom_vec.push(phase2.clone());
             point_vec.push(phase2.y_i.clone());
-            if index != params.share_params.party_index - 1 {
+            if index != own_index - 1 {
+                paillier_key_proof
+                    .verify(
+                        &commit_vec[index].e,
+                        own_index,
+                        &commit_vec[own_index - 1].dlog_statement,
+                        &proof_context(params.key_id, index + 1)
+                    )
+                    .map_err(|err| {
+                        anyhow!("Party {} sent an invalid Paillier key: {}", index + 1, err)
+                    })?;
                 let x_coord = (&phase2.y_i * &party_keys.u_i).x_coord().unwrap();
                 let key_bytes: Vec<u8> = BigInt::to_bytes(&x_coord);
                 match AES_KEY_BYTES_LEN - key_bytes.len() {
@@ -240,7 +293,8 @@ impl KeygenClient {
     }
 
     fn phase1_part1(params: &KeyGenContext) -> Phase1Part1Data {
-        let keys = Keys::create(params.share_params.party_index);
+        // Safe primes make the Paillier modulus a Blum integer, as required by its proof
+        let keys = Keys::create_safe_prime(params.share_params.party_index);
         let (commit_i, decom_i) =
             keys.phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2();
         Phase1Part1Data {
@@ -501,13 +555,14 @@ impl KeygenClient {
         let keyshare = ECDSA {
             x_i: self.shared_keys.x_i.clone(),
             y_sum: self.y_sum.clone(),
-            threshold: THRESHOLD,
+            threshold: self.threshold,
             party_index: self.party_id,
             vss_scheme_vec: self.vss_scheme_vec.to_vec(),
             paillier_key_vec,
             h1_h2_N_tilde_vec: self.h1_h2_n_tilde_vec.to_vec(),
             public_key_vec,
             paillier_dk: self.private_keys.dk.clone(),
+            chain_code: Some(self.chain_code.clone()),
         };
 
         keysaver.save_key(&keyshare)
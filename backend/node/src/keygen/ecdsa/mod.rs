@@ -11,6 +11,8 @@ use shared::ecdsa::Sum;
 #[derive(Serialize, Deserialize, Debug)]
 pub struct KeyGenResult {
     pub y_sum: Sum,
+    #[serde(default)]
+    pub chain_code: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -26,6 +28,7 @@ pub struct NewKeyGenSession {
 pub struct KeyGenParams {
     pub num_parties: usize,
     pub party_num: usize,
+    pub threshold: usize,
 }
 
 pub struct KeyGenContext<'a> {
@@ -51,8 +54,13 @@ pub struct NewKeyGenMessage {
     pub key_id: String,
     pub extra_shares: Vec<Option<String>>,
     pub client_e2e_public_key: String,
+    /// Access key encrypted to this node, not needed when an identity key is registered
+    #[serde(default)]
     pub encrypted_signing_key: String,
     pub email: String,
+    /// Identity key of the user, whose signatures authorize its requests instead of the access key
+    #[serde(default)]
+    pub identity_key: Option<ClientIdentityKey>,
 }
 
 #[test]
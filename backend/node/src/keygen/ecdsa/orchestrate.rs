@@ -4,7 +4,7 @@ use crate::keygen::ecdsa::{ KeyGenParams, KeyGenResult, NewKeyGenSession };
 use crate::keygen::{ KeyGenCommand, KeyGenResponse };
 use crate::storage::fs::WriteOpts;
 use crate::storage::KeyInfoStore;
-use anyhow::{ bail, Result };
+use anyhow::Result;
 use shared::key_info::{ Key, KeyInfo, Node, NodeInfo, UpdateKeyInfoCommand };
 use tracing::instrument;
 
@@ -13,13 +13,11 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
     let app = ctx.get_app()?;
     let nc = app.nc;
 
+    let threshold = cmd.keyshare_threshold()?;
     let party_nodes = cmd.party_nodes;
     let key_id = cmd.key_id;
 
     let party_count = party_nodes.len();
-    if party_count < 3 {
-        bail!("Not enough nodes in party");
-    }
 
     let join_key = format!("network.gridlock.nodes.keyGen.session.{}.join", &key_id);
     let join_sub = nc.subscribe(&join_key)?;
@@ -66,6 +64,7 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
                     &(KeyGenParams {
                         num_parties: party_count,
                         party_num: i,
+                        threshold,
                     })
                 )
                 .unwrap()
@@ -91,8 +90,10 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
     let key_info = KeyInfo {
         kind: Key::ECDSA {
             y_sum: key_gen_result.y_sum.clone(),
+            chain_code: key_gen_result.chain_code.clone(),
         },
         node_pool: node_pool.clone(),
+        threshold,
     };
 
     for node in node_pool {
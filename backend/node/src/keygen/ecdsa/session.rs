@@ -1,10 +1,6 @@
+use crate::client_identity::save_client_credentials;
 use crate::communication::ecdsa::JoinMessage;
-use crate::keygen::ecdsa::client::{
-    AllRoundSubscriptions,
-    KeygenClient,
-    SessionJoinParams,
-    THRESHOLD,
-};
+use crate::keygen::ecdsa::client::{ AllRoundSubscriptions, KeygenClient, SessionJoinParams };
 use crate::keygen::ecdsa::{
     KeyGenContext,
     KeyGenParams,
@@ -21,10 +17,6 @@ use curv::arithmetic::Converter;
 use std::thread;
 use std::time::Duration;
 use tracing::{ error, info, instrument };
-use crate::auth::e2e_decrypt;
-use crate::node::NodeIdentity;
-use crate::storage::fs::WriteOpts;
-use crate::storage::key_metadata_store::KeyMetadataStore;
 
 #[instrument(skip_all)]
 fn keygen_session(app: App, session: NewKeyGenSession, extra_share_index: usize) {
@@ -43,7 +35,7 @@ fn keygen_session(app: App, session: NewKeyGenSession, extra_share_index: usize)
     let context = KeyGenContext {
         nc: app.nc.clone(),
         share_params: ShareParams {
-            threshold: THRESHOLD,
+            threshold: received_params.threshold,
             party_count: received_params.parties,
             party_index: received_params.party_id,
         },
@@ -93,6 +85,7 @@ fn keygen_session(app: App, session: NewKeyGenSession, extra_share_index: usize)
                                 x: kg_client.y_sum.x_coord().unwrap().to_hex(),
                                 y: kg_client.y_sum.y_coord().unwrap().to_hex(),
                             },
+                            chain_code: Some(kg_client.chain_code.clone()),
                         })
                     )
                     .unwrap()
@@ -143,6 +136,7 @@ fn keygen_session_join(
 
     Ok(SessionJoinParams {
         parties: params_w_id.num_parties,
+        threshold: params_w_id.threshold,
         party_id: party_index + 1,
         session_start,
         all_round_subs,
@@ -158,60 +152,17 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         }
     };
 
-    let node = match NodeIdentity::load() {
-        Ok(node) => node,
-        Err(err) => {
-            error!("Failed to load node identity: {}", err);
-            return;
-        }
-    };
-
-    let decrypted_signing_key = match
-        e2e_decrypt(
-            &parsed_message.encrypted_signing_key,
-            &node.e2e_private_key,
-            &parsed_message.client_e2e_public_key
-        )
-    {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to decrypt signing key: {}", err);
-            return;
-        }
-    };
-
-    let node_signing_key = match String::from_utf8(decrypted_signing_key.clone()) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to convert decrypted signing key to string: {}", err);
-            return;
-        }
-    };
-    // Save node_signing_key to file with email
     if
-        let Err(e) = KeyMetadataStore::save(
-            &node_signing_key,
+        let Err(err) = save_client_credentials(
             &parsed_message.key_id,
-            "access",
             &parsed_message.email,
-            &WriteOpts::Modify
-        )
-    {
-        error!("Failed to save access key file: {}", e);
-    }
-
-    // Also save the client's e2e public key
-    if
-        let Err(e) = KeyMetadataStore::save_user_level(
+            parsed_message.identity_key.as_ref(),
             &parsed_message.client_e2e_public_key,
-            "e2e_key",
-            &parsed_message.email,
-            &WriteOpts::Modify
+            &parsed_message.encrypted_signing_key
         )
     {
-        error!("Failed to save client's e2e public key: {}", e);
-    } else {
-        info!("Saved client e2e public key for email: {}", parsed_message.email);
+        error!("Failed to save client credentials: {}", err);
+        return;
     }
 
     let session = NewKeyGenSession {
@@ -3,25 +3,21 @@ use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
 use crate::keygen::eddsa::session::NewKeyGenSession;
 use crate::keygen::eddsa::KeyGenResult;
 use crate::keygen::{ KeyGenCommand, KeyGenResponse };
-use anyhow::{ bail, Result };
+use anyhow::Result;
 use shared::key_info::{ Key, KeyInfo, Node, NodeInfo, UpdateKeyInfoCommand };
 use tracing::{ error, info, instrument };
 
-static THRESHOLD: usize = 2;
-
 #[instrument(skip_all)]
 pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse> {
     let app = ctx.get_app()?;
     let nc = app.nc;
     let _session_id = cmd.session_id.clone();
 
+    let threshold = cmd.keyshare_threshold()?;
     let party_nodes = cmd.party_nodes;
     let key_id = cmd.key_id;
 
     let party_count = party_nodes.len();
-    if party_count < 3 {
-        bail!("Not enough nodes in party");
-    }
 
     let join_key = format!("network.gridlock.nodes.KeyGenEdDSA.{}.Join", &key_id);
     let join_sub = nc.subscribe(&join_key)?;
@@ -29,22 +25,14 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
     let result_key = format!("network.gridlock.nodes.KeyGenEdDSA.{}.Result", &key_id);
     let result_sub = nc.subscribe(&result_key)?;
 
-    let shares1 = vec![1];
-    let shares2 = vec![2];
-    let shares3 = vec![3];
-    let shares4 = vec![4];
-    let shares5 = vec![5];
-
-    let all_shares = vec![shares1, shares2, shares3, shares4, shares5];
-
-    for (node_id, shares) in party_nodes.iter().zip(all_shares) {
+    for (i, node_id) in party_nodes.iter().enumerate() {
         let key_gen_new = format!("network.gridlock.nodes.KeyGenEdDSA.new.{node_id}");
         let key_gen_new_data = serde_json
             ::to_string(
                 &(NewKeyGenSession {
                     key_id: key_id.to_owned(),
-                    threshold: THRESHOLD,
-                    share_indices: shares.to_vec(),
+                    threshold,
+                    share_indices: vec![i + 1],
                 })
             )
             .unwrap();
@@ -60,7 +48,7 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
     }
 
     let mut node_pool = Vec::new();
-    if msg_vec.len() >= 3 {
+    if msg_vec.len() == party_count {
         let mut indices = Vec::new();
         for m in msg_vec.iter() {
             let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
@@ -106,6 +94,7 @@ pub fn orchestrate(cmd: KeyGenCommand, ctx: MsgContext) -> Result<KeyGenResponse
             y_sum: pk.y_sum.clone(),
         },
         node_pool: node_pool.clone(),
+        threshold,
     };
 
     for node in node_pool {
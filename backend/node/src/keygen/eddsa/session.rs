@@ -1,4 +1,4 @@
-use crate::auth::e2e_decrypt;
+use crate::client_identity::{ save_client_credentials, ClientIdentityKey };
 use crate::communication::nats::{
     BaseMessenger,
     NatsBaseMessenger,
@@ -10,10 +10,8 @@ use crate::keygen::eddsa::client::KeyGenClient;
 use crate::keygen::eddsa::KeyGenResult;
 use crate::keygen::ShareParams;
 use crate::node::NodeIdentity;
-use crate::storage::fs::WriteOpts;
 use crate::storage::KeyshareSaver;
 use crate::App;
-use crate::storage::key_metadata_store::KeyMetadataStore;
 use anyhow::bail;
 use serde::{ Deserialize, Serialize };
 use std::thread;
@@ -26,20 +24,19 @@ pub struct NewKeyGenSession {
     pub threshold: usize,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct E2EData {
-    pub client_e2e_public_key: String,
-    pub encrypted_signing_key: String,
-}
-
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct NewKeyGenMessage {
     pub key_id: String,
     pub share_indices: Vec<usize>,
     pub threshold: usize,
     pub client_e2e_public_key: String,
+    /// Access key encrypted to this node, not needed when an identity key is registered
+    #[serde(default)]
     pub encrypted_signing_key: String,
     pub email: String,
+    /// Identity key of the user, whose signatures authorize its requests instead of the access key
+    #[serde(default)]
+    pub identity_key: Option<ClientIdentityKey>,
 }
 
 pub fn handle_new_session_message(app: &App, message: nats::Message) {
@@ -56,64 +53,19 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         share_indices: parsed_message.share_indices,
         threshold: parsed_message.threshold,
     };
-    let e2e = E2EData {
-        client_e2e_public_key: parsed_message.client_e2e_public_key.clone(),
-        encrypted_signing_key: parsed_message.encrypted_signing_key,
-    };
-
-    let node = match NodeIdentity::load() {
-        Ok(node) => node,
-        Err(err) => {
-            error!("Failed to load node identity: {}", err);
-            return;
-        }
-    };
-
-    let decrypted_signing_key = match
-        e2e_decrypt(&e2e.encrypted_signing_key, &node.e2e_private_key, &e2e.client_e2e_public_key)
-    {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to decrypt signing key: {}", err);
-            return;
-        }
-    };
-
-    let node_signing_key = match String::from_utf8(decrypted_signing_key.clone()) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to convert decrypted signing key to string: {}", err);
-            return;
-        }
-    };
 
     let recovery_email = parsed_message.email.clone();
-
-    // Save node_signing_key to file with email
     if
-        let Err(e) = KeyMetadataStore::save(
-            &node_signing_key,
+        let Err(err) = save_client_credentials(
             &session.key_id,
-            "access",
-            &recovery_email,
-            &WriteOpts::Modify
-        )
-    {
-        error!("Failed to save access key file: {}", e);
-    }
-
-    // Also save the client's e2e public key
-    if
-        let Err(e) = KeyMetadataStore::save_user_level(
-            &e2e.client_e2e_public_key,
-            "e2e_key",
             &recovery_email,
-            &WriteOpts::Modify
+            parsed_message.identity_key.as_ref(),
+            &parsed_message.client_e2e_public_key,
+            &parsed_message.encrypted_signing_key
         )
     {
-        error!("Failed to save client's e2e public key: {}", e);
-    } else {
-        info!("Saved client e2e public key for email: {}", recovery_email);
+        error!("Failed to save client credentials: {}", err);
+        return;
     }
 
     for (thread_index, party_index) in session.share_indices.clone().iter().enumerate() {
@@ -1,34 +1,373 @@
 use crate::command::{ JsonCommand, MsgContext };
-use crate::storage::{ KeyshareSaver, SchnorrkelSecretKey, Sr25519 };
-use anyhow::{ bail, Result };
-use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::elliptic::curves::{ Ed25519, Scalar };
+use crate::node::NodeIdentity;
+use crate::recovery::reshare::{
+    collect_paillier_key_proofs,
+    create_reshare_package,
+    CompleteReshareCommand,
+    ReceiveResharePackagesCommand,
+    ResharePackage,
+    ReshareValidationResult,
+};
+use crate::recovery::ReshareNode;
+use crate::storage::{ KeyInfoStore, KeyshareSaver, SchnorrkelSecretKey, Sr25519 };
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing,
+    VerifiableSS,
+};
+use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use curv::BigInt;
 use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha512 };
+use shared::ecdsa::Sum;
+use shared::key_info::{ Key as KeyInfoKind, KeyInfo, NodeInfo, UpdateKeyInfoCommand };
+use shared::recovery::{ Key, PublicKeysEnum };
+use std::collections::HashMap;
 use std::convert::{ TryFrom, TryInto };
+use std::fmt::Debug;
+use tracing::info;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct KeyImportCommand {
     pub key_id: String,
     pub key_type: String,
+    /// Private key, hex encoded. EdDSA keys are given as their 32 byte seed, or as the 64 byte
+    /// keypair that Solana wallets export
     pub key: String,
+    /// Threshold of the key as stored in its key info, signing takes `threshold + 1` parties
     pub threshold: usize,
     pub share_count: usize,
+    /// Guardians of an imported ECDSA or EdDSA key, share indices are assigned in the given order
+    #[serde(default)]
+    pub party_nodes: Vec<ReshareNode>,
+    /// Public key the private key is expected to belong to, hex encoded and compressed
+    #[serde(default)]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+impl Debug for KeyImportCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("KeyImportCommand")
+            .field("key_id", &self.key_id)
+            .field("key_type", &self.key_type)
+            .field("threshold", &self.threshold)
+            .field("share_count", &self.share_count)
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyImportResponse {
+    /// Public key of the imported key, hex encoded and compressed
+    pub public_key: String,
 }
 
 impl JsonCommand for KeyImportCommand {
-    type Response = Vec<KeyImportShareCommand>;
+    type Response = KeyImportResponse;
 
-    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
         match self.key_type.as_str() {
             "sr25519" => bail!("sr25519 import not yet implemented"),
-            "eddsa" => bail!("EdDSA import not yet implemented"),
-            "ecdsa" => bail!("ECDSA import not yet implemented"),
+            "eddsa" => self.import_eddsa(ctx),
+            "ecdsa" => self.import_ecdsa(ctx),
             _ => bail!("Unknown type provided for key being imported"),
         }
     }
 }
 
+impl KeyImportCommand {
+    fn import_ecdsa(self, ctx: MsgContext) -> Result<KeyImportResponse> {
+        let secret = parse_ecdsa_key(&decode_key(&self.key)?)?;
+        let y_sum = Point::<Secp256k1>::generator() * &secret;
+        let public_key = self.check_public_key(&y_sum)?;
+
+        // Imported keys get a fresh chain code, as there is no BIP32 parent to take it from
+        let chain_code = hex::encode(rand::random::<[u8; 32]>());
+        let kind = KeyInfoKind::ECDSA {
+            y_sum: Sum {
+                x: y_sum.x_coord().ok_or_else(|| anyhow!("Invalid public key"))?.to_hex(),
+                y: y_sum.y_coord().ok_or_else(|| anyhow!("Invalid public key"))?.to_hex(),
+            },
+            chain_code: Some(chain_code.clone()),
+        };
+        self.deal_keyshares(ctx, Key::ECDSA, &secret, kind, Some(chain_code))?;
+
+        Ok(KeyImportResponse { public_key })
+    }
+
+    fn import_eddsa(self, ctx: MsgContext) -> Result<KeyImportResponse> {
+        let key = decode_key(&self.key)?;
+        let secret = parse_eddsa_key(&key)?;
+        let y_sum = Point::<Ed25519>::generator() * &secret;
+        if key.len() == 64 && key[32..] != *y_sum.to_bytes(true) {
+            bail!("Public key of the keypair does not belong to its seed");
+        }
+        let public_key = self.check_public_key(&y_sum)?;
+
+        let kind = KeyInfoKind::EDDSA {
+            y_sum: hex::encode(&*y_sum.to_bytes(false)),
+        };
+        self.deal_keyshares(ctx, Key::EDDSA, &secret, kind, None)?;
+
+        Ok(KeyImportResponse { public_key })
+    }
+
+    fn check_public_key<C: Curve>(&self, y_sum: &Point<C>) -> Result<String> {
+        let public_key = hex::encode(&*y_sum.to_bytes(true));
+        if let Some(expected) = &self.public_key {
+            if expected.trim_start_matches("0x").to_lowercase() != public_key {
+                bail!("Private key does not belong to the public key {}", expected);
+            }
+        }
+        Ok(public_key)
+    }
+
+    fn check_parameters(&self) -> Result<()> {
+        if self.party_nodes.len() != self.share_count {
+            bail!(
+                "Share count of {} does not match the {} party nodes",
+                self.share_count,
+                self.party_nodes.len()
+            );
+        }
+        if self.threshold < 1 || self.threshold >= self.share_count {
+            bail!(
+                "Threshold of {} is not valid for a party of {} nodes",
+                self.threshold,
+                self.share_count
+            );
+        }
+        Ok(())
+    }
+
+    /// Deals the key to the guardians the way a single old holder deals its keyshare in a
+    /// reshare, so the guardians validate their shares and create their paillier keys the
+    /// same way a new committee does
+    fn deal_keyshares<C: Curve>(
+        &self,
+        ctx: MsgContext,
+        kind: Key,
+        secret: &Scalar<C>,
+        key_info_kind: KeyInfoKind,
+        chain_code: Option<String>
+    ) -> Result<()> {
+        let app = ctx.get_app()?;
+        let nc = app.nc;
+
+        let email = self.email.clone().ok_or_else(|| anyhow!("Email is needed to import a key"))?;
+        self.check_parameters()?;
+        let threshold = self.threshold;
+        if KeyInfoStore::get_key_info(&self.key_id).is_ok() {
+            bail!("Key {} already exists", &self.key_id);
+        }
+
+        let node_pool: Vec<NodeInfo> = self.party_nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| NodeInfo {
+                node_id: node.node_id.clone(),
+                networking_public_key: node.networking_public_key.clone(),
+                kind: node.kind.clone(),
+                share_index: i + 1,
+            })
+            .collect();
+        let public_keys: HashMap<usize, String> = node_pool
+            .iter()
+            .map(|node| (node.share_index, node.networking_public_key.clone()))
+            .collect();
+
+        let node = NodeIdentity::load()?;
+        let package = create_import_package(
+            secret,
+            threshold,
+            &public_keys,
+            &node.networking_private_key
+        )?;
+        info!("Dealt keyshares of imported key {}", &self.key_id);
+
+        let dealing = ImportDealing {
+            kind: kind.clone(),
+            key_id: self.key_id.clone(),
+            email: email.clone(),
+            threshold,
+            dealer_public_key: node.networking_public_key.clone(),
+            package: serde_json::to_string(&package)?,
+            chain_code,
+            public_key: key_info_kind.clone(),
+        };
+
+        let mut key_proofs = Vec::new();
+        for node_info in &node_pool {
+            let message = dealing.message_for(node_info.share_index);
+            let message_new_key = format!(
+                "network.gridlock.nodes.async.Message.new.{}",
+                node_info.node_id
+            );
+            let res = nc.request(&message_new_key, serde_json::to_string(&message)?)?;
+            let validation_msg = serde_json
+                ::from_slice::<ReshareValidationResult>(&res.data)
+                .map_err(|_| anyhow!("{}", String::from_utf8_lossy(&res.data)))?;
+            match validation_msg {
+                ReshareValidationResult::Validated => {}
+                ReshareValidationResult::ValidatedWithKeyProof(key_proof) => {
+                    key_proofs.push(*key_proof);
+                }
+                ReshareValidationResult::ValidationError(err) => {
+                    bail!("Import failed for party {}: {}", node_info.share_index, err);
+                }
+            }
+        }
+        info!("Imported keyshares validated by all parties");
+
+        let paillier_key_proofs = match kind {
+            Key::ECDSA => {
+                collect_paillier_key_proofs(&nc, &node_pool, &self.key_id, &email, &key_proofs)?
+            }
+            _ => Vec::new(),
+        };
+
+        let complete = serde_json::to_string(
+            &(CompleteReshareCommand {
+                kind,
+                key_id: self.key_id.clone(),
+                email,
+                key_proofs,
+                paillier_key_proofs,
+            })
+        )?;
+        for node_info in &node_pool {
+            let message_new_key = format!(
+                "network.gridlock.nodes.async.Message.new.{}",
+                node_info.node_id
+            );
+            let res = nc.request(&message_new_key, &complete)?;
+            if res.data.starts_with(b"ERROR") {
+                bail!(
+                    "Imported keyshare could not be saved by party {}: {}",
+                    node_info.share_index,
+                    String::from_utf8_lossy(&res.data)
+                );
+            }
+        }
+        info!("Imported keyshares saved");
+
+        let key_info = KeyInfo {
+            kind: key_info_kind,
+            node_pool: node_pool.clone(),
+            threshold,
+        };
+        for node_info in node_pool {
+            nc.publish(
+                &format!("network.gridlock.nodes.async.Message.new.{}", node_info.node_id),
+                &serde_json::to_string(
+                    &(UpdateKeyInfoCommand {
+                        key_id: self.key_id.clone(),
+                        key_info: key_info.clone(),
+                    })
+                )?
+            )?;
+        }
+        info!("Key info of imported key {} published", &self.key_id);
+
+        Ok(())
+    }
+}
+
+/// Deals an imported key from a sharing of degree zero held by a single party, whose only
+/// commitment is the public key that every guardian checks its combined VSS against
+pub(crate) fn create_import_package<C: Curve>(
+    secret: &Scalar<C>,
+    threshold: usize,
+    public_keys: &HashMap<usize, String>,
+    private_key: &str
+) -> Result<ResharePackage<C>> {
+    let y_sum = Point::<C>::generator() * secret;
+    let import_vss = VerifiableSS {
+        parameters: ShamirSecretSharing {
+            threshold: 0,
+            share_count: 1,
+        },
+        commitments: vec![y_sum.clone()],
+    };
+    let package = create_reshare_package(
+        secret,
+        1,
+        &[1],
+        &[import_vss],
+        threshold,
+        public_keys,
+        private_key
+    )?;
+    if package.vss.commitments[0] != y_sum {
+        bail!("Dealt keyshares do not match the imported public key");
+    }
+    Ok(package)
+}
+
+/// Reshare packages of an imported key. Guardians have no key info of the key yet, so the
+/// public key is sent along for them to check the dealt keyshares against.
+pub(crate) struct ImportDealing {
+    pub kind: Key,
+    pub key_id: String,
+    pub email: String,
+    pub threshold: usize,
+    pub dealer_public_key: String,
+    pub package: String,
+    pub chain_code: Option<String>,
+    pub public_key: KeyInfoKind,
+}
+
+impl ImportDealing {
+    pub fn message_for(&self, share_index: usize) -> ReceiveResharePackagesCommand {
+        ReceiveResharePackagesCommand {
+            kind: self.kind.clone(),
+            key_id: self.key_id.clone(),
+            email: self.email.clone(),
+            reshare_index: share_index,
+            threshold: self.threshold,
+            dealers: vec![1],
+            dealer_public_keys: PublicKeysEnum::Map(vec![(1, self.dealer_public_key.clone())]),
+            packages: vec![self.package.clone()],
+            chain_code: self.chain_code.clone(),
+            public_key: self.public_key.clone(),
+        }
+    }
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>> {
+    hex::decode(key.trim_start_matches("0x")).map_err(|_| anyhow!("Private key is not hex encoded"))
+}
+
+fn parse_ecdsa_key(key: &[u8]) -> Result<Scalar<Secp256k1>> {
+    if key.len() != 32 {
+        bail!("ECDSA private keys have to be 32 bytes long");
+    }
+    let key = BigInt::from_bytes(key);
+    if key == BigInt::from(0u32) || &key >= Scalar::<Secp256k1>::group_order() {
+        bail!("ECDSA private key is out of range");
+    }
+    Ok(Scalar::from_bigint(&key))
+}
+
+/// Secret scalar of an Ed25519 key, expanded from its seed as RFC 8032 defines it
+fn parse_eddsa_key(key: &[u8]) -> Result<Scalar<Ed25519>> {
+    if key.len() != 32 && key.len() != 64 {
+        bail!("EdDSA private keys have to be 32 byte seeds or 64 byte keypairs");
+    }
+    let hash = Sha512::digest(&key[..32]);
+    let mut scalar = [0; 32];
+    scalar.copy_from_slice(&hash[..32]);
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    scalar.reverse();
+    Ok(Scalar::from_bigint(&BigInt::from_bytes(&scalar)))
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct KeyImportShareCommand {
@@ -74,3 +413,43 @@ impl JsonCommand for KeyImportShareCommand {
         }
     }
 }
+
+#[test]
+fn eddsa_seed_expands_to_ed25519_dalek_public_key() {
+    let seed = [42; 32];
+    let secret_key = ed25519_dalek::SecretKey::from_bytes(&seed).unwrap();
+    let public_key = ed25519_dalek::PublicKey::from(&secret_key);
+
+    let secret = parse_eddsa_key(&seed).unwrap();
+    let y_sum = Point::<Ed25519>::generator() * secret;
+    assert_eq!(&*y_sum.to_bytes(true), public_key.as_bytes());
+}
+
+#[test]
+fn import_parameters_are_checked_against_the_party() {
+    use shared::key_info::{ Node, NodeId };
+
+    let command = |threshold: usize, share_count: usize, party_count: usize| KeyImportCommand {
+        key_id: "key".to_string(),
+        key_type: "ecdsa".to_string(),
+        key: String::new(),
+        threshold,
+        share_count,
+        party_nodes: (0..party_count)
+            .map(|i| ReshareNode {
+                node_id: NodeId::new(format!("node-{}", i)),
+                networking_public_key: String::new(),
+                kind: Node::Guardian,
+            })
+            .collect(),
+        public_key: None,
+        email: None,
+    };
+
+    assert!(command(1, 3, 3).check_parameters().is_ok());
+    assert!(command(2, 3, 3).check_parameters().is_ok());
+    assert!(command(2, 5, 5).check_parameters().is_ok());
+    assert!(command(0, 3, 3).check_parameters().is_err());
+    assert!(command(3, 3, 3).check_parameters().is_err());
+    assert!(command(1, 3, 4).check_parameters().is_err());
+}
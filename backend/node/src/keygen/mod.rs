@@ -1,10 +1,12 @@
+pub mod bls_dkg;
 pub mod ecdsa;
 pub mod eddsa;
 pub mod key_import;
 pub mod sr25519;
+pub mod sr25519_dkg;
 
 use crate::command::{ JsonCommand, MsgContext };
-use anyhow::Result;
+use anyhow::{ bail, Result };
 use serde::{ Deserialize, Serialize };
 use shared::key_info::NodeId;
 
@@ -15,12 +17,32 @@ pub struct KeyGenCommand {
     pub party_nodes: Vec<NodeId>,
     pub key_id: String,
     pub session_id: String,
+    /// Threshold as stored in keyshares, threshold + 1 parties are needed to sign
+    #[serde(default = "default_threshold")]
+    pub threshold: usize,
+}
+
+/// Keys were generated with a threshold of 2 before it could be chosen
+fn default_threshold() -> usize {
+    2
 }
 
 impl KeyGenCommand {
     pub fn share_count(&self) -> usize {
         self.party_nodes.len()
     }
+
+    /// Threshold of the keyshares, once checked against the size of the party
+    pub fn keyshare_threshold(&self) -> Result<usize> {
+        if self.threshold == 0 || self.threshold >= self.share_count() {
+            bail!(
+                "Threshold of {} is not valid for a party of {} nodes",
+                self.threshold,
+                self.share_count()
+            );
+        }
+        Ok(self.threshold)
+    }
 }
 
 impl JsonCommand for KeyGenCommand {
@@ -30,7 +52,8 @@ impl JsonCommand for KeyGenCommand {
         match self.kind {
             Key::ECDSA => ecdsa::orchestrate::orchestrate(self, ctx),
             Key::EDDSA => eddsa::orchestrate::orchestrate(self, ctx),
-            Key::Sr25519 => { todo!() }
+            Key::Sr25519 => sr25519_dkg::orchestrate::orchestrate(self, ctx),
+            Key::Bls12381 => bls_dkg::orchestrate::orchestrate(self, ctx),
         }
     }
 }
@@ -41,6 +64,8 @@ pub enum Key {
     ECDSA,
     EDDSA,
     Sr25519,
+    /// Keys on G1 with signatures on G2, as Ethereum validators use them
+    Bls12381,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,6 +74,7 @@ pub enum KeyGenResponse {
     ECDSA(ecdsa::KeyGenResult),
     EDDSA(eddsa::KeyGenResult),
     Sr25519(sr25519::KeyGenResponse),
+    Bls12381(bls_dkg::KeyGenResult),
 }
 
 pub struct ShareParams {
@@ -0,0 +1,179 @@
+pub mod orchestrate;
+pub mod session;
+
+use crate::recovery::calculator::RecoveryCalculator;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar };
+use curv::BigInt;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{ CompressedRistretto, RistrettoPoint };
+use curve25519_dalek::scalar::Scalar as RistrettoScalar;
+use curve25519_dalek::traits::Identity;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha512 };
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct KeyGenResult {
+    /// Sr25519 public key, hex encoded the way schnorrkel encodes it
+    pub pk: String,
+}
+
+/// Public share of a party on the Ristretto group that Sr25519 keys live on.
+///
+/// Sr25519 keyshares are created by the EdDSA key generation, so the VSS commitments only fix the
+/// public shares on Ed25519. The proof shows that both public shares have the same discrete log,
+/// which keeps a party from using anything but its keyshare on Ristretto.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RistrettoPublicShare {
+    pub party_index: usize,
+    /// Compressed Ristretto point, hex encoded
+    pub point: String,
+    ed25519_commitment: Point<Ed25519>,
+    ristretto_commitment: String,
+    response: Scalar<Ed25519>,
+}
+
+impl RistrettoPublicShare {
+    pub fn new(party_index: usize, x_i: &Scalar<Ed25519>) -> Self {
+        let point = RISTRETTO_BASEPOINT_POINT * to_ristretto_scalar(x_i);
+        let nonce = Scalar::<Ed25519>::random();
+        let ed25519_commitment = Point::generator() * &nonce;
+        let ristretto_commitment = RISTRETTO_BASEPOINT_POINT * to_ristretto_scalar(&nonce);
+        let challenge = proof_challenge(
+            party_index,
+            &(Point::generator() * x_i),
+            &point.compress(),
+            &ed25519_commitment,
+            &ristretto_commitment.compress()
+        );
+
+        Self {
+            party_index,
+            point: hex::encode(point.compress().as_bytes()),
+            ed25519_commitment,
+            ristretto_commitment: hex::encode(ristretto_commitment.compress().as_bytes()),
+            response: nonce + challenge * x_i,
+        }
+    }
+
+    /// Checks the proof against the Ed25519 public share of the party and returns its public
+    /// share on Ristretto
+    pub fn verify(&self, ed25519_public_share: &Point<Ed25519>) -> Result<RistrettoPoint> {
+        let point = decode_point(&self.point)?;
+        let ristretto_commitment = decode_point(&self.ristretto_commitment)?;
+        let challenge = proof_challenge(
+            self.party_index,
+            ed25519_public_share,
+            &point.compress(),
+            &self.ed25519_commitment,
+            &ristretto_commitment.compress()
+        );
+
+        let ed25519_valid =
+            Point::generator() * &self.response ==
+            &self.ed25519_commitment + ed25519_public_share * &challenge;
+        let ristretto_valid =
+            RISTRETTO_BASEPOINT_POINT * to_ristretto_scalar(&self.response) ==
+            ristretto_commitment + point * to_ristretto_scalar(&challenge);
+        if !ed25519_valid || !ristretto_valid {
+            bail!("Public share of party {} does not match its keyshare", self.party_index);
+        }
+        Ok(point)
+    }
+}
+
+/// Interpolates the public key from the Ristretto public shares of at least threshold + 1 parties
+pub fn public_key_from_shares(shares: &[(usize, RistrettoPoint)]) -> Result<RistrettoPoint> {
+    let indices = shares
+        .iter()
+        .map(|(index, _)| *index)
+        .collect::<Vec<_>>();
+    let mut public_key = RistrettoPoint::identity();
+    for (index, share) in shares {
+        let lambda = RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(
+            *index,
+            &indices
+        )?;
+        public_key += share * to_ristretto_scalar(&lambda);
+    }
+    Ok(public_key)
+}
+
+/// Commitments to the sum of the polynomials that every party dealt, which the keyshares lie on
+pub fn combine_vss<E: Curve>(vss_scheme_vec: &[VerifiableSS<E>]) -> Result<VerifiableSS<E>> {
+    let first = vss_scheme_vec.first().ok_or_else(|| anyhow!("No VSS schemes to combine"))?;
+    if vss_scheme_vec.iter().any(|vss| vss.commitments.len() != first.commitments.len()) {
+        bail!("VSS schemes were created with different thresholds");
+    }
+    let commitments = (0..first.commitments.len())
+        .map(|k| {
+            vss_scheme_vec.iter().fold(Point::zero(), |acc, vss| acc + &vss.commitments[k])
+        })
+        .collect();
+    Ok(VerifiableSS {
+        parameters: first.parameters.clone(),
+        commitments,
+    })
+}
+
+/// Both groups have the same prime order, so scalars carry over as their little endian bytes
+pub fn to_ristretto_scalar(scalar: &Scalar<Ed25519>) -> RistrettoScalar {
+    let be_bytes = scalar.to_bigint().to_bytes();
+    let mut bytes = [0; 32];
+    bytes[32 - be_bytes.len()..].copy_from_slice(&be_bytes);
+    bytes.reverse();
+    RistrettoScalar::from_bytes_mod_order(bytes)
+}
+
+pub fn decode_point(encoded: &str) -> Result<RistrettoPoint> {
+    let bytes = hex::decode(encoded)?;
+    if bytes.len() != 32 {
+        bail!("Ristretto points have to be 32 bytes long");
+    }
+    CompressedRistretto::from_slice(&bytes)
+        .decompress()
+        .ok_or_else(|| anyhow!("Invalid Ristretto point encoding"))
+}
+
+fn proof_challenge(
+    party_index: usize,
+    ed25519_public_share: &Point<Ed25519>,
+    ristretto_public_share: &CompressedRistretto,
+    ed25519_commitment: &Point<Ed25519>,
+    ristretto_commitment: &CompressedRistretto
+) -> Scalar<Ed25519> {
+    let mut hasher = Sha512::new();
+    hasher.update(b"gridlock/sr25519/public-share");
+    hasher.update((party_index as u32).to_be_bytes());
+    hasher.update(&*ed25519_public_share.to_bytes(true));
+    hasher.update(ristretto_public_share.as_bytes());
+    hasher.update(&*ed25519_commitment.to_bytes(true));
+    hasher.update(ristretto_commitment.as_bytes());
+    Scalar::<Ed25519>::from_bigint(&BigInt::from_bytes(&hasher.finalize()))
+}
+
+#[test]
+fn public_shares_interpolate_to_public_key() {
+    let secret = Scalar::<Ed25519>::random();
+    let (vss, shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+
+    let public_shares = [1, 3]
+        .iter()
+        .map(|&party_index| {
+            let public_share = RistrettoPublicShare::new(party_index, &shares[party_index - 1]);
+            let point = public_share
+                .verify(&vss.get_point_commitment(party_index as u16))
+                .unwrap();
+            (party_index, point)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        public_key_from_shares(&public_shares).unwrap(),
+        RISTRETTO_BASEPOINT_POINT * to_ristretto_scalar(&secret)
+    );
+
+    let public_share = RistrettoPublicShare::new(2, &Scalar::<Ed25519>::random());
+    assert!(public_share.verify(&vss.get_point_commitment(2)).is_err());
+}
@@ -0,0 +1,205 @@
+use crate::client_identity::save_client_credentials;
+use crate::communication::nats::{
+    BaseMessenger,
+    NatsBaseMessenger,
+    NatsBaseSession,
+    NatsPeerMessenger,
+    PeerMessenger,
+};
+use crate::communication::protocol::{
+    AllRounds,
+    KeyGenAllRounds,
+    KeyGenSr25519AllRounds,
+    Topic,
+};
+use crate::keygen::eddsa::client::KeyGenClient;
+use crate::keygen::eddsa::session::{ NewKeyGenMessage, NewKeyGenSession };
+use crate::keygen::sr25519_dkg::{
+    combine_vss,
+    public_key_from_shares,
+    KeyGenResult,
+    RistrettoPublicShare,
+};
+use crate::keygen::ShareParams;
+use crate::node::NodeIdentity;
+use crate::storage::{ KeyshareSaver, Sr25519 };
+use crate::App;
+use anyhow::bail;
+use std::thread;
+use tracing::{ error, info, instrument };
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let parsed_message = match serde_json::from_slice::<NewKeyGenMessage>(&message.data[..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    let recovery_email = parsed_message.email.clone();
+    if
+        let Err(err) = save_client_credentials(
+            &parsed_message.key_id,
+            &recovery_email,
+            parsed_message.identity_key.as_ref(),
+            &parsed_message.client_e2e_public_key,
+            &parsed_message.encrypted_signing_key
+        )
+    {
+        error!("Failed to save client credentials: {}", err);
+        return;
+    }
+
+    let session = NewKeyGenSession {
+        key_id: parsed_message.key_id,
+        share_indices: parsed_message.share_indices,
+        threshold: parsed_message.threshold,
+    };
+
+    for (thread_index, party_index) in session.share_indices.clone().iter().enumerate() {
+        let key = session.key_id.clone();
+        let nc = app.nc.clone();
+        let session = session.clone();
+        let party_index = *party_index;
+
+        let mut keyshare_saver = KeyshareSaver::new_creator(&key).with_email(&recovery_email);
+        if thread_index > 0 {
+            keyshare_saver = KeyshareSaver::new_encryptor(&key, thread_index).with_email(
+                &recovery_email
+            );
+        }
+
+        match
+            thread::Builder
+                ::new()
+                .name(format!("sr25519_key_gen_session_{}_{}", key, thread_index))
+                .spawn(move ||
+                    keygen_session(nc, session, party_index, thread_index, keyshare_saver)
+                )
+        {
+            Ok(_) => info!("Spawned a thread to handle Sr25519 key gen"),
+            Err(_) => error!("Failed to spawn thread for Sr25519 keygen session {}", key),
+        };
+    }
+}
+
+#[instrument(skip_all)]
+fn keygen_session(
+    conn: nats::Connection,
+    session: NewKeyGenSession,
+    party_index: usize,
+    thread_index: usize,
+    keysaver: KeyshareSaver
+) -> anyhow::Result<()> {
+    let session_id = session.key_id.clone();
+    match keygen_session_inner(conn, session, party_index, thread_index, keysaver) {
+        Ok(_) => {
+            info!("Sr25519 key generation completed sucessfully, key id: {}", session_id);
+        }
+        Err(err) => error!("Error in key generation: session id: {}, error: {}", session_id, err),
+    }
+    Ok(())
+}
+
+fn keygen_session_inner(
+    conn: nats::Connection,
+    session: NewKeyGenSession,
+    party_index: usize,
+    thread_index: usize,
+    keysaver: KeyshareSaver
+) -> anyhow::Result<()> {
+    let node = NodeIdentity::load()?;
+    let key_id = session.key_id.clone();
+
+    let nats_session = NatsBaseSession {
+        session_id: key_id.clone(),
+        thread_index,
+        node_id: node.node_id.to_string(),
+        public_key: node.networking_public_key,
+        party_index,
+    };
+
+    // Both messengers subscribe before joining, so no public share is missed
+    let public_share_messenger = NatsBaseMessenger::<KeyGenSr25519AllRounds>::new(
+        Topic::KeyGenSr25519,
+        conn.clone(),
+        nats_session.clone()
+    )?;
+    let messenger = NatsBaseMessenger::<KeyGenAllRounds>::new(
+        Topic::KeyGenSr25519,
+        conn,
+        nats_session
+    )?;
+    let join_response = messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+
+    let party_count = join_response.party_count;
+    let mut all_party_indices = join_response.all_party_indices;
+    all_party_indices.sort();
+
+    let public_share_peer_messenger = NatsPeerMessenger::from(
+        public_share_messenger,
+        party_count,
+        all_party_indices.clone()
+    )?;
+    let peer_messenger = NatsPeerMessenger::from(
+        messenger,
+        party_count,
+        all_party_indices.clone()
+    )?;
+
+    let keygen_client = KeyGenClient {
+        peer_messenger,
+        share_params: ShareParams {
+            threshold: session.threshold,
+            party_count,
+            party_index,
+        },
+        all_party_indices: all_party_indices.clone(),
+    };
+
+    let eddsa_keyshare = keygen_client.create_shared_key()?;
+    let vss_scheme = combine_vss(&eddsa_keyshare.vss_scheme_vec)?;
+
+    let public_shares = public_share_peer_messenger.broadcast_and_collect_messages(
+        &<KeyGenSr25519AllRounds as AllRounds>::BroadcastRound::PublicShare,
+        RistrettoPublicShare::new(party_index, &eddsa_keyshare.x_i)
+    )?;
+    let mut points = Vec::new();
+    for public_share in public_shares.iter() {
+        let ed25519_public_share = vss_scheme.get_point_commitment(
+            public_share.party_index as u16
+        );
+        points.push((public_share.party_index, public_share.verify(&ed25519_public_share)?));
+    }
+    let mut share_indices = points
+        .iter()
+        .map(|(index, _)| *index)
+        .collect::<Vec<_>>();
+    share_indices.sort();
+    if share_indices != all_party_indices {
+        bail!("Public shares do not match the parties of the session");
+    }
+    let pk = hex::encode(public_key_from_shares(&points)?.compress().as_bytes());
+    info!("Verified Ristretto public shares of all parties");
+
+    let keyshare = Sr25519 {
+        secret_key: None,
+        threshold: session.threshold,
+        party_index,
+        x_i: eddsa_keyshare.x_i.into(),
+        vss_scheme: vss_scheme.into(),
+    };
+    match keysaver.save_key(&keyshare) {
+        Ok(()) => {
+            info!("Saved new key to file: {}", &key_id);
+        }
+        Err(err) => {
+            bail!("Unable to save key to file: {}", err);
+        }
+    }
+
+    keygen_client.publish_result(KeyGenResult { pk })?;
+
+    Ok(())
+}
@@ -1,10 +1,13 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
 
+pub mod address;
 pub mod auth;
+pub mod client_identity;
 pub mod command;
 pub mod communication;
 pub mod config;
+pub mod decryption;
 pub mod eject;
 pub mod encryption;
 pub mod ghost_shares;
@@ -124,17 +127,35 @@ pub fn handle_message(app: &App, message: nats::Message) {
         keygen::ecdsa::session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.keySign.") {
         signing::ecdsa::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.keyPresign.") {
+        signing::ecdsa::session::handle_new_presign_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.KeyGenEdDSA.") {
         eddsa::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeyGenSr25519.") {
+        keygen::sr25519_dkg::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeyGenBls12381.") {
+        keygen::bls_dkg::session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.KeySignEdDSA.") {
         signing::eddsa::session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.KeySignSr25519.") {
         signing::sr25519_musign::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeySignSchnorrSecp256k1.") {
+        signing::schnorr_secp256k1::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeySignBls12381.") {
+        signing::bls::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeySignAdaptor.") {
+        signing::adaptor::session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.Decrypt.") {
+        decryption::session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.Message.") {
         // To be able manage partner, user and gridlock nodes
         let _ = command::handle_nats_command(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.KeyShareRecovery.") {
         recovery::recovery_session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeyShareRefresh.") {
+        recovery::refresh_session::handle_new_session_message(app, message);
+    } else if message.subject.starts_with("network.gridlock.nodes.KeyShareReshare.") {
+        recovery::reshare_session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.UserRecovery.") {
         user_recovery::session::handle_new_session_message(app, message);
     } else if message.subject.starts_with("network.gridlock.nodes.UserRecoveryConfirm.") {
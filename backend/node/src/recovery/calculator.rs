@@ -1,8 +1,5 @@
 use anyhow::{ anyhow, bail, Result };
-use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
-    ShamirSecretSharing,
-    VerifiableSS,
-};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::{ Curve, Point, Scalar };
 use curv::BigInt;
 use itertools::Itertools;
@@ -51,24 +48,37 @@ impl<C> RecoveryCalculator<C> where C: Curve {
         Self::create_linear_shares_of_scalar(lc, self.threshold)
     }
 
-    pub fn create_secret_sharing_of_zero_point(&self) -> LinearShareParts<C> {
-        let sss_params = ShamirSecretSharing {
-            threshold: self.threshold as u16,
-            share_count: 5,
-        };
-        let all_parties = &*self.party.all_parties
+    /// Feldman sharing of zero across all parties, so adding the shares to existing keyshares
+    /// re-randomises them without changing the shared secret
+    pub fn create_secret_sharing_of_zero_point(&self) -> (VerifiableSS<C>, LinearShareParts<C>) {
+        let all_parties = self.party.all_parties
             .iter()
             .map(|&i| i as u16)
             .collect_vec();
-        let li = VerifiableSS::<C>::map_share_to_new_params(
-            &sss_params,
-            self.party.party_index as u16,
-            all_parties
+        let (vss, shares) = VerifiableSS::<C>::share_at_indices(
+            self.threshold as u16,
+            all_parties.len() as u16,
+            &Scalar::zero(),
+            &all_parties
         );
 
-        let lc = self.secret_share.clone() * li;
+        let mut retained = Scalar::<C>::zero();
+        let mut for_peer_exchange = Vec::new();
+        for (&index, share) in self.party.all_parties.iter().zip(shares.iter()) {
+            if index == self.party.party_index {
+                retained = share.clone();
+            } else {
+                for_peer_exchange.push(share.clone());
+            }
+        }
 
-        Self::create_linear_shares_of_scalar(lc, self.threshold)
+        (
+            vss,
+            LinearShareParts {
+                retained,
+                for_peer_exchange,
+            },
+        )
     }
 
     fn create_linear_shares_of_scalar(
@@ -115,6 +125,32 @@ impl<C> RecoveryCalculator<C> where C: Curve {
         }
     }
 
+    /// Adds a sharing of zero to a keyshare VSS, commitment by commitment
+    pub fn add_zero_sharing_to_vss(
+        vss: &VerifiableSS<C>,
+        zero_vss: &VerifiableSS<C>
+    ) -> Result<VerifiableSS<C>> {
+        if vss.commitments.len() != zero_vss.commitments.len() {
+            bail!(
+                "Zero sharing has {} commitments, but the keyshare VSS has {}",
+                zero_vss.commitments.len(),
+                vss.commitments.len()
+            );
+        }
+        if !zero_vss.commitments.first().map_or(false, |c| c.is_zero()) {
+            bail!("Zero sharing does not commit to zero");
+        }
+
+        Ok(VerifiableSS {
+            parameters: vss.parameters.clone(),
+            commitments: vss.commitments
+                .iter()
+                .zip(zero_vss.commitments.iter())
+                .map(|(c, z)| c + z)
+                .collect(),
+        })
+    }
+
     pub fn calculate_y_sum_from_vss_vec(vss_vec: &[VerifiableSS<C>]) -> Result<Point<C>>
         where C: Curve
     {
@@ -141,6 +177,20 @@ impl<C> RecoveryCalculator<C> where C: Curve {
         Ok(y_sum_from_vss.clone())
     }
 
+    /// Lagrange coefficient at zero for the share held at x = `index`, with shares held at `indices`
+    pub fn lagrange_coefficient_at_zero(index: usize, indices: &[usize]) -> Result<Scalar<C>> {
+        let xi = Scalar::<C>::from(&BigInt::from(index as u32));
+        let mut num = Scalar::<C>::from(&BigInt::from(1u32));
+        let mut denum = Scalar::<C>::from(&BigInt::from(1u32));
+        for &j in indices.iter().filter(|&&j| j != index) {
+            let xj = Scalar::<C>::from(&BigInt::from(j as u32));
+            num = num * &xj;
+            denum = denum * (xj - &xi);
+        }
+        let denum = denum.invert().ok_or(anyhow!("Share indices are not unique"))?;
+        Ok(num * denum)
+    }
+
     pub fn map_share_to_new_params_for_x(x_index: usize, index: usize, s: &[usize]) -> Scalar<C> {
         let s_len = s.len();
         let mut all_indices = s.to_vec();
@@ -3,6 +3,7 @@ use crate::communication::{ nats::DummyMessenger, protocol::Topic };
 use crate::node::NodeIdentity;
 use crate::recovery::encryption::NKeyTargetEncryptor;
 use crate::recovery::target_role::{
+    Bls12381BehaviourTargetRole,
     ECDSABehaviourTargetRole,
     EdDSABehaviourTargetRole,
     KeyshareBehaviourTargetRole,
@@ -16,8 +17,9 @@ use crate::recovery::{
     RecoveryValidationResult,
 };
 use crate::security::check_for_small_primes;
+use crate::security::paillier_proofs::{ proof_context, PaillierKeyProof };
 use crate::storage::{ KeyshareAccessor, ECDSA };
-use anyhow::{ anyhow, Result };
+use anyhow::{ anyhow, bail, Result };
 use paillier::EncryptionKey;
 use serde::{ Deserialize, Serialize };
 use shared::recovery::{
@@ -68,19 +70,52 @@ impl JsonCommand for ReceiveRecoveryPackages {
                 let role = Sr25519BehaviourTargetRole::new(&self.recovery_info.key_id);
                 process_rec_package(self, role)
             }
+            Key::Bls12381 => {
+                let role = Bls12381BehaviourTargetRole::new(&self.recovery_info.key_id);
+                process_rec_package(self, role)
+            }
         }
     }
 }
 
+/// Check the Paillier key of party `index` before it replaces the one we hold, verifying its
+/// proofs against our own ring-Pedersen parameters
+fn verify_paillier_key(
+    key: &ECDSA,
+    key_id: &str,
+    index: usize,
+    ek: &EncryptionKey,
+    key_proof: &str
+) -> Result<()> {
+    // Security issue: CVE-2023-33241
+    check_for_small_primes(ek)?;
+
+    if index == key.party_index {
+        if ek.n != &key.paillier_dk.p * &key.paillier_dk.q {
+            bail!("Paillier key for own keyshare does not match its decryption key");
+        }
+        return Ok(());
+    }
+    let own_statement = key.h1_h2_N_tilde_vec
+        .get(key.party_index - 1)
+        .ok_or_else(|| anyhow!("Ring-Pedersen parameters for own keyshare are missing"))?;
+    let proof = serde_json::from_str::<PaillierKeyProof>(key_proof)?;
+    proof
+        .verify(ek, key.party_index, own_statement, &proof_context(key_id, index))
+        .map_err(|err| anyhow!("Paillier key for party {} is invalid: {}", index, err))
+}
+
 impl JsonCommand for UpdatePaillierKeysCommand {
     type Response = ();
     fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
-        // Security issue: CVE-2023-33241
-        for ek in &self.new_eks {
-            check_for_small_primes(ek)?;
+        if self.key_proofs.len() != self.new_eks.len() {
+            bail!("Expected a proof for each of the {} Paillier keys", self.new_eks.len());
         }
 
         let mut ka = KeyshareAccessor::<ECDSA>::modifiable(&self.key_id)?;
+        for (i, (ek, key_proof)) in self.new_eks.iter().zip(&self.key_proofs).enumerate() {
+            verify_paillier_key(&ka.key, &self.key_id, i + 1, ek, key_proof)?;
+        }
         save_new_paillier_keys(&mut ka, self.new_eks)?;
         Ok(())
     }
@@ -89,10 +124,8 @@ impl JsonCommand for UpdatePaillierKeysCommand {
 impl JsonCommand for UpdateSinglePaillierKeyCommand {
     type Response = ();
     fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
-        // Security issue: CVE-2023-33241
-        check_for_small_primes(&self.new_ek)?;
-
         let mut ka = KeyshareAccessor::<ECDSA>::modifiable(&self.key_id)?;
+        verify_paillier_key(&ka.key, &self.key_id, self.index, &self.new_ek, &self.key_proof)?;
         update_paillier_keys(&mut ka, self.index, self.new_ek)?;
         Ok(())
     }
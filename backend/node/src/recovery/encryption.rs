@@ -17,8 +17,12 @@ pub trait HelperEncryptor {
     fn encrypt_for_peers<T: Serialize>(&self, inputs: Vec<T>) -> Result<Vec<Self::Output>>;
     //decrypt from other party members (number of messages will be total number of party members minus one)
     fn decrypt_from_peers<T: DeserializeOwned>(&self, inputs: Vec<Self::Output>) -> Result<Vec<T>>;
-    //encrypt for individual by index
-    fn encrypt_for_target<T: Serialize>(&self, input: T) -> Result<Self::Output>;
+    //encrypt for the target receiving the share at the recovery index
+    fn encrypt_for_target<T: Serialize>(
+        &self,
+        recovery_index: usize,
+        input: T
+    ) -> Result<Self::Output>;
 }
 pub trait TargetEncryptor {
     type Output: Serialize + Clone + DeserializeOwned;
@@ -29,6 +33,14 @@ pub trait TargetEncryptor {
     ) -> Result<Vec<T>>;
 }
 
+pub trait PeerEncryptor {
+    type Output: Serialize + Clone + DeserializeOwned;
+    //encrypt for other party members
+    fn encrypt_for_peers<T: Serialize>(&self, inputs: Vec<T>) -> Result<Vec<Self::Output>>;
+    //decrypt from other party members (number of messages will be total number of party members minus one)
+    fn decrypt_from_peers<T: DeserializeOwned>(&self, inputs: Vec<Self::Output>) -> Result<Vec<T>>;
+}
+
 pub struct Plaintext;
 
 impl Plaintext {
@@ -56,7 +68,11 @@ impl HelperEncryptor for Plaintext {
             .map(|p| self.decrypt(p.to_vec()))
             .collect()
     }
-    fn encrypt_for_target<T: Serialize>(&self, input: T) -> Result<Self::Output> {
+    fn encrypt_for_target<T: Serialize>(
+        &self,
+        _recovery_index: usize,
+        input: T
+    ) -> Result<Self::Output> {
         self.encrypt(input)
     }
 }
@@ -76,13 +92,13 @@ impl TargetEncryptor for Plaintext {
 
 pub struct NKeyHelperEncryptor {
     peer_encryption_keys: Vec<Vec<u8>>,
-    target_encryption_key: Vec<u8>,
+    target_encryption_keys: HashMap<usize, Vec<u8>>,
 }
 
 impl NKeyHelperEncryptor {
     pub fn new<'a>(
         public_keys: &'a HashMap<usize, String>,
-        recovery_index: usize,
+        target_public_keys: &'a HashMap<usize, String>,
         own_index: usize,
         peers: &'a [usize],
         private_key: String
@@ -98,16 +114,18 @@ impl NKeyHelperEncryptor {
                     })
             })
             .collect::<Result<Vec<_>>>()?;
-        let target_pk = public_keys
-            .get(&recovery_index).map(|s| s.to_owned())
-            .ok_or_else(|| anyhow!("Could not find public key corresponding to the target node"))?;
         let peer_encryption_keys = shared_secrets_from_nkeys(&private_key, &peer_pks)?;
         info!("Created peer encryption keys");
-        let target_encryption_key = shared_secret_from_nkeys(&private_key, &target_pk)?;
-        info!("Created target encryption key");
+        let target_encryption_keys = target_public_keys
+            .iter()
+            .map(|(&recovery_index, target_pk)| {
+                Ok((recovery_index, shared_secret_from_nkeys(&private_key, target_pk)?))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        info!("Created target encryption keys");
         Ok(Self {
             peer_encryption_keys,
-            target_encryption_key,
+            target_encryption_keys,
         })
     }
 }
@@ -129,8 +147,64 @@ impl HelperEncryptor for NKeyHelperEncryptor {
             .map(|(i, input)| decrypt_and_deserialize(input, &self.peer_encryption_keys[i]))
             .collect()
     }
-    fn encrypt_for_target<T: Serialize>(&self, input: T) -> Result<Self::Output> {
-        serialize_and_encrypt(&input, &self.target_encryption_key)
+    fn encrypt_for_target<T: Serialize>(
+        &self,
+        recovery_index: usize,
+        input: T
+    ) -> Result<Self::Output> {
+        let target_encryption_key = self.target_encryption_keys
+            .get(&recovery_index)
+            .ok_or_else(|| anyhow!("No target node for the share at index {recovery_index}"))?;
+        serialize_and_encrypt(&input, target_encryption_key)
+    }
+}
+
+pub struct NKeyPeerEncryptor {
+    peer_encryption_keys: Vec<Vec<u8>>,
+}
+
+impl NKeyPeerEncryptor {
+    pub fn new<'a>(
+        public_keys: &'a HashMap<usize, String>,
+        own_index: usize,
+        peers: &'a [usize],
+        private_key: String
+    ) -> Result<Self> {
+        let peer_pks = peers
+            .iter()
+            .filter(|&x| *x != own_index)
+            .map(|i| {
+                public_keys
+                    .get(i).map(|s| s.to_owned())
+                    .ok_or_else(|| {
+                        anyhow!("Could not find public key corresponding to node with keyshare {i}")
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let peer_encryption_keys = shared_secrets_from_nkeys(&private_key, &peer_pks)?;
+        info!("Created peer encryption keys");
+        Ok(Self {
+            peer_encryption_keys,
+        })
+    }
+}
+
+impl PeerEncryptor for NKeyPeerEncryptor {
+    type Output = EncryptedData;
+
+    fn encrypt_for_peers<T: Serialize>(&self, inputs: Vec<T>) -> Result<Vec<Self::Output>> {
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| serialize_and_encrypt(&input, &self.peer_encryption_keys[i]))
+            .collect()
+    }
+    fn decrypt_from_peers<T: DeserializeOwned>(&self, inputs: Vec<Self::Output>) -> Result<Vec<T>> {
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| decrypt_and_deserialize(input, &self.peer_encryption_keys[i]))
+            .collect()
     }
 }
 
@@ -1,10 +1,16 @@
 use crate::communication::nats::PeerMessenger;
 use crate::communication::protocol::{ AllRounds, KeyShareRegenAllRounds };
 use crate::recovery::encryption::HelperEncryptor;
-use crate::recovery::{ ECDSARecoveryPackage, EdDSARecoveryPackage, Party, ShareRecoveryInfo };
-use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
-use anyhow::Result;
-use curv::elliptic::curves::{ Curve, Ed25519, Scalar, Secp256k1 };
+use crate::recovery::{
+    Bls12381RecoveryPackage,
+    ECDSARecoveryPackage,
+    EdDSARecoveryPackage,
+    Party,
+    ShareRecoveryInfo,
+};
+use crate::storage::{ Bls12381, KeyshareAccessor, ECDSA, EDDSA };
+use anyhow::{ anyhow, Result };
+use curv::elliptic::curves::{ Bls12_381_1, Curve, Ed25519, Scalar, Secp256k1 };
 use itertools::Itertools;
 use serde::Serialize;
 use tracing::info;
@@ -33,15 +39,44 @@ impl<M, E, K> KeyshareRecoveryHelper<M, E, K>
         }
     }
 
-    pub fn try_recovery(&mut self, recovery_index: usize, party: Party) -> Result<()> {
-        self.send_recovery_package(recovery_index, party)
+    /// Sends the recovery packages for the shares at `recovery_indices`, produced in a single
+    /// exchange with the other helpers, as one list in the order of the indices
+    pub fn try_recovery(&mut self, recovery_indices: &[usize], party: Party) -> Result<()> {
+        let recovery_packages = self.create_recovery_packages(recovery_indices, party)?;
+        self.messenger.broadcast_message(
+            &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound::DeliverRecoveryPackage,
+            recovery_packages
+        )?;
+        info!("Sent recovery packages for shares {:?}", recovery_indices);
+        Ok(())
     }
 
-    fn send_recovery_package(&mut self, recovery_index: usize, party: Party) -> Result<()> {
+    fn create_recovery_packages(
+        &mut self,
+        recovery_indices: &[usize],
+        party: Party
+    ) -> Result<Vec<<E as HelperEncryptor>::Output>> {
         info!("Starting recovery process as a helper node");
-        let recovery = self.key.get_recovery_params(recovery_index, party);
-        let contrib = recovery.create_secret_sharing_of_lost_share();
-        let encrypted_shares = self.encryptor.encrypt_for_peers(contrib.for_peer_exchange)?;
+        let recoveries = recovery_indices
+            .iter()
+            .map(|&recovery_index| self.key.get_recovery_params(recovery_index, party.clone()))
+            .collect::<Vec<_>>();
+        let contribs = recoveries
+            .iter()
+            .map(|recovery| recovery.create_secret_sharing_of_lost_share())
+            .collect::<Vec<_>>();
+
+        // Every peer gets its part of each lost share in one message
+        let peer_count = contribs.first().map_or(0, |contrib| contrib.for_peer_exchange.len());
+        let for_peer_exchange = (0..peer_count)
+            .map(|peer| {
+                contribs
+                    .iter()
+                    .map(|contrib| contrib.for_peer_exchange[peer].clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let encrypted_shares = self.encryptor.encrypt_for_peers(for_peer_exchange)?;
         info!("Encrypted secret shares");
 
         let received_shares = self.messenger.send_p2p_and_collect_messages(
@@ -49,27 +84,36 @@ impl<M, E, K> KeyshareRecoveryHelper<M, E, K>
             encrypted_shares
         )?;
 
-        let decrypted_shares = self.encryptor.decrypt_from_peers(received_shares)?;
+        let decrypted_shares: Vec<Vec<Scalar<K::Curve>>> = self.encryptor.decrypt_from_peers(
+            received_shares
+        )?;
         info!("Decrypted secret shares");
-        let partial_share = recovery.sum_secret_shares(contrib.retained, decrypted_shares);
-
-        let recovery_package = self.package_result(partial_share)?;
 
-        self.messenger.broadcast_message(
-            &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound::DeliverRecoveryPackage,
-            recovery_package
-        )?;
-        info!("Sent a recovery package");
-        Ok(())
+        let mut recovery_packages = Vec::new();
+        for (i, (recovery, contrib)) in recoveries.iter().zip(contribs).enumerate() {
+            let mixed_shares = decrypted_shares
+                .iter()
+                .map(|shares| {
+                    shares
+                        .get(i)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("Peer did not send a part of every lost share"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let partial_share = recovery.sum_secret_shares(contrib.retained, mixed_shares);
+            recovery_packages.push(self.package_result(recovery.recovery_index, partial_share)?);
+        }
+        Ok(recovery_packages)
     }
 
     pub fn package_result(
         &self,
+        recovery_index: usize,
         secret_share: Scalar<K::Curve>
     ) -> Result<<E as HelperEncryptor>::Output> {
         let result = self.key.create_recovery_result(secret_share);
         info!("Encrypting recovery packages");
-        self.encryptor.encrypt_for_target(result)
+        self.encryptor.encrypt_for_target(recovery_index, result)
     }
 }
 
@@ -161,6 +205,45 @@ impl KeyshareBehaviourHelperRole for ECDSABehaviourHelperRole {
                 .cloned()
                 .map_into()
                 .collect(),
+            chain_code: self.key_accessor.key.chain_code.clone(),
+        }
+    }
+
+    fn get_recovery_params(
+        &self,
+        recovery_index: usize,
+        party: Party
+    ) -> RecoveryCalculator<Self::Curve> {
+        RecoveryCalculator::<Self::Curve> {
+            secret_share: self.key_accessor.key.x_i.clone(),
+            threshold: self.key_accessor.key.threshold,
+            party,
+            recovery_index,
+        }
+    }
+}
+
+/// BLS12-381 specific behaviour for keyshare recovery by a helper guardian
+pub struct Bls12381BehaviourHelperRole {
+    key_accessor: KeyshareAccessor<Bls12381>,
+}
+
+impl Bls12381BehaviourHelperRole {
+    pub fn from_key_accessor(key_accessor: KeyshareAccessor<Bls12381>) -> Self {
+        Self { key_accessor }
+    }
+}
+
+impl KeyshareBehaviourHelperRole for Bls12381BehaviourHelperRole {
+    type Curve = Bls12_381_1;
+    type RecoveryPackage = Bls12381RecoveryPackage;
+
+    fn create_recovery_result(&self, result: Scalar<Self::Curve>) -> Self::RecoveryPackage {
+        Bls12381RecoveryPackage {
+            share_recovery_info: ShareRecoveryInfo {
+                partial_secret: result,
+                vss_vec: vec![self.key_accessor.key.vss_scheme.clone()],
+            },
         }
     }
 
@@ -0,0 +1,236 @@
+use crate::command::{ JsonCommand, MsgContext };
+use crate::encryption::{ sign_with_nkey, verify_nkey_signature };
+use crate::node::NodeIdentity;
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::signing::request_auth::CanonicalEncoder;
+use crate::storage::fs::FileSystem;
+use crate::storage::{ Bls12381, KeyInfoStore, KeyshareAccessor, Sr25519, ECDSA, EDDSA };
+use anyhow::{ anyhow, bail, Result };
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Curve, Ed25519, Scalar };
+use serde::{ Deserialize, Serialize };
+use shared::key_info::{ KeyInfo, NodeId };
+use std::fmt::Debug;
+use tracing::info;
+
+/// Sent to the new holder once it saved the migrated share, which it confirms by signing the
+/// handover with its networking key once the share matches the VSS commitments of the key
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ConfirmKeyshareHandoverCommand {
+    pub key_id: String,
+    pub handover_share_index: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct KeyshareHandoverConfirmation {
+    /// Base64 signature of the handover by the networking key of the new holder
+    pub signature: String,
+}
+
+impl JsonCommand for ConfirmKeyshareHandoverCommand {
+    type Response = KeyshareHandoverConfirmation;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let node = NodeIdentity::load()?;
+        let share_index = validated_share_index(&self.key_id)?;
+        if share_index != self.handover_share_index {
+            bail!(
+                "This node holds share {} instead of share {} of key {}",
+                share_index,
+                self.handover_share_index,
+                &self.key_id
+            );
+        }
+
+        let node_id = NodeId::new_from_uuid(node.node_id);
+        let message = handover_encoding(&self.key_id, share_index, &node_id);
+        Ok(KeyshareHandoverConfirmation {
+            signature: sign_with_nkey(&node.networking_private_key, &message)?,
+        })
+    }
+}
+
+/// Sent to the old holder once its share was migrated, so it deletes its keyshare
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetireKeyshareCommand {
+    pub key_id: String,
+    pub email: String,
+    pub successor_node_id: NodeId,
+    /// Key info after the migration, with the successor in place of this node
+    pub key_info: KeyInfo,
+    /// Confirmation by the successor that it holds the share
+    pub successor_signature: String,
+    /// Holder of the key that orchestrated the migration
+    pub orchestrator_node_id: NodeId,
+    /// Base64 signature of the command by the networking key of the orchestrator
+    pub orchestrator_signature: String,
+}
+
+impl RetireKeyshareCommand {
+    /// Build the command, signed by the networking key of this node as the orchestrator
+    pub fn new_signed(
+        key_id: &str,
+        email: &str,
+        successor_node_id: NodeId,
+        key_info: KeyInfo,
+        successor_signature: String
+    ) -> Result<Self> {
+        let node = NodeIdentity::load()?;
+        let mut command = Self {
+            key_id: key_id.to_string(),
+            email: email.to_string(),
+            successor_node_id,
+            key_info,
+            successor_signature,
+            orchestrator_node_id: NodeId::new_from_uuid(node.node_id),
+            orchestrator_signature: String::new(),
+        };
+        command.orchestrator_signature = sign_with_nkey(
+            &node.networking_private_key,
+            &command.canonical_encoding()?
+        )?;
+        Ok(command)
+    }
+
+    fn canonical_encoding(&self) -> Result<Vec<u8>> {
+        Ok(
+            CanonicalEncoder::new("retire_keyshare")
+                .str("key_id", &self.key_id)
+                .str("email", &self.email)
+                .str("successor_node_id", &self.successor_node_id.to_string())
+                .str("key_info", &serde_json::to_string(&self.key_info)?)
+                .str("successor_signature", &self.successor_signature)
+                .str("orchestrator_node_id", &self.orchestrator_node_id.to_string())
+                .finish()
+        )
+    }
+}
+
+impl Debug for RetireKeyshareCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RetireKeyshareCommand")
+            .field("key_id", &self.key_id)
+            .field("successor_node_id", &self.successor_node_id)
+            .field("orchestrator_node_id", &self.orchestrator_node_id)
+            .finish()
+    }
+}
+
+impl JsonCommand for RetireKeyshareCommand {
+    type Response = ();
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let node = NodeIdentity::load()?;
+        let node_id = NodeId::new_from_uuid(node.node_id);
+
+        let stored_key_info = KeyInfoStore::get_key_info(&self.key_id)?;
+        let orchestrator = stored_key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == self.orchestrator_node_id)
+            .ok_or_else(|| anyhow!("Orchestrator is not a holder of key {}", &self.key_id))?;
+        verify_nkey_signature(
+            &orchestrator.networking_public_key,
+            &self.canonical_encoding()?,
+            &self.orchestrator_signature
+        ).map_err(|err| anyhow!("Retirement is not signed by the orchestrator: {}", err))?;
+
+        let share_index = stored_key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == node_id)
+            .ok_or_else(|| anyhow!("This node does not hold a share of key {}", &self.key_id))?
+            .share_index;
+
+        if self.key_info.node_pool.iter().any(|n| n.node_id == node_id) {
+            bail!("Key info still lists this node as a holder of key {}", &self.key_id);
+        }
+        let successor = self.key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == self.successor_node_id)
+            .ok_or_else(|| anyhow!("Successor is not a holder of key {}", &self.key_id))?;
+        if successor.share_index != share_index {
+            bail!(
+                "Successor holds share {} instead of share {}",
+                successor.share_index,
+                share_index
+            );
+        }
+        verify_nkey_signature(
+            &successor.networking_public_key,
+            &handover_encoding(&self.key_id, share_index, &self.successor_node_id),
+            &self.successor_signature
+        ).map_err(|err| anyhow!("Successor did not confirm the handover: {}", err))?;
+
+        FileSystem::shred_key_files(&self.key_id, &self.email)?;
+        KeyInfoStore::remove_key_info(&self.key_id)?;
+        info!("Retired keyshare {} of key {}", share_index, &self.key_id);
+        Ok(())
+    }
+}
+
+/// Message the new holder signs to confirm it holds share `share_index` of a key
+fn handover_encoding(key_id: &str, share_index: usize, node_id: &NodeId) -> Vec<u8> {
+    CanonicalEncoder::new("keyshare_handover")
+        .str("key_id", key_id)
+        .str("share_index", &share_index.to_string())
+        .str("node_id", &node_id.to_string())
+        .finish()
+}
+
+/// Index of the share of a key this node holds, whatever its kind, once the share was checked
+/// against the VSS commitments of the key
+fn validated_share_index(key_id: &str) -> Result<usize> {
+    if let Ok(ka) = KeyshareAccessor::<ECDSA>::read_only(key_id) {
+        let key = ka.key;
+        return validate_share(&key.x_i, &key.vss_scheme_vec, key.party_index);
+    }
+    if let Ok(ka) = KeyshareAccessor::<EDDSA>::read_only(key_id) {
+        let key = ka.key;
+        return validate_share(&key.x_i, &key.vss_scheme_vec, key.party_index);
+    }
+    if let Ok(ka) = KeyshareAccessor::<Sr25519>::read_only(key_id) {
+        let key = ka.key;
+        let x_i: Scalar<Ed25519> = key.x_i.into();
+        return validate_share(&x_i, &[key.vss_scheme.into()], key.party_index);
+    }
+    if let Ok(ka) = KeyshareAccessor::<Bls12381>::read_only(key_id) {
+        let key = ka.key;
+        return validate_share(&key.x_i, &[key.vss_scheme], key.party_index);
+    }
+    bail!("This node does not hold a share of key {}", key_id)
+}
+
+fn validate_share<C: Curve>(
+    x_i: &Scalar<C>,
+    vss_scheme_vec: &[VerifiableSS<C>],
+    share_index: usize
+) -> Result<usize> {
+    if vss_scheme_vec.is_empty() {
+        bail!("Keyshare {} has no VSS commitments", share_index);
+    }
+    RecoveryCalculator::<C>
+        ::validate_recovered_share(x_i, vss_scheme_vec, share_index)
+        .map_err(|_| {
+            anyhow!("Keyshare {} does not match the VSS commitments of the key", share_index)
+        })?;
+    Ok(share_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::elliptic::curves::Secp256k1;
+
+    #[test]
+    fn handover_is_confirmed_only_for_a_share_matching_the_commitments() {
+        let secret = Scalar::<Secp256k1>::random();
+        let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+        let vss_scheme_vec = vec![vss];
+
+        assert_eq!(validate_share(&shares[1], &vss_scheme_vec, 2).unwrap(), 2);
+        assert!(validate_share(&shares[1], &vss_scheme_vec, 3).is_err());
+        assert!(validate_share(&Scalar::random(), &vss_scheme_vec, 2).is_err());
+        assert!(validate_share(&shares[1], &[], 2).is_err());
+    }
+}
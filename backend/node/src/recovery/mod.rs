@@ -2,43 +2,94 @@ mod calculator;
 mod commands;
 mod encryption;
 mod helper_role;
+pub mod migration;
 pub mod orchestrate;
 pub mod recovery_session;
+mod refresh_role;
+pub mod refresh_session;
+pub mod reshare;
+pub mod reshare_session;
 mod target_role;
 
 use crate::command::{ JsonCommand, MsgContext };
-use crate::recovery::orchestrate::orchestrate;
+use crate::recovery::orchestrate::{
+    orchestrate,
+    orchestrate_migration,
+    orchestrate_refresh,
+    orchestrate_reshare,
+};
+use crate::security::paillier_proofs::PaillierKeyProof;
 use crate::storage::KeyshareAccessor;
 use crate::storage::ECDSA;
-use anyhow::{ anyhow, Result };
+use anyhow::{ anyhow, bail, Result };
 pub use calculator::RecoveryCalculator;
 pub use commands::GetPaillierKeysCommand;
 use curv::arithmetic::Zero;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use curv::elliptic::curves::{ Bls12_381_1, Curve, Ed25519, Point, Scalar, Secp256k1 };
 use curv::BigInt;
 use derive_more::Display;
 use itertools::Itertools;
 use paillier::EncryptionKey;
 use serde::{ Deserialize, Serialize };
-use shared::key_info::NodeId;
+use shared::key_info::{ Node, NodeId };
 use shared::recovery::Key;
 use std::collections::HashMap;
 use zk_paillier::zkproofs::DLogStatement;
 
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ShareReplacement {
+    pub old_node_id: NodeId,
+    pub new_node_id: NodeId,
+    pub new_node_public_key: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct RecoveryCommand {
     #[serde(flatten)]
     kind: Key,
     key_id: String,
     session_id: String,
-    new_node_id: NodeId,
-    new_node_public_key: String,
-    old_node_id: NodeId,
+    #[serde(default)]
+    new_node_id: Option<NodeId>,
+    #[serde(default)]
+    new_node_public_key: Option<String>,
+    #[serde(default)]
+    old_node_id: Option<NodeId>,
+    /// Lost shares recovered together in one session, in place of a single replacement
+    #[serde(default)]
+    replacements: Vec<ShareReplacement>,
     party_nodes: Vec<NodeId>,
     email: String,
 }
 
+impl RecoveryCommand {
+    /// Replaced shares, from either the single replacement or the list of replacements
+    fn replacements(&self) -> Result<Vec<ShareReplacement>> {
+        match (&self.old_node_id, &self.new_node_id, &self.new_node_public_key) {
+            (Some(old_node_id), Some(new_node_id), Some(new_node_public_key)) => {
+                if !self.replacements.is_empty() {
+                    bail!("Either a single replacement or a list of replacements has to be given");
+                }
+                Ok(
+                    vec![ShareReplacement {
+                        old_node_id: old_node_id.clone(),
+                        new_node_id: new_node_id.clone(),
+                        new_node_public_key: new_node_public_key.clone(),
+                    }]
+                )
+            }
+            (None, None, None) => {
+                if self.replacements.is_empty() {
+                    bail!("No share to recover was given");
+                }
+                Ok(self.replacements.clone())
+            }
+            _ => bail!("Old node, new node and its public key have to be given together"),
+        }
+    }
+}
+
 impl JsonCommand for RecoveryCommand {
     type Response = RecoveryResponse;
 
@@ -52,12 +103,100 @@ pub enum RecoveryResponse {
     Completed,
 }
 
+/// Move a share from a healthy holder to a new node, the old holder deletes its keyshare once the
+/// new one is validated
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct MigrationCommand {
+    #[serde(flatten)]
+    kind: Key,
+    key_id: String,
+    session_id: String,
+    old_node_id: NodeId,
+    new_node_id: NodeId,
+    new_node_public_key: String,
+    /// Holders helping the old node, one less than the number of nodes needed to sign
+    helper_nodes: Vec<NodeId>,
+    email: String,
+}
+
+impl JsonCommand for MigrationCommand {
+    type Response = MigrationResponse;
+
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        orchestrate_migration(self, ctx).map(|_| MigrationResponse::Completed)
+    }
+}
+
+#[derive(Serialize)]
+pub enum MigrationResponse {
+    Completed,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct KeyShareRefreshCommand {
+    #[serde(flatten)]
+    kind: Key,
+    key_id: String,
+    session_id: String,
+    email: String,
+}
+
+impl JsonCommand for KeyShareRefreshCommand {
+    type Response = KeyShareRefreshResponse;
+
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        orchestrate_refresh(self, ctx).map(|_| KeyShareRefreshResponse::Completed)
+    }
+}
+
+#[derive(Serialize)]
+pub enum KeyShareRefreshResponse {
+    Completed,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReshareNode {
+    pub node_id: NodeId,
+    pub networking_public_key: String,
+    pub kind: Node,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReshareCommand {
+    #[serde(flatten)]
+    kind: Key,
+    key_id: String,
+    session_id: String,
+    /// Current holders dealing their keyshares, more than the current threshold are needed
+    party_nodes: Vec<NodeId>,
+    /// New committee, share indices are assigned in the given order
+    new_nodes: Vec<ReshareNode>,
+    /// Threshold as stored in keyshares, threshold + 1 members of the new committee are needed to
+    /// sign
+    threshold: usize,
+    email: String,
+}
+
+impl JsonCommand for ReshareCommand {
+    type Response = ReshareResponse;
+
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        orchestrate_reshare(self, ctx).map(|_| ReshareResponse::Completed)
+    }
+}
+
+#[derive(Serialize)]
+pub enum ReshareResponse {
+    Completed,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub enum RecoveryRole {
     Helper,
     Target,
 }
 
+#[derive(Clone)]
 pub struct Party {
     pub party_index: usize,
     pub all_parties: Vec<usize>,
@@ -68,12 +207,19 @@ pub struct EdDSARecoveryPackage {
     pub share_recovery_info: ShareRecoveryInfo<Ed25519>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bls12381RecoveryPackage {
+    pub share_recovery_info: ShareRecoveryInfo<Bls12_381_1>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ECDSARecoveryPackage {
     pub share_recovery_info: ShareRecoveryInfo<Secp256k1>,
     pub h1_h2_N_tilde_vec: Vec<DLogStatement>,
     pub paillier_key_vec: Vec<EncryptionKey>,
     pub public_key_vec: Vec<Point<Secp256k1>>,
+    #[serde(default)]
+    pub chain_code: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -102,8 +248,11 @@ impl RecoveryValidationResult {
         RecoveryValidationResult::EDDSA(ValidatedResult::Validated)
     }
 
-    pub fn validated_with_eks(eks: EncryptionKey) -> RecoveryValidationResult {
-        RecoveryValidationResult::ECDSA(ValidatedWithEksResult::Validated(eks))
+    pub fn validated_with_eks(
+        eks: EncryptionKey,
+        proof: PaillierKeyProof
+    ) -> RecoveryValidationResult {
+        RecoveryValidationResult::ECDSA(ValidatedWithEksResult::Validated(eks, proof))
     }
 
     pub fn error(err: String) -> RecoveryValidationResult {
@@ -113,13 +262,14 @@ impl RecoveryValidationResult {
 
 #[derive(Serialize, Clone, Deserialize, Debug)]
 pub enum ValidatedWithEksResult {
-    Validated(EncryptionKey),
+    Validated(EncryptionKey, PaillierKeyProof),
 }
 
 impl ValidatedWithEksResult {
-    pub fn eks(self) -> EncryptionKey {
+    /// The new Paillier key of the recovered share with the proof that it is sound
+    pub fn eks_with_proof(self) -> (EncryptionKey, PaillierKeyProof) {
         match self {
-            ValidatedWithEksResult::Validated(eks) => eks,
+            ValidatedWithEksResult::Validated(eks, proof) => (eks, proof),
         }
     }
 }
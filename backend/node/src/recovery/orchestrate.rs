@@ -1,35 +1,193 @@
 use crate::command::MsgContext;
 use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
+use crate::recovery::migration::{
+    ConfirmKeyshareHandoverCommand,
+    KeyshareHandoverConfirmation,
+    RetireKeyshareCommand,
+};
 use crate::recovery::recovery_session::NewKeyShareRecoverySession;
-use crate::recovery::{ Key, NodeId, RecoveryCommand, RecoveryRole, RecoveryValidationResult };
+use crate::recovery::refresh_session::{
+    CompleteKeyShareRefreshCommand,
+    NewKeyShareRefreshSession,
+    RefreshPhase,
+};
+use crate::recovery::reshare::{
+    collect_paillier_key_proofs,
+    CompleteReshareCommand,
+    ReceiveResharePackagesCommand,
+    ReshareValidationResult,
+    ShredKeyshareCommand,
+};
+use crate::recovery::reshare_session::NewKeyShareReshareSession;
+use crate::recovery::{
+    Key,
+    KeyShareRefreshCommand,
+    MigrationCommand,
+    NodeId,
+    RecoveryCommand,
+    RecoveryRole,
+    RecoveryValidationResult,
+    ReshareCommand,
+};
 use crate::storage::KeyInfoStore;
 use anyhow::{ anyhow, bail, Context, Result };
+use serde::de::DeserializeOwned;
 use shared::recovery::{
     EncryptedData,
     PublicKeysEnum,
     ReceiveRecoveryPackages,
     RecoveryPackageInfo,
-    UpdatePaillierKeysCommand,
+    UpdateSinglePaillierKeyCommand,
 };
 
 use shared::key_info::{ KeyInfo, NodeInfo, UpdateKeyInfoCommand };
 use tracing::{ error, info, instrument };
 
-static THRESHOLD: usize = 2;
-
 #[instrument(skip_all)]
 pub fn orchestrate(cmd: RecoveryCommand, ctx: MsgContext) -> Result<()> {
     let app = ctx.get_app()?;
     let nc = app.nc;
 
-    let RecoveryCommand {
+    let replacements = cmd.replacements()?;
+    let RecoveryCommand { kind, key_id, session_id, party_nodes, email, .. } = cmd;
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_| {
+        let msg = format!("Key info is not found - key_id: {}", &key_id);
+        error!("{}", &msg);
+        anyhow!("{msg}\n
+            Try node that has information about the key")
+    })?;
+
+    let mut recovery_share_indices = Vec::new();
+    for replacement in &replacements {
+        let old_node_id = &replacement.old_node_id;
+        let recovery_share_index = key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == *old_node_id)
+            .ok_or_else(|| {
+                let msg = format!("Old node id was not found - old_node_id: {old_node_id}");
+                error!("{}", &msg);
+                anyhow!("{}", &msg)
+            })?.share_index;
+        if recovery_share_indices.contains(&recovery_share_index) {
+            bail!("Share {} is replaced more than once", recovery_share_index);
+        }
+        recovery_share_indices.push(recovery_share_index);
+    }
+
+    let mut key_info = key_info;
+    for replacement in &replacements {
+        key_info = enrich_key_info(
+            key_info,
+            &replacement.new_node_id,
+            &replacement.new_node_public_key,
+            &replacement.old_node_id
+        );
+    }
+
+    // Reorder public keys to be in order of the share index they hold
+    let mut rearranged_keys = Vec::new();
+    for node in &key_info.node_pool {
+        rearranged_keys.push((node.share_index, node.networking_public_key.clone()));
+    }
+
+    let helper_message = NewKeyShareRecoverySession {
+        key_id: key_id.to_string(),
+        session_id: session_id.to_string(),
+        kind: kind.clone(),
+        threshold: key_info.threshold,
+        recovery_index: recovery_share_indices[0],
+        recovery_indices: recovery_share_indices.clone(),
+        public_keys: PublicKeysEnum::Map(rearranged_keys.clone()),
+        role: RecoveryRole::Helper,
+        email: Some(email.clone()),
+        target_public_key: None,
+    };
+
+    // Helpers deliver the packages of all recovered shares as one list, ordered as the indices
+    let (share_indices, packages_by_target) = collect_packages_by_target(
+        &nc,
+        &helper_message,
+        &party_nodes,
+        replacements.len()
+    )?;
+
+    let targets = replacements.iter().zip(recovery_share_indices.iter()).zip(packages_by_target);
+    let mut paillier_keys = Vec::new();
+    for ((replacement, &recovery_share_index), encrypted_packages) in targets {
+        let message = ReceiveRecoveryPackages {
+            recovery_info: RecoveryPackageInfo {
+                key_id: key_id.to_string(),
+                recovery_index: recovery_share_index,
+                threshold: key_info.threshold,
+                peers: share_indices.clone(),
+                public_keys: PublicKeysEnum::Map(rearranged_keys.clone()),
+                encrypted_packages,
+            },
+            kind: kind.clone(),
+        };
+        let paillier_key = deliver_recovery_packages(&nc, &replacement.new_node_id, &message)?;
+        if let Some(update) = paillier_key {
+            paillier_keys.push((&replacement.new_node_id, update));
+        }
+    }
+
+    // The recovered keyshares hold the lost Paillier keys of each other, so the new nodes are
+    // updated along with the helpers
+    let old_node_ids = replacements
+        .iter()
+        .map(|replacement| &replacement.old_node_id)
+        .collect::<Vec<_>>();
+    for (new_node_id, update) in paillier_keys {
+        info!("Updating paillier keys");
+        let node_ids_to_update = party_nodes
+            .iter()
+            .filter(|&node_id| !old_node_ids.contains(&node_id))
+            .chain(
+                replacements
+                    .iter()
+                    .map(|replacement| &replacement.new_node_id)
+                    .filter(|&node_id| node_id != new_node_id)
+            );
+
+        for node_id in node_ids_to_update {
+            let message_new_key = format!("network.gridlock.nodes.async.Message.new.{node_id}");
+            let msg = serde_json::to_string(&update)?;
+            nc.publish(&message_new_key, msg)?;
+        }
+        info!("Paillier keys updated");
+    }
+
+    info!("Publishing key info updates");
+    for node in &key_info.node_pool {
+        nc.publish(
+            &format!("network.gridlock.nodes.async.Message.new.{}", node.node_id),
+            &serde_json::to_string(
+                &(UpdateKeyInfoCommand {
+                    key_id: key_id.to_string(),
+                    key_info: key_info.clone(),
+                })
+            )?
+        )?;
+    }
+    info!("Key info updated");
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub fn orchestrate_migration(cmd: MigrationCommand, ctx: MsgContext) -> Result<()> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+
+    let MigrationCommand {
         kind,
         key_id,
         session_id,
+        old_node_id,
         new_node_id,
         new_node_public_key,
-        old_node_id,
-        party_nodes,
+        helper_nodes,
         email,
     } = cmd;
 
@@ -40,46 +198,543 @@ pub fn orchestrate(cmd: RecoveryCommand, ctx: MsgContext) -> Result<()> {
             Try node that has information about the key")
     })?;
 
-    let recovery_share_index = key_info.node_pool
+    let migration_share_index = key_info.node_pool
         .iter()
         .find(|&n| n.node_id == old_node_id)
-        .ok_or_else(|| {
-            let msg = format!("Old node id was not found - old_node_id: {old_node_id}");
-            error!("{}", &msg);
-            anyhow!("{}", &msg)
-        })?.share_index;
+        .ok_or_else(|| anyhow!("Old node is not a holder of the key - old_node_id: {old_node_id}"))?
+        .share_index;
+    if key_info.node_pool.iter().any(|n| n.node_id == new_node_id) {
+        bail!("New node already holds a share of the key - new_node_id: {new_node_id}");
+    }
+
+    // The old holder takes part with its own share, so the key threshold is met with t-1 helpers
+    if helper_nodes.contains(&old_node_id) {
+        bail!("Old node takes part in the migration and cannot be listed as a helper");
+    }
+    if helper_nodes.len() != key_info.threshold {
+        bail!(
+            "Migration needs the old node and {} helpers, but {} were given",
+            key_info.threshold,
+            helper_nodes.len()
+        );
+    }
+    for node_id in &helper_nodes {
+        if !key_info.node_pool.iter().any(|n| n.node_id == *node_id) {
+            bail!("Helper is not a holder of the key - node_id: {node_id}");
+        }
+    }
+
+    // Helpers and the new node address the old holder by the key it holds its share under, the
+    // packages are encrypted to the new node instead
+    let public_keys = key_info.node_pool
+        .iter()
+        .map(|node| (node.share_index, node.networking_public_key.clone()))
+        .collect::<Vec<_>>();
+
+    let mut party_nodes = helper_nodes;
+    party_nodes.push(old_node_id.clone());
+
+    let helper_message = NewKeyShareRecoverySession {
+        key_id: key_id.to_string(),
+        session_id: session_id.to_string(),
+        kind: kind.clone(),
+        threshold: key_info.threshold,
+        recovery_index: migration_share_index,
+        recovery_indices: Vec::new(),
+        public_keys: PublicKeysEnum::Map(public_keys.clone()),
+        role: RecoveryRole::Helper,
+        email: Some(email.clone()),
+        target_public_key: Some(new_node_public_key.clone()),
+    };
+    let (share_indices, mut packages_by_target) = collect_packages_by_target(
+        &nc,
+        &helper_message,
+        &party_nodes,
+        1
+    )?;
+    let encrypted_packages = packages_by_target.remove(0);
+
+    let message = ReceiveRecoveryPackages {
+        recovery_info: RecoveryPackageInfo {
+            key_id: key_id.to_string(),
+            recovery_index: migration_share_index,
+            threshold: key_info.threshold,
+            peers: share_indices,
+            public_keys: PublicKeysEnum::Map(public_keys),
+            encrypted_packages,
+        },
+        kind: kind.clone(),
+    };
+    let paillier_key = deliver_recovery_packages(&nc, &new_node_id, &message)?;
 
     let key_info = enrich_key_info(key_info, &new_node_id, &new_node_public_key, &old_node_id);
 
-    // Reorder public keys to be in order of the share index they hold
-    let mut rearranged_keys = Vec::new();
+    if let Some(update) = paillier_key {
+        info!("Updating paillier keys");
+        let msg = serde_json::to_string(&update)?;
+        for node in key_info.node_pool.iter().filter(|&n| n.node_id != new_node_id) {
+            let message_new_key = format!(
+                "network.gridlock.nodes.async.Message.new.{}",
+                node.node_id
+            );
+            nc.publish(&message_new_key, &msg)?;
+        }
+        info!("Paillier keys updated");
+    }
+
+
+    // Only once the new node confirms it holds the validated share is the old one deleted
+    let confirm = ConfirmKeyshareHandoverCommand {
+        key_id: key_id.to_string(),
+        handover_share_index: migration_share_index,
+    };
+    let res = nc.request(
+        &format!("network.gridlock.nodes.async.Message.new.{new_node_id}"),
+        serde_json::to_string(&confirm)?
+    )?;
+    if res.data.starts_with(b"ERROR") {
+        bail!(
+            "New node did not confirm the migrated keyshare: {}",
+            String::from_utf8_lossy(&res.data)
+        );
+    }
+    let confirmation = serde_json::from_slice::<KeyshareHandoverConfirmation>(&res.data)?;
+    info!("New node confirmed the migrated keyshare");
+
+    // Holders only learn about the new node once it confirmed the validated share
+    info!("Publishing key info updates");
     for node in &key_info.node_pool {
-        rearranged_keys.push((node.share_index, node.networking_public_key.clone()));
+        nc.publish(
+            &format!("network.gridlock.nodes.async.Message.new.{}", node.node_id),
+            &serde_json::to_string(
+                &(UpdateKeyInfoCommand {
+                    key_id: key_id.to_string(),
+                    key_info: key_info.clone(),
+                })
+            )?
+        )?;
     }
+    info!("Key info updated");
 
-    let helper_message = NewKeyShareRecoverySession {
+    let retire = RetireKeyshareCommand::new_signed(
+        &key_id,
+        &email,
+        new_node_id,
+        key_info,
+        confirmation.signature
+    )?;
+    let res = nc.request(
+        &format!("network.gridlock.nodes.async.Message.new.{old_node_id}"),
+        serde_json::to_string(&retire)?
+    )?;
+    if res.data.starts_with(b"ERROR") {
+        bail!(
+            "Keyshare was migrated, but the old one has to be retired manually: {}",
+            String::from_utf8_lossy(&res.data)
+        );
+    }
+    info!("Old keyshare retired");
+
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub fn orchestrate_refresh(cmd: KeyShareRefreshCommand, ctx: MsgContext) -> Result<()> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+
+    let KeyShareRefreshCommand { kind, key_id, session_id, email } = cmd;
+
+    if let Key::Sr25519 | Key::Bls12381 = kind {
+        bail!("Keyshare refresh is only supported for ECDSA and EdDSA keys");
+    }
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_| {
+        let msg = format!("Key info is not found - key_id: {}", &key_id);
+        error!("{}", &msg);
+        anyhow!("{msg}\n
+            Try node that has information about the key")
+    })?;
+
+    let public_keys = key_info.node_pool
+        .iter()
+        .map(|node| (node.share_index, node.networking_public_key.clone()))
+        .collect();
+
+    let refresh_message = NewKeyShareRefreshSession {
+        kind: kind.clone(),
         key_id: key_id.to_string(),
         session_id: session_id.to_string(),
+        public_keys: PublicKeysEnum::Map(public_keys),
+        email: Some(email.clone()),
+    };
+
+    let join_key = format!("network.gridlock.nodes.KeyShareRefresh.{}.Join", &session_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!(
+        "network.gridlock.nodes.KeyShareRefresh.{}.ValidationResult",
+        &session_id
+    );
+    let result_sub = nc.subscribe(&result_key)?;
+
+    let refresh_new_message = serde_json::to_string(&refresh_message)?;
+
+    for node in &key_info.node_pool {
+        let refresh_new_key = format!(
+            "network.gridlock.nodes.KeyShareRefresh.new.{}",
+            node.node_id
+        );
+        nc.publish(&refresh_new_key, &refresh_new_message)?;
+    }
+
+    // Every holder has to take part, otherwise the shares of absent holders would no longer fit
+    // the key
+    let party_count = key_info.node_pool.len();
+    let mut join_msgs = Vec::new();
+    for _ in 0..party_count {
+        let join_msg = join_sub.next().context("Waiting for parties to join")?;
+        join_msgs.push(join_msg);
+    }
+
+    let mut share_indices = Vec::new();
+    for m in join_msgs.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        share_indices.push(confirmation.party_index);
+    }
+    share_indices.sort();
+
+    info!("Parties joined to refresh orchestration - share_indices: {:?}", &share_indices);
+    let join_resp = JoinResponse {
+        party_count: share_indices.len(),
+        all_party_indices: share_indices.clone(),
+    };
+    for m in &join_msgs {
+        m.respond(&serde_json::to_string(&join_resp)?)?;
+    }
+    nc.flush()?;
+
+    info!("Waiting for refresh validation results");
+    let mut failures = Vec::new();
+    for _ in 0..party_count {
+        let m = result_sub.next().context("Waiting for refresh validation results")?;
+        let resp = serde_json::from_slice::<BroadcastMessage<RecoveryValidationResult>>(&m.data)?;
+        if let RecoveryValidationResult::Error(err) = resp.message {
+            failures.push(format!("party {}: {}", resp.sender_id, err));
+        }
+    }
+
+    // Every holder sets its keyshare aside before any of them switches, and keeps it until all
+    // of them have switched, so that a failure on any holder can be rolled back everywhere
+    let command = |phase| CompleteKeyShareRefreshCommand {
         kind: kind.clone(),
-        threshold: THRESHOLD,
-        recovery_index: recovery_share_index,
-        public_keys: PublicKeysEnum::Map(rearranged_keys.clone()),
-        role: RecoveryRole::Helper,
+        key_id: key_id.to_string(),
+        email: email.clone(),
+        refresh_session_id: session_id.to_string(),
+        phase,
+    };
+    let validated = if failures.is_empty() {
+        info!("Keyshare refresh validated by all parties");
+        Ok(())
+    } else {
+        Err(anyhow!("Keyshare refresh failed - {}", failures.join(", ")))
+    };
+    let switched = validated
+        .and_then(|_| {
+            run_refresh_phase(&nc, &key_info.node_pool, &command(RefreshPhase::Prepare))
+        })
+        .and_then(|_| {
+            info!("Refreshed keyshares prepared by all parties");
+            run_refresh_phase(&nc, &key_info.node_pool, &command(RefreshPhase::Commit))
+        });
+    if let Err(err) = switched {
+        error!("Rolling back keyshare refresh: {}", err);
+        run_refresh_phase(&nc, &key_info.node_pool, &command(RefreshPhase::Rollback)).context(
+            "Keyshare refresh could not be rolled back"
+        )?;
+        bail!("Keyshare refresh was rolled back - {}", err);
+    }
+    run_refresh_phase(&nc, &key_info.node_pool, &command(RefreshPhase::Release))?;
+    info!("Refreshed keyshares saved");
+
+    Ok(())
+}
+
+/// Run a phase of the refresh commit on every holder, failing if any of them failed
+fn run_refresh_phase(
+    nc: &nats::Connection,
+    node_pool: &[NodeInfo],
+    command: &CompleteKeyShareRefreshCommand
+) -> Result<()> {
+    let message = serde_json::to_string(command)?;
+    let mut failures = Vec::new();
+    for node in node_pool {
+        let message_new_key = format!("network.gridlock.nodes.async.Message.new.{}", node.node_id);
+        let failure = match nc.request(&message_new_key, &message) {
+            Ok(res) if res.data.starts_with(b"ERROR") => {
+                Some(String::from_utf8_lossy(&res.data).to_string())
+            }
+            Ok(_) => None,
+            Err(err) => Some(err.to_string()),
+        };
+        if let Some(failure) = failure {
+            failures.push(format!("party {}: {}", node.share_index, failure));
+        }
+    }
+    if !failures.is_empty() {
+        bail!("{:?} of the refresh failed - {}", command.phase, failures.join(", "));
+    }
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub fn orchestrate_reshare(cmd: ReshareCommand, ctx: MsgContext) -> Result<()> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+
+    let ReshareCommand { kind, key_id, session_id, party_nodes, new_nodes, threshold, email } = cmd;
+
+    if let Key::Sr25519 | Key::Bls12381 = kind {
+        bail!("Resharing is only supported for ECDSA and EdDSA keys");
+    }
+    if threshold == 0 || threshold >= new_nodes.len() {
+        bail!(
+            "Threshold of {} is not valid for a new committee of {} nodes",
+            threshold,
+            new_nodes.len()
+        );
+    }
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_| {
+        let msg = format!("Key info is not found - key_id: {}", &key_id);
+        error!("{}", &msg);
+        anyhow!("{msg}\n
+            Try node that has information about the key")
+    })?;
+
+    let mut dealer_keys = Vec::new();
+    for node_id in &party_nodes {
+        let node = key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == *node_id)
+            .ok_or_else(|| anyhow!("Node is not a holder of the key - node_id: {node_id}"))?;
+        dealer_keys.push((node.share_index, node.networking_public_key.clone()));
+    }
+
+    let new_node_pool: Vec<NodeInfo> = new_nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| NodeInfo {
+            node_id: node.node_id.clone(),
+            networking_public_key: node.networking_public_key.clone(),
+            kind: node.kind.clone(),
+            share_index: i + 1,
+        })
+        .collect();
+    let new_public_keys = new_node_pool
+        .iter()
+        .map(|node| (node.share_index, node.networking_public_key.clone()))
+        .collect();
+
+    let dealer_message = NewKeyShareReshareSession {
+        kind: kind.clone(),
+        key_id: key_id.to_string(),
+        session_id: session_id.to_string(),
+        threshold,
+        public_keys: PublicKeysEnum::Map(new_public_keys),
         email: Some(email.clone()),
     };
 
-    let join_key = format!("network.gridlock.nodes.KeyShareRecovery.{}.Join", &session_id);
+    let join_key = format!("network.gridlock.nodes.KeyShareReshare.{}.Join", &session_id);
     let join_sub = nc.subscribe(&join_key)?;
 
     let package_key = format!(
-        "network.gridlock.nodes.KeyShareRecovery.{}.DeliverRecoveryPackage",
+        "network.gridlock.nodes.KeyShareReshare.{}.DeliverResharePackage",
         &session_id
     );
     let package_sub = nc.subscribe(&package_key)?;
 
-    let recovery_new_helper_message = serde_json::to_string(&helper_message)?;
+    let reshare_new_message = serde_json::to_string(&dealer_message)?;
 
     for node_id in &party_nodes {
+        let reshare_new_key = format!("network.gridlock.nodes.KeyShareReshare.new.{node_id}");
+        nc.publish(&reshare_new_key, &reshare_new_message)?;
+    }
+
+    let mut join_msgs = Vec::new();
+    let party_count = party_nodes.len();
+    for _ in 0..party_count {
+        let join_msg = join_sub.next().context("Waiting for parties to join")?;
+        join_msgs.push(join_msg);
+    }
+
+    let mut share_indices = Vec::new();
+    for m in join_msgs.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        share_indices.push(confirmation.party_index);
+    }
+    share_indices.sort();
+
+    info!("Parties joined to reshare orchestration - share_indices: {:?}", &share_indices);
+    let join_resp = JoinResponse {
+        party_count: share_indices.len(),
+        all_party_indices: share_indices.clone(),
+    };
+    for m in &join_msgs {
+        m.respond(&serde_json::to_string(&join_resp)?)?;
+    }
+    nc.flush()?;
+
+    // Gather reshare packages
+    let mut packages = Vec::new();
+    for _ in 0..party_count {
+        let m = package_sub.next().context("Waiting for reshare packages")?;
+        let resp = serde_json::from_slice::<BroadcastMessage<String>>(&m.data)?;
+        packages.push(resp);
+    }
+    info!("Reshare packages received - packages count: {}", packages.len());
+
+    packages.sort_by_key(|x| x.sender_id);
+    let packages: Vec<String> = packages
+        .into_iter()
+        .map(|x| x.message)
+        .collect();
+
+    // Resharing keeps the public key, so derived keys stay the same for the new committee
+    let chain_code = match &key_info.kind {
+        shared::key_info::Key::ECDSA { chain_code, .. } => chain_code.clone(),
+        _ => None,
+    };
+
+    let mut key_proofs = Vec::new();
+    for node in &new_node_pool {
+        let message = ReceiveResharePackagesCommand {
+            kind: kind.clone(),
+            key_id: key_id.to_string(),
+            email: email.clone(),
+            reshare_index: node.share_index,
+            threshold,
+            dealers: share_indices.clone(),
+            dealer_public_keys: PublicKeysEnum::Map(dealer_keys.clone()),
+            packages: packages.clone(),
+            chain_code: chain_code.clone(),
+            public_key: key_info.kind.clone(),
+        };
+        let message_new_key = format!("network.gridlock.nodes.async.Message.new.{}", node.node_id);
+        let res = nc.request(&message_new_key, serde_json::to_string(&message)?)?;
+        let validation_msg = serde_json::from_slice::<ReshareValidationResult>(&res.data).map_err(
+            |_| anyhow!("{}", String::from_utf8_lossy(&res.data))
+        )?;
+        match validation_msg {
+            ReshareValidationResult::Validated => {}
+            ReshareValidationResult::ValidatedWithKeyProof(key_proof) => {
+                key_proofs.push(*key_proof);
+            }
+            ReshareValidationResult::ValidationError(err) => {
+                bail!("Reshare failed for new party {}: {}", node.share_index, err);
+            }
+        }
+    }
+    info!("Reshared keyshares validated by the new committee");
+
+    let paillier_key_proofs = match kind {
+        Key::ECDSA => {
+            collect_paillier_key_proofs(&nc, &new_node_pool, &key_id, &email, &key_proofs)?
+        }
+        _ => Vec::new(),
+    };
+
+    let complete = serde_json::to_string(
+        &(CompleteReshareCommand {
+            kind: kind.clone(),
+            key_id: key_id.to_string(),
+            email: email.clone(),
+            key_proofs,
+            paillier_key_proofs,
+        })
+    )?;
+    for node in &new_node_pool {
+        let message_new_key = format!("network.gridlock.nodes.async.Message.new.{}", node.node_id);
+        let res = nc.request(&message_new_key, &complete)?;
+        if res.data.starts_with(b"ERROR") {
+            bail!(
+                "Reshared keyshare could not be saved by new party {}: {}",
+                node.share_index,
+                String::from_utf8_lossy(&res.data)
+            );
+        }
+    }
+    info!("Reshared keyshares saved");
+
+    let old_node_pool = key_info.node_pool.clone();
+    let key_info = KeyInfo {
+        node_pool: new_node_pool,
+        threshold,
+        ..key_info
+    };
+
+    info!("Publishing key info updates");
+    for node in &key_info.node_pool {
+        nc.publish(
+            &format!("network.gridlock.nodes.async.Message.new.{}", node.node_id),
+            &serde_json::to_string(
+                &(UpdateKeyInfoCommand {
+                    key_id: key_id.to_string(),
+                    key_info: key_info.clone(),
+                })
+            )?
+        )?;
+    }
+    info!("Key info updated");
+
+    // Old holders outside the new committee still hold shares of the old sharing, which
+    // together with others of them could rebuild the key, so they have to delete them
+    let removed_nodes = old_node_pool
+        .iter()
+        .filter(|&old| !key_info.node_pool.iter().any(|n| n.node_id == old.node_id))
+        .collect::<Vec<_>>();
+    let shred = ShredKeyshareCommand::new_signed(&key_id, &email, key_info)?;
+    let shred = serde_json::to_string(&shred)?;
+    let mut failures = Vec::new();
+    for node in removed_nodes {
+        let message_new_key = format!("network.gridlock.nodes.async.Message.new.{}", node.node_id);
+        let res = nc.request(&message_new_key, &shred)?;
+        if res.data.starts_with(b"ERROR") {
+            failures.push(format!("node {}: {}", node.node_id, String::from_utf8_lossy(&res.data)));
+        }
+    }
+    if !failures.is_empty() {
+        bail!(
+            "Key was reshared, but removed holders still hold their keyshares - {}",
+            failures.join(", ")
+        );
+    }
+    info!("Keyshares of removed holders shredded");
+
+    Ok(())
+}
+
+/// Starts a recovery session with the helpers and gathers their encrypted packages in the order
+/// of their share indices
+fn collect_recovery_packages<T: DeserializeOwned>(
+    nc: &nats::Connection,
+    helper_message: &NewKeyShareRecoverySession,
+    party_nodes: &[NodeId]
+) -> Result<(Vec<usize>, Vec<T>)> {
+    let session_id = &helper_message.session_id;
+
+    let join_key = format!("network.gridlock.nodes.KeyShareRecovery.{}.Join", session_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let package_key = format!(
+        "network.gridlock.nodes.KeyShareRecovery.{}.DeliverRecoveryPackage",
+        session_id
+    );
+    let package_sub = nc.subscribe(&package_key)?;
+
+    let recovery_new_helper_message = serde_json::to_string(helper_message)?;
+
+    for node_id in party_nodes {
         let recovery_new_key = format!("network.gridlock.nodes.KeyShareRecovery.new.{node_id}");
         nc.publish(&recovery_new_key, &recovery_new_helper_message)?;
     }
@@ -116,39 +771,66 @@ pub fn orchestrate(cmd: RecoveryCommand, ctx: MsgContext) -> Result<()> {
     let mut encrypted_packages = Vec::new();
     for _ in 0..party_count {
         let m = package_sub.next().unwrap();
-        let resp = serde_json::from_slice::<BroadcastMessage<EncryptedData>>(&m.data).unwrap();
+        let resp = serde_json::from_slice::<BroadcastMessage<T>>(&m.data).unwrap();
 
         encrypted_packages.push(resp);
     }
     info!("Encrypted packages received - encrypted packages count: {}", encrypted_packages.len());
 
     encrypted_packages.sort_by_key(|x| x.sender_id);
-    let encrypted_packages: Vec<EncryptedData> = encrypted_packages
-        .iter()
-        .map(|x| x.message.clone())
+    let encrypted_packages: Vec<T> = encrypted_packages
+        .into_iter()
+        .map(|x| x.message)
         .collect();
 
-    let message = ReceiveRecoveryPackages {
-        recovery_info: RecoveryPackageInfo {
-            key_id: key_id.to_string(),
-            recovery_index: recovery_share_index,
-            threshold: THRESHOLD,
-            peers: share_indices.clone(),
-            public_keys: PublicKeysEnum::Map(rearranged_keys.clone()),
-            encrypted_packages,
-        },
-        kind: kind.clone(),
-    };
-    let msg = serde_json::to_string(&message)?;
+    Ok((share_indices, encrypted_packages))
+}
+
+/// Collects the packages of every helper and regroups them by the recovered share they are for
+fn collect_packages_by_target(
+    nc: &nats::Connection,
+    helper_message: &NewKeyShareRecoverySession,
+    party_nodes: &[NodeId],
+    target_count: usize
+) -> Result<(Vec<usize>, Vec<Vec<EncryptedData>>)> {
+    let (share_indices, encrypted_packages) = collect_recovery_packages::<Vec<EncryptedData>>(
+        nc,
+        helper_message,
+        party_nodes
+    )?;
+    if encrypted_packages.iter().any(|packages| packages.len() != target_count) {
+        bail!("Not every helper sent a package for each of the recovered shares");
+    }
+    let packages_by_target = (0..target_count)
+        .map(|i| {
+            encrypted_packages
+                .iter()
+                .map(|packages| packages[i].clone())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    Ok((share_indices, packages_by_target))
+}
+
+/// Sends the recovery packages to the target, which validates the keyshare against the VSS
+/// commitments before saving it. For ECDSA the update of the new Paillier key is returned
+fn deliver_recovery_packages(
+    nc: &nats::Connection,
+    new_node_id: &NodeId,
+    message: &ReceiveRecoveryPackages
+) -> Result<Option<UpdateSinglePaillierKeyCommand>> {
+    let kind = &message.kind;
+    let msg = serde_json::to_string(message)?;
     let message_new_key = format!("network.gridlock.nodes.async.Message.new.{new_node_id}");
     let res = nc.request(&message_new_key, msg)?;
     info!("Validating recovery result");
     match kind {
-        Key::EDDSA | Key::Sr25519 => {
+        Key::EDDSA | Key::Sr25519 | Key::Bls12381 => {
             let validation_msg = serde_json::from_slice::<RecoveryValidationResult>(&res.data)?;
             match validation_msg {
                 RecoveryValidationResult::EDDSA(_) => {
                     info!("{} recovery validated", kind);
+                    Ok(None)
                 }
                 RecoveryValidationResult::Error(err) => {
                     bail!("{}", err);
@@ -160,46 +842,28 @@ pub fn orchestrate(cmd: RecoveryCommand, ctx: MsgContext) -> Result<()> {
         }
         Key::ECDSA => {
             let validation_msg = serde_json::from_slice::<RecoveryValidationResult>(&res.data)?;
-            if let RecoveryValidationResult::ECDSA(res) = validation_msg {
-                info!("ECDSA recovery validated");
-
-                info!("Updating paillier keys");
-                let update = UpdatePaillierKeysCommand {
-                    key_id: key_id.to_string(),
-                    new_eks: vec![res.eks()],
-                };
-
-                let node_ids_to_update = party_nodes
-                    .iter()
-                    .filter(|&node_id| *node_id != old_node_id);
-
-                for node_id in node_ids_to_update {
-                    let message_new_key = format!(
-                        "network.gridlock.nodes.async.Message.new.{node_id}"
-                    );
-                    let msg = serde_json::to_string(&update)?;
-                    nc.publish(&message_new_key, msg)?;
+            match validation_msg {
+                RecoveryValidationResult::ECDSA(res) => {
+                    info!("ECDSA recovery validated");
+                    let (new_ek, key_proof) = res.eks_with_proof();
+                    Ok(
+                        Some(UpdateSinglePaillierKeyCommand {
+                            key_id: message.recovery_info.key_id.to_string(),
+                            new_ek,
+                            index: message.recovery_info.recovery_index,
+                            key_proof: serde_json::to_string(&key_proof)?,
+                        })
+                    )
+                }
+                RecoveryValidationResult::Error(err) => {
+                    bail!("{}", err);
+                }
+                _ => {
+                    bail!("Wrong validation result");
                 }
-                info!("Paillier keys updated");
             }
         }
     }
-
-    info!("Publishing key info updates");
-    for node in &key_info.node_pool {
-        nc.publish(
-            &format!("network.gridlock.nodes.async.Message.new.{}", node.node_id),
-            &serde_json::to_string(
-                &(UpdateKeyInfoCommand {
-                    key_id: key_id.to_string(),
-                    key_info: key_info.clone(),
-                })
-            )?
-        )?;
-    }
-    info!("Key info updated");
-
-    Ok(())
 }
 
 /// Enrich key info with new recovery node id and public key
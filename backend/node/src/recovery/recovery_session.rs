@@ -4,18 +4,20 @@ use crate::config::ConfigProvider;
 use crate::node::NodeIdentity;
 use crate::recovery::encryption::{ NKeyHelperEncryptor, NKeyTargetEncryptor };
 use crate::recovery::helper_role::{
+    Bls12381BehaviourHelperRole,
     ECDSABehaviourHelperRole,
     EdDSABehaviourHelperRole,
     KeyshareRecoveryHelper,
 };
 use crate::recovery::target_role::{
+    Bls12381BehaviourTargetRole,
     ECDSABehaviourTargetRole,
     EdDSABehaviourTargetRole,
     KeyshareRecoveryTarget,
     Sr25519BehaviourTargetRole,
 };
 use crate::recovery::{ Key, Party, RecoveryRole };
-use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
+use crate::storage::{ Bls12381, KeyshareAccessor, ECDSA, EDDSA };
 use crate::App;
 use anyhow::{ anyhow, bail, Result };
 use serde::{ Deserialize, Serialize };
@@ -31,11 +33,18 @@ pub struct NewKeyShareRecoverySession {
     pub key_id: String,
     pub session_id: String,
     pub recovery_index: usize,
+    /// Shares recovered together in the session, only `recovery_index` is recovered when empty
+    #[serde(default)]
+    pub recovery_indices: Vec<usize>,
     pub threshold: usize,
     pub public_keys: PublicKeysEnum,
     pub role: RecoveryRole,
     #[serde(default)]
     pub email: Option<String>,
+    /// Networking key of the node receiving the keyshare, when it is not the holder of
+    /// `recovery_index` in `public_keys`, as in a migration where the old holder helps
+    #[serde(default)]
+    pub target_public_key: Option<String>,
 }
 
 impl NewKeyShareRecoverySession {
@@ -57,9 +66,10 @@ impl NewKeyShareRecoverySession {
 
         let topic = Topic::KeyShareRecovery;
 
+        let recovery_indices = self.recovery_indices();
         match self.kind {
             Key::Sr25519 => {}
-            _ if self.recovery_index == 0 => {
+            _ if recovery_indices.contains(&0) => {
                 bail!(
                     "Recovery of the zero index keyshare (the underlying secret key) is restricted to Sr25519 recoveries"
                 );
@@ -89,7 +99,7 @@ impl NewKeyShareRecoverySession {
 
                 let encryptor = NKeyHelperEncryptor::new(
                     &public_keys,
-                    self.recovery_index,
+                    &self.target_public_keys(&public_keys)?,
                     party_index,
                     &peers,
                     private_key
@@ -101,7 +111,7 @@ impl NewKeyShareRecoverySession {
                     key_behaviour
                 );
 
-                recoverer.try_recovery(self.recovery_index, Party {
+                recoverer.try_recovery(&recovery_indices, Party {
                     party_index,
                     all_parties: peers,
                 })
@@ -127,7 +137,7 @@ impl NewKeyShareRecoverySession {
 
                 let encryptor = NKeyHelperEncryptor::new(
                     &public_keys,
-                    self.recovery_index,
+                    &self.target_public_keys(&public_keys)?,
                     party_index,
                     &peers,
                     private_key
@@ -139,7 +149,7 @@ impl NewKeyShareRecoverySession {
                     key_behaviour
                 );
 
-                recoverer.try_recovery(self.recovery_index, Party {
+                recoverer.try_recovery(&recovery_indices, Party {
                     party_index,
                     all_parties: peers,
                 })
@@ -165,7 +175,9 @@ impl NewKeyShareRecoverySession {
 
                 let recoverer = KeyshareRecoveryTarget::new(messenger, encryptor, key_behaviour);
 
-                let encrypted_packages = recoverer.try_recieve_encrypted_packages()?;
+                let encrypted_packages = recoverer.try_recieve_encrypted_packages(
+                    self.target_position()
+                )?;
 
                 let result = recoverer.recover_keyshare(
                     self.recovery_index,
@@ -196,7 +208,7 @@ impl NewKeyShareRecoverySession {
 
                 let encryptor = NKeyHelperEncryptor::new(
                     &public_keys,
-                    self.recovery_index,
+                    &self.target_public_keys(&public_keys)?,
                     party_index,
                     &peers,
                     private_key
@@ -208,7 +220,7 @@ impl NewKeyShareRecoverySession {
                     key_behaviour
                 );
 
-                recoverer.try_recovery(self.recovery_index, Party {
+                recoverer.try_recovery(&recovery_indices, Party {
                     party_index,
                     all_parties: peers,
                 })
@@ -234,7 +246,9 @@ impl NewKeyShareRecoverySession {
 
                 let recoverer = KeyshareRecoveryTarget::new(messenger, encryptor, key_behaviour);
 
-                let encrypted_packages = recoverer.try_recieve_encrypted_packages()?;
+                let encrypted_packages = recoverer.try_recieve_encrypted_packages(
+                    self.target_position()
+                )?;
 
                 let result = recoverer.recover_keyshare(
                     self.recovery_index,
@@ -265,7 +279,80 @@ impl NewKeyShareRecoverySession {
 
                 let recoverer = KeyshareRecoveryTarget::new(messenger, encryptor, key_behaviour);
 
-                let encrypted_packages = recoverer.try_recieve_encrypted_packages()?;
+                let encrypted_packages = recoverer.try_recieve_encrypted_packages(
+                    self.target_position()
+                )?;
+
+                let result = recoverer.recover_keyshare(
+                    self.recovery_index,
+                    self.threshold,
+                    encrypted_packages
+                )?;
+
+                recoverer.broadcast_result(result)
+            }
+            //Recovery of a BLS12-381 keyshare by a helper guardian
+            (RecoveryRole::Helper, Key::Bls12381) => {
+                let key_accessor = KeyshareAccessor::<Bls12381>::read_only_with_email(
+                    &key_id,
+                    &email
+                )?;
+                let party_index = key_accessor.key.party_index;
+
+                let (messenger, peers) = Nats::new_session(
+                    conn,
+                    &session_id,
+                    &node,
+                    &key_id,
+                    party_index,
+                    topic
+                )?;
+
+                let key_behaviour = Bls12381BehaviourHelperRole::from_key_accessor(key_accessor);
+
+                let encryptor = NKeyHelperEncryptor::new(
+                    &public_keys,
+                    &self.target_public_keys(&public_keys)?,
+                    party_index,
+                    &peers,
+                    private_key
+                ).map_err(|err| anyhow!("Unable to create encryptor: {}", err))?;
+
+                let mut recoverer = KeyshareRecoveryHelper::new(
+                    messenger,
+                    encryptor,
+                    key_behaviour
+                );
+
+                recoverer.try_recovery(&recovery_indices, Party {
+                    party_index,
+                    all_parties: peers,
+                })
+            }
+            //Recovery procedure followed by target of BLS12-381 key recovery to receive and validate their new keyshare
+            (RecoveryRole::Target, Key::Bls12381) => {
+                let party_index = self.recovery_index;
+
+                let (messenger, peers) = Nats::new_session(
+                    conn,
+                    &session_id,
+                    &node,
+                    &key_id,
+                    party_index,
+                    topic
+                )?;
+
+                let key_behaviour = Bls12381BehaviourTargetRole::new(&key_id);
+
+                let encryptor = NKeyTargetEncryptor::new(&public_keys, &peers, private_key).map_err(
+                    |err| anyhow!("Unable to create encryptor: {}", err)
+                )?;
+
+                let recoverer = KeyshareRecoveryTarget::new(messenger, encryptor, key_behaviour);
+
+                let encrypted_packages = recoverer.try_recieve_encrypted_packages(
+                    self.target_position()
+                )?;
 
                 let result = recoverer.recover_keyshare(
                     self.recovery_index,
@@ -278,8 +365,47 @@ impl NewKeyShareRecoverySession {
         }
     }
 
+    /// Position of the share of this target among the shares recovered in the session
+    fn target_position(&self) -> usize {
+        self.recovery_indices()
+            .iter()
+            .position(|&index| index == self.recovery_index)
+            .unwrap_or(0)
+    }
+
+    fn recovery_indices(&self) -> Vec<usize> {
+        if self.recovery_indices.is_empty() {
+            vec![self.recovery_index]
+        } else {
+            self.recovery_indices.clone()
+        }
+    }
+
+    /// Networking keys the recovery packages are encrypted to, by the index of the share
+    fn target_public_keys(
+        &self,
+        public_keys: &HashMap<usize, String>
+    ) -> Result<HashMap<usize, String>> {
+        self.recovery_indices()
+            .into_iter()
+            .map(|recovery_index| {
+                let target_public_key = match &self.target_public_key {
+                    Some(target_public_key) if recovery_index == self.recovery_index => {
+                        target_public_key.clone()
+                    }
+                    _ =>
+                        public_keys
+                            .get(&recovery_index)
+                            .cloned()
+                            .ok_or_else(|| anyhow!("No target node for share {recovery_index}"))?,
+                };
+                Ok((recovery_index, target_public_key))
+            })
+            .collect()
+    }
+
     // Function to find the email for a key ID by searching the file system
-    fn find_email_for_key(key_id: &str) -> Result<String> {
+    pub(crate) fn find_email_for_key(key_id: &str) -> Result<String> {
         use std::fs;
 
         use crate::config::Config;
@@ -0,0 +1,431 @@
+use crate::communication::nats::PeerMessenger;
+use crate::communication::protocol::{ AllRounds, KeyShareRefreshAllRounds };
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::recovery::encryption::PeerEncryptor;
+use crate::recovery::{ Party, RecoveryValidationResult };
+use crate::storage::fs::WriteOpts;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
+use anyhow::{ bail, Result };
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing,
+    VerifiableSS,
+};
+use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use itertools::Itertools;
+use serde::{ Deserialize, Serialize };
+use std::iter;
+use tracing::{ error, info };
+
+/// Metadata entry holding a refreshed keyshare until every holder has switched to theirs
+pub(crate) const PENDING_REFRESH: &str = "refresh";
+/// Metadata entry holding the keyshare a refresh replaces, until every holder has switched
+pub(crate) const PREVIOUS_KEYSHARE: &str = "refresh_previous";
+
+/// Keyshare together with the refresh session it was staged in, so that a keyshare staged by an
+/// earlier, failed refresh is never committed or restored
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PendingRefresh {
+    pub session_id: String,
+    pub keyshare: String,
+}
+
+/// Where a refreshed keyshare is staged
+pub struct RefreshStaging {
+    pub key_id: String,
+    pub email: String,
+    pub session_id: String,
+}
+
+impl RefreshStaging {
+    fn stage<K: Serialize>(&self, keyshare: &K) -> Result<()> {
+        let pending = PendingRefresh {
+            session_id: self.session_id.clone(),
+            keyshare: serde_json::to_string(keyshare)?,
+        };
+        KeyMetadataStore::save(
+            &serde_json::to_string(&pending)?,
+            &self.key_id,
+            PENDING_REFRESH,
+            &self.email,
+            &WriteOpts::Modify
+        )
+    }
+}
+
+/// Proactive refresh of a keyshare, run by every holder of the key at the same time.
+/// The refreshed keyshare is only staged, the orchestrator commits it on every holder in two
+/// phases once it has seen every holder validate theirs.
+pub struct KeyshareRefresher<M, E, K> {
+    pub messenger: M,
+    pub encryptor: E,
+    pub key: K,
+    pub staging: RefreshStaging,
+}
+
+impl<M, E, K> KeyshareRefresher<M, E, K>
+    where
+        M: PeerMessenger<KeyShareRefreshAllRounds>,
+        E: PeerEncryptor,
+        K: KeyshareBehaviourRefreshRole,
+        K::Curve: Curve
+{
+    pub fn new(messenger: M, encryptor: E, key: K, staging: RefreshStaging) -> Self {
+        Self {
+            messenger,
+            encryptor,
+            key,
+            staging,
+        }
+    }
+
+    pub fn try_refresh(&mut self, party: Party) -> Result<()> {
+        info!("Starting keyshare refresh");
+        let party_count = self.key.vss_scheme_vec().len();
+        if party.all_parties.len() != party_count {
+            bail!(
+                "All {} keyshare holders need to take part in a refresh, but only {} joined",
+                party_count,
+                party.all_parties.len()
+            );
+        }
+
+        let calculator = self.key.get_refresh_params(party);
+        let (zero_vss, contrib) = calculator.create_secret_sharing_of_zero_point();
+
+        // The constant term of a zero sharing is always the point at infinity, so it is implied
+        // rather than sent
+        let zero_commitments = zero_vss.commitments.iter().skip(1).cloned().collect_vec();
+        let all_zero_commitments = self.messenger.broadcast_and_collect_messages(
+            &<KeyShareRefreshAllRounds as AllRounds>::BroadcastRound::ZeroShareCommitments,
+            zero_commitments
+        )?;
+        let zero_vss_vec = all_zero_commitments
+            .into_iter()
+            .map(|commitments| zero_vss_from_commitments(&calculator, commitments))
+            .collect::<Result<Vec<_>>>()?;
+
+        let encrypted_shares = self.encryptor.encrypt_for_peers(contrib.for_peer_exchange)?;
+        info!("Encrypted zero shares");
+
+        let received_shares = self.messenger.send_p2p_and_collect_messages(
+            &<KeyShareRefreshAllRounds as AllRounds>::P2PRound::ExchangeZeroShares,
+            encrypted_shares
+        )?;
+
+        let decrypted_shares: Vec<Scalar<K::Curve>> = self.encryptor.decrypt_from_peers(
+            received_shares
+        )?;
+        info!("Decrypted zero shares");
+
+        let result = refresh_keyshare(
+            &calculator,
+            self.key.vss_scheme_vec(),
+            self.key.y_sum(),
+            &zero_vss_vec,
+            &decrypted_shares,
+            contrib.retained
+        ).and_then(|(x_i, vss_scheme_vec)| {
+            self.staging.stage(&self.key.refreshed_keyshare(x_i, vss_scheme_vec))
+        });
+        if let Err(err) = &result {
+            error!("Refreshed keyshare could not be validated and staged: {}", err);
+        }
+
+        let validation_result = match &result {
+            Ok(_) => RecoveryValidationResult::validated(),
+            Err(err) => RecoveryValidationResult::error(err.to_string()),
+        };
+        let all_results = self.messenger.broadcast_and_collect_messages(
+            &<KeyShareRefreshAllRounds as AllRounds>::BroadcastRound::ValidationResult,
+            validation_result
+        )?;
+
+        result?;
+        for (index, result) in calculator.party.all_parties.iter().zip(all_results) {
+            if let RecoveryValidationResult::Error(err) = result {
+                bail!("Keyshare refresh failed for party {}: {}", index, err);
+            }
+        }
+
+        info!("Staged refreshed keyshare");
+        Ok(())
+    }
+}
+
+/// Adds the zero shares received from the other holders to the keyshare and the zero sharing
+/// commitments to its VSS, checking that the public key stays the same
+fn refresh_keyshare<C: Curve>(
+    calculator: &RecoveryCalculator<C>,
+    vss_scheme_vec: &[VerifiableSS<C>],
+    y_sum: &Point<C>,
+    zero_vss_vec: &[VerifiableSS<C>],
+    zero_shares: &[Scalar<C>],
+    retained: Scalar<C>
+) -> Result<(Scalar<C>, Vec<VerifiableSS<C>>)> {
+    let party_index = calculator.party.party_index;
+    let senders = calculator.party.all_parties
+        .iter()
+        .zip(zero_vss_vec)
+        .filter(|(&index, _)| index != party_index);
+    for ((index, vss), share) in senders.zip(zero_shares) {
+        if vss.validate_share(share, party_index as u16).is_err() {
+            bail!("Zero share from party {} did not pass validation", index);
+        }
+    }
+
+    let zero_share_sum = calculator.sum_secret_shares(retained, zero_shares.to_vec());
+    let x_i = calculator.secret_share.clone() + zero_share_sum;
+
+    let vss_scheme_vec = vss_scheme_vec
+        .iter()
+        .zip(zero_vss_vec)
+        .map(|(vss, zero_vss)| RecoveryCalculator::add_zero_sharing_to_vss(vss, zero_vss))
+        .collect::<Result<Vec<_>>>()?;
+
+    RecoveryCalculator::validate_recovered_share(&x_i, &vss_scheme_vec, party_index)?;
+
+    let refreshed_y_sum = RecoveryCalculator::calculate_y_sum_from_vss_vec(&vss_scheme_vec)?;
+    if &refreshed_y_sum != y_sum {
+        bail!("Refreshed keyshare commitments do not match the public key");
+    }
+
+    Ok((x_i, vss_scheme_vec))
+}
+
+fn zero_vss_from_commitments<C: Curve>(
+    calculator: &RecoveryCalculator<C>,
+    commitments: Vec<Point<C>>
+) -> Result<VerifiableSS<C>> {
+    if commitments.len() != calculator.threshold {
+        bail!(
+            "Expected {} zero share commitments, but received {}",
+            calculator.threshold,
+            commitments.len()
+        );
+    }
+    Ok(VerifiableSS {
+        parameters: ShamirSecretSharing {
+            threshold: calculator.threshold as u16,
+            share_count: calculator.party.all_parties.len() as u16,
+        },
+        commitments: iter::once(Point::zero()).chain(commitments).collect(),
+    })
+}
+
+/// Keyshare specific behaviour for proactive keyshare refresh
+pub trait KeyshareBehaviourRefreshRole {
+    type Curve: Curve;
+    type Keyshare: Serialize;
+
+    fn get_refresh_params(&self, party: Party) -> RecoveryCalculator<Self::Curve>;
+
+    fn vss_scheme_vec(&self) -> &[VerifiableSS<Self::Curve>];
+
+    fn y_sum(&self) -> &Point<Self::Curve>;
+
+    /// Keyshare with the refreshed secret share and commitments
+    fn refreshed_keyshare(
+        &self,
+        x_i: Scalar<Self::Curve>,
+        vss_scheme_vec: Vec<VerifiableSS<Self::Curve>>
+    ) -> Self::Keyshare;
+}
+
+/// EdDSA specific behaviour for proactive keyshare refresh
+pub struct EdDSABehaviourRefreshRole {
+    key_accessor: KeyshareAccessor<EDDSA>,
+}
+
+impl EdDSABehaviourRefreshRole {
+    pub fn from_key_accessor(key_accessor: KeyshareAccessor<EDDSA>) -> Self {
+        Self { key_accessor }
+    }
+}
+
+impl KeyshareBehaviourRefreshRole for EdDSABehaviourRefreshRole {
+    type Curve = Ed25519;
+    type Keyshare = EDDSA;
+
+    fn get_refresh_params(&self, party: Party) -> RecoveryCalculator<Self::Curve> {
+        RecoveryCalculator::<Self::Curve> {
+            secret_share: self.key_accessor.key.x_i.clone(),
+            threshold: self.key_accessor.key.threshold,
+            recovery_index: party.party_index,
+            party,
+        }
+    }
+
+    fn vss_scheme_vec(&self) -> &[VerifiableSS<Self::Curve>] {
+        &self.key_accessor.key.vss_scheme_vec
+    }
+
+    fn y_sum(&self) -> &Point<Self::Curve> {
+        &self.key_accessor.key.y_sum
+    }
+
+    fn refreshed_keyshare(
+        &self,
+        x_i: Scalar<Self::Curve>,
+        vss_scheme_vec: Vec<VerifiableSS<Self::Curve>>
+    ) -> Self::Keyshare {
+        EDDSA {
+            x_i,
+            vss_scheme_vec,
+            ..self.key_accessor.key.clone()
+        }
+    }
+}
+
+/// ECDSA specific behaviour for proactive keyshare refresh
+pub struct ECDSABehaviourRefreshRole {
+    key_accessor: KeyshareAccessor<ECDSA>,
+}
+
+impl ECDSABehaviourRefreshRole {
+    pub fn from_key_accessor(key_accessor: KeyshareAccessor<ECDSA>) -> Self {
+        Self { key_accessor }
+    }
+}
+
+impl KeyshareBehaviourRefreshRole for ECDSABehaviourRefreshRole {
+    type Curve = Secp256k1;
+    type Keyshare = ECDSA;
+
+    fn get_refresh_params(&self, party: Party) -> RecoveryCalculator<Self::Curve> {
+        RecoveryCalculator::<Self::Curve> {
+            secret_share: self.key_accessor.key.x_i.clone(),
+            threshold: self.key_accessor.key.threshold,
+            recovery_index: party.party_index,
+            party,
+        }
+    }
+
+    fn vss_scheme_vec(&self) -> &[VerifiableSS<Self::Curve>] {
+        &self.key_accessor.key.vss_scheme_vec
+    }
+
+    fn y_sum(&self) -> &Point<Self::Curve> {
+        &self.key_accessor.key.y_sum
+    }
+
+    fn refreshed_keyshare(
+        &self,
+        x_i: Scalar<Self::Curve>,
+        vss_scheme_vec: Vec<VerifiableSS<Self::Curve>>
+    ) -> Self::Keyshare {
+        // Public shares of every party change along with their keyshares
+        let public_key_vec = (1..=self.key_accessor.key.public_key_vec.len())
+            .map(|index| {
+                vss_scheme_vec
+                    .iter()
+                    .fold(Point::zero(), |acc, vss| acc + vss.get_point_commitment(index as u16))
+            })
+            .collect::<Vec<Point<Self::Curve>>>();
+
+        ECDSA {
+            x_i,
+            vss_scheme_vec,
+            public_key_vec,
+            ..self.key_accessor.key.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_changes_the_shares_but_keeps_the_key() {
+        let parties = vec![1, 2, 3];
+
+        // Keygen of a 2-of-3 key, every party deals a secret of its own
+        let dealings = parties
+            .iter()
+            .map(|_| VerifiableSS::<Ed25519>::share(1, 3, &Scalar::random()))
+            .collect::<Vec<_>>();
+        let vss_scheme_vec = dealings
+            .iter()
+            .map(|(vss, _)| vss.clone())
+            .collect::<Vec<_>>();
+        let y_sum = RecoveryCalculator::calculate_y_sum_from_vss_vec(&vss_scheme_vec).unwrap();
+        let shares = parties
+            .iter()
+            .map(|&index| {
+                dealings
+                    .iter()
+                    .map(|(_, shares)| shares[index - 1].clone())
+                    .sum::<Scalar<Ed25519>>()
+            })
+            .collect::<Vec<_>>();
+
+        let calculators = parties
+            .iter()
+            .map(|&index| {
+                RecoveryCalculator::new(index, index, parties.clone(), 1, shares[index - 1].clone())
+            })
+            .collect::<Vec<_>>();
+        let zero_sharings = calculators
+            .iter()
+            .map(|calculator| calculator.create_secret_sharing_of_zero_point())
+            .collect::<Vec<_>>();
+
+        let refreshed = calculators
+            .iter()
+            .zip(&zero_sharings)
+            .map(|(calculator, (_, contrib))| {
+                let party_index = calculator.party.party_index;
+                let zero_vss_vec = zero_sharings
+                    .iter()
+                    .map(|(zero_vss, _)| {
+                        let commitments = zero_vss.commitments.iter().skip(1).cloned().collect();
+                        zero_vss_from_commitments(calculator, commitments).unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                let zero_shares = parties
+                    .iter()
+                    .zip(&zero_sharings)
+                    .filter(|(&sender, _)| sender != party_index)
+                    .map(|(&sender, (_, sender_contrib))| {
+                        let position = parties
+                            .iter()
+                            .filter(|&&index| index != sender)
+                            .position(|&index| index == party_index)
+                            .unwrap();
+                        sender_contrib.for_peer_exchange[position].clone()
+                    })
+                    .collect::<Vec<_>>();
+
+                refresh_keyshare(
+                    calculator,
+                    &vss_scheme_vec,
+                    &y_sum,
+                    &zero_vss_vec,
+                    &zero_shares,
+                    contrib.retained.clone()
+                ).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for (index, (x_i, refreshed_vss_vec)) in parties.iter().zip(&refreshed) {
+            assert_ne!(x_i, &shares[index - 1]);
+            assert_eq!(
+                RecoveryCalculator::calculate_y_sum_from_vss_vec(refreshed_vss_vec).unwrap(),
+                y_sum
+            );
+        }
+
+        // Any two of the refreshed shares still interpolate to the secret of the key
+        let signers = [1, 3];
+        let secret = signers
+            .iter()
+            .map(|&index| {
+                &refreshed[index - 1].0 *
+                    RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(index, &signers)
+                        .unwrap()
+            })
+            .sum::<Scalar<Ed25519>>();
+        assert_eq!(Point::generator() * secret, y_sum);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::command::{ JsonCommand, MsgContext };
+use crate::communication::nats_session::Nats;
+use crate::communication::protocol::Topic;
+use crate::node::NodeIdentity;
+use crate::recovery::encryption::NKeyPeerEncryptor;
+use crate::recovery::recovery_session::NewKeyShareRecoverySession;
+use crate::recovery::refresh_role::{
+    ECDSABehaviourRefreshRole,
+    EdDSABehaviourRefreshRole,
+    KeyshareRefresher,
+    PendingRefresh,
+    RefreshStaging,
+    PENDING_REFRESH,
+    PREVIOUS_KEYSHARE,
+};
+use crate::recovery::{ Key, Party };
+use crate::storage::fs::WriteOpts;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use crate::storage::{
+    CurrentKeyshareFormat,
+    KeyshareAccessor,
+    KeyshareSaver,
+    Keystore,
+    ECDSA,
+    EDDSA,
+};
+use crate::App;
+use anyhow::{ anyhow, bail, Result };
+use serde::{ Deserialize, Serialize };
+use shared::recovery::PublicKeysEnum;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::thread;
+use tracing::{ error, info };
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NewKeyShareRefreshSession {
+    #[serde(flatten)]
+    pub kind: Key,
+    pub key_id: String,
+    pub session_id: String,
+    pub public_keys: PublicKeysEnum,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+impl NewKeyShareRefreshSession {
+    pub fn handle(&self, conn: nats::Connection) -> Result<()> {
+        let key_id = self.key_id.clone();
+        let session_id = self.session_id.clone();
+
+        let email = match &self.email {
+            Some(email) => email.clone(),
+            None => NewKeyShareRecoverySession::find_email_for_key(&key_id)?,
+        };
+
+        let node = NodeIdentity::load()?;
+        let private_key = node.networking_private_key.clone();
+
+        let public_keys: HashMap<usize, String> = self.public_keys.clone().into();
+
+        let topic = Topic::KeyShareRefresh;
+        let staging = RefreshStaging {
+            key_id: key_id.clone(),
+            email: email.clone(),
+            session_id: session_id.clone(),
+        };
+
+        match self.kind {
+            //Refresh of an EdDSA keyshare together with all other holders of the key
+            Key::EDDSA => {
+                let key_accessor = KeyshareAccessor::<EDDSA>::read_only_with_email(
+                    &key_id,
+                    &email
+                )?;
+                let party_index = key_accessor.key.party_index;
+
+                let (messenger, peers) = Nats::new_session(
+                    conn,
+                    &session_id,
+                    &node,
+                    &key_id,
+                    party_index,
+                    topic
+                )?;
+
+                let key_behaviour = EdDSABehaviourRefreshRole::from_key_accessor(key_accessor);
+
+                let encryptor = NKeyPeerEncryptor::new(
+                    &public_keys,
+                    party_index,
+                    &peers,
+                    private_key
+                ).map_err(|err| anyhow!("Unable to create encryptor: {}", err))?;
+
+                let mut refresher = KeyshareRefresher::new(
+                    messenger,
+                    encryptor,
+                    key_behaviour,
+                    staging
+                );
+
+                refresher.try_refresh(Party {
+                    party_index,
+                    all_parties: peers,
+                })
+            }
+            //Refresh of an ECDSA keyshare together with all other holders of the key
+            Key::ECDSA => {
+                let key_accessor = KeyshareAccessor::<ECDSA>::read_only_with_email(
+                    &key_id,
+                    &email
+                )?;
+                let party_index = key_accessor.key.party_index;
+
+                let (messenger, peers) = Nats::new_session(
+                    conn,
+                    &session_id,
+                    &node,
+                    &key_id,
+                    party_index,
+                    topic
+                )?;
+
+                let key_behaviour = ECDSABehaviourRefreshRole::from_key_accessor(key_accessor);
+
+                let encryptor = NKeyPeerEncryptor::new(
+                    &public_keys,
+                    party_index,
+                    &peers,
+                    private_key
+                ).map_err(|err| anyhow!("Unable to create encryptor: {}", err))?;
+
+                let mut refresher = KeyshareRefresher::new(
+                    messenger,
+                    encryptor,
+                    key_behaviour,
+                    staging
+                );
+
+                refresher.try_refresh(Party {
+                    party_index,
+                    all_parties: peers,
+                })
+            }
+            Key::Sr25519 | Key::Bls12381 => {
+                bail!("Keyshare refresh is only supported for ECDSA and EdDSA keys");
+            }
+        }
+    }
+}
+
+/// Step of the two phase commit of a refreshed keyshare, run by the orchestrator on every holder
+/// once all of them validated their refreshed keyshare
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshPhase {
+    /// Check the staged keyshare and set the current one aside, without switching yet
+    Prepare,
+    /// Replace the keyshare with the staged one, keeping the previous one until every holder
+    /// has switched
+    Commit,
+    /// Drop the previous keyshare once every holder switched
+    Release,
+    /// Restore the previous keyshare and drop the staged one
+    Rollback,
+}
+
+/// Sent to every holder for each phase of the commit of a refreshed keyshare
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompleteKeyShareRefreshCommand {
+    #[serde(flatten)]
+    pub kind: Key,
+    pub key_id: String,
+    pub email: String,
+    pub refresh_session_id: String,
+    pub phase: RefreshPhase,
+}
+
+impl Debug for CompleteKeyShareRefreshCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CompleteKeyShareRefreshCommand")
+            .field("key_id", &self.key_id)
+            .field("refresh_session_id", &self.refresh_session_id)
+            .field("phase", &self.phase)
+            .finish()
+    }
+}
+
+impl JsonCommand for CompleteKeyShareRefreshCommand {
+    type Response = ();
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        match self.kind {
+            Key::EDDSA => self.run_phase::<EDDSA>(),
+            Key::ECDSA => self.run_phase::<ECDSA>(),
+            Key::Sr25519 | Key::Bls12381 => {
+                bail!("Keyshare refresh is only supported for ECDSA and EdDSA keys");
+            }
+        }
+    }
+}
+
+impl CompleteKeyShareRefreshCommand {
+    fn run_phase<K: CurrentKeyshareFormat>(&self) -> Result<()> {
+        let key_saver = KeyshareSaver::new_creator_modifier(&self.key_id).with_email(&self.email);
+        match self.phase {
+            RefreshPhase::Prepare => {
+                serde_json::from_str::<K>(&self.load(PENDING_REFRESH)?.keyshare)?;
+                let current = K::try_from(Keystore::get_key_with_email(&self.key_id, &self.email)?)
+                    .map_err(|_| anyhow!("Keyshare of key {} has another format", &self.key_id))?;
+                let previous = PendingRefresh {
+                    session_id: self.refresh_session_id.clone(),
+                    keyshare: serde_json::to_string(&current)?,
+                };
+                KeyMetadataStore::save(
+                    &serde_json::to_string(&previous)?,
+                    &self.key_id,
+                    PREVIOUS_KEYSHARE,
+                    &self.email,
+                    &WriteOpts::Modify
+                )?;
+                info!("Prepared refreshed keyshare for key {}", &self.key_id);
+            }
+            RefreshPhase::Commit => {
+                // Only holders that set their current keyshare aside may switch
+                self.load(PREVIOUS_KEYSHARE)?;
+                let pending = self.load(PENDING_REFRESH)?;
+                key_saver.save_key(&serde_json::from_str::<K>(&pending.keyshare)?)?;
+                info!("Switched to refreshed keyshare for key {}", &self.key_id);
+            }
+            RefreshPhase::Release => {
+                self.load(PREVIOUS_KEYSHARE)?;
+                KeyMetadataStore::remove(&self.key_id, PENDING_REFRESH, &self.email)?;
+                KeyMetadataStore::remove(&self.key_id, PREVIOUS_KEYSHARE, &self.email)?;
+                info!("Saved refreshed keyshare for key {}", &self.key_id);
+            }
+            RefreshPhase::Rollback => {
+                if let Ok(previous) = self.load(PREVIOUS_KEYSHARE) {
+                    key_saver.save_key(&serde_json::from_str::<K>(&previous.keyshare)?)?;
+                    KeyMetadataStore::remove(&self.key_id, PREVIOUS_KEYSHARE, &self.email)?;
+                }
+                if self.load(PENDING_REFRESH).is_ok() {
+                    KeyMetadataStore::remove(&self.key_id, PENDING_REFRESH, &self.email)?;
+                }
+                info!("Rolled back refresh of key {}", &self.key_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keyshare staged under the given metadata entry by this refresh session
+    fn load(&self, metadata_type: &str) -> Result<PendingRefresh> {
+        let pending = KeyMetadataStore::get(&self.key_id, metadata_type, &self.email)?;
+        let pending = serde_json::from_str::<PendingRefresh>(&pending)?;
+        if pending.session_id != self.refresh_session_id {
+            bail!(
+                "Keyshare of key {} is from refresh session {} instead of {}",
+                &self.key_id,
+                pending.session_id,
+                &self.refresh_session_id
+            );
+        }
+        Ok(pending)
+    }
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let session = match serde_json::from_slice::<NewKeyShareRefreshSession>(&message.data[..]) {
+        Ok(session) => session,
+        Err(err) => {
+            error!("Incorrect keyshare refresh message format: {}", err);
+            return;
+        }
+    };
+
+    let nc = app.nc.clone();
+    let session_id = session.session_id.clone();
+    let thread_session_id = session_id.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(format!("keyshare_refresh_session_{}", &session_id))
+            .spawn(move || {
+                match session.handle(nc) {
+                    Ok(_) => {
+                        info!(
+                            "Keyshare refresh was successful for session id {}",
+                            &thread_session_id
+                        );
+                    }
+                    Err(err) => {
+                        error!(
+                            "Keyshare refresh failed: session id: {}, error: {}",
+                            &thread_session_id,
+                            err
+                        );
+                    }
+                };
+            })
+    {
+        Ok(_) => {
+            info!("Spawned a thread to handle keyshare refresh");
+        }
+        Err(_) => {
+            error!("Failed to spawn thread for keyshare refresh");
+        }
+    };
+}
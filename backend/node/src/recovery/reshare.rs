@@ -0,0 +1,805 @@
+use crate::address::point_from_sum;
+use crate::command::{ JsonCommand, MsgContext };
+use crate::encryption::{
+    decrypt_and_deserialize,
+    serialize_and_encrypt,
+    shared_secret_from_nkeys,
+    sign_with_nkey,
+    verify_nkey_signature,
+};
+use crate::node::NodeIdentity;
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::recovery::Key;
+use crate::security::check_for_small_primes;
+use crate::security::paillier_proofs::{ proof_context, PaillierKeyProof };
+use crate::signing::request_auth::CanonicalEncoder;
+use crate::storage::fs::{ FileSystem, WriteOpts };
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use crate::storage::{ KeyInfoStore, KeyshareSaver, ECDSA, EDDSA };
+use anyhow::{ anyhow, bail, Result };
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use itertools::Itertools;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2020::party_i::{
+    KeyGenBroadcastMessage1,
+    Keys,
+};
+use serde::{ Deserialize, Serialize };
+use shared::key_info::{ Key as KeyInfoKind, KeyInfo, NodeId, NodeInfo };
+use shared::recovery::{ EncryptedData, PublicKeysEnum };
+use std::collections::HashMap;
+use std::fmt::Debug;
+use tracing::{ error, info };
+use zk_paillier::zkproofs::{ DLogStatement, SALT_STRING };
+
+/// Metadata entry holding a reshared keyshare until the whole new committee has validated theirs
+const PENDING_RESHARE: &str = "reshare";
+
+/// Sub-shares of one old keyshare, dealt by an old holder to the new committee
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ResharePackage<C> where C: Curve {
+    pub vss: VerifiableSS<C>,
+    pub old_vss_vec: Vec<VerifiableSS<C>>,
+    pub encrypted_shares: Vec<(usize, EncryptedData)>,
+}
+
+/// Deals the Lagrange weighted keyshare of an old holder to the new committee,
+/// so that the new shares interpolate to the same secret
+pub fn create_reshare_package<C: Curve>(
+    x_i: &Scalar<C>,
+    party_index: usize,
+    dealers: &[usize],
+    old_vss_vec: &[VerifiableSS<C>],
+    threshold: usize,
+    new_public_keys: &HashMap<usize, String>,
+    private_key: &str
+) -> Result<ResharePackage<C>> {
+    let li = RecoveryCalculator::<C>::lagrange_coefficient_at_zero(party_index, dealers)?;
+    let w_i = x_i * &li;
+
+    let new_indices = new_public_keys.keys().cloned().sorted().collect_vec();
+    let (vss, shares) = VerifiableSS::<C>::share_at_indices(
+        threshold as u16,
+        new_indices.len() as u16,
+        &w_i,
+        &new_indices
+            .iter()
+            .map(|&i| i as u16)
+            .collect_vec()
+    );
+
+    let encrypted_shares = new_indices
+        .iter()
+        .zip(shares.iter())
+        .map(|(index, share)| {
+            let encryption_key = shared_secret_from_nkeys(private_key, &new_public_keys[index])?;
+            Ok((*index, serialize_and_encrypt(share, &encryption_key)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ResharePackage {
+        vss,
+        old_vss_vec: old_vss_vec.to_vec(),
+        encrypted_shares,
+    })
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReceiveResharePackagesCommand {
+    #[serde(flatten)]
+    pub kind: Key,
+    pub key_id: String,
+    pub email: String,
+    pub reshare_index: usize,
+    pub threshold: usize,
+    pub dealers: Vec<usize>,
+    pub dealer_public_keys: PublicKeysEnum,
+    pub packages: Vec<String>,
+    /// BIP32 chain code of an ECDSA key
+    #[serde(default)]
+    pub chain_code: Option<String>,
+    /// Public key of the key, nodes that already hold the key check its stored key info instead
+    pub public_key: KeyInfoKind,
+}
+
+impl Debug for ReceiveResharePackagesCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ReceiveResharePackagesCommand")
+            .field("key_id", &self.key_id)
+            .field("reshare_index", &self.reshare_index)
+            .field("dealers", &self.dealers)
+            .finish()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ReshareValidationResult {
+    Validated,
+    ValidatedWithKeyProof(Box<KeyGenBroadcastMessage1>),
+    ValidationError(String),
+}
+
+impl JsonCommand for ReceiveResharePackagesCommand {
+    type Response = ReshareValidationResult;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let result = match self.kind {
+            Key::ECDSA => self.stage_ecdsa_keyshare(),
+            Key::EDDSA => self.stage_eddsa_keyshare(),
+            Key::Sr25519 | Key::Bls12381 => {
+                Err(anyhow!("Resharing is only supported for ECDSA and EdDSA keys"))
+            }
+        };
+
+        Ok(
+            result.unwrap_or_else(|err| {
+                error!("Reshared keyshare could not be validated: {}", err);
+                ReshareValidationResult::ValidationError(err.to_string())
+            })
+        )
+    }
+}
+
+impl ReceiveResharePackagesCommand {
+    fn stage_eddsa_keyshare(&self) -> Result<ReshareValidationResult> {
+        let node = NodeIdentity::load()?;
+        let (x_i, vss_scheme_vec, y_sum) = self.combine_reshare_packages::<Ed25519>(
+            &node.networking_private_key
+        )?;
+        self.check_public_key(&y_sum)?;
+
+        let keyshare = EDDSA {
+            threshold: self.threshold,
+            party_index: self.reshare_index,
+            x_i,
+            y_sum,
+            vss_scheme_vec,
+        };
+        self.save_pending(&keyshare)?;
+
+        Ok(ReshareValidationResult::Validated)
+    }
+
+    fn stage_ecdsa_keyshare(&self) -> Result<ReshareValidationResult> {
+        let node = NodeIdentity::load()?;
+        let (x_i, vss_scheme_vec, y_sum) = self.combine_reshare_packages::<Secp256k1>(
+            &node.networking_private_key
+        )?;
+        self.check_public_key(&y_sum)?;
+
+        let public_key_vec = (1..=vss_scheme_vec.len())
+            .map(|index| {
+                vss_scheme_vec
+                    .iter()
+                    .fold(Point::zero(), |acc, vss| acc + vss.get_point_commitment(index as u16))
+            })
+            .collect::<Vec<Point<Secp256k1>>>();
+
+        info!("Creating paillier keys for the reshared keyshare");
+        let keys = Keys::create_safe_prime(self.reshare_index);
+        let (key_proof, _) =
+            keys.phase1_broadcast_phase3_proof_of_correct_key_proof_of_correct_h1h2();
+
+        // Paillier keys and h1_h2_N_tilde of the new committee are filled in once all members
+        // have created theirs, until then only our own ring-Pedersen parameters are kept so that
+        // they can't be swapped for the ones the other members prove their keys against
+        let keyshare = ECDSA {
+            threshold: self.threshold,
+            y_sum,
+            x_i,
+            party_index: self.reshare_index,
+            public_key_vec,
+            vss_scheme_vec,
+            paillier_key_vec: Vec::new(),
+            h1_h2_N_tilde_vec: vec![key_proof.dlog_statement.clone()],
+            paillier_dk: keys.dk,
+            chain_code: self.chain_code.clone(),
+        };
+        self.save_pending(&keyshare)?;
+
+        Ok(ReshareValidationResult::ValidatedWithKeyProof(Box::new(key_proof)))
+    }
+
+    fn save_pending<K: Serialize>(&self, keyshare: &K) -> Result<()> {
+        KeyMetadataStore::save(
+            &serde_json::to_string(keyshare)?,
+            &self.key_id,
+            PENDING_RESHARE,
+            &self.email,
+            &WriteOpts::Modify
+        )
+    }
+
+    /// The old VSS commitments come from the dealers, so the public key they commit to has to be
+    /// the one of the key
+    fn check_public_key<C: Curve>(&self, y_sum: &Point<C>) -> Result<()> {
+        let public_key = match KeyInfoStore::get_key_info(&self.key_id) {
+            Ok(stored_key_info) => stored_key_info.kind,
+            Err(_) => self.public_key.clone(),
+        };
+        let public_key_bytes = match public_key {
+            KeyInfoKind::ECDSA { y_sum, .. } => point_from_sum(&y_sum)?.to_bytes(true).to_vec(),
+            KeyInfoKind::EDDSA { y_sum } => hex::decode(y_sum)?,
+            KeyInfoKind::Sr25519 { .. } | KeyInfoKind::Bls12381 { .. } => {
+                bail!("Resharing is only supported for ECDSA and EdDSA keys");
+            }
+        };
+        let public_key = Point::<C>
+            ::from_bytes(&public_key_bytes)
+            .map_err(|_| anyhow!("Invalid public key in key info"))?;
+        if *y_sum != public_key {
+            bail!("Old VSS commitments do not match the public key of key {}", &self.key_id);
+        }
+        Ok(())
+    }
+
+    fn combine_reshare_packages<C: Curve>(
+        &self,
+        private_key: &str
+    ) -> Result<(Scalar<C>, Vec<VerifiableSS<C>>, Point<C>)> {
+        if self.packages.len() != self.dealers.len() {
+            bail!(
+                "Received {} reshare packages from {} old keyshare holders",
+                self.packages.len(),
+                self.dealers.len()
+            );
+        }
+
+        let dealer_public_keys: HashMap<usize, String> = self.dealer_public_keys.clone().into();
+
+        let packages = self.packages
+            .iter()
+            .map(|package| serde_json::from_str::<ResharePackage<C>>(package))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let first_package = packages.first().ok_or(anyhow!("No reshare packages received"))?;
+        let old_vss_vec = first_package.old_vss_vec.clone();
+        if packages.iter().any(|package| package.old_vss_vec != old_vss_vec) {
+            bail!("Old VSS scheme vecs provided do not match, reshare cannot be validated");
+        }
+        let old_threshold = old_vss_vec
+            .first()
+            .ok_or(anyhow!("VSS empty"))?
+            .parameters.threshold as usize;
+        if self.dealers.len() <= old_threshold {
+            bail!(
+                "At least {} old keyshare holders need to take part in a reshare, but only {} did",
+                old_threshold + 1,
+                self.dealers.len()
+            );
+        }
+        let y_sum = RecoveryCalculator::calculate_y_sum_from_vss_vec(&old_vss_vec)?;
+
+        let parameters = first_package.vss.parameters.clone();
+        if parameters.threshold as usize != self.threshold {
+            bail!("Reshare packages were dealt with a different threshold");
+        }
+
+        let mut x_i = Scalar::<C>::zero();
+        let mut commitments = vec![Point::<C>::zero(); self.threshold + 1];
+        for (&dealer, package) in self.dealers.iter().zip(&packages) {
+            if
+                package.vss.parameters != parameters ||
+                package.vss.commitments.len() != self.threshold + 1
+            {
+                bail!("Reshare package from old party {} has the wrong parameters", dealer);
+            }
+
+            // The dealt secret has to be the Lagrange weighted keyshare committed to in the old VSS
+            let old_public_share = old_vss_vec
+                .iter()
+                .fold(Point::zero(), |acc, vss| acc + vss.get_point_commitment(dealer as u16));
+            let li = RecoveryCalculator::<C>::lagrange_coefficient_at_zero(dealer, &self.dealers)?;
+            if package.vss.commitments[0] != old_public_share * li {
+                bail!("Reshare package from old party {} does not match the old VSS", dealer);
+            }
+
+            let (_, encrypted_share) = package.encrypted_shares
+                .iter()
+                .find(|(index, _)| *index == self.reshare_index)
+                .ok_or(anyhow!("Reshare package from old party {} has no share for us", dealer))?;
+            let dealer_public_key = dealer_public_keys
+                .get(&dealer)
+                .ok_or(anyhow!("Could not find public key of old party {}", dealer))?;
+            let decryption_key = shared_secret_from_nkeys(private_key, dealer_public_key)?;
+            let share: Scalar<C> = decrypt_and_deserialize(encrypted_share, &decryption_key)?;
+
+            if package.vss.validate_share(&share, self.reshare_index as u16).is_err() {
+                bail!("Share from old party {} did not pass validation", dealer);
+            }
+
+            x_i = x_i + share;
+            commitments = commitments
+                .iter()
+                .zip(&package.vss.commitments)
+                .map(|(c, d)| c + d)
+                .collect();
+        }
+
+        if commitments[0] != y_sum {
+            bail!("Reshared commitments do not match the public key");
+        }
+
+        // Keyshares keep one VSS per party, so the combined VSS is padded out with sharings of zero
+        let share_count = parameters.share_count as usize;
+        let zero_vss = VerifiableSS {
+            parameters: parameters.clone(),
+            commitments: vec![Point::zero(); self.threshold + 1],
+        };
+        let mut vss_scheme_vec = vec![VerifiableSS { parameters, commitments }];
+        vss_scheme_vec.resize(share_count, zero_vss);
+
+        RecoveryCalculator::validate_recovered_share(&x_i, &vss_scheme_vec, self.reshare_index)?;
+        info!("Validated reshared keyshare {}", self.reshare_index);
+
+        Ok((x_i, vss_scheme_vec, y_sum))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CompleteReshareCommand {
+    #[serde(flatten)]
+    pub kind: Key,
+    pub key_id: String,
+    pub email: String,
+    pub key_proofs: Vec<KeyGenBroadcastMessage1>,
+    /// Proofs of the Paillier keys in `key_proofs`, empty for EdDSA keys
+    pub paillier_key_proofs: Vec<PaillierKeyProof>,
+}
+
+impl Debug for CompleteReshareCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CompleteReshareCommand").field("key_id", &self.key_id).finish()
+    }
+}
+
+impl JsonCommand for CompleteReshareCommand {
+    type Response = ();
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let pending = KeyMetadataStore::get(&self.key_id, PENDING_RESHARE, &self.email)?;
+        let key_saver = KeyshareSaver::new_creator_modifier(&self.key_id).with_email(&self.email);
+
+        match self.kind {
+            Key::EDDSA => {
+                let keyshare = serde_json::from_str::<EDDSA>(&pending)?;
+                key_saver.save_key(&keyshare)?;
+            }
+            Key::ECDSA => {
+                let mut keyshare = serde_json::from_str::<ECDSA>(&pending)?;
+                if
+                    self.key_proofs.len() != keyshare.vss_scheme_vec.len() ||
+                    self.paillier_key_proofs.len() != keyshare.vss_scheme_vec.len()
+                {
+                    bail!(
+                        "Expected paillier keys for {} keyshare holders, but received {}",
+                        keyshare.vss_scheme_vec.len(),
+                        self.key_proofs.len()
+                    );
+                }
+                for key_proof in &self.key_proofs {
+                    verify_key_proof(key_proof)?;
+                }
+
+                let own_key_proof = &self.key_proofs[keyshare.party_index - 1];
+                if own_key_proof.e.n != &keyshare.paillier_dk.p * &keyshare.paillier_dk.q {
+                    bail!("Paillier key of this keyshare was not included in the reshare");
+                }
+                check_own_statement(&keyshare, &own_key_proof.dlog_statement)?;
+
+                // Security issue: CVE-2023-33241
+                for (i, (key_proof, proof)) in self.key_proofs
+                    .iter()
+                    .zip(&self.paillier_key_proofs)
+                    .enumerate()
+                    .filter(|(i, _)| i + 1 != keyshare.party_index)
+                {
+                    proof
+                        .verify(
+                            &key_proof.e,
+                            keyshare.party_index,
+                            &own_key_proof.dlog_statement,
+                            &proof_context(&self.key_id, i + 1)
+                        )
+                        .map_err(|err| {
+                            anyhow!("Paillier key for party {} is invalid: {}", i + 1, err)
+                        })?;
+                }
+
+                keyshare.paillier_key_vec = self.key_proofs
+                    .iter()
+                    .map(|key_proof| key_proof.e.clone())
+                    .collect();
+                keyshare.h1_h2_N_tilde_vec = self.key_proofs
+                    .iter()
+                    .map(|key_proof| key_proof.dlog_statement.clone())
+                    .collect();
+                key_saver.save_key(&keyshare)?;
+            }
+            Key::Sr25519 | Key::Bls12381 => {
+                bail!("Resharing is only supported for ECDSA and EdDSA keys");
+            }
+        }
+
+        KeyMetadataStore::remove(&self.key_id, PENDING_RESHARE, &self.email)?;
+        info!("Saved reshared keyshare for key {}", &self.key_id);
+        Ok(())
+    }
+}
+
+/// Sent to every member of the new committee once all of them created their Paillier keys, so
+/// they prove theirs sound against the ring-Pedersen parameters of each other member
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProvePaillierKeyCommand {
+    pub key_id: String,
+    pub email: String,
+    pub key_proofs: Vec<KeyGenBroadcastMessage1>,
+}
+
+impl Debug for ProvePaillierKeyCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ProvePaillierKeyCommand").field("key_id", &self.key_id).finish()
+    }
+}
+
+impl JsonCommand for ProvePaillierKeyCommand {
+    type Response = PaillierKeyProof;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let pending = KeyMetadataStore::get(&self.key_id, PENDING_RESHARE, &self.email)?;
+        let keyshare = serde_json::from_str::<ECDSA>(&pending)?;
+        let own_key_proof = self.key_proofs
+            .get(keyshare.party_index - 1)
+            .ok_or_else(|| anyhow!("Paillier key of this keyshare was not included"))?;
+        check_own_statement(&keyshare, &own_key_proof.dlog_statement)?;
+
+        // The no small factor proofs are only made against ring-Pedersen parameters that are
+        // proven sound themselves
+        for key_proof in &self.key_proofs {
+            verify_key_proof(key_proof)?;
+        }
+        let verifiers = self.key_proofs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i + 1 != keyshare.party_index)
+            .map(|(i, key_proof)| (i + 1, &key_proof.dlog_statement))
+            .collect::<Vec<(usize, &DLogStatement)>>();
+        PaillierKeyProof::new(
+            &keyshare.paillier_dk,
+            &verifiers,
+            &proof_context(&self.key_id, keyshare.party_index)
+        )
+    }
+}
+
+/// Has every member of the new committee prove its Paillier key to the others, returning the
+/// proofs in the order of `key_proofs`
+pub fn collect_paillier_key_proofs(
+    nc: &nats::Connection,
+    node_pool: &[NodeInfo],
+    key_id: &str,
+    email: &str,
+    key_proofs: &[KeyGenBroadcastMessage1]
+) -> Result<Vec<PaillierKeyProof>> {
+    let message = serde_json::to_string(
+        &(ProvePaillierKeyCommand {
+            key_id: key_id.to_string(),
+            email: email.to_string(),
+            key_proofs: key_proofs.to_vec(),
+        })
+    )?;
+    let mut node_pool = node_pool.iter().collect_vec();
+    node_pool.sort_by_key(|node| node.share_index);
+
+    let mut paillier_key_proofs = Vec::new();
+    for node in node_pool {
+        let message_new_key = format!("network.gridlock.nodes.async.Message.new.{}", node.node_id);
+        let res = nc.request(&message_new_key, &message)?;
+        let proof = serde_json::from_slice::<PaillierKeyProof>(&res.data).map_err(|_| {
+            anyhow!(
+                "Party {} could not prove its Paillier key: {}",
+                node.share_index,
+                String::from_utf8_lossy(&res.data)
+            )
+        })?;
+        paillier_key_proofs.push(proof);
+    }
+    Ok(paillier_key_proofs)
+}
+
+/// Sent to the old holders that are not part of the new committee once the reshared keyshares
+/// are saved, so they delete their keyshare
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShredKeyshareCommand {
+    pub key_id: String,
+    pub email: String,
+    /// Key info after the reshare, without this node
+    pub key_info: KeyInfo,
+    /// Holder of the key that orchestrated the reshare
+    pub orchestrator_node_id: NodeId,
+    /// Base64 signature of the command by the networking key of the orchestrator
+    pub orchestrator_signature: String,
+}
+
+impl ShredKeyshareCommand {
+    /// Build the command, signed by the networking key of this node as the orchestrator
+    pub fn new_signed(key_id: &str, email: &str, key_info: KeyInfo) -> Result<Self> {
+        let node = NodeIdentity::load()?;
+        let mut command = Self {
+            key_id: key_id.to_string(),
+            email: email.to_string(),
+            key_info,
+            orchestrator_node_id: NodeId::new_from_uuid(node.node_id),
+            orchestrator_signature: String::new(),
+        };
+        command.orchestrator_signature = sign_with_nkey(
+            &node.networking_private_key,
+            &command.canonical_encoding()?
+        )?;
+        Ok(command)
+    }
+
+    fn canonical_encoding(&self) -> Result<Vec<u8>> {
+        Ok(
+            CanonicalEncoder::new("shred_keyshare")
+                .str("key_id", &self.key_id)
+                .str("email", &self.email)
+                .str("key_info", &serde_json::to_string(&self.key_info)?)
+                .str("orchestrator_node_id", &self.orchestrator_node_id.to_string())
+                .finish()
+        )
+    }
+}
+
+impl Debug for ShredKeyshareCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShredKeyshareCommand")
+            .field("key_id", &self.key_id)
+            .field("orchestrator_node_id", &self.orchestrator_node_id)
+            .finish()
+    }
+}
+
+impl JsonCommand for ShredKeyshareCommand {
+    type Response = ();
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let node = NodeIdentity::load()?;
+        let node_id = NodeId::new_from_uuid(node.node_id);
+
+        let stored_key_info = KeyInfoStore::get_key_info(&self.key_id)?;
+        let orchestrator = stored_key_info.node_pool
+            .iter()
+            .find(|&n| n.node_id == self.orchestrator_node_id)
+            .ok_or_else(|| anyhow!("Orchestrator is not a holder of key {}", &self.key_id))?;
+        verify_nkey_signature(
+            &orchestrator.networking_public_key,
+            &self.canonical_encoding()?,
+            &self.orchestrator_signature
+        ).map_err(|err| anyhow!("Shredding is not signed by the orchestrator: {}", err))?;
+
+        if !stored_key_info.node_pool.iter().any(|n| n.node_id == node_id) {
+            bail!("This node does not hold a share of key {}", &self.key_id);
+        }
+        if self.key_info.node_pool.iter().any(|n| n.node_id == node_id) {
+            bail!("Key info still lists this node as a holder of key {}", &self.key_id);
+        }
+        if
+            serde_json::to_string(&self.key_info.kind)? !=
+            serde_json::to_string(&stored_key_info.kind)?
+        {
+            bail!("Key info after the reshare is for a different public key");
+        }
+
+        FileSystem::shred_key_files(&self.key_id, &self.email)?;
+        KeyInfoStore::remove_key_info(&self.key_id)?;
+        info!("Shredded keyshare of key {} after the reshare", &self.key_id);
+        Ok(())
+    }
+}
+
+/// Ring-Pedersen parameters sent back for this keyshare have to be the ones it created
+fn check_own_statement(keyshare: &ECDSA, statement: &DLogStatement) -> Result<()> {
+    let own_statement = keyshare.h1_h2_N_tilde_vec
+        .first()
+        .ok_or_else(|| anyhow!("Ring-Pedersen parameters of this keyshare are missing"))?;
+    if
+        own_statement.N != statement.N ||
+        own_statement.g != statement.g ||
+        own_statement.ni != statement.ni
+    {
+        bail!("Ring-Pedersen parameters of this keyshare were replaced");
+    }
+    Ok(())
+}
+
+fn verify_key_proof(key_proof: &KeyGenBroadcastMessage1) -> Result<()> {
+    // Security issue: CVE-2023-33241
+    check_for_small_primes(&key_proof.e)?;
+
+    key_proof.correct_key_proof
+        .verify(&key_proof.e, SALT_STRING)
+        .map_err(|_| anyhow!("Paillier key proof did not verify"))?;
+
+    let dlog_statement_base_h2 = DLogStatement {
+        N: key_proof.dlog_statement.N.clone(),
+        g: key_proof.dlog_statement.ni.clone(),
+        ni: key_proof.dlog_statement.g.clone(),
+    };
+    key_proof.composite_dlog_proof_base_h1
+        .verify(&key_proof.dlog_statement)
+        .map_err(|_| anyhow!("h1_h2_N_tilde proof did not verify"))?;
+    key_proof.composite_dlog_proof_base_h2
+        .verify(&dlog_statement_base_h2)
+        .map_err(|_| anyhow!("h1_h2_N_tilde proof did not verify"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keygen::key_import::{ create_import_package, ImportDealing };
+    use nkeys::KeyPair;
+
+    #[test]
+    fn reshare_to_a_new_committee_keeps_the_key() {
+        let secret = Scalar::<Ed25519>::random();
+        let public_key = Point::<Ed25519>::generator() * &secret;
+        let (old_vss, old_shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+        let old_vss_vec = vec![old_vss];
+
+        // Old holders 1 and 3 deal to a 3-of-4 committee
+        let dealers = vec![1, 3];
+        let dealer_keys = dealers
+            .iter()
+            .map(|&index| (index, KeyPair::new_user()))
+            .collect::<Vec<_>>();
+        let new_keys = (1..=4).map(|index| (index, KeyPair::new_user())).collect::<Vec<_>>();
+        let new_public_keys = new_keys
+            .iter()
+            .map(|(index, kp)| (*index, kp.public_key()))
+            .collect::<HashMap<_, _>>();
+
+        let packages = dealer_keys
+            .iter()
+            .map(|(index, kp)| {
+                let package = create_reshare_package(
+                    &old_shares[index - 1],
+                    *index,
+                    &dealers,
+                    &old_vss_vec,
+                    2,
+                    &new_public_keys,
+                    &kp.seed().unwrap()
+                ).unwrap();
+                serde_json::to_string(&package).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let new_shares = new_keys
+            .iter()
+            .map(|(index, kp)| {
+                let command = ReceiveResharePackagesCommand {
+                    kind: Key::EDDSA,
+                    key_id: "key".to_string(),
+                    email: "user@example.com".to_string(),
+                    reshare_index: *index,
+                    threshold: 2,
+                    dealers: dealers.clone(),
+                    dealer_public_keys: PublicKeysEnum::Map(
+                        dealer_keys
+                            .iter()
+                            .map(|(index, kp)| (*index, kp.public_key()))
+                            .collect()
+                    ),
+                    packages: packages.clone(),
+                    chain_code: None,
+                    public_key: KeyInfoKind::EDDSA {
+                        y_sum: hex::encode(&*public_key.to_bytes(false)),
+                    },
+                };
+                let (x_i, vss_scheme_vec, y_sum) = command
+                    .combine_reshare_packages::<Ed25519>(&kp.seed().unwrap())
+                    .unwrap();
+                assert_eq!(y_sum, public_key);
+                assert_eq!(vss_scheme_vec.len(), 4);
+                (*index, x_i)
+            })
+            .collect::<Vec<_>>();
+
+        // Any three of the new shares interpolate to the old secret, two do not
+        let signers = [1, 2, 4];
+        let recombined = new_shares
+            .iter()
+            .filter(|(index, _)| signers.contains(index))
+            .map(|(index, x_i)| {
+                x_i * RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(*index, &signers)
+                    .unwrap()
+            })
+            .sum::<Scalar<Ed25519>>();
+        assert_eq!(recombined, secret);
+
+        let too_few = [2, 3];
+        let recombined = new_shares
+            .iter()
+            .filter(|(index, _)| too_few.contains(index))
+            .map(|(index, x_i)| {
+                x_i * RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(*index, &too_few)
+                    .unwrap()
+            })
+            .sum::<Scalar<Ed25519>>();
+        assert_ne!(recombined, secret);
+    }
+
+    #[test]
+    fn import_deals_the_key_to_a_committee() {
+        let secret = Scalar::<Ed25519>::random();
+        let public_key = Point::<Ed25519>::generator() * &secret;
+
+        let dealer_key = KeyPair::new_user();
+        let new_keys = (1..=3).map(|index| (index, KeyPair::new_user())).collect::<Vec<_>>();
+        let new_public_keys = new_keys
+            .iter()
+            .map(|(index, kp)| (*index, kp.public_key()))
+            .collect::<HashMap<_, _>>();
+        let package = create_import_package(
+            &secret,
+            1,
+            &new_public_keys,
+            &dealer_key.seed().unwrap()
+        ).unwrap();
+
+        // Guardians have no key info of an imported key, the dealing carries its public key
+        let dealing = ImportDealing {
+            kind: Key::EDDSA,
+            key_id: "imported key without key info".to_string(),
+            email: "user@example.com".to_string(),
+            threshold: 1,
+            dealer_public_key: dealer_key.public_key(),
+            package: serde_json::to_string(&package).unwrap(),
+            chain_code: None,
+            public_key: KeyInfoKind::EDDSA {
+                y_sum: hex::encode(&*public_key.to_bytes(false)),
+            },
+        };
+
+        let new_shares = new_keys
+            .iter()
+            .map(|(index, kp)| {
+                let command = dealing.message_for(*index);
+                let (x_i, vss_scheme_vec, y_sum) = command
+                    .combine_reshare_packages::<Ed25519>(&kp.seed().unwrap())
+                    .unwrap();
+                command.check_public_key(&y_sum).unwrap();
+                assert_eq!(vss_scheme_vec.len(), 3);
+                (*index, x_i)
+            })
+            .collect::<Vec<_>>();
+
+        let signers = [2, 3];
+        let recombined = new_shares
+            .iter()
+            .filter(|(index, _)| signers.contains(index))
+            .map(|(index, x_i)| {
+                x_i * RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(*index, &signers)
+                    .unwrap()
+            })
+            .sum::<Scalar<Ed25519>>();
+        assert_eq!(recombined, secret);
+
+        let other_public_key = Point::<Ed25519>::generator() * Scalar::<Ed25519>::random();
+        let other_key = ImportDealing {
+            public_key: KeyInfoKind::EDDSA {
+                y_sum: hex::encode(&*other_public_key.to_bytes(false)),
+            },
+            ..dealing
+        };
+        let command = other_key.message_for(1);
+        let (_, _, y_sum) = command
+            .combine_reshare_packages::<Ed25519>(&new_keys[0].1.seed().unwrap())
+            .unwrap();
+        assert!(command.check_public_key(&y_sum).is_err());
+    }
+}
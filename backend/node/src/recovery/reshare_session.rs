@@ -0,0 +1,143 @@
+use crate::communication::nats::{ NatsPeerMessenger, PeerMessenger };
+use crate::communication::nats_session::Nats;
+use crate::communication::protocol::{ AllRounds, KeyShareReshareAllRounds, Topic };
+use crate::node::NodeIdentity;
+use crate::recovery::recovery_session::NewKeyShareRecoverySession;
+use crate::recovery::reshare::create_reshare_package;
+use crate::recovery::Key;
+use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
+use crate::App;
+use anyhow::{ bail, Result };
+use serde::{ Deserialize, Serialize };
+use shared::recovery::PublicKeysEnum;
+use std::collections::HashMap;
+use std::thread;
+use tracing::{ error, info };
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NewKeyShareReshareSession {
+    #[serde(flatten)]
+    pub kind: Key,
+    pub key_id: String,
+    pub session_id: String,
+    pub threshold: usize,
+    pub public_keys: PublicKeysEnum,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+impl NewKeyShareReshareSession {
+    pub fn handle(&self, conn: nats::Connection) -> Result<()> {
+        let key_id = self.key_id.clone();
+
+        let email = match &self.email {
+            Some(email) => email.clone(),
+            None => NewKeyShareRecoverySession::find_email_for_key(&key_id)?,
+        };
+
+        let node = NodeIdentity::load()?;
+        let new_public_keys: HashMap<usize, String> = self.public_keys.clone().into();
+
+        let (messenger, package) = match self.kind {
+            //Deal sub-shares of an EdDSA keyshare to the new committee
+            Key::EDDSA => {
+                let key = KeyshareAccessor::<EDDSA>::read_only_with_email(&key_id, &email)?.key;
+                let (messenger, dealers) = self.join_session(conn, &node, key.party_index)?;
+                let package = create_reshare_package(
+                    &key.x_i,
+                    key.party_index,
+                    &dealers,
+                    &key.vss_scheme_vec,
+                    self.threshold,
+                    &new_public_keys,
+                    &node.networking_private_key
+                )?;
+                (messenger, serde_json::to_string(&package)?)
+            }
+            //Deal sub-shares of an ECDSA keyshare to the new committee
+            Key::ECDSA => {
+                let key = KeyshareAccessor::<ECDSA>::read_only_with_email(&key_id, &email)?.key;
+                let (messenger, dealers) = self.join_session(conn, &node, key.party_index)?;
+                let package = create_reshare_package(
+                    &key.x_i,
+                    key.party_index,
+                    &dealers,
+                    &key.vss_scheme_vec,
+                    self.threshold,
+                    &new_public_keys,
+                    &node.networking_private_key
+                )?;
+                (messenger, serde_json::to_string(&package)?)
+            }
+            Key::Sr25519 | Key::Bls12381 => {
+                bail!("Resharing is only supported for ECDSA and EdDSA keys");
+            }
+        };
+
+        messenger.broadcast_message(
+            &<KeyShareReshareAllRounds as AllRounds>::BroadcastRound::DeliverResharePackage,
+            package
+        )?;
+        info!("Delivered reshare package");
+        Ok(())
+    }
+
+    fn join_session(
+        &self,
+        conn: nats::Connection,
+        node: &NodeIdentity,
+        party_index: usize
+    ) -> Result<(NatsPeerMessenger<KeyShareReshareAllRounds>, Vec<usize>)> {
+        Nats::new_session(
+            conn,
+            &self.session_id,
+            node,
+            &self.key_id,
+            party_index,
+            Topic::KeyShareReshare
+        )
+    }
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let session = match serde_json::from_slice::<NewKeyShareReshareSession>(&message.data[..]) {
+        Ok(session) => session,
+        Err(err) => {
+            error!("Incorrect reshare message format: {}", err);
+            return;
+        }
+    };
+
+    let nc = app.nc.clone();
+    let session_id = session.session_id.clone();
+    let thread_session_id = session_id.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(format!("keyshare_reshare_session_{}", &session_id))
+            .spawn(move || {
+                match session.handle(nc) {
+                    Ok(_) => {
+                        info!(
+                            "Reshare package was dealt for session id {}",
+                            &thread_session_id
+                        );
+                    }
+                    Err(err) => {
+                        error!(
+                            "Reshare failed: session id: {}, error: {}",
+                            &thread_session_id,
+                            err
+                        );
+                    }
+                };
+            })
+    {
+        Ok(_) => {
+            info!("Spawned a thread to handle reshare");
+        }
+        Err(_) => {
+            error!("Failed to spawn thread for reshare");
+        }
+    };
+}
@@ -5,7 +5,8 @@ use crate::communication::{
 };
 use crate::recovery::calculator::RecoveryCalculator;
 use crate::recovery::encryption::TargetEncryptor;
-use crate::storage::{ KeyshareSaver, Sr25519, ECDSA, EDDSA };
+use crate::security::paillier_proofs::{ proof_context, PaillierKeyProof };
+use crate::storage::{ Bls12381, KeyshareSaver, Sr25519, ECDSA, EDDSA };
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use paillier::{ DecryptionKey, EncryptionKey, KeyGeneration, Paillier };
 use serde::de::DeserializeOwned;
@@ -14,13 +15,14 @@ use zk_paillier::zkproofs::DLogStatement;
 
 use crate::recovery::{
     replace_elem_in_vec,
+    Bls12381RecoveryPackage,
     ECDSARecoveryPackage,
     EdDSARecoveryPackage,
     RecoveryValidationResult,
     ShareRecoveryInfo,
 };
-use anyhow::{ bail, Result };
-use curv::elliptic::curves::{ Curve, Ed25519, Point, Scalar, Secp256k1 };
+use anyhow::{ anyhow, bail, Result };
+use curv::elliptic::curves::{ Bls12_381_1, Curve, Ed25519, Point, Scalar, Secp256k1 };
 use itertools::Itertools;
 use tracing::{ error, info };
 
@@ -72,13 +74,26 @@ impl<PM, E, R> KeyshareRecoveryTarget<PM, E, R>
         self.broadcast_validation_result(result)
     }
 
-    pub fn try_recieve_encrypted_packages(&self) -> Result<Vec<<E as TargetEncryptor>::Output>> {
+    /// Packages of every helper for the share at `position` of the shares recovered in the
+    /// session, as helpers send theirs for all of them in one list
+    pub fn try_recieve_encrypted_packages(
+        &self,
+        position: usize
+    ) -> Result<Vec<<E as TargetEncryptor>::Output>> {
         info!("Attempting to recieve recovery packages as the target node");
-        let received_packages = self.messenger.collect_messages::<E::Output>(
+        let received_packages = self.messenger.collect_messages::<Vec<E::Output>>(
             &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound::DeliverRecoveryPackage
         )?;
         info!("Received recovery packages");
-        Ok(received_packages)
+        received_packages
+            .into_iter()
+            .map(|packages| {
+                packages
+                    .into_iter()
+                    .nth(position)
+                    .ok_or_else(|| anyhow!("Helper did not send a package for every lost share"))
+            })
+            .collect()
     }
 
     fn broadcast_validation_result(
@@ -164,12 +179,14 @@ impl KeyshareBehaviourTargetRole for EdDSABehaviourTargetRole {
 }
 
 pub struct ECDSABehaviourTargetRole {
+    key_id: String,
     key_saver: KeyshareSaver,
 }
 
 impl ECDSABehaviourTargetRole {
     pub fn new(key_id: &str) -> Self {
         Self {
+            key_id: key_id.to_string(),
             key_saver: KeyshareSaver::new_creator_modifier(key_id),
         }
     }
@@ -203,7 +220,7 @@ impl KeyshareBehaviourTargetRole for ECDSABehaviourTargetRole {
         info!("Validated share info recieved");
 
         let validated_recovery_items = match
-            validate_ecdsa_specific_recovery_package_items(packages, recovery_index)
+            validate_ecdsa_specific_recovery_package_items(&self.key_id, packages, recovery_index)
         {
             Ok(ss) => {
                 info!("Returned new ecdsa items");
@@ -234,13 +251,17 @@ impl KeyshareBehaviourTargetRole for ECDSABehaviourTargetRole {
                 .map_into()
                 .collect(),
             paillier_dk: validated_recovery_items.paillier_dk,
+            chain_code: validated_recovery_items.chain_code,
         };
         info!("Calculated new keyshare");
 
         match self.key_saver.save_key(&keyshare) {
             Ok(()) => {
                 info!("New file successfully saved for keyshare {}", recovery_index);
-                RecoveryValidationResult::validated_with_eks(validated_recovery_items.paillier_ek)
+                RecoveryValidationResult::validated_with_eks(
+                    validated_recovery_items.paillier_ek,
+                    validated_recovery_items.paillier_key_proof
+                )
             }
             Err(err) => {
                 let msg =
@@ -327,15 +348,87 @@ impl KeyshareBehaviourTargetRole for Sr25519BehaviourTargetRole {
     }
 }
 
+pub struct Bls12381BehaviourTargetRole {
+    key_saver: KeyshareSaver,
+}
+
+impl Bls12381BehaviourTargetRole {
+    pub fn new(key_id: &str) -> Self {
+        Self {
+            key_saver: KeyshareSaver::new_creator_modifier(key_id),
+        }
+    }
+}
+
+impl KeyshareBehaviourTargetRole for Bls12381BehaviourTargetRole {
+    type Curve = Bls12_381_1;
+    type RecoveryPackage = Bls12381RecoveryPackage;
+    fn process_recovery_packages(
+        &self,
+        recovery_index: usize,
+        threshold: usize,
+        packages: &[Self::RecoveryPackage]
+    ) -> RecoveryValidationResult {
+        let share_info = packages
+            .iter()
+            .map(|x| x.share_recovery_info.clone())
+            .collect::<Vec<ShareRecoveryInfo<Self::Curve>>>();
+        let (recovered_secret, vss, public_key) = match
+            recover_and_validate_secret(recovery_index, share_info)
+        {
+            Ok(ss) => ss,
+            Err(err) => {
+                return RecoveryValidationResult::error(
+                    format!("The provided recovery packages could not be validated: {err}")
+                );
+            }
+        };
+
+        let vss_scheme = match vss.into_iter().next() {
+            Some(vss) => vss,
+            None => {
+                let msg = "Could not retrieve the vss scheme from the recovered items";
+                error!("{}", msg);
+                return RecoveryValidationResult::error(msg.to_string());
+            }
+        };
+
+        let new_keyshare = Bls12381 {
+            threshold,
+            party_index: recovery_index,
+            x_i: recovered_secret,
+            public_key,
+            vss_scheme,
+        };
+
+        match self.key_saver.save_key(&new_keyshare) {
+            Ok(()) => {
+                info!("New file successfully saved for keyshare {}", recovery_index);
+                RecoveryValidationResult::validated()
+            }
+
+            Err(err) => {
+                let msg =
+                    format!("The keyshare was recovered and validated successfully but the new keyshare file could not be saved: {}", err);
+                error!("{}", msg);
+                RecoveryValidationResult::error(msg.to_string())
+            }
+        }
+    }
+}
+
 struct ECDSASpecificValidatedRecoveryItems {
     public_key_vec: Vec<Point<Secp256k1>>,
     new_paillier_key_vec: Vec<EncryptionKey>,
     h1_h2_N_tilde_vec: Vec<DLogStatement>,
     paillier_ek: EncryptionKey,
     paillier_dk: DecryptionKey,
+    paillier_key_proof: PaillierKeyProof,
+    chain_code: Option<String>,
 }
 
 fn validate_ecdsa_specific_recovery_package_items(
+    key_id: &str,
     recovery_packages: &[ECDSARecoveryPackage],
     recovery_index: usize
 ) -> Result<ECDSASpecificValidatedRecoveryItems> {
@@ -359,6 +452,12 @@ fn validate_ecdsa_specific_recovery_package_items(
 
     let public_key_vec = validate_all_matching_items(&public_key_vecs, "Public key vec")?;
 
+    let chain_codes = recovery_packages
+        .iter()
+        .map(|x| x.chain_code.clone())
+        .collect::<Vec<Option<String>>>();
+    let chain_code = validate_all_matching_items(&chain_codes, "Chain codes")?;
+
     let mut paillier_key_vec = validate_all_matching_items(
         &paillier_key_vecs,
         "Paillier encryption keys"
@@ -369,12 +468,28 @@ fn validate_ecdsa_specific_recovery_package_items(
         recovery_index
     )?;
 
+    // The other parties only accept the new key once it is proven sound against their
+    // ring-Pedersen parameters
+    let verifiers = h1_h2_N_tilde_vec
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != recovery_index - 1)
+        .map(|(i, statement)| (i + 1, statement))
+        .collect::<Vec<(usize, &DLogStatement)>>();
+    let paillier_key_proof = PaillierKeyProof::new(
+        &paillier_dk,
+        &verifiers,
+        &proof_context(key_id, recovery_index)
+    )?;
+
     Ok(ECDSASpecificValidatedRecoveryItems {
         public_key_vec,
         new_paillier_key_vec: paillier_key_vec,
         h1_h2_N_tilde_vec,
         paillier_ek,
         paillier_dk,
+        paillier_key_proof,
+        chain_code,
     })
 }
 
@@ -382,7 +497,8 @@ fn create_new_paillier_keys_and_update_vec(
     paillier_key_vec: &mut [EncryptionKey],
     recovery_index: usize
 ) -> Result<(EncryptionKey, DecryptionKey)> {
-    let (new_ek, new_dk) = Paillier::keypair().keys();
+    // Safe primes make the modulus a Blum integer, as required by its proof
+    let (new_ek, new_dk) = Paillier::keypair_safe_primes().keys();
     replace_elem_in_vec(paillier_key_vec, recovery_index - 1, new_ek.clone())?;
     Ok((new_ek, new_dk))
 }
@@ -420,3 +536,186 @@ fn validate_all_matching_items<T: PartialEq + Clone>(items: &[T], description: &
     }
     Ok(first_item.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recovery::encryption::Plaintext;
+    use crate::recovery::helper_role::{ KeyshareBehaviourHelperRole, KeyshareRecoveryHelper };
+    use crate::recovery::Party;
+    use std::sync::mpsc::{ channel, Receiver, Sender };
+    use std::thread;
+
+    type Envelope = (usize, Vec<u8>);
+
+    /// Helpers exchanging their shares over channels, their broadcasts go to the test
+    struct ChannelMessenger {
+        party_index: usize,
+        peers: Vec<(usize, Sender<Envelope>)>,
+        inbox: Receiver<Envelope>,
+        broadcasts: Sender<Envelope>,
+    }
+
+    impl PeerMessenger<KeyShareRegenAllRounds> for ChannelMessenger {
+        fn broadcast_message<T: Serialize + DeserializeOwned + Clone>(
+            &self,
+            _round: &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound,
+            message: T
+        ) -> Result<()> {
+            self.broadcasts.send((self.party_index, serde_json::to_vec(&message)?))?;
+            Ok(())
+        }
+
+        fn collect_messages<T: Serialize + DeserializeOwned + Clone>(
+            &self,
+            _round: &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound
+        ) -> Result<Vec<T>> {
+            unimplemented!()
+        }
+
+        fn collect_message<T: Serialize + DeserializeOwned + Clone>(
+            &self,
+            _round: &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound
+        ) -> Result<T> {
+            unimplemented!()
+        }
+
+        fn broadcast_and_collect_messages<T: Serialize + DeserializeOwned + Clone>(
+            &self,
+            _round: &<KeyShareRegenAllRounds as AllRounds>::BroadcastRound,
+            _message: T
+        ) -> Result<Vec<T>> {
+            unimplemented!()
+        }
+
+        fn send_p2p_and_collect_messages<T: Serialize + DeserializeOwned + Clone>(
+            &self,
+            _round: &<KeyShareRegenAllRounds as AllRounds>::P2PRound,
+            messages: Vec<T>
+        ) -> Result<Vec<T>> {
+            for ((_, peer), message) in self.peers.iter().zip(messages) {
+                peer.send((self.party_index, serde_json::to_vec(&message)?))?;
+            }
+            let mut received = self.peers
+                .iter()
+                .map(|_| self.inbox.recv())
+                .collect::<Result<Vec<_>, _>>()?;
+            received.sort_by_key(|(sender, _)| *sender);
+            received
+                .into_iter()
+                .map(|(_, message)| Ok(serde_json::from_slice(&message)?))
+                .collect()
+        }
+    }
+
+    struct TestShare {
+        x_i: Scalar<Ed25519>,
+        vss_scheme_vec: Vec<VerifiableSS<Ed25519>>,
+        threshold: usize,
+    }
+
+    impl KeyshareBehaviourHelperRole for TestShare {
+        type Curve = Ed25519;
+        type RecoveryPackage = EdDSARecoveryPackage;
+
+        fn create_recovery_result(&self, result: Scalar<Ed25519>) -> EdDSARecoveryPackage {
+            EdDSARecoveryPackage {
+                share_recovery_info: ShareRecoveryInfo {
+                    partial_secret: result,
+                    vss_vec: self.vss_scheme_vec.clone(),
+                },
+            }
+        }
+
+        fn get_recovery_params(
+            &self,
+            recovery_index: usize,
+            party: Party
+        ) -> RecoveryCalculator<Ed25519> {
+            RecoveryCalculator {
+                secret_share: self.x_i.clone(),
+                threshold: self.threshold,
+                party,
+                recovery_index,
+            }
+        }
+    }
+
+    #[test]
+    fn helpers_recover_two_lost_shares_in_one_session() {
+        // 3-of-5 key whose holders 4 and 5 lost their shares
+        let threshold = 2;
+        let secret = Scalar::<Ed25519>::random();
+        let (vss, shares) = VerifiableSS::<Ed25519>::share(threshold as u16, 5, &secret);
+        let vss_scheme_vec = vec![vss];
+        let helpers = vec![1, 2, 3];
+        let lost = vec![4, 5];
+
+        let inboxes = helpers
+            .iter()
+            .map(|_| channel())
+            .collect::<Vec<_>>();
+        let peers = helpers
+            .iter()
+            .zip(&inboxes)
+            .map(|(&index, (sender, _))| (index, sender.clone()))
+            .collect::<Vec<_>>();
+        let (broadcasts, delivered) = channel();
+        let sessions = helpers
+            .iter()
+            .zip(inboxes)
+            .map(|(&party_index, (_, inbox))| {
+                let messenger = ChannelMessenger {
+                    party_index,
+                    peers: peers
+                        .iter()
+                        .filter(|(index, _)| *index != party_index)
+                        .cloned()
+                        .collect(),
+                    inbox,
+                    broadcasts: broadcasts.clone(),
+                };
+                let key = TestShare {
+                    x_i: shares[party_index - 1].clone(),
+                    vss_scheme_vec: vss_scheme_vec.clone(),
+                    threshold,
+                };
+                let party = Party {
+                    party_index,
+                    all_parties: helpers.clone(),
+                };
+                let lost = lost.clone();
+                thread::spawn(move || {
+                    KeyshareRecoveryHelper::new(messenger, Plaintext, key).try_recovery(&lost, party)
+                })
+            })
+            .collect::<Vec<_>>();
+        for session in sessions {
+            session.join().unwrap().unwrap();
+        }
+
+        // Every helper delivers one list holding a package for each lost share
+        let delivered = delivered
+            .try_iter()
+            .map(|(_, packages)| serde_json::from_slice::<Vec<Vec<u8>>>(&packages).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(delivered.len(), helpers.len());
+        for (position, &recovery_index) in lost.iter().enumerate() {
+            let packages = delivered
+                .iter()
+                .map(|packages| packages[position].clone())
+                .collect();
+            let share_infos = Plaintext.decrypt_from_all_parties::<EdDSARecoveryPackage>(packages)
+                .unwrap()
+                .into_iter()
+                .map(|package| package.share_recovery_info)
+                .collect();
+            let (x_i, recovered_vss, _) = recover_and_validate_secret(
+                recovery_index,
+                share_infos
+            ).unwrap();
+            assert_eq!(x_i, shares[recovery_index - 1]);
+            assert_eq!(recovered_vss, vss_scheme_vec);
+        }
+    }
+}
@@ -3,6 +3,8 @@ use curv::arithmetic::Zero;
 use curv::BigInt;
 use paillier::EncryptionKey;
 
+pub mod paillier_proofs;
+
 /// Check paillier public key for small prime factors (<2^16).
 /// Security issue: CVE-2023-33241
 ///
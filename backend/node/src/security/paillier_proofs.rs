@@ -0,0 +1,399 @@
+//! Zero knowledge proofs about Paillier moduli (CGGMP21, figures 16 and 28).
+//!
+//! Every party proves that its Paillier modulus `N` is a Paillier-Blum modulus and that
+//! neither of its factors is small, the latter against the ring-Pedersen parameters
+//! (`h1_h2_N_tilde`) of each verifier. Together they close CVE-2023-33241 without relying on
+//! trial division.
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::{
+    BasicOps,
+    BitManipulation,
+    Converter,
+    Modulo,
+    NumberTests,
+    One,
+    Primes,
+    Samplable,
+    Zero,
+};
+use curv::elliptic::curves::{ Scalar, Secp256k1 };
+use curv::BigInt;
+use paillier::{ DecryptionKey, EncryptionKey };
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+use zk_paillier::zkproofs::DLogStatement;
+
+/// Number of challenges in the Paillier-Blum modulus proof
+const MODULUS_PROOF_ROUNDS: usize = 80;
+/// Bit size of the secrets the Paillier key is used with (secp256k1 scalars)
+const L: usize = 256;
+/// Slack parameter of the no small factor proof
+const EPSILON: usize = 512;
+
+const MODULUS_PROOF_TAG: &[u8] = b"gridlock/paillier/blum-modulus";
+const NO_SMALL_FACTOR_TAG: &[u8] = b"gridlock/paillier/no-small-factor";
+
+/// Binds the proofs to the key and the party that made them
+pub fn proof_context(key_id: &str, prover_index: usize) -> Vec<u8> {
+    format!("{}:{}", key_id, prover_index).into_bytes()
+}
+
+/// Proof that a Paillier key is sound, sent by its owner to every other party
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaillierKeyProof {
+    pub modulus_proof: PaillierBlumModulusProof,
+    /// No small factor proofs keyed by the party index of the verifier whose ring-Pedersen
+    /// parameters they are made against
+    pub no_small_factor_proofs: Vec<(usize, NoSmallFactorProof)>,
+}
+
+impl PaillierKeyProof {
+    pub fn new(
+        dk: &DecryptionKey,
+        verifiers: &[(usize, &DLogStatement)],
+        context: &[u8]
+    ) -> Result<Self> {
+        let modulus_proof = PaillierBlumModulusProof::new(dk, context)?;
+        let no_small_factor_proofs = verifiers
+            .iter()
+            .map(|(index, statement)| {
+                Ok((*index, NoSmallFactorProof::new(dk, statement, context)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            modulus_proof,
+            no_small_factor_proofs,
+        })
+    }
+
+    /// Verify the proof for `ek` as the party `verifier_index` owning `verifier_statement`
+    pub fn verify(
+        &self,
+        ek: &EncryptionKey,
+        verifier_index: usize,
+        verifier_statement: &DLogStatement,
+        context: &[u8]
+    ) -> Result<()> {
+        self.modulus_proof.verify(&ek.n, context)?;
+        let (_, proof) = self.no_small_factor_proofs
+            .iter()
+            .find(|(index, _)| *index == verifier_index)
+            .ok_or_else(||
+                anyhow!("No small factor proof for party {} is missing", verifier_index)
+            )?;
+        proof.verify(&ek.n, verifier_statement, context)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ModulusProofRound {
+    x: BigInt,
+    a: bool,
+    b: bool,
+    z: BigInt,
+}
+
+/// Proof that `N = pq` with `p = q = 3 mod 4` and `gcd(N, phi(N)) = 1` (CGGMP21 figure 16)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaillierBlumModulusProof {
+    w: BigInt,
+    rounds: Vec<ModulusProofRound>,
+}
+
+impl PaillierBlumModulusProof {
+    pub fn new(dk: &DecryptionKey, context: &[u8]) -> Result<Self> {
+        let (p, q) = (&dk.p, &dk.q);
+        let three = BigInt::from(3u32);
+        if p.modulus(&BigInt::from(4u32)) != three || q.modulus(&BigInt::from(4u32)) != three {
+            bail!("Paillier key is not a Blum modulus");
+        }
+        let n = p * q;
+        let phi = &(p - &BigInt::one()) * &(q - &BigInt::one());
+        let n_inv = BigInt::mod_inv(&n, &phi).ok_or_else(||
+            anyhow!("Paillier modulus is not coprime to its totient")
+        )?;
+
+        // w has Jacobi symbol -1, a residue modulo one factor and a non-residue modulo the
+        // other, so that exactly one of y, -y, wy, -wy is a quadratic residue for every y
+        let w = loop {
+            let w = BigInt::sample_below(&n);
+            if is_quadratic_residue(&w, p) != is_quadratic_residue(&w, q) {
+                break w;
+            }
+        };
+
+        let rounds = (0..MODULUS_PROOF_ROUNDS)
+            .map(|i| {
+                let y = modulus_challenge(&n, &w, i, context);
+                let z = BigInt::mod_pow(&y, &n_inv, &n);
+                let (a, b, y) = [
+                    (false, false),
+                    (true, false),
+                    (false, true),
+                    (true, true),
+                ]
+                    .iter()
+                    .map(|&(a, b)| (a, b, twist(&y, a, b, &w, &n)))
+                    .find(|(_, _, y)| is_quadratic_residue(y, p) && is_quadratic_residue(y, q))
+                    .ok_or_else(|| anyhow!("Paillier modulus challenge has no fourth root"))?;
+                let x = crt(&fourth_root(&y, p), &fourth_root(&y, q), p, q)?;
+                Ok(ModulusProofRound { x, a, b, z })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { w, rounds })
+    }
+
+    pub fn verify(&self, n: &BigInt, context: &[u8]) -> Result<()> {
+        if !n.test_bit(0) || n.is_probable_prime(64) {
+            bail!("Paillier modulus is not an odd composite");
+        }
+        if self.w <= BigInt::zero() || &self.w >= n {
+            bail!("Paillier-Blum modulus proof is malformed");
+        }
+        if self.rounds.len() != MODULUS_PROOF_ROUNDS {
+            bail!("Paillier-Blum modulus proof has wrong number of rounds");
+        }
+        for (i, round) in self.rounds.iter().enumerate() {
+            let y = modulus_challenge(n, &self.w, i, context);
+            if BigInt::mod_pow(&round.z.modulus(n), n, n) != y {
+                bail!("Paillier-Blum modulus proof failed: N-th root is invalid");
+            }
+            let x4 = BigInt::mod_pow(&round.x.modulus(n), &BigInt::from(4u32), n);
+            if x4 != twist(&y, round.a, round.b, &self.w, n) {
+                bail!("Paillier-Blum modulus proof failed: fourth root is invalid");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Proof that neither factor of `N0` is smaller than about `2^L`, made against the
+/// ring-Pedersen parameters `(N^, s, t)` of the verifier (CGGMP21 figure 28)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoSmallFactorProof {
+    P: BigInt,
+    Q: BigInt,
+    A: BigInt,
+    B: BigInt,
+    T: BigInt,
+    sigma: BigInt,
+    z1: BigInt,
+    z2: BigInt,
+    w1: BigInt,
+    w2: BigInt,
+    v: BigInt,
+}
+
+impl NoSmallFactorProof {
+    pub fn new(dk: &DecryptionKey, verifier: &DLogStatement, context: &[u8]) -> Result<Self> {
+        let (p, q) = (&dk.p, &dk.q);
+        let n0 = p * q;
+        let (n_hat, s, t) = (&verifier.N, &verifier.g, &verifier.ni);
+
+        let factor_bound = pow2(L + EPSILON + sqrt_bits(&n0));
+        let l_n_hat = &pow2(L) * n_hat;
+        let alpha = sample_signed(&factor_bound);
+        let beta = sample_signed(&factor_bound);
+        let mu = sample_signed(&l_n_hat);
+        let nu = sample_signed(&l_n_hat);
+        let sigma = sample_signed(&(&l_n_hat * &n0));
+        let r = sample_signed(&(&(&pow2(L + EPSILON) * &n0) * n_hat));
+        let x = sample_signed(&(&pow2(L + EPSILON) * n_hat));
+        let y = sample_signed(&(&pow2(L + EPSILON) * n_hat));
+
+        let P = pedersen(s, p, t, &mu, n_hat)?;
+        let Q = pedersen(s, q, t, &nu, n_hat)?;
+        let A = pedersen(s, &alpha, t, &x, n_hat)?;
+        let B = pedersen(s, &beta, t, &y, n_hat)?;
+        let T = pedersen(&Q, &alpha, t, &r, n_hat)?;
+
+        let e = no_small_factor_challenge(&n0, verifier, [&P, &Q, &A, &B, &T, &sigma], context);
+        let z1 = &alpha + &(&e * p);
+        let z2 = &beta + &(&e * q);
+        let w1 = &x + &(&e * &mu);
+        let w2 = &y + &(&e * &nu);
+        let v = &r + &(&e * &(&sigma - &(&nu * p)));
+
+        Ok(Self { P, Q, A, B, T, sigma, z1, z2, w1, w2, v })
+    }
+
+    pub fn verify(&self, n0: &BigInt, verifier: &DLogStatement, context: &[u8]) -> Result<()> {
+        let (n_hat, s, t) = (&verifier.N, &verifier.g, &verifier.ni);
+        let e = no_small_factor_challenge(
+            n0,
+            verifier,
+            [&self.P, &self.Q, &self.A, &self.B, &self.T, &self.sigma],
+            context
+        );
+        let R = pedersen(s, n0, t, &self.sigma, n_hat)?;
+
+        let lhs = pedersen(s, &self.z1, t, &self.w1, n_hat)?;
+        let rhs = BigInt::mod_mul(&self.A, &pow_mod(&self.P, &e, n_hat)?, n_hat);
+        if lhs != rhs {
+            bail!("No small factor proof failed: commitment to p is invalid");
+        }
+        let lhs = pedersen(s, &self.z2, t, &self.w2, n_hat)?;
+        let rhs = BigInt::mod_mul(&self.B, &pow_mod(&self.Q, &e, n_hat)?, n_hat);
+        if lhs != rhs {
+            bail!("No small factor proof failed: commitment to q is invalid");
+        }
+        let lhs = pedersen(&self.Q, &self.z1, t, &self.v, n_hat)?;
+        let rhs = BigInt::mod_mul(&self.T, &pow_mod(&R, &e, n_hat)?, n_hat);
+        if lhs != rhs {
+            bail!("No small factor proof failed: commitment to N is invalid");
+        }
+
+        let factor_bound = pow2(L + EPSILON + sqrt_bits(n0));
+        if abs(&self.z1) > factor_bound || abs(&self.z2) > factor_bound {
+            bail!("No small factor proof failed: response is out of range");
+        }
+        Ok(())
+    }
+}
+
+/// `(-1)^a * w^b * y mod n`
+fn twist(y: &BigInt, a: bool, b: bool, w: &BigInt, n: &BigInt) -> BigInt {
+    let y = if b { BigInt::mod_mul(w, y, n) } else { y.modulus(n) };
+    if a {
+        BigInt::mod_sub(&BigInt::zero(), &y, n)
+    } else {
+        y
+    }
+}
+
+/// Euler's criterion for an odd prime `p`
+fn is_quadratic_residue(a: &BigInt, p: &BigInt) -> bool {
+    let exp = (p - &BigInt::one()) / BigInt::from(2u32);
+    BigInt::mod_pow(&a.modulus(p), &exp, p) == BigInt::one()
+}
+
+/// Fourth root of a quadratic residue `y` modulo a prime `p = 3 mod 4` that is itself a
+/// quadratic residue: `y^(((p + 1) / 4)^2 mod (p - 1))`
+fn fourth_root(y: &BigInt, p: &BigInt) -> BigInt {
+    let p_minus_one = p - &BigInt::one();
+    let k = (p + &BigInt::one()) / BigInt::from(4u32);
+    let exp = BigInt::mod_mul(&k, &k, &p_minus_one);
+    BigInt::mod_pow(&y.modulus(p), &exp, p)
+}
+
+fn crt(xp: &BigInt, xq: &BigInt, p: &BigInt, q: &BigInt) -> Result<BigInt> {
+    let p_inv = BigInt::mod_inv(p, q).ok_or_else(|| anyhow!("Paillier factors are not coprime"))?;
+    let h = BigInt::mod_mul(&BigInt::mod_sub(xq, xp, q), &p_inv, q);
+    Ok(xp + &(&h * p))
+}
+
+/// `g^a * h^b mod n` for exponents of any sign
+fn pedersen(g: &BigInt, a: &BigInt, h: &BigInt, b: &BigInt, n: &BigInt) -> Result<BigInt> {
+    Ok(BigInt::mod_mul(&pow_mod(g, a, n)?, &pow_mod(h, b, n)?, n))
+}
+
+/// `base^exp mod n` for exponents of any sign
+fn pow_mod(base: &BigInt, exp: &BigInt, n: &BigInt) -> Result<BigInt> {
+    let base = base.modulus(n);
+    if exp.is_negative() {
+        let inverse = BigInt::mod_inv(&base, n).ok_or_else(||
+            anyhow!("Proof element is not invertible")
+        )?;
+        Ok(BigInt::mod_pow(&inverse, &abs(exp), n))
+    } else {
+        Ok(BigInt::mod_pow(&base, exp, n))
+    }
+}
+
+fn abs(x: &BigInt) -> BigInt {
+    if x.is_negative() { &BigInt::zero() - x } else { x.clone() }
+}
+
+fn pow2(bits: usize) -> BigInt {
+    BigInt::from(2u32).pow(bits as u32)
+}
+
+/// Bit size of the square root of `n`, rounded up
+fn sqrt_bits(n: &BigInt) -> usize {
+    (n.bit_length() + 1) / 2
+}
+
+/// Uniform sample from `[-bound, bound)`
+fn sample_signed(bound: &BigInt) -> BigInt {
+    &BigInt::sample_below(&(bound + bound)) - bound
+}
+
+fn modulus_challenge(n: &BigInt, w: &BigInt, round: usize, context: &[u8]) -> BigInt {
+    hash_below(n, MODULUS_PROOF_TAG, context, &[n, w, &BigInt::from(round as u64)])
+}
+
+fn no_small_factor_challenge(
+    n0: &BigInt,
+    verifier: &DLogStatement,
+    commitments: [&BigInt; 6],
+    context: &[u8]
+) -> BigInt {
+    let q = Scalar::<Secp256k1>::group_order();
+    let mut elements = vec![n0, &verifier.N, &verifier.g, &verifier.ni];
+    elements.extend_from_slice(&commitments);
+    let e = hash_below(&(&(q + q) + &BigInt::one()), NO_SMALL_FACTOR_TAG, context, &elements);
+    &e - q
+}
+
+/// Fiat-Shamir challenge in `[0, modulus)`, expanded from SHA-256 in counter mode with enough
+/// extra bits to make the modular bias negligible
+fn hash_below(modulus: &BigInt, tag: &[u8], context: &[u8], elements: &[&BigInt]) -> BigInt {
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update((context.len() as u64).to_be_bytes());
+    hasher.update(context);
+    for element in elements {
+        let bytes = element.to_bytes();
+        hasher.update([element.is_negative() as u8]);
+        hasher.update((bytes.len() as u64).to_be_bytes());
+        hasher.update(&bytes);
+    }
+    let seed = hasher.finalize();
+
+    let len = modulus.bit_length() / 8 + 16;
+    let mut output = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while output.len() < len {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update(counter.to_be_bytes());
+        output.extend_from_slice(&hasher.finalize());
+        counter += 1;
+    }
+    BigInt::from_bytes(&output[..len]).modulus(modulus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blum_prime(bits: usize) -> BigInt {
+        loop {
+            let p = BigInt::sample(bits).next_prime();
+            if p.modulus(&BigInt::from(4u32)) == BigInt::from(3u32) {
+                return p;
+            }
+        }
+    }
+
+    #[test]
+    fn paillier_key_proof_verifies_only_in_its_context() {
+        let dk = DecryptionKey { p: blum_prime(1024), q: blum_prime(1024) };
+        let ek = EncryptionKey::from(&dk);
+
+        let n_hat = &blum_prime(1024) * &blum_prime(1024);
+        let s = BigInt::mod_pow(&BigInt::sample_below(&n_hat), &BigInt::from(2u32), &n_hat);
+        let lambda = BigInt::sample_below(&n_hat);
+        let statement = DLogStatement {
+            ni: BigInt::mod_pow(&s, &lambda, &n_hat),
+            N: n_hat,
+            g: s,
+        };
+
+        let proof = PaillierKeyProof::new(&dk, &[(2, &statement)], b"key:1").unwrap();
+        proof.verify(&ek, 2, &statement, b"key:1").unwrap();
+        assert!(proof.verify(&ek, 2, &statement, b"key:3").is_err());
+        assert!(proof.verify(&ek, 3, &statement, b"key:1").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::communication::nats::PeerMessenger;
+use crate::communication::protocol::{ AllRounds, KeySignAdaptorAllRounds };
+use crate::signing::adaptor::{ ed25519, secp256k1, AdaptorCurve, AdaptorSignatureResult };
+use crate::signing::eddsa::frost::{ self, scalar_to_le_bytes };
+use crate::signing::schnorr_secp256k1::client::{
+    NonceCommitment,
+    SignatureShare,
+    SigningPackage,
+};
+use crate::signing::schnorr_secp256k1::{ x_only, SigningKey };
+use crate::storage::{ ECDSA, EDDSA };
+use anyhow::{ anyhow, bail, Result };
+use curv::elliptic::curves::{ Ed25519, Point, Scalar, Secp256k1 };
+use tracing::info;
+
+/// Two round FROST signing of adaptor pre-signatures with an existing keyshare
+pub struct AdaptorKeySignClient<C> {
+    pub peer_messenger: C,
+    pub all_party_indices: Vec<usize>,
+}
+
+impl<C> AdaptorKeySignClient<C> where C: PeerMessenger<KeySignAdaptorAllRounds> {
+    /// Pre-signature that completes into a BIP340 signature, signed with the ECDSA keyshare
+    pub fn create_secp256k1_pre_sig(
+        &self,
+        message: &[u8],
+        keyshare: &ECDSA,
+        signing_key: &SigningKey,
+        adaptor_point: &Point<Secp256k1>
+    ) -> Result<AdaptorSignatureResult> {
+        let hiding_nonce = Scalar::<Secp256k1>::random();
+        let binding_nonce = Scalar::<Secp256k1>::random();
+        let commitment = NonceCommitment {
+            party_index: keyshare.party_index,
+            hiding: Point::generator() * &hiding_nonce,
+            binding: Point::generator() * &binding_nonce,
+        };
+
+        let commitments = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignAdaptorAllRounds as AllRounds>::BroadcastRound::NonceCommitments,
+            commitment
+        )?;
+        info!("Nonce commitments received - msg count: {}", commitments.len());
+
+        let package = SigningPackage::with_adaptor_point(
+            message,
+            signing_key.clone(),
+            commitments,
+            Some(adaptor_point)
+        )?;
+        self.check_indices(package.indices())?;
+
+        let z_i = package.signature_share(
+            keyshare.party_index,
+            &hiding_nonce,
+            &binding_nonce,
+            &keyshare.x_i
+        )?;
+        let shares = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignAdaptorAllRounds as AllRounds>::BroadcastRound::PreSignatureShare,
+            SignatureShare {
+                party_index: keyshare.party_index,
+                z_i,
+            }
+        )?;
+        info!("Pre-signature shares received - msg count: {}", shares.len());
+
+        for share in shares.iter() {
+            let public_share = share.party_index
+                .checked_sub(1)
+                .and_then(|index| keyshare.public_key_vec.get(index))
+                .ok_or_else(|| anyhow!("Unknown party index {}", share.party_index))?;
+            package.verify_share(share, public_share)?;
+        }
+        info!("Verified all pre-signature shares");
+
+        let pre_signature = package.aggregate_pre_signature(&shares)?;
+        let public_key = x_only(&signing_key.public_key);
+        secp256k1::verify_pre_signature(&public_key, message, adaptor_point, &pre_signature)?;
+        info!("Pre-signature generated and verified");
+
+        Ok(AdaptorSignatureResult {
+            curve: AdaptorCurve::Secp256k1,
+            pre_signature: hex::encode(pre_signature),
+            adaptor_point: hex::encode(&*adaptor_point.to_bytes(true)),
+            public_key: hex::encode(public_key),
+        })
+    }
+
+    /// Pre-signature that completes into an Ed25519 signature, signed with the EdDSA keyshare
+    pub fn create_ed25519_pre_sig(
+        &self,
+        message: &[u8],
+        keyshare: &EDDSA,
+        adaptor_point: &Point<Ed25519>
+    ) -> Result<AdaptorSignatureResult> {
+        let hiding_nonce = Scalar::<Ed25519>::random();
+        let binding_nonce = Scalar::<Ed25519>::random();
+        let commitment = frost::NonceCommitment {
+            party_index: keyshare.party_index,
+            hiding: Point::generator() * &hiding_nonce,
+            binding: Point::generator() * &binding_nonce,
+        };
+
+        let commitments = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignAdaptorAllRounds as AllRounds>::BroadcastRound::NonceCommitments,
+            commitment
+        )?;
+        info!("Nonce commitments received - msg count: {}", commitments.len());
+
+        let package = frost::SigningPackage::with_adaptor_point(
+            message,
+            &keyshare.y_sum,
+            commitments,
+            Some(adaptor_point)
+        )?;
+        self.check_indices(package.indices())?;
+
+        let z_i = package.signature_share(
+            keyshare.party_index,
+            &hiding_nonce,
+            &binding_nonce,
+            &keyshare.x_i
+        )?;
+        let shares = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignAdaptorAllRounds as AllRounds>::BroadcastRound::PreSignatureShare,
+            frost::SignatureShare {
+                party_index: keyshare.party_index,
+                z_i,
+            }
+        )?;
+        info!("Pre-signature shares received - msg count: {}", shares.len());
+
+        for share in shares.iter() {
+            let public_share = keyshare.vss_scheme_vec
+                .iter()
+                .fold(Point::zero(), |acc, vss| {
+                    acc + vss.get_point_commitment(share.party_index as u16)
+                });
+            package.verify_share(share, &public_share)?;
+        }
+        info!("Verified all pre-signature shares");
+
+        let (nonce, pre_s) = package.aggregate(&shares)?;
+        let mut pre_signature = nonce.to_bytes(true).to_vec();
+        pre_signature.extend_from_slice(&scalar_to_le_bytes(&pre_s));
+        ed25519::verify_pre_signature(&keyshare.y_sum, message, adaptor_point, &pre_signature)?;
+        info!("Pre-signature generated and verified");
+
+        Ok(AdaptorSignatureResult {
+            curve: AdaptorCurve::Ed25519,
+            pre_signature: hex::encode(pre_signature),
+            adaptor_point: hex::encode(&*adaptor_point.to_bytes(true)),
+            public_key: hex::encode(&*keyshare.y_sum.to_bytes(true)),
+        })
+    }
+
+    pub fn publish_result(&self, result: AdaptorSignatureResult) -> Result<()> {
+        self.peer_messenger.broadcast_message(
+            &<KeySignAdaptorAllRounds as AllRounds>::BroadcastRound::Result,
+            result
+        )
+    }
+
+    fn check_indices(&self, indices: &[usize]) -> Result<()> {
+        if indices != self.all_party_indices {
+            bail!("Nonce commitments do not match the parties of the session");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Ed25519 adaptor signatures.
+//!
+//! A pre-signature is the adapted nonce point `R'` and a scalar `s'` with
+//! `s'·G = R' - T + k·A`, where `k` is the challenge of RFC 8032 over `R'`. Completed with `t`,
+//! it is the signature `R' || s' + t`.
+use crate::signing::eddsa::frost::{ hash_to_scalar, scalar_to_le_bytes };
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Ed25519, Point, Scalar };
+use curv::BigInt;
+
+/// Checks that the pre-signature becomes a valid signature of the message under the public key
+/// once the secret of the adaptor point is added to it
+pub fn verify_pre_signature(
+    public_key: &Point<Ed25519>,
+    message: &[u8],
+    adaptor_point: &Point<Ed25519>,
+    pre_signature: &[u8]
+) -> Result<()> {
+    let (nonce, s) = decode_pre_signature(pre_signature)?;
+    let k = hash_to_scalar(&[&nonce.to_bytes(true), &public_key.to_bytes(true), message]);
+    if Point::generator() * s != nonce - adaptor_point + public_key * k {
+        bail!("Pre-signature did not pass verification");
+    }
+    Ok(())
+}
+
+/// Completes the pre-signature into a 64 byte `R || S` signature with the adaptor secret
+pub fn complete(pre_signature: &[u8], secret: &Scalar<Ed25519>) -> Result<[u8; 64]> {
+    let (_, s) = decode_pre_signature(pre_signature)?;
+    let mut signature = [0; 64];
+    signature[..32].copy_from_slice(&pre_signature[..32]);
+    signature[32..].copy_from_slice(&scalar_to_le_bytes(&(s + secret)));
+    Ok(signature)
+}
+
+/// Recovers the adaptor secret from a signature that was completed from the pre-signature
+pub fn extract_secret(
+    pre_signature: &[u8],
+    signature: &[u8],
+    adaptor_point: &Point<Ed25519>
+) -> Result<Scalar<Ed25519>> {
+    let (_, pre_s) = decode_pre_signature(pre_signature)?;
+    if signature.len() != 64 {
+        bail!("Ed25519 signatures have to be 64 bytes long");
+    }
+    if signature[..32] != pre_signature[..32] {
+        bail!("Signature was not completed from the pre-signature");
+    }
+    let secret = decode_scalar(&signature[32..])? - pre_s;
+    if &(Point::generator() * &secret) != adaptor_point {
+        bail!("Signature does not reveal the secret of the adaptor point");
+    }
+    Ok(secret)
+}
+
+/// Decodes a compressed adaptor point from hex
+pub fn decode_adaptor_point(encoded: &str) -> Result<Point<Ed25519>> {
+    let bytes = hex::decode(encoded)?;
+    if bytes.len() != 32 {
+        bail!("Adaptor points on Ed25519 have to be 32 byte compressed points");
+    }
+    let point = Point::<Ed25519>
+        ::from_bytes(&bytes)
+        .map_err(|_| anyhow!("Adaptor point is not on the curve"))?;
+    if point.is_zero() {
+        bail!("Adaptor point is the point at infinity");
+    }
+    Ok(point)
+}
+
+fn decode_pre_signature(pre_signature: &[u8]) -> Result<(Point<Ed25519>, Scalar<Ed25519>)> {
+    if pre_signature.len() != 64 {
+        bail!("Pre-signatures on Ed25519 have to be 64 bytes long");
+    }
+    let nonce = Point::<Ed25519>
+        ::from_bytes(&pre_signature[..32])
+        .map_err(|_| anyhow!("Nonce point is not on the curve"))?;
+    Ok((nonce, decode_scalar(&pre_signature[32..])?))
+}
+
+/// Scalar from its 32 byte little endian encoding, rejecting non-canonical encodings
+fn decode_scalar(bytes: &[u8]) -> Result<Scalar<Ed25519>> {
+    let mut be_bytes = bytes.to_vec();
+    be_bytes.reverse();
+    let s_bn = BigInt::from_bytes(&be_bytes);
+    if &s_bn >= Scalar::<Ed25519>::group_order() {
+        bail!("Signature scalar is out of range");
+    }
+    Ok(Scalar::<Ed25519>::from_bigint(&s_bn))
+}
+
+#[test]
+fn threshold_pre_signature_completes_and_reveals_secret() {
+    use crate::signing::eddsa::frost::{ verify, NonceCommitment, SignatureShare, SigningPackage };
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+    let secret = Scalar::<Ed25519>::random();
+    let (vss, shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+    let public_key = Point::generator() * &secret;
+    let adaptor_secret = Scalar::<Ed25519>::random();
+    let adaptor_point = Point::generator() * &adaptor_secret;
+    let message = b"swap transaction";
+
+    let signers = [1, 3];
+    let nonces = signers
+        .iter()
+        .map(|_| (Scalar::<Ed25519>::random(), Scalar::<Ed25519>::random()))
+        .collect::<Vec<_>>();
+    let commitments = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| NonceCommitment {
+            party_index,
+            hiding: Point::generator() * hiding,
+            binding: Point::generator() * binding,
+        })
+        .collect();
+    let package = SigningPackage::with_adaptor_point(
+        message,
+        &public_key,
+        commitments,
+        Some(&adaptor_point)
+    ).unwrap();
+    let pre_signature_shares = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| SignatureShare {
+            party_index,
+            z_i: package
+                .signature_share(party_index, hiding, binding, &shares[party_index - 1])
+                .unwrap(),
+        })
+        .collect::<Vec<_>>();
+    for share in pre_signature_shares.iter() {
+        let public_share = vss.get_point_commitment(share.party_index as u16);
+        package.verify_share(share, &public_share).unwrap();
+    }
+
+    let (nonce, pre_s) = package.aggregate(&pre_signature_shares).unwrap();
+    assert!(verify(&public_key, message, &nonce, &pre_s).is_err());
+    let mut pre_signature = nonce.to_bytes(true).to_vec();
+    pre_signature.extend_from_slice(&scalar_to_le_bytes(&pre_s));
+    verify_pre_signature(&public_key, message, &adaptor_point, &pre_signature).unwrap();
+
+    let signature = complete(&pre_signature, &adaptor_secret).unwrap();
+    let s = decode_scalar(&signature[32..]).unwrap();
+    verify(&public_key, message, &nonce, &s).unwrap();
+    let extracted = extract_secret(&pre_signature, &signature, &adaptor_point).unwrap();
+    assert!(extracted == adaptor_secret);
+}
@@ -0,0 +1,83 @@
+//! Threshold adaptor signatures for atomic swaps.
+//!
+//! A pre-signature is a FROST signature whose nonce point includes the adaptor point `T = t·G` of
+//! a secret `t` that the signers do not know. It commits the signers to the message, but only
+//! becomes a valid signature once `t` is added to its scalar, and publishing the completed
+//! signature reveals `t` to everyone who holds the pre-signature.
+pub mod client;
+pub mod ed25519;
+pub mod orchestrate;
+pub mod secp256k1;
+pub mod session;
+
+use crate::command::{ JsonCommand, MsgContext };
+use crate::signing::eddsa::frost::scalar_to_le_bytes;
+use anyhow::Result;
+use serde::{ Deserialize, Serialize };
+
+/// Curve of an adaptor pre-signature, BIP340 Schnorr on secp256k1 or Ed25519
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdaptorCurve {
+    Secp256k1,
+    Ed25519,
+}
+
+impl AdaptorCurve {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdaptorCurve::Secp256k1 => "secp256k1",
+            AdaptorCurve::Ed25519 => "ed25519",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AdaptorSignatureResult {
+    pub curve: AdaptorCurve,
+    /// Pre-signature, hex encoded. On secp256k1 the compressed nonce point followed by the 32
+    /// byte scalar, on Ed25519 64 bytes in the layout of a signature
+    pub pre_signature: String,
+    /// Compressed adaptor point, hex encoded
+    pub adaptor_point: String,
+    /// Public key the completed signature is valid for, x-only on secp256k1
+    pub public_key: String,
+}
+
+/// Recovers the adaptor secret from a pre-signature and the signature completed from it, which
+/// is how the counterparty of a swap learns the secret once its side is claimed
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ExtractAdaptorSecretCommand {
+    pub curve: AdaptorCurve,
+    pub pre_signature: String,
+    pub signature: String,
+    pub adaptor_point: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct AdaptorSecret {
+    /// Adaptor secret, hex encoded, big endian on secp256k1 and little endian on Ed25519
+    pub secret: String,
+}
+
+impl JsonCommand for ExtractAdaptorSecretCommand {
+    type Response = AdaptorSecret;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        let pre_signature = hex::decode(&self.pre_signature)?;
+        let signature = hex::decode(&self.signature)?;
+        let secret = match self.curve {
+            AdaptorCurve::Secp256k1 => {
+                let adaptor_point = secp256k1::decode_adaptor_point(&self.adaptor_point)?;
+                let secret = secp256k1::extract_secret(&pre_signature, &signature, &adaptor_point)?;
+                hex::encode(&*secret.to_bytes())
+            }
+            AdaptorCurve::Ed25519 => {
+                let adaptor_point = ed25519::decode_adaptor_point(&self.adaptor_point)?;
+                let secret = ed25519::extract_secret(&pre_signature, &signature, &adaptor_point)?;
+                hex::encode(scalar_to_le_bytes(&secret))
+            }
+        };
+        Ok(AdaptorSecret { secret })
+    }
+}
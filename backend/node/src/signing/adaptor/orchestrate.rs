@@ -0,0 +1,126 @@
+use crate::command::MsgContext;
+use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
+use crate::signing::adaptor::session::NewAdaptorKeySignSession;
+use crate::signing::adaptor::{ ed25519, secp256k1, AdaptorCurve, AdaptorSignatureResult };
+use crate::signing::{ Key, SigningCommand, SigningResponse };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
+use shared::key_info::Key as KeyInfoKind;
+use tracing::{ error, info, instrument };
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+
+    let party_nodes = cmd.party_nodes;
+    let key_id = cmd.key_id;
+    let adaptor_point = cmd.adaptor_point.ok_or_else(|| anyhow!("Adaptor point is missing"))?;
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", &key_id)
+    )?;
+    let curve = match (&cmd.kind, &key_info.kind) {
+        (Key::SchnorrSecp256k1, KeyInfoKind::ECDSA { .. }) => AdaptorCurve::Secp256k1,
+        (Key::EDDSA, KeyInfoKind::EDDSA { .. }) => AdaptorCurve::Ed25519,
+        (Key::SchnorrSecp256k1 | Key::EDDSA, _) => {
+            bail!("Key {} does not match the requested signature scheme", &key_id);
+        }
+        _ => bail!("Adaptor signatures are only supported for BIP340 Schnorr and EdDSA"),
+    };
+    match curve {
+        AdaptorCurve::Secp256k1 => {
+            secp256k1::decode_adaptor_point(&adaptor_point)?;
+        }
+        AdaptorCurve::Ed25519 => {
+            ed25519::decode_adaptor_point(&adaptor_point)?;
+            if cmd.taproot.is_some() {
+                bail!("Taproot tweaks are only supported for Schnorr signatures");
+            }
+        }
+    }
+
+    let party_count = party_nodes.len();
+    if party_count <= key_info.threshold {
+        bail!("Not enough nodes in party");
+    }
+
+    if !cmd.msgs.is_empty() {
+        bail!("Batch signing is not supported for adaptor signatures");
+    }
+    if cmd.presignature_id.is_some() {
+        bail!("Presignatures are only supported for ECDSA signatures");
+    }
+    if cmd.derivation_path.is_some() {
+        bail!("Key derivation is not supported for adaptor signatures");
+    }
+
+    let join_key = format!("network.gridlock.nodes.KeySignAdaptor.{}.Join", &session_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!("network.gridlock.nodes.KeySignAdaptor.{}.Result", &session_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    let key_sign_new_data = serde_json::to_string(
+        &(NewAdaptorKeySignSession {
+            key_id: key_id.to_owned(),
+            session_id: session_id.to_owned(),
+            message: cmd.msg.clone(),
+            curve,
+            adaptor_point,
+            email: None,
+            taproot: cmd.taproot.clone(),
+        })
+    )?;
+    for node in party_nodes.iter() {
+        let sign_new_key = format!("network.gridlock.nodes.KeySignAdaptor.new.{}", node);
+        nc.publish(&sign_new_key, &key_sign_new_data)?;
+    }
+
+    let mut join_msg_vec = Vec::new();
+    for _ in 0..party_count {
+        let next = join_sub.next().context("Get next join message")?;
+        join_msg_vec.push(next);
+    }
+
+    if join_msg_vec.len() < party_count {
+        let msg = format!("Not every party joined - party_joined_count: {}", join_msg_vec.len());
+        error!("{}", &msg);
+        bail!(msg);
+    }
+
+    let mut indices = Vec::new();
+    for m in join_msg_vec.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        indices.push(confirmation.party_index);
+    }
+    indices.sort();
+    let join_resp = JoinResponse {
+        party_count: indices.len(),
+        all_party_indices: indices,
+    };
+    for msg in join_msg_vec {
+        msg.respond(
+            &serde_json::to_string(&join_resp).context("Respond to join message for every party")?
+        )?;
+    }
+    nc.flush()?;
+
+    info!("Parties joined to adaptor signing");
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub.next().context("Pre-signature received from every party")?;
+        results.push(
+            serde_json::from_slice::<BroadcastMessage<AdaptorSignatureResult>>(&res.data)?.message
+        );
+    }
+
+    if results.iter().any(|result| result != &results[0]) {
+        bail!("Parties do not agree on the pre-signature");
+    }
+    info!("Pre-signature received");
+
+    Ok(SigningResponse::Adaptor(results.remove(0)))
+}
@@ -0,0 +1,156 @@
+//! BIP340 adaptor signatures.
+//!
+//! A pre-signature is the compressed adapted nonce point `R'` and a scalar `s'` with
+//! `s'·G = f·(R' - T) + e·P`, where `f` is 1 or -1 as `R'` has an even or an odd y coordinate and
+//! `e` is the BIP340 challenge of `f·R'`. Completed with `t`, it is the signature
+//! `x(R') || s' + f·t`.
+use crate::signing::schnorr_secp256k1::{ challenge, lift_x, parity_factor, x_only };
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Point, Scalar, Secp256k1 };
+use curv::BigInt;
+
+/// Checks that the pre-signature becomes a valid signature of the message under the x-only
+/// public key once the secret of the adaptor point is added to it
+pub fn verify_pre_signature(
+    public_key: &[u8],
+    message: &[u8],
+    adaptor_point: &Point<Secp256k1>,
+    pre_signature: &[u8]
+) -> Result<()> {
+    let (nonce, s) = decode_pre_signature(pre_signature)?;
+    let public_key = lift_x(public_key)?;
+    let nonce_factor = parity_factor(&nonce);
+    let c = challenge(&(&nonce * &nonce_factor), &public_key, message);
+    if Point::generator() * s != (nonce - adaptor_point) * nonce_factor + public_key * c {
+        bail!("Pre-signature did not pass verification");
+    }
+    Ok(())
+}
+
+/// Completes the pre-signature into a 64 byte BIP340 signature with the adaptor secret
+pub fn complete(pre_signature: &[u8], secret: &Scalar<Secp256k1>) -> Result<[u8; 64]> {
+    let (nonce, s) = decode_pre_signature(pre_signature)?;
+    let s = s + secret * parity_factor(&nonce);
+    let mut signature = [0; 64];
+    signature[..32].copy_from_slice(&x_only(&nonce));
+    signature[32..].copy_from_slice(&s.to_bytes());
+    Ok(signature)
+}
+
+/// Recovers the adaptor secret from a signature that was completed from the pre-signature
+pub fn extract_secret(
+    pre_signature: &[u8],
+    signature: &[u8],
+    adaptor_point: &Point<Secp256k1>
+) -> Result<Scalar<Secp256k1>> {
+    let (nonce, pre_s) = decode_pre_signature(pre_signature)?;
+    if signature.len() != 64 {
+        bail!("BIP340 signatures have to be 64 bytes long");
+    }
+    if signature[..32] != x_only(&nonce) {
+        bail!("Signature was not completed from the pre-signature");
+    }
+    let s = decode_scalar(&signature[32..])?;
+    let secret = (s - pre_s) * parity_factor(&nonce);
+    if &(Point::generator() * &secret) != adaptor_point {
+        bail!("Signature does not reveal the secret of the adaptor point");
+    }
+    Ok(secret)
+}
+
+/// Decodes a compressed adaptor point from hex
+pub fn decode_adaptor_point(encoded: &str) -> Result<Point<Secp256k1>> {
+    let bytes = hex::decode(encoded)?;
+    if bytes.len() != 33 {
+        bail!("Adaptor points on secp256k1 have to be 33 byte compressed points");
+    }
+    let point = Point::<Secp256k1>
+        ::from_bytes(&bytes)
+        .map_err(|_| anyhow!("Adaptor point is not on the curve"))?;
+    if point.is_zero() {
+        bail!("Adaptor point is the point at infinity");
+    }
+    Ok(point)
+}
+
+fn decode_pre_signature(pre_signature: &[u8]) -> Result<(Point<Secp256k1>, Scalar<Secp256k1>)> {
+    if pre_signature.len() != 65 {
+        bail!("Pre-signatures on secp256k1 have to be 65 bytes long");
+    }
+    let nonce = Point::<Secp256k1>
+        ::from_bytes(&pre_signature[..33])
+        .map_err(|_| anyhow!("Nonce point is not on the curve"))?;
+    Ok((nonce, decode_scalar(&pre_signature[33..])?))
+}
+
+fn decode_scalar(bytes: &[u8]) -> Result<Scalar<Secp256k1>> {
+    let s_bn = BigInt::from_bytes(bytes);
+    if &s_bn >= Scalar::<Secp256k1>::group_order() {
+        bail!("Signature scalar is out of range");
+    }
+    Ok(Scalar::<Secp256k1>::from_bigint(&s_bn))
+}
+
+#[test]
+fn threshold_pre_signature_completes_and_reveals_secret() {
+    use crate::signing::schnorr_secp256k1::client::{
+        NonceCommitment,
+        SignatureShare,
+        SigningPackage,
+    };
+    use crate::signing::schnorr_secp256k1::{ verify, SigningKey };
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+    let secret = Scalar::<Secp256k1>::random();
+    let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+    let signing_key = SigningKey::new(&(Point::generator() * &secret), None).unwrap();
+    let public_key = x_only(&signing_key.public_key);
+    let adaptor_secret = Scalar::<Secp256k1>::random();
+    let adaptor_point = Point::generator() * &adaptor_secret;
+    let message = [3; 32];
+
+    let signers = [1, 2];
+    let nonces = signers
+        .iter()
+        .map(|_| (Scalar::<Secp256k1>::random(), Scalar::<Secp256k1>::random()))
+        .collect::<Vec<_>>();
+    let commitments = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| NonceCommitment {
+            party_index,
+            hiding: Point::generator() * hiding,
+            binding: Point::generator() * binding,
+        })
+        .collect();
+    let package = SigningPackage::with_adaptor_point(
+        &message,
+        signing_key,
+        commitments,
+        Some(&adaptor_point)
+    ).unwrap();
+    let pre_signature_shares = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| SignatureShare {
+            party_index,
+            z_i: package
+                .signature_share(party_index, hiding, binding, &shares[party_index - 1])
+                .unwrap(),
+        })
+        .collect::<Vec<_>>();
+    for share in pre_signature_shares.iter() {
+        let public_share = vss.get_point_commitment(share.party_index as u16);
+        package.verify_share(share, &public_share).unwrap();
+    }
+
+    let pre_signature = package.aggregate_pre_signature(&pre_signature_shares).unwrap();
+    verify_pre_signature(&public_key, &message, &adaptor_point, &pre_signature).unwrap();
+    assert!(verify(&public_key, &message, &pre_signature[1..]).is_err());
+
+    let signature = complete(&pre_signature, &adaptor_secret).unwrap();
+    verify(&public_key, &message, &signature).unwrap();
+    let extracted = extract_secret(&pre_signature, &signature, &adaptor_point).unwrap();
+    assert!(extracted == adaptor_secret);
+}
@@ -0,0 +1,243 @@
+use crate::communication::nats::{
+    BaseMessenger,
+    NatsBaseMessenger,
+    NatsBaseSession,
+    NatsPeerMessenger,
+};
+use crate::communication::protocol::{ KeySignAdaptorAllRounds, Topic };
+use crate::node::NodeIdentity;
+use crate::signing::adaptor::client::AdaptorKeySignClient;
+use crate::signing::adaptor::{ ed25519, secp256k1, AdaptorCurve };
+use crate::signing::policy::{ enforce_policy, raw_intents };
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::signing::schnorr_secp256k1::{ SigningKey, TaprootTweak };
+use crate::storage::{ KeyshareAccessor, ECDSA, EDDSA };
+use crate::App;
+use anyhow::{ bail, Result };
+use serde::{ Deserialize, Serialize };
+use std::thread;
+use tracing::{ error, info, instrument };
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewAdaptorKeySignMessage {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    pub curve: AdaptorCurve,
+    /// Compressed adaptor point, hex encoded
+    pub adaptor_point: String,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+    #[serde(default)]
+    pub taproot: Option<TaprootTweak>,
+}
+
+impl AuthenticatedRequest for NewAdaptorKeySignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let merkle_root = self.taproot.as_ref().and_then(|tweak| tweak.merkle_root.as_deref());
+        CanonicalEncoder::new("adaptor")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .bytes("message", &self.message)
+            .str("curve", self.curve.as_str())
+            .str("adaptor_point", &self.adaptor_point)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .bool("taproot", self.taproot.is_some())
+            .opt_str("taproot_merkle_root", merkle_root)
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewAdaptorKeySignSession {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    pub curve: AdaptorCurve,
+    pub adaptor_point: String,
+    pub email: Option<String>,
+    /// Tweak of the signing key for a Taproot key path spend, only used on secp256k1
+    #[serde(default)]
+    pub taproot: Option<TaprootTweak>,
+}
+
+#[instrument(skip_all)]
+fn sign_session(conn: nats::Connection, session: NewAdaptorKeySignSession) -> Result<()> {
+    let session_id = session.session_id.clone();
+    match keysign_session_inner(conn, session) {
+        Ok(()) => info!("Pre-signing completed successfully for session id: {}", session_id),
+        Err(err) => error!("Error in adaptor signing: session id: {}, error: {}", session_id, err),
+    }
+    Ok(())
+}
+
+fn keysign_session_inner(
+    conn: nats::Connection,
+    session: NewAdaptorKeySignSession
+) -> Result<()> {
+    info!("joining adaptor keysign session key_id: {}", &session.key_id);
+    match session.curve {
+        AdaptorCurve::Secp256k1 => {
+            // Signs with the ECDSA keyshare of the key, as BIP340 shares the secp256k1 secret
+            let keyshare = (
+                if let Some(email) = &session.email {
+                    KeyshareAccessor::<ECDSA>::read_only_with_email(&session.key_id, email)?
+                } else {
+                    KeyshareAccessor::<ECDSA>::read_only(&session.key_id)?
+                }
+            ).key;
+            info!("Retrieved keyshare");
+
+            let signing_key = SigningKey::new(&keyshare.y_sum, session.taproot.as_ref())?;
+            let adaptor_point = secp256k1::decode_adaptor_point(&session.adaptor_point)?;
+            let keysign_client = join_session(
+                conn,
+                &session,
+                keyshare.party_index,
+                keyshare.threshold
+            )?;
+            let result = keysign_client.create_secp256k1_pre_sig(
+                &session.message,
+                &keyshare,
+                &signing_key,
+                &adaptor_point
+            )?;
+            keysign_client.publish_result(result)?;
+        }
+        AdaptorCurve::Ed25519 => {
+            if session.taproot.is_some() {
+                bail!("Taproot tweaks are only supported on secp256k1");
+            }
+            let keyshare = (
+                if let Some(email) = &session.email {
+                    KeyshareAccessor::<EDDSA>::read_only_with_email(&session.key_id, email)?
+                } else {
+                    KeyshareAccessor::<EDDSA>::read_only(&session.key_id)?
+                }
+            ).key;
+            info!("Retrieved keyshare");
+
+            let adaptor_point = ed25519::decode_adaptor_point(&session.adaptor_point)?;
+            let keysign_client = join_session(
+                conn,
+                &session,
+                keyshare.party_index,
+                keyshare.threshold
+            )?;
+            let result = keysign_client.create_ed25519_pre_sig(
+                &session.message,
+                &keyshare,
+                &adaptor_point
+            )?;
+            keysign_client.publish_result(result)?;
+        }
+    }
+    info!("Pre-signature published successfully");
+
+    Ok(())
+}
+
+fn join_session(
+    conn: nats::Connection,
+    session: &NewAdaptorKeySignSession,
+    party_index: usize,
+    threshold: usize
+) -> Result<AdaptorKeySignClient<NatsPeerMessenger<KeySignAdaptorAllRounds>>> {
+    let node = NodeIdentity::load()?;
+    info!("Retrieved node identity");
+
+    let nats_session = NatsBaseSession {
+        session_id: session.session_id.clone(),
+        thread_index: 0, // Single keyshare per device
+        node_id: node.node_id.to_string(),
+        public_key: node.networking_public_key,
+        party_index,
+    };
+
+    let sign_messenger = NatsBaseMessenger::<KeySignAdaptorAllRounds>::new(
+        Topic::KeySignAdaptor,
+        conn,
+        nats_session
+    )?;
+
+    let join_response = sign_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+    info!("Got join response");
+
+    let mut all_party_indices = join_response.all_party_indices;
+    all_party_indices.sort();
+    if all_party_indices.len() <= threshold {
+        bail!("Not enough parties joined to sign");
+    }
+
+    let sign_peer_messenger = NatsPeerMessenger::from(
+        sign_messenger,
+        join_response.party_count,
+        all_party_indices.clone()
+    )?;
+
+    Ok(AdaptorKeySignClient {
+        peer_messenger: sign_peer_messenger,
+        all_party_indices,
+    })
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let parsed_message = match
+        serde_json::from_slice::<NewAdaptorKeySignMessage>(&message.data[..])
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
+    };
+
+    // A pre-signature turns into a signature without the signers, so the policy applies as if
+    // the message was signed right away
+    let intents = raw_intents(1, false);
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &intents) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
+    let session = NewAdaptorKeySignSession {
+        key_id: parsed_message.key_id,
+        session_id: parsed_message.session_id,
+        message: parsed_message.message,
+        curve: parsed_message.curve,
+        adaptor_point: parsed_message.adaptor_point,
+        email: Some(email),
+        taproot: parsed_message.taproot,
+    };
+
+    info!("Spawning a thread to handle adaptor pre-signature generation");
+    let thread_name = format!("sign_session_{}", session.session_id);
+    let nc = app.nc.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(thread_name)
+            .spawn(move || sign_session(nc, session))
+    {
+        Ok(_) => info!("Started adaptor signing thread"),
+        Err(err) => error!("Failed to spawn thread for adaptor signing: {}", err),
+    };
+}
@@ -0,0 +1,269 @@
+//! Threshold BLS signatures of BLS12-381 keys, as Ethereum validators sign.
+//!
+//! Public keys and keyshares are on G1 and signatures on G2, hashed to the curve with the
+//! ciphersuite of the Ethereum consensus layer. Guardians sign with their keyshare without any
+//! rounds between them, the orchestrator checks every partial signature against the public
+//! share of its party and interpolates them to the signature of the key.
+pub mod orchestrate;
+pub mod session;
+
+use crate::recovery::RecoveryCalculator;
+use crate::storage::Bls12381;
+use anyhow::{ anyhow, bail, Result };
+use bls12_381::hash_to_curve::{ ExpandMsgXmd, HashToCurve };
+use bls12_381::{ pairing, G1Affine, G1Projective, G2Affine, G2Projective };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Bls12_381_1, Point, Scalar };
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+/// Proof of possession ciphersuite of the Ethereum consensus layer
+const ETH2_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Domain of an Ethereum consensus object, guardians sign the signing root of `msg` with it
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BeaconDomain {
+    /// 4 byte domain type, hex encoded, e.g. 00000000 for block proposals
+    pub domain_type: String,
+    /// 4 byte fork version, hex encoded
+    pub fork_version: String,
+    /// 32 byte genesis validators root, hex encoded
+    pub genesis_validators_root: String,
+}
+
+impl BeaconDomain {
+    pub fn domain(&self) -> Result<[u8; 32]> {
+        Ok(
+            compute_domain(
+                decode_fixed(&self.domain_type, "domain type")?,
+                decode_fixed(&self.fork_version, "fork version")?,
+                decode_fixed(&self.genesis_validators_root, "genesis validators root")?
+            )
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BlsSignatureResult {
+    /// 96 byte compressed G2 signature, hex encoded
+    pub signature: String,
+    /// 48 byte compressed G1 public key, hex encoded
+    pub public_key: String,
+    /// Message that was signed, the signing root for beacon objects, hex encoded
+    pub signing_root: String,
+}
+
+/// Signature of one guardian, with its public share to check it against
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PartialSignature {
+    pub party_index: usize,
+    /// `x_i * G1`, compressed and hex encoded
+    pub public_share: String,
+    /// `x_i * H(m)`, compressed and hex encoded
+    pub signature: String,
+}
+
+impl PartialSignature {
+    pub fn new(keyshare: &Bls12381, message: &[u8]) -> Result<Self> {
+        let x_i = to_bls_scalar(&keyshare.x_i)?;
+        let public_share = G1Affine::from(G1Projective::generator() * x_i);
+        let signature = G2Affine::from(hash_to_g2(message) * x_i);
+        Ok(Self {
+            party_index: keyshare.party_index,
+            public_share: hex::encode(public_share.to_compressed()),
+            signature: hex::encode(signature.to_compressed()),
+        })
+    }
+}
+
+/// `compute_domain` of the consensus specs, the domain type followed by the fork data root
+pub fn compute_domain(
+    domain_type: [u8; 4],
+    fork_version: [u8; 4],
+    genesis_validators_root: [u8; 32]
+) -> [u8; 32] {
+    // Both fields of the fork data are single chunks, so its root is the hash of the two
+    let mut fork_data = [0u8; 64];
+    fork_data[..4].copy_from_slice(&fork_version);
+    fork_data[32..].copy_from_slice(&genesis_validators_root);
+    let fork_data_root = Sha256::digest(&fork_data);
+
+    let mut domain = [0u8; 32];
+    domain[..4].copy_from_slice(&domain_type);
+    domain[4..].copy_from_slice(&fork_data_root[..28]);
+    domain
+}
+
+/// `compute_signing_root` of the consensus specs for the hash tree root of an object
+pub fn signing_root(object_root: &[u8; 32], domain: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(object_root);
+    hasher.update(domain);
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&hasher.finalize());
+    root
+}
+
+/// Message the guardians sign, the signing root of `msg` when it is the root of a beacon object
+pub fn signing_message(msg: &[u8], beacon_domain: Option<&BeaconDomain>) -> Result<Vec<u8>> {
+    match beacon_domain {
+        Some(beacon_domain) => {
+            let object_root: [u8; 32] = msg
+                .try_into()
+                .map_err(|_| anyhow!("Beacon objects are signed by their 32 byte root"))?;
+            Ok(signing_root(&object_root, &beacon_domain.domain()?).to_vec())
+        }
+        None => Ok(msg.to_vec()),
+    }
+}
+
+pub fn hash_to_g2(message: &[u8]) -> G2Projective {
+    <G2Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(message, ETH2_DST)
+}
+
+/// Check more than `threshold` partial signatures and interpolate the signature of the key
+pub fn combine_partial_signatures(
+    partials: &[PartialSignature],
+    public_key: &G1Affine,
+    message: &[u8],
+    threshold: usize
+) -> Result<G2Affine> {
+    if partials.len() <= threshold {
+        bail!(
+            "Signing needs {} partial signatures, only {} were received",
+            threshold + 1,
+            partials.len()
+        );
+    }
+    let indices = partials
+        .iter()
+        .map(|partial| partial.party_index)
+        .collect::<Vec<_>>();
+    let message_point = G2Affine::from(hash_to_g2(message));
+
+    let mut public_key_sum = G1Projective::identity();
+    let mut signature_sum = G2Projective::identity();
+    for partial in partials {
+        let public_share = decode_g1(&partial.public_share)?;
+        let signature = decode_g2(&partial.signature)?;
+        if pairing(&public_share, &message_point) != pairing(&G1Affine::generator(), &signature) {
+            bail!("Partial signature of party {} is invalid", partial.party_index);
+        }
+        let lambda = to_bls_scalar(
+            &RecoveryCalculator::<Bls12_381_1>::lagrange_coefficient_at_zero(
+                partial.party_index,
+                &indices
+            )?
+        )?;
+        public_key_sum += G1Projective::from(public_share) * lambda;
+        signature_sum += G2Projective::from(signature) * lambda;
+    }
+    // Binds the public shares to the key, a party can't sign with a share of its own
+    if &G1Affine::from(public_key_sum) != public_key {
+        bail!("Public shares of the parties don't interpolate to the public key");
+    }
+    let signature = G2Affine::from(signature_sum);
+    verify(public_key, message, &signature)?;
+    Ok(signature)
+}
+
+pub fn verify(public_key: &G1Affine, message: &[u8], signature: &G2Affine) -> Result<()> {
+    let message_point = G2Affine::from(hash_to_g2(message));
+    if bool::from(public_key.is_identity()) {
+        bail!("The identity is not a valid public key");
+    }
+    if pairing(public_key, &message_point) != pairing(&G1Affine::generator(), signature) {
+        bail!("BLS signature verification failed");
+    }
+    Ok(())
+}
+
+/// Both libraries work over the same scalar field, curv encodes its scalars big endian
+pub fn to_bls_scalar(scalar: &Scalar<Bls12_381_1>) -> Result<bls12_381::Scalar> {
+    let be_bytes = scalar.to_bigint().to_bytes();
+    let mut bytes = [0u8; 32];
+    bytes[32 - be_bytes.len()..].copy_from_slice(&be_bytes);
+    bytes.reverse();
+    Option::<bls12_381::Scalar>
+        ::from(bls12_381::Scalar::from_bytes(&bytes))
+        .ok_or_else(|| anyhow!("Scalar is out of range of the BLS12-381 scalar field"))
+}
+
+/// Both libraries use the Zcash encoding of compressed points
+pub fn to_g1_affine(point: &Point<Bls12_381_1>) -> Result<G1Affine> {
+    let bytes = <[u8; 48]>
+        ::try_from(&*point.to_bytes(true))
+        .map_err(|_| anyhow!("Compressed G1 points are 48 bytes long"))?;
+    Option::<G1Affine>
+        ::from(G1Affine::from_compressed(&bytes))
+        .ok_or_else(|| anyhow!("Invalid G1 point"))
+}
+
+pub fn decode_g1(encoded: &str) -> Result<G1Affine> {
+    let bytes = decode_fixed::<48>(encoded, "G1 point")?;
+    Option::<G1Affine>
+        ::from(G1Affine::from_compressed(&bytes))
+        .ok_or_else(|| anyhow!("Invalid G1 point"))
+}
+
+pub fn decode_g2(encoded: &str) -> Result<G2Affine> {
+    let bytes = decode_fixed::<96>(encoded, "G2 point")?;
+    Option::<G2Affine>
+        ::from(G2Affine::from_compressed(&bytes))
+        .ok_or_else(|| anyhow!("Invalid G2 point"))
+}
+
+fn decode_fixed<const N: usize>(encoded: &str, name: &str) -> Result<[u8; N]> {
+    hex::decode(encoded)?
+        .try_into()
+        .map_err(|_| anyhow!("A {} has to be {} bytes long", name, N))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+    #[test]
+    fn deposit_domain_at_genesis() {
+        let domain = compute_domain([3, 0, 0, 0], [0; 4], [0; 32]);
+        assert_eq!(
+            hex::encode(domain),
+            "03000000f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a9"
+        );
+    }
+
+    #[test]
+    fn partial_signatures_interpolate_to_signature_of_key() {
+        let secret = Scalar::<Bls12_381_1>::random();
+        let public_key = to_g1_affine(&(Point::generator() * &secret)).unwrap();
+        let (vss, shares) = VerifiableSS::<Bls12_381_1>::share(1, 3, &secret);
+        let message = signing_message(&[7; 32], Some(&BeaconDomain {
+            domain_type: "00000000".to_string(),
+            fork_version: "00000000".to_string(),
+            genesis_validators_root: "00".repeat(32),
+        })).unwrap();
+
+        let mut partials = [1, 3]
+            .iter()
+            .map(|&index| {
+                let keyshare = Bls12381 {
+                    threshold: 1,
+                    party_index: index,
+                    x_i: shares[index - 1].clone(),
+                    public_key: Point::generator() * &secret,
+                    vss_scheme: vss.clone(),
+                };
+                PartialSignature::new(&keyshare, &message).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let signature = combine_partial_signatures(&partials, &public_key, &message, 1).unwrap();
+        let expected = G2Affine::from(hash_to_g2(&message) * to_bls_scalar(&secret).unwrap());
+        assert_eq!(signature, expected);
+
+        partials[1].signature = partials[0].signature.clone();
+        assert!(combine_partial_signatures(&partials, &public_key, &message, 1).is_err());
+        assert!(combine_partial_signatures(&partials[..1], &public_key, &message, 1).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::command::MsgContext;
+use crate::signing::bls::session::NewBlsKeySignSession;
+use crate::signing::bls::{
+    combine_partial_signatures,
+    decode_g1,
+    signing_message,
+    BlsSignatureResult,
+    PartialSignature,
+};
+use crate::signing::{ SigningCommand, SigningResponse };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
+use shared::key_info::Key;
+use std::time::Duration;
+use tracing::{ info, instrument };
+
+const PARTIAL_SIGNATURE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+    let key_id = cmd.key_id.clone();
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", &key_id)
+    )?;
+    let pk = match &key_info.kind {
+        Key::Bls12381 { pk } => pk.clone(),
+        _ => bail!("Key {} is not a BLS12-381 key", &key_id),
+    };
+
+    let party_count = cmd.party_nodes.len();
+    if party_count <= key_info.threshold {
+        bail!("Not enough nodes in party");
+    }
+    if !cmd.msgs.is_empty() {
+        bail!("Batch signing is not supported for BLS signatures");
+    }
+    if cmd.presignature_id.is_some() {
+        bail!("Presignatures are only supported for ECDSA signatures");
+    }
+    if cmd.derivation_path.is_some() {
+        bail!("Key derivation is only supported for ECDSA keys");
+    }
+    let message = signing_message(&cmd.msg, cmd.beacon_domain.as_ref())?;
+
+    let result_key = format!("network.gridlock.nodes.KeySignBls12381.{}.Result", &session_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    let key_sign_new_data = serde_json::to_string(
+        &(NewBlsKeySignSession {
+            key_id: key_id.clone(),
+            session_id: session_id.clone(),
+            message: cmd.msg.clone(),
+            beacon_domain: cmd.beacon_domain.clone(),
+            email: None,
+        })
+    )?;
+    for node in cmd.party_nodes.iter() {
+        let sign_new_key = format!("network.gridlock.nodes.KeySignBls12381.new.{}", node);
+        nc.publish(&sign_new_key, &key_sign_new_data)?;
+    }
+    nc.flush()?;
+
+    let mut partials = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub
+            .next_timeout(PARTIAL_SIGNATURE_TIMEOUT)
+            .context("Partial signature received from every party")?;
+        partials.push(serde_json::from_slice::<PartialSignature>(&res.data)?);
+    }
+    info!("Partial signatures received");
+
+    let public_key = decode_g1(&pk)?;
+    let signature = combine_partial_signatures(
+        &partials,
+        &public_key,
+        &message,
+        key_info.threshold
+    )?;
+    info!("Partial signatures verified and combined");
+
+    Ok(
+        SigningResponse::Bls12381(BlsSignatureResult {
+            signature: hex::encode(signature.to_compressed()),
+            public_key: pk,
+            signing_root: hex::encode(message),
+        })
+    )
+}
@@ -0,0 +1,133 @@
+use crate::signing::bls::{ signing_message, BeaconDomain, PartialSignature };
+use crate::signing::policy::{ enforce_policy, raw_intents };
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::storage::{ Bls12381, KeyshareAccessor };
+use crate::App;
+use anyhow::Result;
+use serde::{ Deserialize, Serialize };
+use std::thread;
+use tracing::{ error, info, instrument };
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewBlsKeySignMessage {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    /// Signs the signing root of `message` as the root of a beacon object in this domain
+    #[serde(default)]
+    pub beacon_domain: Option<BeaconDomain>,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+}
+
+impl AuthenticatedRequest for NewBlsKeySignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let domain = self.beacon_domain.as_ref();
+        CanonicalEncoder::new("bls12381")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .bytes("message", &self.message)
+            .opt_str("domain_type", domain.map(|domain| domain.domain_type.as_str()))
+            .opt_str("fork_version", domain.map(|domain| domain.fork_version.as_str()))
+            .opt_str(
+                "genesis_validators_root",
+                domain.map(|domain| domain.genesis_validators_root.as_str())
+            )
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewBlsKeySignSession {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    #[serde(default)]
+    pub beacon_domain: Option<BeaconDomain>,
+    pub email: Option<String>,
+}
+
+#[instrument(skip_all)]
+fn sign_session(conn: nats::Connection, session: NewBlsKeySignSession) {
+    let session_id = session.session_id.clone();
+    match sign_session_inner(conn, session) {
+        Ok(()) => info!("Partial signature published for session id: {}", session_id),
+        Err(err) => error!("Error in BLS signing: session id: {}, error: {}", session_id, err),
+    }
+}
+
+fn sign_session_inner(conn: nats::Connection, session: NewBlsKeySignSession) -> Result<()> {
+    let keyshare = (
+        if let Some(email) = &session.email {
+            KeyshareAccessor::<Bls12381>::read_only_with_email(&session.key_id, email)?
+        } else {
+            KeyshareAccessor::<Bls12381>::read_only(&session.key_id)?
+        }
+    ).key;
+    info!("Retrieved keyshare");
+
+    let message = signing_message(&session.message, session.beacon_domain.as_ref())?;
+    let partial = PartialSignature::new(&keyshare, &message)?;
+
+    conn.publish(
+        &format!("network.gridlock.nodes.KeySignBls12381.{}.Result", session.session_id),
+        serde_json::to_vec(&partial)?
+    )?;
+    Ok(())
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let parsed_message = match serde_json::from_slice::<NewBlsKeySignMessage>(&message.data[..]) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
+    };
+
+    // Enforce the signing policy of the key before signing
+    let intents = raw_intents(1, false);
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &intents) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
+    let session = NewBlsKeySignSession {
+        key_id: parsed_message.key_id,
+        session_id: parsed_message.session_id,
+        message: parsed_message.message,
+        beacon_domain: parsed_message.beacon_domain,
+        email: Some(email),
+    };
+
+    let thread_name = format!("sign_session_{}", session.session_id);
+    let nc = app.nc.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(thread_name)
+            .spawn(move || sign_session(nc, session))
+    {
+        Ok(_) => info!("Started BLS signing thread"),
+        Err(err) => error!("Failed to spawn thread for BLS signing: {}", err),
+    };
+}
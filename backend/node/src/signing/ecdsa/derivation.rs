@@ -0,0 +1,204 @@
+use crate::storage::ECDSA;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Point, Scalar, Secp256k1 };
+use curv::BigInt;
+use hmac::{ Hmac, Mac, NewMac };
+use ripemd160::Ripemd160;
+use sha2::{ Digest, Sha256, Sha512 };
+use shared::ecdsa::Sum;
+use std::str::FromStr;
+
+/// Version bytes of a mainnet extended public key
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const HARDENED_OFFSET: u32 = 1 << 31;
+const CHAIN_CODE_DOMAIN: &[u8] = b"gridlock-bip32-chain-code";
+
+/// BIP32 path of non-hardened child indices, such as m/0/17
+#[derive(Clone, Debug, PartialEq)]
+pub struct DerivationPath(Vec<u32>);
+
+impl FromStr for DerivationPath {
+    type Err = anyhow::Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let mut parts = path.split('/');
+        if parts.next() != Some("m") {
+            bail!("Derivation path has to start with \"m\": {}", path);
+        }
+        let indices = parts
+            .map(|part| {
+                // Hardened children need the private key, which no single party holds
+                if part.ends_with('\'') || part.ends_with('h') {
+                    bail!("Hardened derivation is not supported for threshold keys: {}", path);
+                }
+                let index = part
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("Invalid child index \"{}\" in path {}", part, path))?;
+                if index >= HARDENED_OFFSET {
+                    bail!("Child index {} is out of the non-hardened range", index);
+                }
+                Ok(index)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self(indices))
+    }
+}
+
+#[derive(Clone)]
+pub struct ExtendedPublicKey {
+    pub public_key: Point<Secp256k1>,
+    pub chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+}
+
+impl ExtendedPublicKey {
+    pub fn new(public_key: Point<Secp256k1>, chain_code: &str) -> Result<Self> {
+        let chain_code = hex::decode(chain_code)?
+            .try_into()
+            .map_err(|_| anyhow!("Chain code has to be 32 bytes long"))?;
+        Ok(Self {
+            public_key,
+            chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: 0,
+        })
+    }
+
+    pub fn from_keyshare(keyshare: &ECDSA) -> Result<Self> {
+        let chain_code = keyshare.chain_code
+            .as_ref()
+            .ok_or_else(|| anyhow!("Keyshare has no chain code, it can't be used for derivation"))?;
+        Self::new(keyshare.y_sum.clone(), chain_code)
+    }
+
+    pub fn from_sum(y_sum: &Sum, chain_code: &str) -> Result<Self> {
+        let x = BigInt::from_hex(&y_sum.x).map_err(|_| anyhow!("Invalid public key x coordinate"))?;
+        let y = BigInt::from_hex(&y_sum.y).map_err(|_| anyhow!("Invalid public key y coordinate"))?;
+        let public_key = Point::<Secp256k1>::from_coords(&x, &y)?;
+        Self::new(public_key, chain_code)
+    }
+
+    /// Derives the child key at the path, together with the tweak added to the private key
+    pub fn derive(&self, path: &DerivationPath) -> Result<(Self, Scalar<Secp256k1>)> {
+        let mut child = self.clone();
+        let mut tweak = Scalar::<Secp256k1>::zero();
+        for &index in path.0.iter() {
+            let (next, child_tweak) = child.derive_child(index)?;
+            child = next;
+            tweak = tweak + child_tweak;
+        }
+        Ok((child, tweak))
+    }
+
+    fn derive_child(&self, index: u32) -> Result<(Self, Scalar<Secp256k1>)> {
+        let mut mac = Hmac::<Sha512>
+            ::new_from_slice(&self.chain_code)
+            .map_err(|err| anyhow!("Failed to create HMAC instance: {}", err))?;
+        mac.update(&self.public_key.to_bytes(true));
+        mac.update(&index.to_be_bytes());
+        let result = mac.finalize().into_bytes();
+        let (tweak_bytes, chain_code) = result.split_at(32);
+
+        let tweak_bn = BigInt::from_bytes(tweak_bytes);
+        if &tweak_bn >= Scalar::<Secp256k1>::group_order() {
+            bail!("Child key {} is invalid, the next index has to be used", index);
+        }
+        let tweak = Scalar::<Secp256k1>::from_bigint(&tweak_bn);
+        let public_key = &self.public_key + Point::generator() * &tweak;
+        if public_key.is_zero() {
+            bail!("Child key {} is invalid, the next index has to be used", index);
+        }
+
+        let child = Self {
+            public_key,
+            chain_code: chain_code.try_into()?,
+            depth: self.depth
+                .checked_add(1)
+                .ok_or_else(|| anyhow!("Derivation path is too deep"))?,
+            parent_fingerprint: self.fingerprint(),
+            child_number: index,
+        };
+        Ok((child, tweak))
+    }
+
+    fn fingerprint(&self) -> [u8; 4] {
+        let hash = Ripemd160::digest(&Sha256::digest(&self.public_key.to_bytes(true)));
+        let mut fingerprint = [0; 4];
+        fingerprint.copy_from_slice(&hash[..4]);
+        fingerprint
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(&*self.public_key.to_bytes(true))
+    }
+
+    pub fn to_xpub(&self) -> String {
+        let mut data = Vec::with_capacity(78);
+        data.extend_from_slice(&XPUB_VERSION);
+        data.push(self.depth);
+        data.extend_from_slice(&self.parent_fingerprint);
+        data.extend_from_slice(&self.child_number.to_be_bytes());
+        data.extend_from_slice(&self.chain_code);
+        data.extend_from_slice(&self.public_key.to_bytes(true));
+        bs58::encode(data).with_check().into_string()
+    }
+}
+
+/// Shifts the shared secret by the tweak, by adding it to every keyshare and to the constant
+/// term of the first VSS scheme, which keeps all commitments consistent with the new shares
+pub fn apply_tweak(keyshare: &mut ECDSA, tweak: &Scalar<Secp256k1>) -> Result<()> {
+    let tweak_point = Point::generator() * tweak;
+    let first_vss = keyshare.vss_scheme_vec
+        .first_mut()
+        .ok_or_else(|| anyhow!("Keyshare has no VSS schemes"))?;
+    first_vss.commitments[0] = &first_vss.commitments[0] + &tweak_point;
+
+    keyshare.x_i = &keyshare.x_i + tweak;
+    keyshare.y_sum = &keyshare.y_sum + &tweak_point;
+    for public_key in keyshare.public_key_vec.iter_mut() {
+        *public_key = &*public_key + &tweak_point;
+    }
+    Ok(())
+}
+
+/// Chain code of a new key, made from the blinding factors that every party commits to in the
+/// first keygen round and only reveals in the second, so that no party can bias it
+pub fn chain_code_from_blind_factors(blind_factors: &[BigInt]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(CHAIN_CODE_DOMAIN);
+    for blind_factor in blind_factors {
+        let bytes = BigInt::to_bytes(blind_factor);
+        hasher.update((bytes.len() as u32).to_be_bytes());
+        hasher.update(&bytes);
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[test]
+fn derives_bip32_test_vector_2_child() {
+    let public_key = Point::<Secp256k1>
+        ::from_bytes(
+            &hex
+                ::decode("03cbcaa9c98c877a26977d00825c956a238e8dddfbd322cce4f74b0b5bd6ace4a7")
+                .unwrap()
+        )
+        .unwrap();
+    let master = ExtendedPublicKey::new(
+        public_key,
+        "60499f801b896d83179a4374aeb7822aaeaceaa0db1f85ee3e904c4defbd9689"
+    ).unwrap();
+
+    let (child, _) = master.derive(&"m/0".parse().unwrap()).unwrap();
+    assert_eq!(
+        child.public_key_hex(),
+        "02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea"
+    );
+    assert_eq!(
+        child.to_xpub(),
+        "xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH"
+    );
+}
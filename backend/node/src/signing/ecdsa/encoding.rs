@@ -0,0 +1,170 @@
+//! Standard encodings of secp256k1 ECDSA signatures.
+//!
+//! Guardians only release low-S signatures (BIP62, EIP-2), so every encoding here can be
+//! broadcast as it is.
+use crate::signing::ecdsa::SigningResult;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Scalar, Secp256k1 };
+use serde::{ Deserialize, Serialize };
+
+/// Encoding of a signature returned next to the hex `r`, `s` and `recid`
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum SignatureFormat {
+    /// ASN.1 DER, hex encoded
+    Der,
+    /// 65 byte `r || s || v`, hex encoded, with `v = 27 + recid`. The EIP-155
+    /// `v = 35 + 2 * chain_id + recid` of a given chain id is returned separately, as it does not
+    /// fit in one byte for most chains.
+    Compact {
+        #[serde(default)]
+        chain_id: Option<u64>,
+    },
+    /// 65 byte recoverable signature of Bitcoin signed messages for compressed keys, base64
+    /// encoded
+    BitcoinCompact,
+    /// 64 byte `r || s` of JWS `ES256K` (RFC 8812), base64url encoded
+    JwsEs256k,
+}
+
+impl SignatureFormat {
+    /// Check that a signature can be encoded in this format, before any party signs
+    pub fn validate(&self) -> Result<()> {
+        if let SignatureFormat::Compact { chain_id: Some(chain_id) } = self {
+            eip155_v(*chain_id, 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Flip `s` to the lower half of the group order, which negates the recovered `R` as well
+pub fn normalize_low_s(s: &Scalar<Secp256k1>, recid: u8) -> (Scalar<Secp256k1>, u8) {
+    let order = Scalar::<Secp256k1>::group_order();
+    let s_bn = s.to_bigint();
+    if &s_bn + &s_bn > *order {
+        (Scalar::from(&(order - &s_bn)), recid ^ 1)
+    } else {
+        (s.clone(), recid)
+    }
+}
+
+/// Encode a signature in the requested format and fill in the EIP-155 `v` of a compact
+/// signature with a chain id
+pub fn encode_into(result: &mut SigningResult, format: SignatureFormat) -> Result<()> {
+    result.encoded = Some(encode(result, format)?);
+    result.v = match format {
+        SignatureFormat::Compact { chain_id: Some(chain_id) } => {
+            Some(eip155_v(chain_id, result.recid)?)
+        }
+        _ => None,
+    };
+    Ok(())
+}
+
+/// `v = 35 + 2 * chain_id + recid` of EIP-155
+pub fn eip155_v(chain_id: u64, recid: u8) -> Result<u64> {
+    chain_id
+        .checked_mul(2)
+        .and_then(|v| v.checked_add(35 + (recid as u64)))
+        .ok_or_else(|| anyhow!("Chain id {} is out of range", chain_id))
+}
+
+/// Encode a signature in the requested format
+pub fn encode(result: &SigningResult, format: SignatureFormat) -> Result<String> {
+    let r = scalar_bytes(&result.r)?;
+    let s = scalar_bytes(&result.s)?;
+    if result.recid > 1 {
+        bail!("Recovery id {} is out of range", result.recid);
+    }
+
+    let mut rs = r.to_vec();
+    rs.extend_from_slice(&s);
+    let encoded = match format {
+        SignatureFormat::Der => {
+            let mut body = der_integer(&r);
+            body.extend(der_integer(&s));
+            let mut der = vec![0x30, body.len() as u8];
+            der.extend(body);
+            hex::encode(der)
+        }
+        SignatureFormat::Compact { .. } => {
+            rs.push(27 + result.recid);
+            hex::encode(rs)
+        }
+        SignatureFormat::BitcoinCompact => {
+            let mut compact = vec![27 + 4 + result.recid];
+            compact.extend(rs);
+            base64::encode(compact)
+        }
+        SignatureFormat::JwsEs256k => base64::encode_config(rs, base64::URL_SAFE_NO_PAD),
+    };
+    Ok(encoded)
+}
+
+fn scalar_bytes(value: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(format!("{:0>64}", value))?;
+    bytes.try_into().map_err(|_| anyhow!("Signature scalars must be 32 bytes long"))
+}
+
+/// Minimal DER INTEGER of an unsigned big endian value
+fn der_integer(value: &[u8]) -> Vec<u8> {
+    let start = value
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(value.len() - 1);
+    let mut content = value[start..].to_vec();
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    let mut integer = vec![0x02, content.len() as u8];
+    integer.extend(content);
+    integer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::BigInt;
+
+    #[test]
+    fn signature_encodings() {
+        let result = SigningResult {
+            r: format!("80{}", "00".repeat(30) + "01"),
+            s: "7f".to_string(),
+            recid: 1,
+            encoded: None,
+            v: None,
+        };
+        let der = encode(&result, SignatureFormat::Der).unwrap();
+        assert_eq!(der, format!("3026022100{}02017f", result.r));
+
+        let compact = encode(&result, SignatureFormat::Compact { chain_id: None }).unwrap();
+        assert_eq!(&compact[128..], "1c");
+
+        let mut polygon = result.clone();
+        let format = SignatureFormat::Compact { chain_id: Some(137) };
+        format.validate().unwrap();
+        encode_into(&mut polygon, format).unwrap();
+        assert_eq!(polygon.v, Some(310));
+        let encoded = polygon.encoded.unwrap();
+        assert_eq!(encoded.len(), 130);
+        assert_eq!(&encoded[128..], "1c");
+        assert!(SignatureFormat::Compact { chain_id: Some(u64::MAX) }.validate().is_err());
+
+        let bitcoin = base64::decode(encode(&result, SignatureFormat::BitcoinCompact).unwrap());
+        assert_eq!(bitcoin.unwrap()[0], 32);
+        let jws = encode(&result, SignatureFormat::JwsEs256k).unwrap();
+        assert_eq!(base64::decode_config(jws, base64::URL_SAFE_NO_PAD).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn high_s_is_flipped() {
+        let order = Scalar::<Secp256k1>::group_order();
+        let high = Scalar::<Secp256k1>::from(&(order - &BigInt::from(5)));
+        let (low, recid) = normalize_low_s(&high, 0);
+        assert_eq!(low.to_bigint(), BigInt::from(5));
+        assert_eq!(recid, 1);
+        assert_eq!(normalize_low_s(&low, recid), (low.clone(), 1));
+    }
+}
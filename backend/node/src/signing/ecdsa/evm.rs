@@ -0,0 +1,589 @@
+//! Decoding of EVM signing payloads.
+//!
+//! Clients send the payload itself rather than its hash. Each guardian decodes it, computes the
+//! Keccak-256 digest to sign on its own and hands what the payload does to the signing policy.
+use crate::signing::policy::{ MessageKind, SigningIntent, Transfer, NATIVE_ASSET };
+use crate::signing::PayloadType;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::{ BasicOps, BitManipulation, Converter, Zero };
+use curv::BigInt;
+use serde::{ Deserialize, Serialize };
+use serde_json::Value;
+use sha3::{ Digest, Keccak256 };
+use std::collections::{ BTreeMap, BTreeSet };
+
+const EIP1559_TX_TYPE: u8 = 0x02;
+const ERC20_TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+const ERC20_APPROVE: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+const ERC20_TRANSFER_FROM: [u8; 4] = [0x23, 0xb8, 0x72, 0xdd];
+
+/// Decoded payload together with the digest this node signs for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedEvmPayload {
+    pub digest: [u8; 32],
+    pub details: EvmPayloadDetails,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EvmPayloadDetails {
+    Transaction(EvmTransaction),
+    TypedData(TypedDataSummary),
+    PersonalMessage {
+        length: usize,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct EvmTransaction {
+    /// Left out by legacy transactions signed without EIP-155 replay protection
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    /// Lowercase hex address, `None` for contract creations
+    pub to: Option<String>,
+    /// Wei, in decimal
+    pub value: String,
+    /// First four bytes of the calldata, hex encoded
+    pub selector: Option<String>,
+    /// Token movement described by ERC-20 calldata
+    #[serde(skip)]
+    token_transfer: Option<Transfer>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TypedDataSummary {
+    pub primary_type: String,
+    pub chain_id: Option<u64>,
+    pub verifying_contract: Option<String>,
+    /// Token allowance granted by an EIP-2612 permit
+    #[serde(skip)]
+    permit: Option<Transfer>,
+}
+
+impl DecodedEvmPayload {
+    /// What signing the payload does, as seen by the signing policy
+    pub fn intent(&self) -> SigningIntent {
+        match &self.details {
+            EvmPayloadDetails::Transaction(tx) => {
+                let mut transfers = Vec::new();
+                if let Some(to) = &tx.to {
+                    let value = BigInt::from_str_radix(&tx.value, 10).unwrap_or_else(|_|
+                        BigInt::zero()
+                    );
+                    if !value.is_zero() {
+                        transfers.push(Transfer {
+                            asset: NATIVE_ASSET.to_string(),
+                            destination: to.clone(),
+                            amount: saturating_u128(&value),
+                        });
+                    }
+                }
+                transfers.extend(tx.token_transfer.clone());
+                SigningIntent {
+                    kind: MessageKind::EvmTransaction,
+                    destinations: tx.to.iter().cloned().collect(),
+                    transfers,
+                }
+            }
+            EvmPayloadDetails::TypedData(typed) => SigningIntent {
+                kind: MessageKind::Eip712,
+                destinations: typed.verifying_contract.iter().cloned().collect(),
+                transfers: typed.permit.iter().cloned().collect(),
+            },
+            EvmPayloadDetails::PersonalMessage { .. } => SigningIntent {
+                kind: MessageKind::PersonalSign,
+                destinations: Vec::new(),
+                transfers: Vec::new(),
+            },
+        }
+    }
+}
+
+pub fn decode_payload(payload_type: PayloadType, payload: &[u8]) -> Result<DecodedEvmPayload> {
+    let details = match payload_type {
+        PayloadType::EvmLegacyTx => EvmPayloadDetails::Transaction(decode_legacy_tx(payload)?),
+        PayloadType::EvmEip1559Tx => EvmPayloadDetails::Transaction(decode_eip1559_tx(payload)?),
+        PayloadType::Eip712 => {
+            let (digest, summary) = decode_typed_data(payload)?;
+            return Ok(DecodedEvmPayload { digest, details: EvmPayloadDetails::TypedData(summary) });
+        }
+        PayloadType::PersonalSign => {
+            let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", payload.len())
+                .into_bytes();
+            prefixed.extend_from_slice(payload);
+            return Ok(DecodedEvmPayload {
+                digest: keccak256(&prefixed),
+                details: EvmPayloadDetails::PersonalMessage { length: payload.len() },
+            });
+        }
+        PayloadType::SolanaMessage | PayloadType::SubstratePayload => {
+            bail!("ECDSA keys can't sign {} payloads", payload_type.as_str())
+        }
+    };
+    // Unsigned transactions are signed over their own encoding
+    Ok(DecodedEvmPayload { digest: keccak256(payload), details })
+}
+
+/// RLP list of nonce, gas price, gas limit, to, value and data, followed by the chain id and
+/// two empty items with EIP-155
+fn decode_legacy_tx(payload: &[u8]) -> Result<EvmTransaction> {
+    let items = decode_rlp_list(payload)?;
+    let chain_id = match items.len() {
+        6 => None,
+        9 => {
+            if !items[7].is_empty() || !items[8].is_empty() {
+                bail!("EIP-155 transactions must be signed with empty r and s");
+            }
+            Some(rlp_u64(&items[6])?)
+        }
+        len => bail!("Legacy transactions have 6 or 9 fields, got {}", len),
+    };
+    transaction(chain_id, &items[0], &items[3], &items[4], &items[5])
+}
+
+/// Type byte followed by the RLP list of chain id, nonce, max priority fee, max fee, gas limit,
+/// to, value, data and access list
+fn decode_eip1559_tx(payload: &[u8]) -> Result<EvmTransaction> {
+    match payload.split_first() {
+        Some((&EIP1559_TX_TYPE, rest)) => {
+            let items = decode_rlp_list(rest)?;
+            if items.len() != 9 {
+                bail!("EIP-1559 transactions have 9 fields, got {}", items.len());
+            }
+            if !matches!(items[8], Rlp::List(_)) {
+                bail!("Access list of an EIP-1559 transaction must be a list");
+            }
+            transaction(Some(rlp_u64(&items[0])?), &items[1], &items[5], &items[6], &items[7])
+        }
+        _ => bail!("EIP-1559 transactions start with type byte 0x02"),
+    }
+}
+
+fn transaction(
+    chain_id: Option<u64>,
+    nonce: &Rlp,
+    to: &Rlp,
+    value: &Rlp,
+    data: &Rlp
+) -> Result<EvmTransaction> {
+    let to = match to.bytes()? {
+        [] => None,
+        address if address.len() == 20 => Some(format!("0x{}", hex::encode(address))),
+        address => bail!("Invalid recipient address of {} bytes", address.len()),
+    };
+    let data = data.bytes()?;
+    let token_transfer = match &to {
+        Some(token) => decode_erc20_call(token, data),
+        None => None,
+    };
+    Ok(EvmTransaction {
+        chain_id,
+        nonce: rlp_u64(nonce)?,
+        to,
+        value: rlp_uint(value)?.to_str_radix(10),
+        selector: data.get(..4).map(hex::encode),
+        token_transfer,
+    })
+}
+
+/// Token movement of ERC-20 transfers and approvals, where an allowance counts as moved
+fn decode_erc20_call(token: &str, data: &[u8]) -> Option<Transfer> {
+    let (selector, args) = (data.get(..4)?, &data[4..]);
+    let word = |i: usize| args.get(i * 32..(i + 1) * 32);
+    let address = |word: &[u8]| {
+        word[..12].iter().all(|b| *b == 0).then(|| format!("0x{}", hex::encode(&word[12..])))
+    };
+    let (destination, amount) = match selector {
+        s if s == ERC20_TRANSFER || s == ERC20_APPROVE => (word(0)?, word(1)?),
+        s if s == ERC20_TRANSFER_FROM => (word(1)?, word(2)?),
+        _ => {
+            return None;
+        }
+    };
+    Some(Transfer {
+        asset: token.to_string(),
+        destination: address(destination)?,
+        amount: saturating_u128(&BigInt::from_bytes(amount)),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Rlp<'a> {
+    Bytes(&'a [u8]),
+    List(Vec<Rlp<'a>>),
+}
+
+impl<'a> Rlp<'a> {
+    fn bytes(&self) -> Result<&'a [u8]> {
+        match self {
+            Rlp::Bytes(bytes) => Ok(bytes),
+            Rlp::List(_) => Err(anyhow!("Expected an RLP string, got a list")),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        matches!(self, Rlp::Bytes([]))
+    }
+}
+
+fn decode_rlp_list(input: &[u8]) -> Result<Vec<Rlp<'_>>> {
+    match decode_rlp(input)? {
+        (Rlp::List(items), []) => Ok(items),
+        (Rlp::List(_), rest) => bail!("{} trailing bytes after the RLP list", rest.len()),
+        (Rlp::Bytes(_), _) => bail!("Expected an RLP list"),
+    }
+}
+
+/// Decode one canonically encoded RLP item, returning it and the remaining input
+fn decode_rlp(input: &[u8]) -> Result<(Rlp<'_>, &[u8])> {
+    let (&prefix, rest) = input.split_first().ok_or_else(|| anyhow!("Unexpected end of RLP"))?;
+    let (is_list, offset, len) = match prefix {
+        0x00..=0x7f => {
+            return Ok((Rlp::Bytes(&input[..1]), rest));
+        }
+        0x80..=0xb7 => (false, 0, (prefix - 0x80) as usize),
+        0xb8..=0xbf => (false, (prefix - 0xb7) as usize, rlp_length(rest, prefix - 0xb7)?),
+        0xc0..=0xf7 => (true, 0, (prefix - 0xc0) as usize),
+        0xf8..=0xff => (true, (prefix - 0xf7) as usize, rlp_length(rest, prefix - 0xf7)?),
+    };
+    let end = offset.checked_add(len).filter(|end| *end <= rest.len());
+    let end = end.ok_or_else(|| anyhow!("RLP item is longer than its input"))?;
+    let (payload, rest) = (&rest[offset..end], &rest[end..]);
+
+    if !is_list {
+        if len == 1 && payload[0] < 0x80 {
+            bail!("Single bytes below 0x80 must be encoded as themselves");
+        }
+        return Ok((Rlp::Bytes(payload), rest));
+    }
+    let mut items = Vec::new();
+    let mut remaining = payload;
+    while !remaining.is_empty() {
+        let (item, next) = decode_rlp(remaining)?;
+        items.push(item);
+        remaining = next;
+    }
+    Ok((Rlp::List(items), rest))
+}
+
+/// Length of a long string or list, which must not fit the short form
+fn rlp_length(input: &[u8], len_of_len: u8) -> Result<usize> {
+    let bytes = input
+        .get(..len_of_len as usize)
+        .ok_or_else(|| anyhow!("Unexpected end of RLP length"))?;
+    if bytes[0] == 0 || bytes.len() > 8 {
+        bail!("Non-canonical RLP length");
+    }
+    let len = bytes.iter().fold(0u64, |acc, b| (acc << 8) | (*b as u64)) as usize;
+    if len < 56 {
+        bail!("Non-canonical RLP length");
+    }
+    Ok(len)
+}
+
+fn rlp_uint(item: &Rlp) -> Result<BigInt> {
+    let bytes = item.bytes()?;
+    if bytes.len() > 32 || bytes.first() == Some(&0) {
+        bail!("Invalid RLP integer");
+    }
+    Ok(BigInt::from_bytes(bytes))
+}
+
+fn rlp_u64(item: &Rlp) -> Result<u64> {
+    let bytes = item.bytes()?;
+    if bytes.len() > 8 || bytes.first() == Some(&0) {
+        bail!("Invalid RLP integer");
+    }
+    Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | (*b as u64)))
+}
+
+fn saturating_u128(value: &BigInt) -> u128 {
+    let bytes = value.to_bytes();
+    if bytes.len() > 16 {
+        return u128::MAX;
+    }
+    bytes.iter().fold(0u128, |acc, b| (acc << 8) | (*b as u128))
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TypedData {
+    types: BTreeMap<String, Vec<TypedField>>,
+    primary_type: String,
+    domain: Value,
+    message: Value,
+}
+
+#[derive(Deserialize)]
+struct TypedField {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// EIP-712 typed data as JSON, signed over `0x19 0x01 || domainSeparator || hashStruct(message)`
+fn decode_typed_data(payload: &[u8]) -> Result<([u8; 32], TypedDataSummary)> {
+    let typed: TypedData = serde_json::from_slice(payload)?;
+    let domain_separator = typed.hash_struct("EIP712Domain", &typed.domain)?;
+    let message_hash = typed.hash_struct(&typed.primary_type, &typed.message)?;
+    let mut encoded = vec![0x19, 0x01];
+    encoded.extend_from_slice(&domain_separator);
+    encoded.extend_from_slice(&message_hash);
+
+    let chain_id = match typed.domain.get("chainId") {
+        Some(chain_id) => Some(json_uint(chain_id, 64)?.to_str_radix(10).parse()?),
+        None => None,
+    };
+    let verifying_contract = typed.domain
+        .get("verifyingContract")
+        .map(|address| json_address(address).map(|a| format!("0x{}", hex::encode(a))))
+        .transpose()?;
+    let permit = match (&verifying_contract, typed.primary_type.as_str()) {
+        (Some(token), "Permit") => {
+            let spender = typed.message.get("spender").map(json_address).transpose()?;
+            let value = typed.message.get("value").map(|v| json_uint(v, 256)).transpose()?;
+            spender.zip(value).map(|(spender, value)| Transfer {
+                asset: token.clone(),
+                destination: format!("0x{}", hex::encode(spender)),
+                amount: saturating_u128(&value),
+            })
+        }
+        _ => None,
+    };
+
+    let summary = TypedDataSummary {
+        primary_type: typed.primary_type.clone(),
+        chain_id,
+        verifying_contract,
+        permit,
+    };
+    Ok((keccak256(&encoded), summary))
+}
+
+impl TypedData {
+    fn fields(&self, name: &str) -> Result<&Vec<TypedField>> {
+        self.types.get(name).ok_or_else(|| anyhow!("Type {} is not defined", name))
+    }
+
+    /// `Name(type field,...)` followed by the definitions of referenced structs sorted by name
+    fn encode_type(&self, name: &str) -> Result<String> {
+        let mut dependencies = BTreeSet::new();
+        self.collect_dependencies(name, &mut dependencies)?;
+        dependencies.remove(name);
+        let mut encoded = String::new();
+        for name in std::iter::once(name).chain(dependencies.iter().map(String::as_str)) {
+            let fields = self
+                .fields(name)?
+                .iter()
+                .map(|field| format!("{} {}", field.kind, field.name))
+                .collect::<Vec<_>>();
+            encoded.push_str(&format!("{}({})", name, fields.join(",")));
+        }
+        Ok(encoded)
+    }
+
+    fn collect_dependencies(&self, name: &str, found: &mut BTreeSet<String>) -> Result<()> {
+        if !found.insert(name.to_string()) {
+            return Ok(());
+        }
+        for field in self.fields(name)? {
+            let base = field.kind.split('[').next().unwrap_or_default();
+            if self.types.contains_key(base) {
+                self.collect_dependencies(base, found)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn hash_struct(&self, name: &str, value: &Value) -> Result<[u8; 32]> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| anyhow!("Value of struct {} must be an object", name))?;
+        let mut encoded = keccak256(self.encode_type(name)?.as_bytes()).to_vec();
+        for field in self.fields(name)? {
+            let value = object
+                .get(&field.name)
+                .ok_or_else(|| anyhow!("Missing field {} of {}", field.name, name))?;
+            encoded.extend_from_slice(&self.encode_value(&field.kind, value)?);
+        }
+        Ok(keccak256(&encoded))
+    }
+
+    fn encode_value(&self, kind: &str, value: &Value) -> Result<[u8; 32]> {
+        if let Some(element) = kind.strip_suffix(']') {
+            let (element, size) = element
+                .rsplit_once('[')
+                .ok_or_else(|| anyhow!("Invalid array type {}", kind))?;
+            let items = value.as_array().ok_or_else(|| anyhow!("Expected an array"))?;
+            if !size.is_empty() && size.parse::<usize>()? != items.len() {
+                bail!("Array of type {} has {} elements", kind, items.len());
+            }
+            let mut encoded = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                encoded.extend_from_slice(&self.encode_value(element, item)?);
+            }
+            return Ok(keccak256(&encoded));
+        }
+        if self.types.contains_key(kind) {
+            return self.hash_struct(kind, value);
+        }
+
+        let mut word = [0u8; 32];
+        match kind {
+            "string" => {
+                let string = value.as_str().ok_or_else(|| anyhow!("Expected a string"))?;
+                return Ok(keccak256(string.as_bytes()));
+            }
+            "bytes" => {
+                return Ok(keccak256(&json_bytes(value)?));
+            }
+            "bool" => {
+                word[31] = value.as_bool().ok_or_else(|| anyhow!("Expected a boolean"))? as u8;
+            }
+            "address" => word[12..].copy_from_slice(&json_address(value)?),
+            _ if kind.starts_with("bytes") => {
+                let size: usize = kind[5..].parse()?;
+                let bytes = json_bytes(value)?;
+                if size == 0 || size > 32 || bytes.len() != size {
+                    bail!("Invalid value of type {}", kind);
+                }
+                word[..size].copy_from_slice(&bytes);
+            }
+            _ if kind.starts_with("uint") || kind.starts_with("int") => {
+                let signed = kind.starts_with("int");
+                let bits: usize = kind
+                    .trim_start_matches('u')[3..]
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid integer type {}", kind))?;
+                if bits == 0 || bits > 256 || bits % 8 != 0 {
+                    bail!("Invalid integer type {}", kind);
+                }
+                let mut number = if signed {
+                    json_int(value, bits)?
+                } else {
+                    json_uint(value, bits)?
+                };
+                if number < BigInt::zero() {
+                    number = number + BigInt::from(2).pow(256);
+                }
+                let bytes = number.to_bytes();
+                word[32 - bytes.len()..].copy_from_slice(&bytes);
+            }
+            _ => bail!("Unsupported EIP-712 type {}", kind),
+        }
+        Ok(word)
+    }
+}
+
+/// Number given as a JSON number, a decimal string or a 0x prefixed hex string
+fn json_number(value: &Value) -> Result<BigInt> {
+    let number = match value {
+        Value::Number(number) => BigInt::from_str_radix(&number.to_string(), 10),
+        Value::String(s) =>
+            match s.strip_prefix("0x") {
+                Some(hex) => BigInt::from_str_radix(hex, 16),
+                None => BigInt::from_str_radix(s, 10),
+            }
+        _ => bail!("Expected a number"),
+    };
+    number.map_err(|_| anyhow!("Invalid number {}", value))
+}
+
+fn json_uint(value: &Value, bits: usize) -> Result<BigInt> {
+    let number = json_number(value)?;
+    if number < BigInt::zero() || number.bit_length() > bits {
+        bail!("{} is out of the range of uint{}", value, bits);
+    }
+    Ok(number)
+}
+
+fn json_int(value: &Value, bits: usize) -> Result<BigInt> {
+    let number = json_number(value)?;
+    let bound = BigInt::from(2).pow((bits - 1) as u32);
+    if number >= bound || number < BigInt::zero() - &bound {
+        bail!("{} is out of the range of int{}", value, bits);
+    }
+    Ok(number)
+}
+
+fn json_bytes(value: &Value) -> Result<Vec<u8>> {
+    let string = value.as_str().ok_or_else(|| anyhow!("Expected hex encoded bytes"))?;
+    let hex = string.strip_prefix("0x").ok_or_else(|| anyhow!("Bytes must be 0x prefixed"))?;
+    Ok(hex::decode(hex)?)
+}
+
+fn json_address(value: &Value) -> Result<[u8; 20]> {
+    json_bytes(value)?
+        .try_into()
+        .map_err(|_| anyhow!("Addresses must be 20 bytes long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eip155_transaction_digest_and_fields() {
+        // Example transaction of EIP-155
+        let payload = hex::decode(
+            "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080018080"
+        ).unwrap();
+        let decoded = decode_payload(PayloadType::EvmLegacyTx, &payload).unwrap();
+        assert_eq!(
+            hex::encode(decoded.digest),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        let intent = decoded.intent();
+        assert_eq!(intent.destinations, vec!["0x3535353535353535353535353535353535353535"]);
+        assert_eq!(intent.transfers[0].amount, 1_000_000_000_000_000_000);
+
+        let mut trailing = payload.clone();
+        trailing.push(0);
+        assert!(decode_payload(PayloadType::EvmLegacyTx, &trailing).is_err());
+    }
+
+    #[test]
+    fn eip712_mail_example_digest() {
+        // Example of the EIP-712 specification
+        let typed_data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "string" },
+                    { "name": "wallet", "type": "address" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+                "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+                "contents": "Hello, Bob!"
+            }
+        }"#;
+        let decoded = decode_payload(PayloadType::Eip712, typed_data.as_bytes()).unwrap();
+        assert_eq!(
+            hex::encode(decoded.digest),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+    }
+}
@@ -1,7 +1,17 @@
+pub mod derivation;
+pub mod encoding;
+pub mod evm;
 pub mod orchestrate;
 pub mod session;
 
 use crate::communication::ecdsa::{ HasSenderId, HasTargetId };
+use crate::signing::request_auth::{
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::signing::PayloadType;
 use curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof;
 use curv::elliptic::curves::{ Point, Scalar, Secp256k1 };
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2020::party_i::{
@@ -18,6 +28,43 @@ pub struct NewSignSession {
     pub session_id: String,
     pub key_id: String,
     pub message: Vec<u8>,
+    /// Messages of a batch, each signed in parallel within the session
+    #[serde(default)]
+    pub messages: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub presignature_id: Option<String>,
+    /// BIP32 path of the non-hardened child key to sign with
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+    /// Set when the messages are digests this node computed from payloads of this type, which
+    /// are then signed instead of the messages relayed by the coordinator
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct NewPresignMessage {
+    pub session_id: String,
+    pub key_id: String,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+}
+
+impl AuthenticatedRequest for NewPresignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("ecdsa_presign")
+            .str("session_id", &self.session_id)
+            .str("key_id", &self.key_id)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -25,18 +72,52 @@ pub struct NewSignMessage {
     pub session_id: String,
     pub key_id: String,
     pub message: Vec<u8>,
-    pub client_e2e_public_key: String,
-    pub encrypted_signing_key: String,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
     pub is_transfer_tx: Option<bool>,
-    pub timestamp: Option<String>,
-    pub message_hmac: Option<String>,
-    pub email: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub presignature_id: Option<String>,
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+    /// Format of `message` or `messages` when they are payloads to decode rather than digests
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+}
+
+impl AuthenticatedRequest for NewSignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let encoder = CanonicalEncoder::new("ecdsa")
+            .str("session_id", &self.session_id)
+            .str("key_id", &self.key_id)
+            .bytes("message", &self.message)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_bool("is_transfer_tx", self.is_transfer_tx)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .list("messages", &self.messages)
+            .opt_str("presignature_id", self.presignature_id.as_deref())
+            .opt_str("derivation_path", self.derivation_path.as_deref());
+        // Appended only when set, keeping the encoding of requests without one unchanged
+        match self.payload_type {
+            Some(payload_type) => encoder.str("payload_type", payload_type.as_str()).finish(),
+            None => encoder.finish(),
+        }
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct JoinSignSessionResponse {
     pub id_in_session: usize,
     pub message: Vec<u8>,
+    #[serde(default)]
+    pub messages: Vec<Vec<u8>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -49,6 +130,18 @@ pub struct SigningResult {
     pub r: String,
     pub s: String,
     pub recid: u8,
+    /// Signature in the format requested with the signing command
+    #[serde(default)]
+    pub encoded: Option<String>,
+    /// EIP-155 `v` of a compact signature requested with a chain id
+    #[serde(default)]
+    pub v: Option<u64>,
+}
+
+#[derive(Deserialize, PartialEq, Serialize, Clone, Debug)]
+pub struct PresignResult {
+    pub presignature_id: String,
+    pub r: String,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -1,7 +1,24 @@
 use crate::command::MsgContext;
-use crate::signing::ecdsa::{ JoinSignSessionResponse, NewSignSession, SigningResult };
-use crate::signing::{ SigningCommand, SigningResponse };
-use anyhow::{ bail, Context, Result };
+use crate::signing::ecdsa::derivation::{ DerivationPath, ExtendedPublicKey };
+use crate::signing::ecdsa::encoding::{ encode_into, SignatureFormat };
+use crate::signing::ecdsa::{
+    JoinSignSessionResponse,
+    NewSignSession,
+    PresignResult,
+    SigningResult,
+};
+use crate::signing::{
+    merge_batch_results,
+    DerivePublicKeyCommand,
+    DerivedPublicKey,
+    PresignCommand,
+    PresignResponse,
+    SigningCommand,
+    SigningResponse,
+};
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
+use shared::key_info::Key;
 use tracing::{ error, info, instrument };
 
 #[instrument(skip_all)]
@@ -14,10 +31,27 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
     let key_id = cmd.key_id;
 
     let party_count = party_nodes.len();
-    if party_count < 3 {
-        let msg = "Not enough nodes in party";
-        error!("{}", msg);
-        bail!(msg);
+    check_party_count(&key_id, party_count)?;
+
+    let is_batch = !cmd.msgs.is_empty();
+    if is_batch && cmd.presignature_id.is_some() {
+        bail!("Presignatures can't be used to sign a batch of messages");
+    }
+
+    if cmd.taproot.is_some() {
+        bail!("Taproot tweaks are only supported for Schnorr signatures");
+    }
+
+    if let Some(format) = &cmd.signature_format {
+        format.validate()?;
+    }
+
+    if let Some(derivation_path) = &cmd.derivation_path {
+        if cmd.presignature_id.is_some() {
+            bail!("Presignatures can't be used to sign with a derived key");
+        }
+        let child = derive_child_key(&key_id, derivation_path)?;
+        info!("Signing with child key {} at {}", child.public_key_hex(), derivation_path);
     }
 
     let join_key = format!("network.gridlock.nodes.keySign.session.{session_id}.join");
@@ -31,6 +65,10 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
             session_id: session_id.clone(),
             key_id,
             message: cmd.msg.clone(),
+            messages: cmd.msgs.clone(),
+            presignature_id: cmd.presignature_id.clone(),
+            derivation_path: cmd.derivation_path.clone(),
+            payload_type: None,
         })
     )?;
     for node_id in party_nodes.iter() {
@@ -47,6 +85,7 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
                     &(JoinSignSessionResponse {
                         id_in_session: i,
                         message: cmd.msg.clone(),
+                        messages: cmd.msgs.clone(),
                     })
                 )?
             )
@@ -71,6 +110,159 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
 
     info!("Signature result received");
 
-    let sig = serde_json::from_slice::<SigningResult>(&res_vec[0].data)?;
+    if is_batch {
+        let party_results = res_vec
+            .iter()
+            .map(|res| serde_json::from_slice::<Vec<SigningResponse>>(&res.data))
+            .collect::<Result<Vec<_>, _>>()?;
+        let merged = merge_batch_results(party_results, cmd.msgs.len())?;
+        return match (merged, cmd.signature_format) {
+            (SigningResponse::Batch(items), Some(format)) =>
+                Ok(
+                    SigningResponse::Batch(
+                        items
+                            .into_iter()
+                            .map(|item| encode_response(item, format))
+                            .collect::<Result<_>>()?
+                    )
+                ),
+            (merged, _) => Ok(merged),
+        };
+    }
+
+    let mut sig = serde_json::from_slice::<SigningResult>(&res_vec[0].data)?;
+    if let Some(format) = cmd.signature_format {
+        encode_into(&mut sig, format)?;
+    }
     Ok(SigningResponse::ECDSA(sig))
 }
+
+fn encode_response(response: SigningResponse, format: SignatureFormat) -> Result<SigningResponse> {
+    match response {
+        SigningResponse::ECDSA(mut sig) => {
+            encode_into(&mut sig, format)?;
+            Ok(SigningResponse::ECDSA(sig))
+        }
+        other => Ok(other),
+    }
+}
+
+#[instrument(skip_all)]
+pub fn orchestrate_presign(cmd: PresignCommand, ctx: MsgContext) -> Result<PresignResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+
+    let party_nodes = cmd.party_nodes;
+    let key_id = cmd.key_id;
+
+    let party_count = party_nodes.len();
+    check_party_count(&key_id, party_count)?;
+
+    // Guardians authorize the presignature by the client request relayed to them
+    if cmd.requests.len() != party_count {
+        bail!("Expected a client request for each of the {} party nodes", party_count);
+    }
+    for (node_id, request) in party_nodes.iter().zip(cmd.requests.iter()) {
+        if request.key_id != key_id || request.session_id != session_id {
+            bail!("Client request for node {} doesn't match the presign command", node_id);
+        }
+    }
+
+    let join_key = format!("network.gridlock.nodes.keySign.session.{session_id}.join");
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!("network.gridlock.nodes.keySign.session.{}.result", &session_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    for (node_id, request) in party_nodes.iter().zip(cmd.requests.iter()) {
+        let key_presign_key = format!("network.gridlock.nodes.keyPresign.new.{node_id}");
+        nc.publish(&key_presign_key, &serde_json::to_string(request)?)?;
+    }
+
+    for i in 0..party_count {
+        let next = join_sub.next().context("Get next join message")?;
+
+        next
+            .respond(
+                &serde_json::to_string(
+                    &(JoinSignSessionResponse {
+                        id_in_session: i,
+                        message: Vec::new(),
+                        messages: Vec::new(),
+                    })
+                )?
+            )
+            .context("Respond to join message for every party")?;
+
+        nc.flush().context("Flush nats connection")?;
+    }
+
+    info!("Parties joined to ecdsa presigning");
+
+    nc.publish(
+        &format!("network.gridlock.nodes.keySign.session.{session_id}.start"),
+        serde_json::to_string(&party_count).unwrap()
+    )?;
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub.next().context("Presign result received from every party")?;
+        results.push(serde_json::from_slice::<PresignResult>(&res.data)?);
+    }
+
+    if results.iter().any(|result| result != &results[0]) {
+        bail!("Parties do not agree on the presignature");
+    }
+    info!("Presign result received");
+
+    Ok(PresignResponse {
+        presignature_id: session_id,
+        r: results[0].r.clone(),
+    })
+}
+
+#[instrument(skip_all)]
+pub fn derive_public_key(cmd: DerivePublicKeyCommand) -> Result<DerivedPublicKey> {
+    let child = derive_child_key(&cmd.key_id, &cmd.derivation_path)?;
+    Ok(DerivedPublicKey {
+        public_key: child.public_key_hex(),
+        chain_code: hex::encode(child.chain_code),
+        xpub: child.to_xpub(),
+    })
+}
+
+/// Derives a child of the public key in the key info, no keyshares are needed for that
+pub(crate) fn derive_child_key(key_id: &str, derivation_path: &str) -> Result<ExtendedPublicKey> {
+    let key_info = KeyInfoStore::get_key_info(key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", key_id)
+    )?;
+    let (y_sum, chain_code) = match &key_info.kind {
+        Key::ECDSA { y_sum, chain_code } => (y_sum, chain_code),
+        _ => bail!("Key derivation is only supported for ECDSA keys"),
+    };
+    let chain_code = chain_code
+        .as_ref()
+        .ok_or_else(|| anyhow!("Key {} was created without a chain code", key_id))?;
+
+    let path = derivation_path.parse::<DerivationPath>()?;
+    let (child, _) = ExtendedPublicKey::from_sum(y_sum, chain_code)?.derive(&path)?;
+    Ok(child)
+}
+
+/// Every party that joins takes part in signing, so exactly threshold + 1 are needed
+fn check_party_count(key_id: &str, party_count: usize) -> Result<()> {
+    let threshold = KeyInfoStore::get_key_info(key_id)
+        .map_err(|_| anyhow!("Key info is not found - key_id: {}", key_id))?.threshold;
+
+    if party_count != threshold + 1 {
+        let msg = format!(
+            "Signing needs {} nodes in party, but {} were given",
+            threshold + 1,
+            party_count
+        );
+        error!("{}", msg);
+        bail!(msg);
+    }
+    Ok(())
+}
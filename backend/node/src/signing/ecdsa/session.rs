@@ -1,12 +1,20 @@
 use crate::communication::ecdsa::{ collect_messages_ordered, collect_messages_p2p, JoinMessage };
 use crate::signing::ecdsa;
+use crate::signing::ecdsa::encoding::normalize_low_s;
+use crate::signing::ecdsa::evm;
+use crate::signing::ecdsa::derivation::{ apply_tweak, DerivationPath, ExtendedPublicKey };
 use crate::signing::ecdsa::{
     JoinSignSessionErrorResponse,
     JoinSignSessionResponse,
+    NewPresignMessage,
     NewSignSession,
     NewSignMessage,
+    PresignResult,
     SigningResult,
 };
+use crate::signing::policy::{ enforce_policy, raw_intents };
+use crate::signing::request_auth::authorize_session_request;
+use crate::signing::SigningResponse;
 use crate::storage::{ KeyshareAccessor, ECDSA };
 use crate::App;
 use anyhow::{ anyhow, bail };
@@ -34,21 +42,14 @@ use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2020::party_i::{
 use multi_party_ecdsa::utilities::mta::{ MessageA, MessageB };
 use multi_party_ecdsa::utilities::zk_pdl_with_slack::PDLwSlackProof;
 use paillier::EncryptionKey;
+use serde::{ Deserialize, Serialize };
 use sha2::Sha256;
 use std::any::type_name;
 use std::thread;
 use std::time::Duration;
 use tracing::{ error, info, instrument };
-use chrono::{ DateTime, Utc };
-use hmac::{ Hmac, Mac, NewMac };
-use base64;
-use crate::node::NodeIdentity;
 use crate::storage::fs::WriteOpts;
 use crate::storage::key_metadata_store::KeyMetadataStore;
-use crate::auth::e2e_decrypt;
-
-const PARTIES: usize = 5;
-const THRESHOLD: usize = 3;
 
 const PHASES: usize = 8;
 const P2P_PHASE: usize = 2;
@@ -71,10 +72,14 @@ fn signature_recid_to_signing_result(sig: &SignatureRecid) -> SigningResult {
         format!("{:0>width$}", x.to_bigint().to_str_radix(16), width = 64usize)
     };
 
+    // Guardians only release low-S signatures so that they can't be made malleable
+    let (s, recid) = normalize_low_s(&sig.s, sig.recid);
     SigningResult {
         r: fe_to_string(&sig.r),
-        s: fe_to_string(&sig.s),
-        recid: sig.recid,
+        s: fe_to_string(&s),
+        recid,
+        encoded: None,
+        v: None,
     }
 }
 
@@ -101,6 +106,7 @@ impl SignPhase {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 struct Phase1Data {
     pub decommit: SignDecommitPhase1,
     pub bc1_vec: Vec<SignBroadcastPhase1>,
@@ -112,6 +118,7 @@ struct Phase1Data {
     pub xi_com_vec: Vec<Point<Secp256k1>>,
 }
 
+#[derive(Serialize, Deserialize)]
 struct Phase2Data {
     pub alpha_vec: Vec<Scalar<Secp256k1>>,
     pub beta_vec: Vec<Scalar<Secp256k1>>,
@@ -126,6 +133,7 @@ struct Phase2Data {
     pub ni_vec: Vec<Scalar<Secp256k1>>,
 }
 
+#[derive(Serialize, Deserialize)]
 struct Phase3Data {
     pub delta_inv: Scalar<Secp256k1>,
     pub delta_vec: Vec<Scalar<Secp256k1>>,
@@ -135,6 +143,7 @@ struct Phase3Data {
     pub T_vec: Vec<Point<Secp256k1>>,
 }
 
+#[derive(Serialize, Deserialize)]
 struct Phase4Data {
     pub decommit_vec: Vec<SignDecommitPhase1>,
     pub R: Point<Secp256k1>,
@@ -154,6 +163,44 @@ struct Phase7Data {
     pub message_bn: BigInt,
 }
 
+/// State of phases 0-4, which don't depend on the message, kept by every signer for a later
+/// signature
+#[derive(Serialize, Deserialize)]
+struct Presignature {
+    pub id_in_session: usize,
+    pub signers: Vec<usize>,
+    pub p1d: Phase1Data,
+    pub p2d: Phase2Data,
+    pub p3d: Phase3Data,
+    pub p4d: Phase4Data,
+}
+
+impl Presignature {
+    fn metadata_type(presignature_id: &str) -> String {
+        format!("presignature_{}", presignature_id)
+    }
+
+    fn save(&self, key_id: &str, presignature_id: &str, email: &str) -> anyhow::Result<()> {
+        KeyMetadataStore::save(
+            &serde_json::to_string(self)?,
+            key_id,
+            &Self::metadata_type(presignature_id),
+            email,
+            &WriteOpts::CreateNewOnly
+        )
+    }
+
+    /// Reads a presignature and removes it from storage, so that it can't be used more than once
+    fn take(key_id: &str, presignature_id: &str, email: &str) -> anyhow::Result<Self> {
+        let metadata_type = Self::metadata_type(presignature_id);
+        // Only the session that manages to move the presignature away may use it
+        let presignature = KeyMetadataStore::take(key_id, &metadata_type, email).map_err(|_| {
+            anyhow!("Presignature {} is not available for key {}", presignature_id, key_id)
+        })?;
+        Ok(serde_json::from_str(&presignature)?)
+    }
+}
+
 impl SignSession {
     #[instrument(skip_all)]
     fn session_join(
@@ -176,12 +223,25 @@ impl SignSession {
         )?;
 
         match serde_json::from_slice::<JoinSignSessionResponse>(&response_json.data) {
-            Ok(ok) => {
+            Ok(mut ok) => {
                 info!("OK RESPONSE");
-                if ok.message.len() > 32 {
+                // Only the messages of the authenticated request are signed, digests of decoded
+                // payloads are computed by this node and raw messages have to match the request
+                let relayed_other_messages =
+                    ok.message != sess.message || ok.messages != sess.messages;
+                if sess.payload_type.is_none() && relayed_other_messages {
+                    bail!("Join response does not carry the messages of the signing request");
+                }
+                ok.message = sess.message.clone();
+                ok.messages = sess.messages.clone();
+                let oversized = ok.messages
+                    .iter()
+                    .chain([&ok.message])
+                    .find(|message| message.len() > 32);
+                if let Some(message) = oversized {
                     let err_msg = format!(
                         "message has size more than 32 bytes! message: {:?}",
-                        ok.message
+                        message
                     );
                     error!("{}", err_msg);
                     bail!("{}", err_msg);
@@ -210,22 +270,38 @@ impl SignSession {
         }
     }
 
-    #[instrument(skip_all)]
-    pub fn new(
-        connection: nats::Connection,
-        session: NewSignSession,
-        email: Option<String>
-    ) -> anyhow::Result<Self> {
+    fn load_keyshare(session: &NewSignSession, email: &Option<String>) -> anyhow::Result<ECDSA> {
         // Use email-aware keyshare accessor if email is provided
-        let keyshare = (
+        let mut keyshare = (
             if let Some(email_str) = email {
-                KeyshareAccessor::<ECDSA>::read_only_with_email(&session.key_id, &email_str)?
+                KeyshareAccessor::<ECDSA>::read_only_with_email(&session.key_id, email_str)?
             } else {
                 KeyshareAccessor::<ECDSA>::read_only(&session.key_id)?
             }
         ).key;
 
-        let start_phase = SignPhase::new(&connection, &session, "start")?;
+        // The child key is only ever held in memory, for the length of this session
+        if let Some(derivation_path) = &session.derivation_path {
+            if session.presignature_id.is_some() {
+                bail!("Presignatures can't be used to sign with a derived key");
+            }
+            let path = derivation_path.parse::<DerivationPath>()?;
+            let (_, tweak) = ExtendedPublicKey::from_keyshare(&keyshare)?.derive(&path)?;
+            apply_tweak(&mut keyshare, &tweak)?;
+            info!("Derived child key for path {}", derivation_path);
+        }
+        Ok(keyshare)
+    }
+
+    /// Subscribes to the start subject, the broadcast phases and the p2p channels of every
+    /// keyshare holder, with phase subjects prefixed to tell messages of a batch apart
+    fn subscribe_phases(
+        connection: &nats::Connection,
+        session: &NewSignSession,
+        prefix: &str,
+        keyshare: &ECDSA
+    ) -> anyhow::Result<(SignPhase, Vec<SignPhase>, Vec<SignPhase>)> {
+        let start_phase = SignPhase::new(connection, session, "start")?;
 
         let mut phase_vec: Vec<SignPhase> = Vec::with_capacity(PHASES);
         for i in 0..PHASES {
@@ -234,28 +310,114 @@ impl SignSession {
                 continue;
             }
 
-            let phase = SignPhase::new(&connection, &session, &format!("phase{}", i))?;
+            let phase = SignPhase::new(connection, session, &format!("{}phase{}", prefix, i))?;
             phase_vec.push(phase);
         }
 
-        let mut phase2_p2p_vec: Vec<SignPhase> = Vec::with_capacity(PARTIES);
-        for i in 0..PARTIES {
-            let phase = SignPhase::new(&connection, &session, &format!("phase2.to{}", i))?;
+        // Session ids are assigned per signer, so every keyshare holder may need a p2p channel
+        let party_count = keyshare.vss_scheme_vec.len();
+        let mut phase2_p2p_vec: Vec<SignPhase> = Vec::with_capacity(party_count);
+        for i in 0..party_count {
+            let phase = SignPhase::new(
+                connection,
+                session,
+                &format!("{}phase2.to{}", prefix, i)
+            )?;
             phase2_p2p_vec.push(phase);
         }
 
+        Ok((start_phase, phase_vec, phase2_p2p_vec))
+    }
+
+    #[instrument(skip_all)]
+    pub fn new(
+        connection: nats::Connection,
+        session: NewSignSession,
+        email: Option<String>
+    ) -> anyhow::Result<Self> {
+        let keyshare = Self::load_keyshare(&session, &email)?;
+
+        let (start_phase, mut phase_vec, mut phase2_p2p_vec) = Self::subscribe_phases(
+            &connection,
+            &session,
+            "",
+            &keyshare
+        )?;
+
         let party_info = Self::session_join(&connection, &session)?;
         phase_vec.insert(P2P_PHASE, phase2_p2p_vec.remove(party_info.id_in_session));
         Ok(Self {
             connection,
             start_phase,
             phases: phase_vec,
+            prefix: String::new(),
             keyshare,
             party_info,
             session,
+            email: email.unwrap_or_default(),
         })
     }
 
+    /// Creates a signing session for every message of a batch, all joined with a single request
+    #[instrument(skip_all)]
+    pub fn new_batch(
+        connection: nats::Connection,
+        session: NewSignSession,
+        email: Option<String>
+    ) -> anyhow::Result<Vec<Self>> {
+        if session.presignature_id.is_some() {
+            bail!("Presignatures can't be used to sign a batch of messages");
+        }
+        let keyshare = Self::load_keyshare(&session, &email)?;
+
+        let mut batch_phases = Vec::with_capacity(session.messages.len());
+        for index in 0..session.messages.len() {
+            let prefix = format!("m{}.", index);
+            let phases = Self::subscribe_phases(&connection, &session, &prefix, &keyshare)?;
+            batch_phases.push((prefix, phases));
+        }
+
+        let party_info = Self::session_join(&connection, &session)?;
+        if party_info.messages.len() != batch_phases.len() {
+            bail!(
+                "Expected a batch of {} messages, but {} were given when joining",
+                batch_phases.len(),
+                party_info.messages.len()
+            );
+        }
+
+        let id_in_session = party_info.id_in_session;
+        let email = email.unwrap_or_default();
+        Ok(
+            batch_phases
+                .into_iter()
+                .zip(party_info.messages)
+                .map(|((prefix, (start_phase, mut phase_vec, mut phase2_p2p_vec)), message)| {
+                    phase_vec.insert(P2P_PHASE, phase2_p2p_vec.remove(id_in_session));
+                    Self {
+                        connection: connection.clone(),
+                        start_phase,
+                        phases: phase_vec,
+                        prefix,
+                        keyshare: keyshare.clone(),
+                        party_info: JoinSignSessionResponse {
+                            id_in_session,
+                            message,
+                            messages: Vec::new(),
+                        },
+                        session: session.clone(),
+                        email: email.clone(),
+                    }
+                })
+                .collect()
+        )
+    }
+
+    /// Number of parties taking part in signing, one more than the stored keyshare threshold
+    fn signer_count(&self) -> usize {
+        self.keyshare.threshold + 1
+    }
+
     fn wait_for_start_message(&self) {
         self.start_phase.sub.next_timeout(Duration::from_secs(10)).unwrap();
     }
@@ -270,12 +432,15 @@ impl SignSession {
         info!("publishing on subject {}", &self.phases[0].topic);
         self.connection.publish(&self.phases[0].topic, &json).unwrap();
 
-        // Shareholder IDs generated during keygen are in 1..=PARTIES range,
-        // but most of the signing code expects them to be in 0..PARTIES range,
+        // Shareholder IDs generated during keygen are in 1..=party_count range,
+        // but most of the signing code expects them to be in 0..party_count range,
         // hence the -1 in the lambda.
         info!("collecting Phase0Identity");
         Ok(
-            collect_messages_ordered::<ecdsa::Phase0Identity>(&self.phases[0].sub, THRESHOLD)?
+            collect_messages_ordered::<ecdsa::Phase0Identity>(
+                &self.phases[0].sub,
+                self.signer_count()
+            )?
                 .into_iter()
                 .map(|p0i| p0i.shareholder_id - 1)
                 .collect()
@@ -303,7 +468,7 @@ impl SignSession {
 
         for p1c in collect_messages_ordered::<ecdsa::Phase1Commitment>(
             &self.phases[1].sub,
-            THRESHOLD
+            self.signer_count()
         )? {
             com_vec.push(p1c.commitment);
             m_vec.push(p1c.message);
@@ -363,7 +528,7 @@ impl SignSession {
         m_b_vec: &[MessageB]
     ) -> anyhow::Result<(Vec<MessageB>, Vec<MessageB>)> {
         let mut index: usize = 0;
-        for party_id in 0..THRESHOLD {
+        for party_id in 0..self.signer_count() {
             if party_id == self.party_info.id_in_session {
                 continue;
             }
@@ -376,7 +541,10 @@ impl SignSession {
             };
             let json = serde_json::to_string(&mesg).unwrap();
 
-            let subject = format_session_subject(&self.session, &format!("phase2.to{}", party_id));
+            let subject = format_session_subject(
+                &self.session,
+                &format!("{}phase2.to{}", self.prefix, party_id)
+            );
             info!("publish on subject {}", &subject);
             self.connection.publish(&subject, &json).unwrap();
 
@@ -388,7 +556,7 @@ impl SignSession {
         info!("collect_messages_p2p Phase2Gamma");
         for p2g in collect_messages_p2p::<ecdsa::Phase2Gamma>(
             &self.phases[2].sub,
-            THRESHOLD,
+            self.signer_count(),
             self.party_info.id_in_session
         )? {
             gamma_vec.push(p2g.gamma);
@@ -406,7 +574,7 @@ impl SignSession {
         let mut beta_tag_vec = Vec::new();
         let mut ni_vec = Vec::new();
 
-        for (i, &signer) in signers_vec.iter().enumerate().take(THRESHOLD) {
+        for (i, &signer) in signers_vec.iter().enumerate().take(self.signer_count()) {
             if i != self.party_info.id_in_session {
                 let (m_b_gamma, beta_gamma, beta_randomness, beta_tag) = match
                     MessageB::b(
@@ -458,7 +626,7 @@ impl SignSession {
         let mut miu_bigint_vec = Vec::new();
         let mut j = 0;
 
-        for i in 0..THRESHOLD {
+        for i in 0..self.signer_count() {
             if i != self.party_info.id_in_session {
                 let m_b = m_b_gamma_rec_vec[j].clone();
 
@@ -522,7 +690,7 @@ impl SignSession {
         info!("collect Phase3Broadcast");
         for p3b in collect_messages_ordered::<ecdsa::Phase3Broadcast>(
             &self.phases[3].sub,
-            THRESHOLD
+            self.signer_count()
         )? {
             delta_vec.push(p3b.delta);
             t_vec.push(p3b.t);
@@ -563,7 +731,10 @@ impl SignSession {
         self.connection.publish(&self.phases[4].topic, &json).unwrap();
         info!("collect Phase4Decommit");
         Ok(
-            collect_messages_ordered::<ecdsa::Phase4Decommit>(&self.phases[4].sub, THRESHOLD)?
+            collect_messages_ordered::<ecdsa::Phase4Decommit>(
+                &self.phases[4].sub,
+                self.signer_count()
+            )?
                 .into_iter()
                 .map(|p4d| p4d.decommit)
                 .collect()
@@ -610,7 +781,10 @@ impl SignSession {
         info!("collect Phase5RDash");
 
         Ok(
-            collect_messages_ordered::<ecdsa::Phase5RDash>(&self.phases[5].sub, THRESHOLD)?
+            collect_messages_ordered::<ecdsa::Phase5RDash>(
+                &self.phases[5].sub,
+                self.signer_count()
+            )?
                 .into_iter()
                 .map(|p5rd| p5rd.r_dash)
                 .collect()
@@ -623,7 +797,7 @@ impl SignSession {
         // compose beta tag vector:
         let mut beta_tag_vec_to_test = Vec::new();
         let mut beta_randomness_vec_to_test = Vec::new();
-        for j in 0..THRESHOLD - 1 {
+        for j in 0..self.signer_count() - 1 {
             // this code is different from the "simplify to continue" case
             let index = if j < self.party_info.id_in_session + 1 {
                 self.party_info.id_in_session - 1
@@ -654,7 +828,7 @@ impl SignSession {
             .map(|i| p1d.m_a_vec[i].clone())
             .collect::<Vec<MessageA>>();
         // reduce ek vec to only ek of participants :
-        let paillier_key_vector = (0..THRESHOLD)
+        let paillier_key_vector = (0..self.signer_count())
             .map(|k| self.keyshare.paillier_key_vec[k].clone())
             .collect::<Vec<EncryptionKey>>();
 
@@ -686,7 +860,7 @@ impl SignSession {
 
         // phase 5
         let mut phase5_proofs: Vec<PDLwSlackProof> = Vec::new();
-        for i in 0..THRESHOLD {
+        for i in 0..self.signer_count() {
             if i == self.party_info.id_in_session {
                 continue;
             }
@@ -750,7 +924,7 @@ impl SignSession {
         info!("collect Phase6Broadcast");
         for msg in collect_messages_ordered::<ecdsa::Phase6Broadcast>(
             &self.phases[6].sub,
-            THRESHOLD
+            self.signer_count()
         )? {
             S_vec.push(msg.s);
             R_vec.push(msg.r);
@@ -777,7 +951,7 @@ impl SignSession {
         let proof = GlobalStatePhase6::ecddh_proof(&p3d.sigma, &p4d.R, S_i);
 
         let mut miu_randomness_vec = Vec::new();
-        for j in 0..THRESHOLD - 1 {
+        for j in 0..self.signer_count() - 1 {
             let rand = GlobalStatePhase6::extract_paillier_randomness(
                 &p2d.m_b_w_rec_vec[j].c,
                 &self.keyshare.paillier_dk
@@ -801,7 +975,7 @@ impl SignSession {
             .collect::<Vec<MessageA>>();
 
         // reduce ek vec to only ek of participants :
-        let ek_vec = (0..THRESHOLD)
+        let ek_vec = (0..self.signer_count())
             .map(|k| self.keyshare.paillier_key_vec[signers_vec[k]].clone())
             .collect::<Vec<EncryptionKey>>();
 
@@ -867,7 +1041,10 @@ impl SignSession {
         self.connection.publish(&self.phases[7].topic, &json)?;
         info!("collect Phase7Signature");
         Ok(
-            collect_messages_ordered::<ecdsa::Phase7Signature>(&self.phases[7].sub, THRESHOLD)?
+            collect_messages_ordered::<ecdsa::Phase7Signature>(
+                &self.phases[7].sub,
+                self.signer_count()
+            )?
                 .into_iter()
                 .map(|p7s| p7s.signature)
                 .collect()
@@ -919,7 +1096,7 @@ impl SignSession {
         let local_sig_vec = self.phase7_broadcast_signature(&local_sig)?;
 
         // sum the s_i's
-        for i in 0..THRESHOLD {
+        for i in 0..self.signer_count() {
             if i != self.party_info.id_in_session {
                 s_vec.push(local_sig_vec[i].s_i.clone());
             } else {
@@ -984,11 +1161,10 @@ impl SignSession {
     }
 
     #[instrument(skip_all)]
-    fn send_result(&mut self, p7d: &Phase7Data) -> anyhow::Result<()> {
+    fn send_result(&self, mesg: &SigningResult) -> anyhow::Result<()> {
         let subject = format_session_subject(&self.session, "result");
-        let mesg = signature_recid_to_signing_result(&p7d.sig);
 
-        let json = serde_json::to_string(&mesg)?;
+        let json = serde_json::to_string(mesg)?;
         self.connection.publish(&subject, &json)?;
 
         info!("Signing session result sent by node #{}!", self.party_info.id_in_session);
@@ -996,9 +1172,25 @@ impl SignSession {
     }
 
     #[instrument(skip_all)]
-    pub fn sign(&mut self) -> anyhow::Result<()> {
-        info!("waiting for START message from communication-hub");
-        self.wait_for_start_message();
+    fn send_presign_result(&self, presignature: &Presignature) -> anyhow::Result<()> {
+        let subject = format_session_subject(&self.session, "result");
+        let r = presignature.p4d.R
+            .x_coord()
+            .ok_or_else(|| anyhow!("Presignature R is the point at infinity"))?;
+        let mesg = PresignResult {
+            presignature_id: self.session.session_id.clone(),
+            r: format!("{:0>width$}", r.to_str_radix(16), width = 64usize),
+        };
+
+        let json = serde_json::to_string(&mesg)?;
+        self.connection.publish(&subject, &json)?;
+
+        info!("Presign session result sent by node #{}!", self.party_info.id_in_session);
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    fn presign(&self) -> anyhow::Result<Presignature> {
         info!("calling phase 0");
         let signers = self.phase0__exchange_party_ids()?;
         info!("calling phase 1");
@@ -1009,16 +1201,110 @@ impl SignSession {
         let p3d = self.phase3(&p1d, &p2d)?;
         info!("calling phase 4");
         let p4d = self.phase4(&p1d, &p2d, &p3d)?;
+
+        Ok(Presignature {
+            id_in_session: self.party_info.id_in_session,
+            signers,
+            p1d,
+            p2d,
+            p3d,
+            p4d,
+        })
+    }
+
+    #[instrument(skip_all)]
+    pub fn presign_and_save(&mut self) -> anyhow::Result<()> {
+        info!("waiting for START message from communication-hub");
+        self.wait_for_start_message();
+        let presignature = self.presign()?;
+        info!("saving presignature");
+        presignature.save(&self.session.key_id, &self.session.session_id, &self.email)?;
+        info!("send result");
+        self.send_presign_result(&presignature)
+    }
+
+    #[instrument(skip_all)]
+    pub fn sign(&mut self) -> anyhow::Result<()> {
+        let result = self.create_signature()?;
+        info!("send result");
+        self.send_result(&result)
+    }
+
+    /// Signs every message of a batch in its own thread, so a failure only affects its own result
+    #[instrument(skip_all)]
+    pub fn sign_batch(sessions: Vec<Self>) -> anyhow::Result<()> {
+        let (connection, session) = match sessions.first() {
+            Some(first) => (first.connection.clone(), first.session.clone()),
+            None => bail!("Batch has no messages to sign"),
+        };
+
+        let mut handles = Vec::with_capacity(sessions.len());
+        for (index, mut sign_session) in sessions.into_iter().enumerate() {
+            let handle = thread::Builder
+                ::new()
+                .name(format!("sign_session_{}_{}", session.session_id, index))
+                .spawn(move || sign_session.create_signature())?;
+            handles.push(handle);
+        }
+
+        let results = handles
+            .into_iter()
+            .enumerate()
+            .map(|(index, handle)| {
+                match handle.join() {
+                    Ok(Ok(result)) => SigningResponse::ECDSA(result),
+                    Ok(Err(err)) => {
+                        error!("Error in signing message {} of the batch: {}", index, err);
+                        SigningResponse::failed(err.to_string())
+                    }
+                    Err(_) => {
+                        error!("Signing message {} of the batch panicked", index);
+                        SigningResponse::failed("Signing thread panicked".to_string())
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let subject = format_session_subject(&session, "result");
+        connection.publish(&subject, &serde_json::to_string(&results)?)?;
+        info!("Batch signing session result sent");
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    fn create_signature(&mut self) -> anyhow::Result<SigningResult> {
+        // Taken before the session starts, so a presignature is spent even if signing fails
+        let stored_presignature = match &self.session.presignature_id {
+            Some(presignature_id) =>
+                Some(Presignature::take(&self.session.key_id, presignature_id, &self.email)?),
+            None => None,
+        };
+
+        info!("waiting for START message from communication-hub");
+        self.wait_for_start_message();
+        let presignature = match stored_presignature {
+            Some(presignature) => {
+                if presignature.signers.len() != self.signer_count() {
+                    bail!("Presignature was created for a different number of signers");
+                }
+                info!("using presignature, skipping phases 0 to 4");
+                // Later phases are ordered by the ids the signers had when presigning
+                self.party_info.id_in_session = presignature.id_in_session;
+                presignature
+            }
+            None => self.presign()?,
+        };
+        let Presignature { signers, p1d, p2d, p3d, p4d, .. } = &presignature;
+
         info!("calling phase 5");
-        let p5d = self.phase5(&signers, &p1d, &p2d, &p3d, &p4d)?;
+        let p5d = self.phase5(signers, p1d, p2d, p3d, p4d)?;
         info!("calling phase 6");
-        let p6d = self.phase6(&signers, &p1d, &p2d, &p3d, &p4d)?;
+        let p6d = self.phase6(signers, p1d, p2d, p3d, p4d)?;
         info!("calling phase 7");
-        let p7d = self.phase7(&p1d, &p3d, &p4d, &p5d, &p6d)?;
+        let p7d = self.phase7(p1d, p3d, p4d, &p5d, &p6d)?;
         info!("checking signature");
         Self::check_sig(&p7d.sig.r, &p7d.sig.s, &p7d.message_bn, &self.keyshare.y_sum)?;
-        info!("send result");
-        self.send_result(&p7d)
+        Ok(signature_recid_to_signing_result(&p7d.sig))
     }
 }
 
@@ -1030,67 +1316,52 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
             return;
         }
     };
-    // Validate security fields
-    if
-        parsed_message.timestamp.is_none() ||
-        parsed_message.message_hmac.is_none() ||
-        parsed_message.email.is_none()
-    {
-        error!("Missing required security fields: timestamp, message_hmac, or email");
-        return;
-    }
-
-    let node = match NodeIdentity::load() {
-        Ok(node) => node,
-        Err(err) => {
-            error!("Failed to load node identity: {}", err);
-            return;
-        }
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
     };
 
-    let decrypted_signing_key = match
-        e2e_decrypt(
-            &parsed_message.encrypted_signing_key,
-            &node.e2e_private_key,
-            &parsed_message.client_e2e_public_key
-        )
-    {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to decrypt signing key: {}", err);
-            return;
-        }
-    };
-
-    let node_signing_key = match String::from_utf8(decrypted_signing_key) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to convert decrypted signing key to string: {}", err);
-            return;
+    // Decode typed payloads, so that both the digests signed and the policy rely on this node
+    let decoded = match parsed_message.payload_type {
+        Some(payload_type) => {
+            if parsed_message.is_transfer_tx.unwrap_or(false) {
+                error!("Ownership transfers can't be sent as typed payloads");
+                return;
+            }
+            let payloads = if parsed_message.messages.is_empty() {
+                std::slice::from_ref(&parsed_message.message)
+            } else {
+                &parsed_message.messages[..]
+            };
+            let decoded = payloads
+                .iter()
+                .map(|payload| evm::decode_payload(payload_type, payload))
+                .collect::<anyhow::Result<Vec<_>>>();
+            match decoded {
+                Ok(decoded) => {
+                    for payload in &decoded {
+                        info!("Decoded {} payload: {:?}", payload_type.as_str(), payload.details);
+                    }
+                    Some(decoded)
+                }
+                Err(err) => {
+                    error!("Failed to decode {} payload: {}", payload_type.as_str(), err);
+                    return;
+                }
+            }
         }
+        None => None,
     };
 
-    let message_hmac = parsed_message.message_hmac.as_ref().unwrap();
-    let timestamp = parsed_message.timestamp.as_ref().unwrap();
-
-    let email = parsed_message.email.unwrap_or_default();
-
-    // Security verification: HMAC then timestamp
-    if !verify_hmac(message_hmac, timestamp, &email, &node_signing_key) {
-        error!("HMAC verification failed");
-        return;
-    }
-
-    if !verify_timestamp(&parsed_message.key_id, timestamp, &email) {
-        error!("Timestamp verification failed");
-        return;
-    }
-    info!("Timestamp verified");
-
     // Transfer transaction validation
     if parsed_message.is_transfer_tx.unwrap_or(false) {
         info!("Initiating ownership transfer");
 
+        if !parsed_message.messages.is_empty() {
+            error!("Ownership transfers can't be signed in a batch");
+            return;
+        }
+
         let message_str = match String::from_utf8(parsed_message.message.clone()) {
             Ok(s) => s,
             Err(err) => {
@@ -1134,24 +1405,10 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         info!("Successfully removed new_identity_key after ownership verification");
     }
 
-    // Validate access key
-    let saved_access_key = match KeyMetadataStore::get(&parsed_message.key_id, "access", &email) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to load saved access key: {}", err);
-            return;
-        }
-    };
-
-    if node_signing_key != saved_access_key {
-        error!("Access key mismatch: decrypted key does not match saved access key");
-        return;
-    }
-
     // Store the client_e2e_public_key at user level
     if
         let Err(err) = KeyMetadataStore::save_user_level(
-            &parsed_message.client_e2e_public_key,
+            &parsed_message.credentials.client_e2e_public_key,
             "e2e_key",
             &email,
             &WriteOpts::Modify
@@ -1161,10 +1418,39 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         // Continue anyway as this is not critical
     }
 
+    // Enforce the signing policy of the key before joining the session
+    let intents = match &decoded {
+        Some(decoded) => decoded.iter().map(evm::DecodedEvmPayload::intent).collect(),
+        None =>
+            raw_intents(
+                parsed_message.messages.len(),
+                parsed_message.is_transfer_tx.unwrap_or(false)
+            ),
+    };
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &intents) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
+    let (message, messages) = match decoded {
+        Some(decoded) => {
+            let mut digests = decoded.into_iter().map(|payload| payload.digest.to_vec());
+            if parsed_message.messages.is_empty() {
+                (digests.next().unwrap_or_default(), Vec::new())
+            } else {
+                (Vec::new(), digests.collect())
+            }
+        }
+        None => (parsed_message.message, parsed_message.messages),
+    };
     let session = NewSignSession {
         key_id: parsed_message.key_id,
         session_id: parsed_message.session_id,
-        message: parsed_message.message,
+        message,
+        messages,
+        presignature_id: parsed_message.presignature_id,
+        derivation_path: parsed_message.derivation_path,
+        payload_type: parsed_message.payload_type,
     };
 
     // Create a new thread for this signing session
@@ -1178,6 +1464,23 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
             ::new()
             .name(thread_name)
             .spawn(move || {
+                if !session_clone.messages.is_empty() {
+                    let sign_sessions = match
+                        SignSession::new_batch(app_clone.nc, session_clone, Some(email))
+                    {
+                        Ok(ss) => ss,
+                        Err(err) => {
+                            error!("Error creating batch signing session: {}", err);
+                            return;
+                        }
+                    };
+                    match SignSession::sign_batch(sign_sessions) {
+                        Ok(()) => info!("Batch signing completed"),
+                        Err(err) => error!("Error in batch signing: {}", err),
+                    }
+                    return;
+                }
+
                 let mut sign_session = match
                     SignSession::new(app_clone.nc, session_clone, Some(email))
                 {
@@ -1202,85 +1505,70 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
     };
 }
 
-struct SignSession {
-    connection: nats::Connection,
-    start_phase: SignPhase,
-    phases: Vec<SignPhase>,
-    keyshare: ECDSA,
-    party_info: JoinSignSessionResponse,
-    session: NewSignSession,
-}
-
-// Verify that the timestamp is newer than the last one we've seen
-fn verify_timestamp(key_id: &str, new_timestamp: &str, email: &str) -> bool {
-    let timestamp_key = "timestamp";
-    let new_dt = match DateTime::parse_from_rfc3339(new_timestamp) {
-        Ok(dt) => dt.with_timezone(&Utc),
+pub fn handle_new_presign_message(app: &App, message: nats::Message) {
+    let parsed_message = match serde_json::from_slice::<NewPresignMessage>(&message.data[..]) {
+        Ok(parsed) => parsed,
         Err(err) => {
-            error!("Failed to parse timestamp: {}", err);
-            return false;
+            error!("Failed to parse message: {}", err);
+            return;
         }
     };
+    // Presignatures are spent by later signing requests, so creating them is authorized too
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
+    };
 
-    // It's fine if this fails - it just means first tx
-    let previous_timestamp_result = KeyMetadataStore::get(key_id, timestamp_key, email);
+    // Presigning runs without a message, which is only supplied by a later signing session
+    let session = NewSignSession {
+        key_id: parsed_message.key_id,
+        session_id: parsed_message.session_id,
+        message: Vec::new(),
+        messages: Vec::new(),
+        presignature_id: None,
+        derivation_path: None,
+        payload_type: None,
+    };
 
-    match previous_timestamp_result {
-        Ok(prev_timestamp_str) => {
-            let prev_dt = match DateTime::parse_from_rfc3339(&prev_timestamp_str) {
-                Ok(dt) => dt.with_timezone(&Utc),
-                Err(err) => {
-                    error!("Failed to parse stored timestamp: {}", err);
-                    return false;
+    info!("Spawning a thread to handle ECDSA presignature generation");
+    let app_clone = app.clone();
+    let email = Some(email);
+    let session_id = session.session_id.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(format!("presign_session_{}", &session_id))
+            .spawn(move || {
+                let mut presign_session = match SignSession::new(app_clone.nc, session, email) {
+                    Ok(ss) => ss,
+                    Err(err) => {
+                        error!("Error creating presign session: {}", err);
+                        return;
+                    }
+                };
+                match presign_session.presign_and_save() {
+                    Ok(()) => {
+                        info!("Presigning completed successfully");
+                    }
+                    Err(err) => {
+                        error!("Error in presigning: {}", err);
+                    }
                 }
-            };
-
-            if new_dt <= prev_dt {
-                error!(
-                    "Timestamp validation failed: provided timestamp ({}) is not newer than stored timestamp ({})",
-                    new_timestamp,
-                    prev_timestamp_str
-                );
-                return false;
-            }
-        }
-        Err(err) => {
-            info!("No previous timestamp found, likely first transaction: {}", err);
-        }
-    }
-
-    match KeyMetadataStore::save(new_timestamp, key_id, timestamp_key, email, &WriteOpts::Modify) {
-        Ok(_) => true,
-        Err(err) => {
-            error!("Failed to save new timestamp: {}", err);
-            false
-        }
-    }
-}
-
-// HMAC verification using SHA256(timestamp + email) with signing key
-fn verify_hmac(provided_hmac: &str, timestamp: &str, email: &str, signing_key: &str) -> bool {
-    type HmacSha256 = Hmac<Sha256>;
-    let message_input = format!("{}{}", timestamp, email);
-
-    let mut mac = match HmacSha256::new_from_slice(signing_key.as_bytes()) {
-        Ok(m) => m,
-        Err(err) => {
-            error!("Failed to create HMAC instance: {}", err);
-            return false;
-        }
+            })
+    {
+        Ok(_) => (),
+        Err(err) => error!("Failed to spawn thread for presign session {}: {}", session_id, err),
     };
+}
 
-    mac.update(message_input.as_bytes());
-    let calculated_hmac_bytes = mac.finalize().into_bytes();
-
-    // Use base64 encoding instead of hex to match TypeScript implementation
-    let calculated_hmac = base64::encode(calculated_hmac_bytes);
-
-    if calculated_hmac != provided_hmac {
-        error!("HMAC verification failed: expected {}, got {}", provided_hmac, calculated_hmac);
-        return false;
-    }
-
-    true
+struct SignSession {
+    connection: nats::Connection,
+    start_phase: SignPhase,
+    phases: Vec<SignPhase>,
+    /// Prefix of the phase subjects, only set for the messages of a batch
+    prefix: String,
+    keyshare: ECDSA,
+    party_info: JoinSignSessionResponse,
+    session: NewSignSession,
+    email: String,
 }
@@ -0,0 +1,242 @@
+use crate::communication::nats::PeerMessenger;
+use crate::communication::protocol::{ AllRounds, KeySignFrostEdDSAAllRounds };
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::signing::eddsa::SignatureResult;
+use crate::signing::frost::{ self, Ciphersuite };
+use crate::storage::EDDSA;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Ed25519, Point, Scalar };
+use curv::BigInt;
+use sha2::{ Digest, Sha512 };
+use tracing::info;
+
+/// Context string of the FROST(Ed25519, SHA-512) ciphersuite from RFC 9591
+const CONTEXT_STRING: &[u8] = b"FROST-ED25519-SHA512-v1";
+
+/// FROST(Ed25519, SHA-512) from RFC 9591
+pub struct Ed25519Sha512;
+
+pub type NonceCommitment = frost::NonceCommitment<Point<Ed25519>>;
+pub type SignatureShare = frost::SignatureShare<Scalar<Ed25519>>;
+pub type SigningPackage = frost::SigningPackage<Ed25519Sha512>;
+
+/// Two round FROST signing with an existing EdDSA keyshare, which needs no ephemeral key
+pub struct FrostEdDSAKeySignClient<C> {
+    pub peer_messenger: C,
+    pub all_party_indices: Vec<usize>,
+}
+
+impl<C> FrostEdDSAKeySignClient<C> where C: PeerMessenger<KeySignFrostEdDSAAllRounds> {
+    pub fn create_shared_sig(&self, message: &[u8], keyshare: &EDDSA) -> Result<SignatureResult> {
+        let hiding_nonce = Scalar::<Ed25519>::random();
+        let binding_nonce = Scalar::<Ed25519>::random();
+        let commitment = NonceCommitment {
+            party_index: keyshare.party_index,
+            hiding: Point::generator() * &hiding_nonce,
+            binding: Point::generator() * &binding_nonce,
+        };
+
+        let commitments = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignFrostEdDSAAllRounds as AllRounds>::BroadcastRound::NonceCommitments,
+            commitment
+        )?;
+        info!("Nonce commitments received - msg count: {}", commitments.len());
+
+        let package = SigningPackage::new(message, &keyshare.y_sum, commitments)?;
+        if package.indices() != self.all_party_indices {
+            bail!("Nonce commitments do not match the parties of the session");
+        }
+
+        let z_i = package.signature_share(
+            keyshare.party_index,
+            &hiding_nonce,
+            &binding_nonce,
+            &keyshare.x_i
+        )?;
+        let shares = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignFrostEdDSAAllRounds as AllRounds>::BroadcastRound::SignatureShare,
+            SignatureShare {
+                party_index: keyshare.party_index,
+                z_i,
+            }
+        )?;
+        info!("Signature shares received - msg count: {}", shares.len());
+
+        for share in shares.iter() {
+            let public_share = keyshare.vss_scheme_vec
+                .iter()
+                .fold(Point::zero(), |acc, vss| {
+                    acc + vss.get_point_commitment(share.party_index as u16)
+                });
+            package.verify_share(share, &public_share)?;
+        }
+        info!("Verified all signature shares");
+
+        let (r, s) = package.aggregate(&shares)?;
+        verify(&keyshare.y_sum, message, &r, &s)?;
+        info!("Full signature generated and verified");
+
+        Ok(SignatureResult::new(&r, &s))
+    }
+
+    pub fn publish_result(&self, signature: SignatureResult) -> Result<()> {
+        self.peer_messenger.broadcast_message(
+            &<KeySignFrostEdDSAAllRounds as AllRounds>::BroadcastRound::Result,
+            signature
+        )
+    }
+}
+
+impl Ciphersuite for Ed25519Sha512 {
+    type Point = Point<Ed25519>;
+    type Scalar = Scalar<Ed25519>;
+
+    fn identity() -> Self::Point {
+        Point::zero()
+    }
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point {
+        Point::generator() * scalar
+    }
+
+    fn scalar_zero() -> Self::Scalar {
+        Scalar::zero()
+    }
+
+    fn scalar_one() -> Self::Scalar {
+        Scalar::from(&BigInt::from(1u32))
+    }
+
+    fn encode_point(point: &Self::Point) -> Vec<u8> {
+        point.to_bytes(true).to_vec()
+    }
+
+    fn lagrange_coefficient(party_index: usize, indices: &[usize]) -> Result<Self::Scalar> {
+        RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(party_index, indices)
+    }
+
+    fn encode_identifier(party_index: usize) -> Vec<u8> {
+        scalar_to_le_bytes(&Scalar::<Ed25519>::from(&BigInt::from(party_index as u32))).to_vec()
+    }
+
+    fn binding_factor(
+        party_index: usize,
+        public_key: &Self::Point,
+        message: &[u8],
+        encoded_commitments: &[u8],
+        adaptor_point: Option<&Self::Point>
+    ) -> Self::Scalar {
+        let mut rho_input_prefix = public_key.to_bytes(true).to_vec();
+        rho_input_prefix.extend_from_slice(&sha512(&[CONTEXT_STRING, b"msg", message]));
+        rho_input_prefix.extend_from_slice(
+            &sha512(&[CONTEXT_STRING, b"com", encoded_commitments])
+        );
+        if let Some(adaptor_point) = adaptor_point {
+            rho_input_prefix.extend_from_slice(&adaptor_point.to_bytes(true));
+        }
+        hash_to_scalar(
+            &[
+                CONTEXT_STRING,
+                b"rho",
+                &rho_input_prefix,
+                &Self::encode_identifier(party_index),
+            ]
+        )
+    }
+
+    fn challenge(
+        group_commitment: &Self::Point,
+        public_key: &Self::Point,
+        message: &[u8]
+    ) -> Self::Scalar {
+        hash_to_scalar(&[&group_commitment.to_bytes(true), &public_key.to_bytes(true), message])
+    }
+}
+
+/// Verifies the signature as a plain Ed25519 signature, the way any wallet would
+pub fn verify(
+    public_key: &Point<Ed25519>,
+    message: &[u8],
+    r: &Point<Ed25519>,
+    s: &Scalar<Ed25519>
+) -> Result<()> {
+    let mut signature = r.to_bytes(true).to_vec();
+    signature.extend_from_slice(&scalar_to_le_bytes(s));
+    let signature = ed25519_dalek::Signature
+        ::try_from(&signature[..])
+        .map_err(|err| anyhow!("Invalid signature encoding: {}", err))?;
+    ed25519_dalek::PublicKey
+        ::from_bytes(&public_key.to_bytes(true))
+        .map_err(|err| anyhow!("Invalid public key: {}", err))?
+        .verify_strict(message, &signature)
+        .map_err(|_| anyhow!("Signature did not pass verification"))
+}
+
+fn sha512(data: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha512::new();
+    for item in data {
+        hasher.update(item);
+    }
+    hasher.finalize().to_vec()
+}
+
+/// SHA-512 of the data as a little endian integer, reduced modulo the group order
+pub(crate) fn hash_to_scalar(data: &[&[u8]]) -> Scalar<Ed25519> {
+    let mut hash = sha512(data);
+    hash.reverse();
+    Scalar::<Ed25519>::from_bigint(&BigInt::from_bytes(&hash))
+}
+
+pub(crate) fn scalar_to_le_bytes(scalar: &Scalar<Ed25519>) -> [u8; 32] {
+    let be_bytes = scalar.to_bigint().to_bytes();
+    let mut bytes = [0; 32];
+    bytes[32 - be_bytes.len()..].copy_from_slice(&be_bytes);
+    bytes.reverse();
+    bytes
+}
+
+#[test]
+fn threshold_signature_verifies_with_ed25519_dalek() {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+    let secret = Scalar::<Ed25519>::random();
+    let (vss, shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+    let public_key = Point::generator() * &secret;
+    let message = b"solana transaction";
+
+    let signers = [2, 3];
+    let nonces = signers
+        .iter()
+        .map(|_| (Scalar::<Ed25519>::random(), Scalar::<Ed25519>::random()))
+        .collect::<Vec<_>>();
+    let commitments = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| NonceCommitment {
+            party_index,
+            hiding: Point::generator() * hiding,
+            binding: Point::generator() * binding,
+        })
+        .collect();
+
+    let package = SigningPackage::new(message, &public_key, commitments).unwrap();
+    let signature_shares = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| SignatureShare {
+            party_index,
+            z_i: package
+                .signature_share(party_index, hiding, binding, &shares[party_index - 1])
+                .unwrap(),
+        })
+        .collect::<Vec<_>>();
+    for share in signature_shares.iter() {
+        let public_share = vss.get_point_commitment(share.party_index as u16);
+        package.verify_share(share, &public_share).unwrap();
+    }
+
+    let (r, s) = package.aggregate(&signature_shares).unwrap();
+    verify(&public_key, message, &r, &s).unwrap();
+    assert!(verify(&public_key, b"another transaction", &r, &s).is_err());
+}
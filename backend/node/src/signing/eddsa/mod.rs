@@ -1,11 +1,41 @@
 pub mod client;
+pub mod frost;
 pub mod orchestrate;
 pub mod session;
+pub mod solana;
 
+use crate::signing::eddsa::frost::scalar_to_le_bytes;
+use curv::elliptic::curves::{ Ed25519, Point, Scalar };
 use serde::{ Deserialize, Serialize };
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SignatureResult {
     pub sigma: String,
     pub R: String,
+    /// Standard 64 byte `R || S` signature of RFC 8032, hex encoded
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl SignatureResult {
+    pub fn new(R: &Point<Ed25519>, s: &Scalar<Ed25519>) -> Self {
+        let mut signature = R.to_bytes(true).to_vec();
+        signature.extend_from_slice(&scalar_to_le_bytes(s));
+        SignatureResult {
+            sigma: hex::encode(&*s.to_bytes()),
+            R: hex::encode(&*R.to_bytes(false)),
+            signature: hex::encode(signature),
+        }
+    }
+}
+
+/// Protocol used to create an EdDSA signature
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningMode {
+    /// Ephemeral key generation followed by a signing round
+    #[default]
+    Ephemeral,
+    /// Two round FROST(Ed25519, SHA-512) with the stored keyshare
+    Frost,
 }
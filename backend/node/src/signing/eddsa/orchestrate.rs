@@ -1,9 +1,10 @@
 use crate::command::MsgContext;
 use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
 use crate::signing::eddsa::session::NewEdDSAKeySignSession;
-use crate::signing::eddsa::SignatureResult;
-use crate::signing::{ SigningCommand, SigningResponse };
-use anyhow::{ bail, Context, Result };
+use crate::signing::eddsa::{ SignatureResult, SigningMode };
+use crate::signing::{ merge_batch_results, SigningCommand, SigningResponse };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
 use tracing::{ error, info, instrument };
 
 #[instrument(skip_all)]
@@ -15,12 +16,32 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
     let party_nodes = cmd.party_nodes;
     let key_id = cmd.key_id;
 
+    let threshold = KeyInfoStore::get_key_info(&key_id)
+        .map_err(|_| anyhow!("Key info is not found - key_id: {}", &key_id))?.threshold;
+
     let party_count = party_nodes.len();
-    if party_count < 3 {
+    if party_count <= threshold {
         bail!("Not enough nodes in party");
     }
 
-    let join_key = format!("network.gridlock.nodes.EphemeralKeyGenEdDSA.{}.Join", &session_id);
+    if cmd.taproot.is_some() {
+        bail!("Taproot tweaks are only supported for Schnorr signatures");
+    }
+
+    if cmd.derivation_path.is_some() {
+        bail!("Key derivation is only supported for ECDSA keys");
+    }
+
+    if cmd.mode == SigningMode::Frost && !cmd.msgs.is_empty() {
+        bail!("Batch signing is not supported in FROST mode");
+    }
+
+    // FROST signs with the stored keyshare, so parties join the signing topic itself
+    let join_topic = match cmd.mode {
+        SigningMode::Ephemeral => "EphemeralKeyGenEdDSA",
+        SigningMode::Frost => "KeySignEdDSA",
+    };
+    let join_key = format!("network.gridlock.nodes.{}.{}.Join", join_topic, &session_id);
     let join_sub = nc.subscribe(&join_key)?;
 
     let result_key = format!("network.gridlock.nodes.KeySignEdDSA.{}.Result", &session_id);
@@ -34,6 +55,8 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
                 session_id: session_id.to_owned(),
                 message: cmd.msg.clone(),
                 email: None,
+                messages: cmd.msgs.clone(),
+                mode: cmd.mode,
             })
         )?;
         nc.publish(&sign_new_key, key_sign_new_data)?;
@@ -78,6 +101,18 @@ pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningRespon
 
     info!("Signature result received");
 
+    if !cmd.msgs.is_empty() {
+        let party_results = res_vec
+            .iter()
+            .map(|res| {
+                serde_json::from_slice::<BroadcastMessage<Vec<SigningResponse>>>(&res.data).map(
+                    |broadcast| broadcast.message
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        return merge_batch_results(party_results, cmd.msgs.len());
+    }
+
     let sig = serde_json::from_slice::<BroadcastMessage<SignatureResult>>(
         &res_vec[0].data
     )?.message;
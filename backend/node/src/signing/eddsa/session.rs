@@ -1,28 +1,43 @@
-use crate::auth::e2e_decrypt;
 use crate::communication::nats::{
     BaseMessenger,
+    JoinResponse,
     NatsBaseMessenger,
     NatsBaseSession,
     NatsPeerMessenger,
+    PeerMessenger,
+};
+use crate::communication::protocol::{
+    AllRounds,
+    KeyGenAllRounds,
+    KeySignEdDSAAllRounds,
+    KeySignFrostEdDSAAllRounds,
+    Topic,
 };
-use crate::communication::protocol::{ KeyGenAllRounds, KeySignEdDSAAllRounds, Topic };
 use crate::keygen::eddsa::client::KeyGenClient;
 use crate::keygen::ShareParams;
 use crate::node::NodeIdentity;
 use crate::signing::eddsa::client::EdDSAKeySignClient;
-use crate::signing::eddsa::SignatureResult;
+use crate::signing::eddsa::frost::FrostEdDSAKeySignClient;
+use crate::signing::eddsa::solana;
+use crate::signing::eddsa::{ SignatureResult, SigningMode };
+use crate::signing::policy::{ enforce_policy, raw_intents, SigningIntent };
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::signing::{ PayloadType, SigningResponse };
 use crate::storage::fs::WriteOpts;
 use crate::storage::KeyshareAccessor;
 use crate::storage::EDDSA;
 use crate::App;
+use anyhow::bail;
 use serde::{ Deserialize, Serialize };
 use std::thread;
 use tracing::{ error, info, instrument, warn };
 use crate::storage::key_metadata_store::KeyMetadataStore;
-use chrono::{ DateTime, Utc };
-use hmac::{ Hmac, Mac, NewMac };
-use sha2::Sha256;
-use base64;
 use hex;
 
 #[instrument(skip_all)]
@@ -40,12 +55,45 @@ pub struct NewEdDSAKeySignMessage {
     pub key_id: String,
     pub session_id: String,
     pub message: Vec<u8>,
-    pub client_e2e_public_key: String,
-    pub encrypted_signing_key: String,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
     pub is_transfer_tx: Option<bool>,
-    pub timestamp: Option<String>,
-    pub message_hmac: Option<String>,
-    pub email: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub mode: SigningMode,
+    /// Format of `message` or `messages` when the guardian has to parse them
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+}
+
+impl AuthenticatedRequest for NewEdDSAKeySignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let mode = match self.mode {
+            SigningMode::Ephemeral => "ephemeral",
+            SigningMode::Frost => "frost",
+        };
+        let encoder = CanonicalEncoder::new("eddsa")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .bytes("message", &self.message)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_bool("is_transfer_tx", self.is_transfer_tx)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .list("messages", &self.messages)
+            .str("mode", mode);
+        // Appended only when set, keeping the encoding of requests without one unchanged
+        match self.payload_type {
+            Some(payload_type) => encoder.str("payload_type", payload_type.as_str()).finish(),
+            None => encoder.finish(),
+        }
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -54,6 +102,11 @@ pub struct NewEdDSAKeySignSession {
     pub session_id: String,
     pub message: Vec<u8>,
     pub email: Option<String>,
+    /// Messages of a batch, each signed in parallel within the session
+    #[serde(default)]
+    pub messages: Vec<Vec<u8>>,
+    #[serde(default)]
+    pub mode: SigningMode,
 }
 
 pub struct E2EData {
@@ -79,7 +132,6 @@ fn keysign_session_inner(
     };
     info!("Retrieved keyshare");
 
-    let threshold = keyshare.threshold;
     let party_index = keyshare.party_index;
 
     let node = NodeIdentity::load()?;
@@ -97,6 +149,17 @@ fn keysign_session_inner(
         party_index,
     };
 
+    if session.mode == SigningMode::Frost {
+        if !session.messages.is_empty() {
+            bail!("Batch signing is not supported in FROST mode");
+        }
+        return keysign_frost(conn, nats_session, &keyshare, &message);
+    }
+
+    if !session.messages.is_empty() {
+        return keysign_batch(conn, nats_session, session.messages, keyshare);
+    }
+
     let keygen_messenger = NatsBaseMessenger::<KeyGenAllRounds>::new(
         Topic::EphemeralKeyGenEdDSA,
         conn.clone(),
@@ -112,9 +175,158 @@ fn keysign_session_inner(
     let join_response = keygen_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
     info!("Got join response");
 
-    let party_count = join_response.party_count;
+    sign_message(keygen_messenger, sign_messenger, &join_response, &keyshare, &message)?;
+    info!("Signature published successfully");
+
+    Ok(())
+}
+
+/// Signs the message in two rounds with FROST, which runs on the EdDSA signing topic only
+fn keysign_frost(
+    conn: nats::Connection,
+    nats_session: NatsBaseSession,
+    keyshare: &EDDSA,
+    message: &[u8]
+) -> anyhow::Result<()> {
+    let sign_messenger = NatsBaseMessenger::<KeySignFrostEdDSAAllRounds>::new(
+        Topic::KeySignEdDSA,
+        conn,
+        nats_session
+    )?;
+
+    let join_response = sign_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+    info!("Got join response");
+
     let mut all_party_indices = join_response.all_party_indices;
     all_party_indices.sort();
+    if all_party_indices.len() <= keyshare.threshold {
+        bail!("Not enough parties joined to sign");
+    }
+
+    let sign_peer_messenger = NatsPeerMessenger::from(
+        sign_messenger,
+        join_response.party_count,
+        all_party_indices.clone()
+    )?;
+
+    let keysign_client = FrostEdDSAKeySignClient {
+        peer_messenger: sign_peer_messenger,
+        all_party_indices,
+    };
+
+    let signature = keysign_client.create_shared_sig(message, keyshare)?;
+    keysign_client.publish_result(signature)?;
+    info!("Signature published successfully");
+
+    Ok(())
+}
+
+/// Signs every message of a batch in its own sub-session after a single join,
+/// so a failure only affects the result of its own message
+fn keysign_batch(
+    conn: nats::Connection,
+    nats_session: NatsBaseSession,
+    messages: Vec<Vec<u8>>,
+    keyshare: EDDSA
+) -> anyhow::Result<()> {
+    // Subscribe to the rounds of every message before joining, as peers start right after the join
+    let mut item_messengers = Vec::with_capacity(messages.len());
+    for index in 0..messages.len() {
+        let item_session = NatsBaseSession {
+            session_id: format!("{}.m{}", nats_session.session_id, index),
+            ..nats_session.clone()
+        };
+        let keygen_messenger = NatsBaseMessenger::<KeyGenAllRounds>::new(
+            Topic::EphemeralKeyGenEdDSA,
+            conn.clone(),
+            item_session.clone()
+        )?;
+        let sign_messenger = NatsBaseMessenger::<KeySignEdDSAAllRounds>::new(
+            Topic::KeySignEdDSA,
+            conn.clone(),
+            item_session
+        )?;
+        item_messengers.push((keygen_messenger, sign_messenger));
+    }
+
+    let join_messenger = NatsBaseMessenger::<KeyGenAllRounds>::new(
+        Topic::EphemeralKeyGenEdDSA,
+        conn.clone(),
+        nats_session.clone()
+    )?;
+    let result_messenger = NatsBaseMessenger::<KeySignEdDSAAllRounds>::new(
+        Topic::KeySignEdDSA,
+        conn,
+        nats_session
+    )?;
+
+    let join_response = join_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+    info!("Got join response for a batch of {} messages", messages.len());
+
+    let mut handles = Vec::with_capacity(messages.len());
+    let items = item_messengers.into_iter().zip(messages).enumerate();
+    for (index, ((keygen_messenger, sign_messenger), message)) in items {
+        let join_response = JoinResponse {
+            party_count: join_response.party_count,
+            all_party_indices: join_response.all_party_indices.clone(),
+        };
+        let keyshare = keyshare.clone();
+        let handle = thread::Builder
+            ::new()
+            .name(format!("sign_session_{}_{}", join_messenger.session.session_id, index))
+            .spawn(move || {
+                sign_message(keygen_messenger, sign_messenger, &join_response, &keyshare, &message)
+            })?;
+        handles.push(handle);
+    }
+
+    let results = handles
+        .into_iter()
+        .enumerate()
+        .map(|(index, handle)| {
+            match handle.join() {
+                Ok(Ok(signature)) => SigningResponse::EDDSA(signature),
+                Ok(Err(err)) => {
+                    error!("Error in signing message {} of the batch: {}", index, err);
+                    SigningResponse::failed(err.to_string())
+                }
+                Err(_) => {
+                    error!("Signing message {} of the batch panicked", index);
+                    SigningResponse::failed("Signing thread panicked".to_string())
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut all_party_indices = join_response.all_party_indices;
+    all_party_indices.sort();
+    let result_peer_messenger = NatsPeerMessenger::from(
+        result_messenger,
+        join_response.party_count,
+        all_party_indices
+    )?;
+    result_peer_messenger.broadcast_message(
+        &<KeySignEdDSAAllRounds as AllRounds>::BroadcastRound::Result,
+        results
+    )?;
+    info!("Batch signature results published");
+
+    Ok(())
+}
+
+/// Creates an ephemeral key for the message and signs it together with the other parties
+fn sign_message(
+    keygen_messenger: NatsBaseMessenger<KeyGenAllRounds>,
+    sign_messenger: NatsBaseMessenger<KeySignEdDSAAllRounds>,
+    join_response: &JoinResponse,
+    keyshare: &EDDSA,
+    message: &[u8]
+) -> anyhow::Result<SignatureResult> {
+    let threshold = keyshare.threshold;
+    let party_index = keyshare.party_index;
+    let party_count = join_response.party_count;
+    let mut all_party_indices = join_response.all_party_indices.clone();
+    all_party_indices.sort();
 
     let keygen_peer_messenger = NatsPeerMessenger::from(
         keygen_messenger,
@@ -132,7 +344,7 @@ fn keysign_session_inner(
         all_party_indices: all_party_indices.clone(),
     };
 
-    let ephemeral_keyshare = keygen_client.create_ephemeral_shared_key(&message)?;
+    let ephemeral_keyshare = keygen_client.create_ephemeral_shared_key(message)?;
     info!("Successfully created an ephemeral key");
 
     keygen_client.publish_result(ephemeral_keyshare.shared_key.R.clone())?;
@@ -153,15 +365,39 @@ fn keysign_session_inner(
         all_party_indices,
     };
 
-    let signature = keysign_client.create_shared_sig(&message, &ephemeral_keyshare, &keyshare)?;
-    let sigma = hex::encode(&*signature.s.to_bytes());
+    let signature = keysign_client.create_shared_sig(message, &ephemeral_keyshare, keyshare)?;
+    let signature = SignatureResult::new(&signature.R, &signature.s);
+    keysign_client.publish_result(signature.clone())?;
 
-    let R = hex::encode(&*signature.R.to_bytes(false));
-    let signature = SignatureResult { sigma, R };
-    keysign_client.publish_result(signature)?;
-    info!("Signature published successfully");
+    Ok(signature)
+}
 
-    Ok(())
+/// Parse the Solana messages of a request, which the threshold key has to sign
+fn solana_intents(
+    request: &NewEdDSAKeySignMessage,
+    payload_type: PayloadType,
+    email: &str
+) -> anyhow::Result<Vec<SigningIntent>> {
+    if payload_type != PayloadType::SolanaMessage {
+        bail!("EdDSA keys can't sign {} payloads", payload_type.as_str());
+    }
+    let keyshare = KeyshareAccessor::<EDDSA>::read_only_with_email(&request.key_id, email)?.key;
+    let signer: [u8; 32] = (&*keyshare.y_sum.to_bytes(true))
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid EdDSA public key"))?;
+    let messages = if request.messages.is_empty() {
+        std::slice::from_ref(&request.message)
+    } else {
+        &request.messages[..]
+    };
+    messages
+        .iter()
+        .map(|message| {
+            let parsed = solana::parse_message(message, &signer)?;
+            info!("Parsed Solana message: {:?}", parsed);
+            Ok(parsed.intent())
+        })
+        .collect()
 }
 
 pub fn handle_new_session_message(app: &App, message: nats::Message) {
@@ -173,67 +409,20 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         }
     };
 
-    // Validate security fields
-    if
-        parsed_message.timestamp.is_none() ||
-        parsed_message.message_hmac.is_none() ||
-        parsed_message.email.is_none()
-    {
-        error!("Missing required security fields: timestamp, message_hmac, or email");
-        return;
-    }
-
-    let node = match NodeIdentity::load() {
-        Ok(node) => node,
-        Err(err) => {
-            error!("Failed to load node identity: {}", err);
-            return;
-        }
-    };
-
-    let decrypted_signing_key = match
-        e2e_decrypt(
-            &parsed_message.encrypted_signing_key,
-            &node.e2e_private_key,
-            &parsed_message.client_e2e_public_key
-        )
-    {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to decrypt signing key: {}", err);
-            return;
-        }
-    };
-
-    let node_signing_key = match String::from_utf8(decrypted_signing_key) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to convert decrypted signing key to string: {}", err);
-            return;
-        }
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
     };
 
-    let message_hmac = parsed_message.message_hmac.as_ref().unwrap();
-    let timestamp = parsed_message.timestamp.as_ref().unwrap();
-
-    let email = parsed_message.email.unwrap_or_default();
-
-    // Security verification: HMAC then timestamp
-    if !verify_hmac(message_hmac, timestamp, &email, &node_signing_key) {
-        error!("HMAC verification failed");
-        return;
-    }
-
-    if !verify_timestamp(&parsed_message.key_id, timestamp, &email) {
-        error!("Timestamp verification failed");
-        return;
-    }
-    info!("Timestamp verified");
-
     // Transfer transaction validation
     if parsed_message.is_transfer_tx.unwrap_or(false) {
         info!("Initiating ownership transfer");
 
+        if !parsed_message.messages.is_empty() {
+            error!("Ownership transfers can't be signed in a batch");
+            return;
+        }
+
         let message_str = match String::from_utf8(parsed_message.message.clone()) {
             Ok(s) => s,
             Err(err) => {
@@ -277,24 +466,10 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         info!("Successfully removed new_identity_key after ownership verification");
     }
 
-    // Validate access key
-    let saved_access_key = match KeyMetadataStore::get(&parsed_message.key_id, "access", &email) {
-        Ok(key) => key,
-        Err(err) => {
-            error!("Failed to load saved access key: {}", err);
-            return;
-        }
-    };
-
-    if node_signing_key != saved_access_key {
-        error!("Access key mismatch: decrypted key does not match saved access key");
-        return;
-    }
-
     // Store the client_e2e_public_key
     if
         let Err(err) = KeyMetadataStore::save_user_level(
-            &parsed_message.client_e2e_public_key,
+            &parsed_message.credentials.client_e2e_public_key,
             "e2e_key",
             &email,
             &WriteOpts::Modify
@@ -304,12 +479,42 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         // Continue anyway as this is not critical
     }
 
+    // Parse typed payloads, so that the policy sees what the messages do
+    let intents = match parsed_message.payload_type {
+        Some(payload_type) => {
+            if parsed_message.is_transfer_tx.unwrap_or(false) {
+                error!("Ownership transfers can't be sent as typed payloads");
+                return;
+            }
+            match solana_intents(&parsed_message, payload_type, &email) {
+                Ok(intents) => intents,
+                Err(err) => {
+                    error!("Failed to parse {} payload: {}", payload_type.as_str(), err);
+                    return;
+                }
+            }
+        }
+        None =>
+            raw_intents(
+                parsed_message.messages.len(),
+                parsed_message.is_transfer_tx.unwrap_or(false)
+            ),
+    };
+
+    // Enforce the signing policy of the key before joining the session
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &intents) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
     // Create session with the email for email-based storage access
     let session = NewEdDSAKeySignSession {
         key_id: parsed_message.key_id,
         session_id: parsed_message.session_id,
         message: parsed_message.message,
         email: Some(email.clone()),
+        messages: parsed_message.messages,
+        mode: parsed_message.mode,
     };
 
     // Create a new thread for this signing session
@@ -326,78 +531,3 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         Err(err) => error!("Failed to spawn thread for EdDSA signing: {}", err),
     };
 }
-
-// Verify that the timestamp is newer than the last one we've seen
-fn verify_timestamp(key_id: &str, new_timestamp: &str, email: &str) -> bool {
-    let timestamp_key = "timestamp";
-    let new_dt = match DateTime::parse_from_rfc3339(new_timestamp) {
-        Ok(dt) => dt.with_timezone(&Utc),
-        Err(err) => {
-            error!("Failed to parse timestamp: {}", err);
-            return false;
-        }
-    };
-
-    // It's fine if this fails - it just means first tx
-    let previous_timestamp_result = KeyMetadataStore::get(key_id, timestamp_key, email);
-
-    match previous_timestamp_result {
-        Ok(prev_timestamp_str) => {
-            let prev_dt = match DateTime::parse_from_rfc3339(&prev_timestamp_str) {
-                Ok(dt) => dt.with_timezone(&Utc),
-                Err(err) => {
-                    error!("Failed to parse stored timestamp: {}", err);
-                    return false;
-                }
-            };
-
-            if new_dt <= prev_dt {
-                error!(
-                    "Timestamp validation failed: provided timestamp ({}) is not newer than stored timestamp ({})",
-                    new_timestamp,
-                    prev_timestamp_str
-                );
-                return false;
-            }
-        }
-        Err(err) => {
-            info!("No previous timestamp found, likely first transaction: {}", err);
-        }
-    }
-
-    match KeyMetadataStore::save(new_timestamp, key_id, timestamp_key, email, &WriteOpts::Modify) {
-        Ok(_) => true,
-        Err(err) => {
-            error!("Failed to save new timestamp: {}", err);
-            false
-        }
-    }
-}
-
-// HMAC verification using SHA256(timestamp + email) with signing key
-fn verify_hmac(provided_hmac: &str, timestamp: &str, email: &str, signing_key: &str) -> bool {
-    type HmacSha256 = Hmac<Sha256>;
-    let message_input = format!("{}{}", timestamp, email);
-
-    let mut mac = match HmacSha256::new_from_slice(signing_key.as_bytes()) {
-        Ok(m) => m,
-        Err(err) => {
-            error!("Failed to create HMAC instance: {}", err);
-            return false;
-        }
-    };
-
-    mac.update(message_input.as_bytes());
-    let calculated_hmac_bytes = mac.finalize().into_bytes();
-
-    // Use base64 encoding instead of hex to match TypeScript implementation
-    let calculated_hmac = base64::encode(calculated_hmac_bytes);
-
-    if calculated_hmac != provided_hmac {
-        error!("HMAC verification failed: expected {}, got {}", provided_hmac, calculated_hmac);
-        return false;
-    }
-
-    info!("HMAC verified");
-    true
-}
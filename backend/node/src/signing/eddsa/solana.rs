@@ -0,0 +1,353 @@
+//! Parsing of Solana transaction messages signed with EdDSA keys.
+//!
+//! Ed25519 signs the serialized message itself, so nothing is hashed here. The guardian checks
+//! that the message is well formed, that the threshold key has to sign it and extracts the
+//! transfers it makes for the signing policy.
+use crate::signing::policy::{ MessageKind, SigningIntent, Transfer, NATIVE_ASSET };
+use anyhow::{ anyhow, bail, Result };
+use serde::{ Deserialize, Serialize };
+
+const VERSION_PREFIX: u8 = 0x80;
+const SYSTEM_PROGRAM: [u8; 32] = [0; 32];
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const TOKEN_2022_PROGRAM: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCLEpPxuEb";
+const COMPUTE_BUDGET_PROGRAM: &str = "ComputeBudget111111111111111111111111111111";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageVersion {
+    Legacy,
+    V0,
+}
+
+/// Account referenced by an instruction
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Account {
+    /// Base58 address listed in the message
+    Static(String),
+    /// Entry of an address lookup table, only resolved on chain
+    Lookup {
+        table: String,
+        index: u8,
+    },
+}
+
+impl std::fmt::Display for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Account::Static(address) => write!(f, "{}", address),
+            Account::Lookup { table, index } => write!(f, "{}#{}", table, index),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Instruction {
+    /// System program transfer of lamports
+    SystemTransfer {
+        from: Account,
+        to: Account,
+        lamports: u64,
+    },
+    /// SPL token transfer or approval, where an allowance counts as moved. The mint is only
+    /// known for the checked variants, the source token account identifies the asset otherwise.
+    TokenTransfer {
+        asset: Account,
+        destination: Account,
+        amount: u64,
+        approval: bool,
+    },
+    /// Compute unit limits and prices, which don't move funds
+    ComputeBudget,
+    Other {
+        program: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SolanaMessage {
+    pub version: MessageVersion,
+    pub fee_payer: String,
+    pub recent_blockhash: String,
+    pub instructions: Vec<Instruction>,
+}
+
+impl SolanaMessage {
+    /// What signing the message does, as seen by the signing policy
+    pub fn intent(&self) -> SigningIntent {
+        let mut destinations = Vec::new();
+        let mut transfers = Vec::new();
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::SystemTransfer { to, lamports, .. } => {
+                    transfers.push(Transfer {
+                        asset: NATIVE_ASSET.to_string(),
+                        destination: to.to_string(),
+                        amount: *lamports as u128,
+                    });
+                }
+                Instruction::TokenTransfer { asset, destination, amount, .. } => {
+                    transfers.push(Transfer {
+                        asset: asset.to_string(),
+                        destination: destination.to_string(),
+                        amount: *amount as u128,
+                    });
+                }
+                Instruction::ComputeBudget => {}
+                Instruction::Other { program } => destinations.push(program.clone()),
+            }
+        }
+        SigningIntent { kind: MessageKind::SolanaMessage, destinations, transfers }
+    }
+}
+
+/// Parse a serialized legacy or v0 message that `signer` has to sign
+pub fn parse_message(message: &[u8], signer: &[u8; 32]) -> Result<SolanaMessage> {
+    let mut reader = Reader { input: message };
+    let version = match reader.peek()? {
+        prefix if prefix & VERSION_PREFIX == 0 => MessageVersion::Legacy,
+        prefix if prefix == VERSION_PREFIX => {
+            reader.byte()?;
+            MessageVersion::V0
+        }
+        prefix => bail!("Unsupported message version {}", prefix & !VERSION_PREFIX),
+    };
+
+    let required_signatures = reader.byte()? as usize;
+    let readonly_signed = reader.byte()? as usize;
+    let readonly_unsigned = reader.byte()? as usize;
+    let keys = (0..reader.compact_u16()?)
+        .map(|_| reader.key())
+        .collect::<Result<Vec<_>>>()?;
+    if required_signatures == 0 || required_signatures > keys.len() {
+        bail!("Message requires {} signatures of {} accounts", required_signatures, keys.len());
+    }
+    if readonly_signed >= required_signatures {
+        bail!("The fee payer has to be a writable signer");
+    }
+    if readonly_unsigned > keys.len() - required_signatures {
+        bail!("More readonly unsigned accounts than unsigned accounts");
+    }
+    if !keys[..required_signatures].contains(signer) {
+        bail!("The threshold key is not a required signer of the message");
+    }
+    let recent_blockhash = bs58::encode(reader.key()?).into_string();
+
+    let raw_instructions = (0..reader.compact_u16()?)
+        .map(|_| {
+            let program = reader.byte()? as usize;
+            let accounts = reader.bytes()?.to_vec();
+            let data = reader.bytes()?.to_vec();
+            Ok((program, accounts, data))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Accounts of lookup tables are indexed after the static ones, writable entries first
+    let mut accounts: Vec<Account> = keys
+        .iter()
+        .map(|key| Account::Static(bs58::encode(key).into_string()))
+        .collect();
+    if version == MessageVersion::V0 {
+        let mut readonly = Vec::new();
+        for _ in 0..reader.compact_u16()? {
+            let table = bs58::encode(reader.key()?).into_string();
+            let lookup = |index: &u8| Account::Lookup { table: table.clone(), index: *index };
+            accounts.extend(reader.bytes()?.iter().map(lookup));
+            readonly.extend(reader.bytes()?.iter().map(lookup));
+        }
+        accounts.extend(readonly);
+    }
+    if !reader.input.is_empty() {
+        bail!("{} trailing bytes after the message", reader.input.len());
+    }
+    if accounts.len() > 256 {
+        bail!("Messages can't reference more than 256 accounts");
+    }
+
+    let instructions = raw_instructions
+        .into_iter()
+        .map(|(program, indices, data)| {
+            // Programs are invoked from the static accounts only
+            let program = keys
+                .get(program)
+                .ok_or_else(|| anyhow!("Program index {} is out of range", program))?;
+            let accounts = indices
+                .iter()
+                .map(|index| {
+                    accounts
+                        .get(*index as usize)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("Account index {} is out of range", index))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(decode_instruction(program, accounts, &data))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(SolanaMessage {
+        version,
+        fee_payer: bs58::encode(keys[0]).into_string(),
+        recent_blockhash,
+        instructions,
+    })
+}
+
+fn decode_instruction(program: &[u8; 32], accounts: Vec<Account>, data: &[u8]) -> Instruction {
+    let program_id = bs58::encode(program).into_string();
+    let u64_at = |offset: usize| {
+        data.get(offset..offset + 8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let account = |index: usize| accounts.get(index).cloned();
+
+    let decoded = if program == &SYSTEM_PROGRAM {
+        let system_transfer = |from, to| {
+            Some(Instruction::SystemTransfer {
+                from: account(from)?,
+                to: account(to)?,
+                lamports: u64_at(4)?,
+            })
+        };
+        let discriminant = data.get(..4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        match discriminant {
+            // CreateAccount { lamports, .. } with [from, new account]
+            Some(0) => system_transfer(0, 1),
+            // Transfer { lamports } with [from, to]
+            Some(2) => system_transfer(0, 1),
+            // WithdrawNonceAccount { lamports } with [nonce account, to, ..]
+            Some(5) => system_transfer(0, 1),
+            // TransferWithSeed { lamports, .. } with [from, base, to]
+            Some(11) => system_transfer(0, 2),
+            _ => None,
+        }
+    } else if program_id == TOKEN_PROGRAM || program_id == TOKEN_2022_PROGRAM {
+        let token_transfer = |asset, destination, approval| {
+            Some(Instruction::TokenTransfer {
+                asset: account(asset)?,
+                destination: account(destination)?,
+                amount: u64_at(1)?,
+                approval,
+            })
+        };
+        match data.first() {
+            // Transfer with [source, destination, authority]
+            Some(3) => token_transfer(0, 1, false),
+            // Approve with [source, delegate, owner]
+            Some(4) => token_transfer(0, 1, true),
+            // TransferChecked with [source, mint, destination, authority]
+            Some(12) => token_transfer(1, 2, false),
+            // ApproveChecked with [source, mint, delegate, owner]
+            Some(13) => token_transfer(1, 2, true),
+            _ => None,
+        }
+    } else if program_id == COMPUTE_BUDGET_PROGRAM {
+        Some(Instruction::ComputeBudget)
+    } else {
+        None
+    };
+    decoded.unwrap_or(Instruction::Other { program: program_id })
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.input.len() < len {
+            bail!("Unexpected end of message");
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn peek(&self) -> Result<u8> {
+        self.input.first().copied().ok_or_else(|| anyhow!("Empty message"))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn key(&mut self) -> Result<[u8; 32]> {
+        Ok(self.take(32)?.try_into()?)
+    }
+
+    /// Length prefixed byte array
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.compact_u16()? as usize;
+        self.take(len)
+    }
+
+    /// Little endian base 128 u16 of at most three bytes, without redundant bytes
+    fn compact_u16(&mut self) -> Result<u16> {
+        let mut value = 0u32;
+        for i in 0..3 {
+            let byte = self.byte()?;
+            if i > 0 && byte == 0 {
+                bail!("Non-canonical compact-u16");
+            }
+            value |= ((byte & 0x7f) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return u16::try_from(value).map_err(|_| anyhow!("compact-u16 overflow"));
+            }
+        }
+        bail!("compact-u16 is longer than three bytes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_message(payer: [u8; 32], to: [u8; 32], lamports: u64) -> Vec<u8> {
+        let mut message = vec![1, 0, 1, 3];
+        message.extend_from_slice(&payer);
+        message.extend_from_slice(&to);
+        message.extend_from_slice(&SYSTEM_PROGRAM);
+        message.extend_from_slice(&[7; 32]);
+        message.extend_from_slice(&[1, 2, 2, 0, 1, 12, 2, 0, 0, 0]);
+        message.extend_from_slice(&lamports.to_le_bytes());
+        message
+    }
+
+    #[test]
+    fn system_transfer_is_extracted_for_required_signer() {
+        let message = transfer_message([1; 32], [2; 32], 5000);
+        let parsed = parse_message(&message, &[1; 32]).unwrap();
+        assert_eq!(parsed.version, MessageVersion::Legacy);
+        let intent = parsed.intent();
+        assert_eq!(intent.transfers, vec![Transfer {
+            asset: NATIVE_ASSET.to_string(),
+            destination: bs58::encode([2; 32]).into_string(),
+            amount: 5000,
+        }]);
+
+        // The recipient doesn't sign the message
+        assert!(parse_message(&message, &[2; 32]).is_err());
+        assert!(parse_message(&message[..message.len() - 1], &[1; 32]).is_err());
+    }
+
+    #[test]
+    fn v0_lookup_accounts_are_indexed_after_static_ones() {
+        let mut message = vec![VERSION_PREFIX];
+        message.extend(transfer_message([1; 32], [2; 32], 5000));
+        // Recipient taken from the first writable entry of a lookup table
+        let recipient = message.len() - 14;
+        message[recipient] = 3;
+        message.extend_from_slice(&[1]);
+        message.extend_from_slice(&[9; 32]);
+        message.extend_from_slice(&[1, 4, 0]);
+        let parsed = parse_message(&message, &[1; 32]).unwrap();
+        assert_eq!(parsed.version, MessageVersion::V0);
+        match &parsed.instructions[0] {
+            Instruction::SystemTransfer { to, .. } => {
+                let table = bs58::encode([9; 32]).into_string();
+                assert_eq!(to, &Account::Lookup { table, index: 4 });
+            }
+            other => panic!("Unexpected instruction {:?}", other),
+        }
+    }
+}
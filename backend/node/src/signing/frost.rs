@@ -0,0 +1,212 @@
+//! Two round FROST signing, shared by the Ed25519, BIP340 and Sr25519 schemes.
+//!
+//! The nonce commitments, binding factors, signature shares and their verification are the same
+//! for every scheme, so a ciphersuite only provides the group, its encoding and the hashes.
+
+use anyhow::{ anyhow, bail, Result };
+use serde::{ Deserialize, Serialize };
+use std::ops::{ Add, Mul };
+
+/// Commitments to the hiding and binding nonces of a signer
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NonceCommitment<P> {
+    pub party_index: usize,
+    pub hiding: P,
+    pub binding: P,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SignatureShare<S> {
+    pub party_index: usize,
+    pub z_i: S,
+}
+
+/// Group, encoding and hashes of a signature scheme that is signed with FROST
+pub trait Ciphersuite {
+    type Point: Clone +
+        PartialEq +
+        Add<Output = Self::Point> +
+        Mul<Self::Scalar, Output = Self::Point>;
+    type Scalar: Clone + Add<Output = Self::Scalar> + Mul<Output = Self::Scalar>;
+
+    fn identity() -> Self::Point;
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point;
+
+    fn scalar_zero() -> Self::Scalar;
+
+    fn scalar_one() -> Self::Scalar;
+
+    fn encode_point(point: &Self::Point) -> Vec<u8>;
+
+    fn lagrange_coefficient(party_index: usize, indices: &[usize]) -> Result<Self::Scalar>;
+
+    /// Encoding of a signer in the commitments that the binding factors are computed from
+    fn encode_identifier(party_index: usize) -> Vec<u8> {
+        (party_index as u32).to_be_bytes().to_vec()
+    }
+
+    /// Binding factor of a signer, which commits it to the message and the full set of
+    /// commitments, as well as to the adaptor point of a pre-signature
+    fn binding_factor(
+        party_index: usize,
+        public_key: &Self::Point,
+        message: &[u8],
+        encoded_commitments: &[u8],
+        adaptor_point: Option<&Self::Point>
+    ) -> Self::Scalar;
+
+    /// Factor of 1 or -1 that the group commitment is multiplied with, for schemes that only
+    /// accept nonce points of one parity
+    fn nonce_factor(_group_commitment: &Self::Point) -> Result<Self::Scalar> {
+        Ok(Self::scalar_one())
+    }
+
+    fn challenge(
+        group_commitment: &Self::Point,
+        public_key: &Self::Point,
+        message: &[u8]
+    ) -> Self::Scalar;
+}
+
+/// Everything that signers need to agree on after the nonce commitments are exchanged
+pub struct SigningPackage<C: Ciphersuite> {
+    indices: Vec<usize>,
+    commitments: Vec<NonceCommitment<C::Point>>,
+    binding_factors: Vec<C::Scalar>,
+    nonce_factor: C::Scalar,
+    group_commitment: C::Point,
+    challenge: C::Scalar,
+}
+
+impl<C: Ciphersuite> SigningPackage<C> {
+    pub fn new(
+        message: &[u8],
+        public_key: &C::Point,
+        commitments: Vec<NonceCommitment<C::Point>>
+    ) -> Result<Self> {
+        Self::with_adaptor_point(message, public_key, commitments, None)
+    }
+
+    /// Package of an adaptor pre-signature, whose group commitment includes the adaptor point
+    /// so that the aggregated signature only verifies once the adaptor secret is added to it
+    pub fn with_adaptor_point(
+        message: &[u8],
+        public_key: &C::Point,
+        mut commitments: Vec<NonceCommitment<C::Point>>,
+        adaptor_point: Option<&C::Point>
+    ) -> Result<Self> {
+        commitments.sort_by_key(|commitment| commitment.party_index);
+        let indices = commitments
+            .iter()
+            .map(|commitment| commitment.party_index)
+            .collect::<Vec<_>>();
+        if indices.windows(2).any(|pair| pair[0] == pair[1]) {
+            bail!("Received more than one nonce commitment from the same party");
+        }
+
+        let mut encoded_commitments = Vec::new();
+        for commitment in commitments.iter() {
+            encoded_commitments.extend_from_slice(&C::encode_identifier(commitment.party_index));
+            encoded_commitments.extend_from_slice(&C::encode_point(&commitment.hiding));
+            encoded_commitments.extend_from_slice(&C::encode_point(&commitment.binding));
+        }
+        let binding_factors = indices
+            .iter()
+            .map(|&index| {
+                C::binding_factor(index, public_key, message, &encoded_commitments, adaptor_point)
+            })
+            .collect::<Vec<_>>();
+
+        let group_commitment = commitments
+            .iter()
+            .zip(binding_factors.iter())
+            .fold(adaptor_point.cloned().unwrap_or_else(C::identity), |acc, (commitment, rho)| {
+                acc + commitment.hiding.clone() + commitment.binding.clone() * rho.clone()
+            });
+        let nonce_factor = C::nonce_factor(&group_commitment)?;
+        let group_commitment = group_commitment * nonce_factor.clone();
+        let challenge = C::challenge(&group_commitment, public_key, message);
+
+        Ok(Self {
+            indices,
+            commitments,
+            binding_factors,
+            nonce_factor,
+            group_commitment,
+            challenge,
+        })
+    }
+
+    /// Indices of the signers, in ascending order
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn nonce_factor(&self) -> &C::Scalar {
+        &self.nonce_factor
+    }
+
+    pub fn challenge(&self) -> &C::Scalar {
+        &self.challenge
+    }
+
+    pub fn signature_share(
+        &self,
+        party_index: usize,
+        hiding_nonce: &C::Scalar,
+        binding_nonce: &C::Scalar,
+        x_i: &C::Scalar
+    ) -> Result<C::Scalar> {
+        let position = self.position(party_index)?;
+        let lambda = C::lagrange_coefficient(party_index, &self.indices)?;
+        let rho = self.binding_factors[position].clone();
+        let nonce =
+            (hiding_nonce.clone() + binding_nonce.clone() * rho) * self.nonce_factor.clone();
+        Ok(nonce + self.challenge.clone() * lambda * x_i.clone())
+    }
+
+    /// Checks a signature share against the public share of its sender, so that a party
+    /// which sends an invalid share is identified before the signature is put together
+    pub fn verify_share(
+        &self,
+        share: &SignatureShare<C::Scalar>,
+        public_share: &C::Point
+    ) -> Result<()> {
+        let position = self.position(share.party_index)?;
+        let commitment = &self.commitments[position];
+        let lambda = C::lagrange_coefficient(share.party_index, &self.indices)?;
+        let rho = self.binding_factors[position].clone();
+        let nonce_commitment =
+            (commitment.hiding.clone() + commitment.binding.clone() * rho) *
+            self.nonce_factor.clone();
+        let expected = nonce_commitment + public_share.clone() * (self.challenge.clone() * lambda);
+        if C::base_mul(&share.z_i) != expected {
+            bail!("Signature share of party {} did not pass verification", share.party_index);
+        }
+        Ok(())
+    }
+
+    /// Group commitment and the sum of the signature shares
+    pub fn aggregate(
+        &self,
+        shares: &[SignatureShare<C::Scalar>]
+    ) -> Result<(C::Point, C::Scalar)> {
+        if shares.len() != self.indices.len() {
+            bail!(
+                "Expected {} signature shares, but received {}",
+                self.indices.len(),
+                shares.len()
+            );
+        }
+        let s = shares.iter().fold(C::scalar_zero(), |acc, share| acc + share.z_i.clone());
+        Ok((self.group_commitment.clone(), s))
+    }
+
+    fn position(&self, party_index: usize) -> Result<usize> {
+        self.indices
+            .iter()
+            .position(|&index| index == party_index)
+            .ok_or_else(|| anyhow!("Party {} did not commit to nonces", party_index))
+    }
+}
@@ -1,12 +1,19 @@
 use crate::command::{ JsonCommand, MsgContext };
-use anyhow::Result;
+use anyhow::{ anyhow, bail, Result };
 use serde::{ Deserialize, Serialize };
 use shared::key_info::NodeId;
 
+pub mod adaptor;
+pub mod bls;
 pub mod ecdsa;
 pub mod eddsa;
+pub mod frost;
+pub mod policy;
+pub mod request_auth;
+pub mod schnorr_secp256k1;
 pub mod sr25519;
 pub mod sr25519_musign;
+pub mod substrate;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SigningCommand {
@@ -15,17 +22,145 @@ pub struct SigningCommand {
     pub key_id: String,
     pub session_id: String,
     pub party_nodes: Vec<NodeId>,
+    #[serde(default)]
     pub msg: Vec<u8>,
+    /// Messages signed together in a single session, replacing `msg` when not empty
+    #[serde(default)]
+    pub msgs: Vec<Vec<u8>>,
+    /// Presignature created with the same party nodes, only supported for ECDSA
+    #[serde(default)]
+    pub presignature_id: Option<String>,
+    /// BIP32 path of a non-hardened child key such as m/0/17, only supported for secp256k1 keys
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+    /// BIP341 tweak of the signing key, only supported for BIP340 Schnorr
+    #[serde(default)]
+    pub taproot: Option<schnorr_secp256k1::TaprootTweak>,
+    /// Protocol of EdDSA signing, ignored for other keys
+    #[serde(default)]
+    pub mode: eddsa::SigningMode,
+    /// Format of `msg` when the guardians decode it, only supported for Sr25519 here as other
+    /// keys receive payloads with the signing requests of clients
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+    /// Encoding of the signature returned next to `r`, `s` and `recid`, only supported for ECDSA
+    #[serde(default)]
+    pub signature_format: Option<ecdsa::encoding::SignatureFormat>,
+    /// Domain of the beacon object whose root is `msg`, only supported for BLS12-381 keys
+    #[serde(default)]
+    pub beacon_domain: Option<bls::BeaconDomain>,
+    /// Compressed adaptor point, hex encoded, that turns the request into an adaptor
+    /// pre-signature for an atomic swap, only supported for BIP340 Schnorr and EdDSA keys
+    #[serde(default)]
+    pub adaptor_point: Option<String>,
+    /// Request of the client for every party node, in the order of `party_nodes`, required for
+    /// BIP340 Schnorr signatures
+    #[serde(default)]
+    pub schnorr_requests: Vec<schnorr_secp256k1::session::NewSchnorrKeySignMessage>,
 }
 
 impl JsonCommand for SigningCommand {
     type Response = SigningResponse;
 
     fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        if self.payload_type.is_some() && !matches!(self.kind, Key::Sr25519) {
+            bail!("Orchestrated signing only decodes payloads for Sr25519 keys");
+        }
+        if self.signature_format.is_some() && !matches!(self.kind, Key::ECDSA) {
+            bail!("Signature formats are only supported for ECDSA keys");
+        }
+        if self.beacon_domain.is_some() && !matches!(self.kind, Key::Bls12381) {
+            bail!("Beacon domains are only supported for BLS12-381 keys");
+        }
+        if !self.schnorr_requests.is_empty() && !matches!(self.kind, Key::SchnorrSecp256k1) {
+            bail!("Client requests are only relayed for BIP340 Schnorr signatures");
+        }
+        if self.adaptor_point.is_some() {
+            return adaptor::orchestrate::orchestrate(self, ctx);
+        }
         match self.kind {
             Key::ECDSA => ecdsa::orchestrate::orchestrate(self, ctx),
             Key::EDDSA => eddsa::orchestrate::orchestrate(self, ctx),
-            Key::Sr25519 => { todo!() }
+            Key::SchnorrSecp256k1 => schnorr_secp256k1::orchestrate::orchestrate(self, ctx),
+            Key::Sr25519 => sr25519_musign::orchestrate(self, ctx),
+            Key::Bls12381 => bls::orchestrate::orchestrate(self, ctx),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PresignCommand {
+    pub key_id: String,
+    pub session_id: String,
+    pub party_nodes: Vec<NodeId>,
+    /// Request of the client for every party node, in the order of `party_nodes`
+    pub requests: Vec<ecdsa::NewPresignMessage>,
+}
+
+impl JsonCommand for PresignCommand {
+    type Response = PresignResponse;
+
+    fn execute_message(self, ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        ecdsa::orchestrate::orchestrate_presign(self, ctx)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DerivePublicKeyCommand {
+    pub key_id: String,
+    pub derivation_path: String,
+}
+
+impl JsonCommand for DerivePublicKeyCommand {
+    type Response = DerivedPublicKey;
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        ecdsa::orchestrate::derive_public_key(self)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DerivedPublicKey {
+    /// Compressed child public key, hex encoded
+    pub public_key: String,
+    pub chain_code: String,
+    pub xpub: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PresignResponse {
+    pub presignature_id: String,
+    pub r: String,
+}
+
+/// Format of a payload that the guardians decode and hash themselves instead of signing the
+/// client supplied bytes as they are
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadType {
+    /// Unsigned legacy transaction, RLP encoded with or without EIP-155 fields
+    EvmLegacyTx,
+    /// Unsigned type 2 transaction, `0x02 || rlp(...)`
+    EvmEip1559Tx,
+    /// EIP-712 typed data as JSON
+    Eip712,
+    /// Message signed with the `personal_sign` prefix
+    PersonalSign,
+    /// Serialized legacy or v0 Solana message
+    SolanaMessage,
+    /// Substrate extrinsic payload in the JSON form of polkadot-js signer payloads
+    SubstratePayload,
+}
+
+impl PayloadType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadType::EvmLegacyTx => "evm_legacy_tx",
+            PayloadType::EvmEip1559Tx => "evm_eip1559_tx",
+            PayloadType::Eip712 => "eip712",
+            PayloadType::PersonalSign => "personal_sign",
+            PayloadType::SolanaMessage => "solana_message",
+            PayloadType::SubstratePayload => "substrate_payload",
         }
     }
 }
@@ -36,6 +171,9 @@ pub enum Key {
     ECDSA,
     EDDSA,
     Sr25519,
+    /// BIP340 Schnorr signatures with the keyshares of an ECDSA key
+    SchnorrSecp256k1,
+    Bls12381,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -43,4 +181,103 @@ pub enum Key {
 pub enum SigningResponse {
     ECDSA(ecdsa::SigningResult),
     EDDSA(eddsa::SignatureResult),
+    Bls12381(bls::BlsSignatureResult),
+    Adaptor(adaptor::AdaptorSignatureResult),
+    Schnorr(schnorr_secp256k1::SchnorrSignatureResult),
+    Sr25519(sr25519_musign::Sr25519SignatureResult),
+    /// Results of a batch, in the same order as the signed messages
+    Batch(Vec<SigningResponse>),
+    /// A message of a batch that could not be signed
+    Failed(SigningError),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SigningError {
+    pub error: String,
+}
+
+impl SigningResponse {
+    pub fn failed(error: String) -> Self {
+        SigningResponse::Failed(SigningError { error })
+    }
+
+    fn is_failed(&self) -> bool {
+        matches!(self, SigningResponse::Failed(_))
+    }
+}
+
+/// Combines the batch results of every party, an item only fails if no party could sign it
+pub fn merge_batch_results(
+    party_results: Vec<Vec<SigningResponse>>,
+    msg_count: usize
+) -> Result<SigningResponse> {
+    if let Some(results) = party_results.iter().find(|results| results.len() != msg_count) {
+        bail!("Expected {} batch results from every party, got {}", msg_count, results.len());
+    }
+    let mut merged = Vec::with_capacity(msg_count);
+    for index in 0..msg_count {
+        let mut item = None;
+        for results in party_results.iter() {
+            let result = results
+                .get(index)
+                .ok_or_else(|| anyhow!("Batch result is missing item {}", index))?;
+            if item.as_ref().map_or(true, SigningResponse::is_failed) {
+                item = Some(result.clone());
+            }
+        }
+        merged.push(item.ok_or_else(|| anyhow!("No batch results were received"))?);
+    }
+    Ok(SigningResponse::Batch(merged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed() -> SigningResponse {
+        SigningResponse::failed("busy".to_string())
+    }
+
+    fn signed(r: &str) -> SigningResponse {
+        SigningResponse::ECDSA(ecdsa::SigningResult {
+            r: r.to_string(),
+            s: "s".to_string(),
+            recid: 0,
+            encoded: None,
+            v: None,
+        })
+    }
+
+    fn items(merged: SigningResponse) -> Vec<SigningResponse> {
+        match merged {
+            SigningResponse::Batch(items) => items,
+            other => panic!("Expected a batch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn batch_item_fails_only_if_no_party_signed_it() {
+        let party_results = vec![
+            vec![signed("a"), failed()],
+            vec![failed(), failed()]
+        ];
+        let merged = items(merge_batch_results(party_results, 2).unwrap());
+
+        assert!(matches!(&merged[0], SigningResponse::ECDSA(sig) if sig.r == "a"));
+        assert!(matches!(&merged[1], SigningResponse::Failed(_)));
+    }
+
+    #[test]
+    fn batch_results_of_the_wrong_length_are_refused() {
+        let short = vec![vec![signed("a"), signed("b")], vec![signed("a")]];
+        assert!(merge_batch_results(short, 2).is_err());
+
+        let long = vec![vec![signed("a"), signed("b"), signed("c")], vec![signed("a"), failed()]];
+        assert!(merge_batch_results(long, 2).is_err());
+    }
+
+    #[test]
+    fn batch_without_party_results_is_refused() {
+        assert!(merge_batch_results(Vec::new(), 1).is_err());
+    }
 }
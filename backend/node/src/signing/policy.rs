@@ -0,0 +1,449 @@
+//! Signing policies enforced by each guardian on its own.
+//!
+//! A policy is stored per key and checked once a signing request is authorized, before the node
+//! joins the signing session. Policies are set by the compliance administrator of the node, whose
+//! identity key is part of the node configuration, so a compromised client cannot relax them.
+//! Every decision is logged together with its reason.
+//!
+//! Rules on value and destinations need to know what is being signed. Requests whose payload
+//! could not be decoded are therefore refused as soon as such a rule is set.
+use crate::client_identity::{ ClientIdentityKey, IdentityKeyKind };
+use crate::command::{ JsonCommand, MsgContext };
+use crate::config::{ Config, ConfigProvider };
+use crate::signing::request_auth::CanonicalEncoder;
+use crate::storage::fs::WriteOpts;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use anyhow::{ anyhow, bail, Result };
+use chrono::{ DateTime, Duration, NaiveDate, Timelike, Utc };
+use serde::{ Deserialize, Serialize };
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tracing::{ info, warn };
+
+const POLICY_METADATA: &str = "signing_policy";
+const USAGE_METADATA: &str = "signing_policy_usage";
+/// Asset name used for the native currency of a chain in value limits
+pub const NATIVE_ASSET: &str = "native";
+
+// Evaluating a request and recording its usage must not interleave with another request
+static POLICY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    /// Opaque bytes, neither value nor destinations are known
+    Raw,
+    /// Message authorizing the transfer of the key to a new owner
+    OwnershipTransfer,
+    EvmTransaction,
+    Eip712,
+    PersonalSign,
+    SolanaMessage,
+    SubstrateExtrinsic,
+}
+
+/// Value moved by a signed message
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Transfer {
+    /// [NATIVE_ASSET] or the address of a token contract
+    pub asset: String,
+    pub destination: String,
+    pub amount: u128,
+}
+
+/// What a single message to sign does, as far as the node could decode it
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SigningIntent {
+    pub kind: MessageKind,
+    /// Accounts or contracts the message interacts with
+    pub destinations: Vec<String>,
+    pub transfers: Vec<Transfer>,
+}
+
+impl SigningIntent {
+    pub fn raw() -> Self {
+        Self { kind: MessageKind::Raw, destinations: Vec::new(), transfers: Vec::new() }
+    }
+
+    pub fn ownership_transfer() -> Self {
+        Self {
+            kind: MessageKind::OwnershipTransfer,
+            destinations: Vec::new(),
+            transfers: Vec::new(),
+        }
+    }
+
+    /// Whether the node knows what the message does, an ownership transfer moves no value.
+    /// Calls of Substrate extrinsics are only known with the runtime metadata.
+    fn is_decoded(&self) -> bool {
+        !matches!(self.kind, MessageKind::Raw | MessageKind::SubstrateExtrinsic)
+    }
+}
+
+/// UTC hours during which signing is allowed, wrapping past midnight when `end_hour` is
+/// smaller than `start_hour`
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TimeWindow {
+    pub start_hour: u32,
+    /// Exclusive
+    pub end_hour: u32,
+}
+
+impl TimeWindow {
+    fn contains(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct VelocityLimit {
+    /// Messages signed within `window_secs`
+    pub max_messages: usize,
+    pub window_secs: i64,
+}
+
+/// Rules a key is signed under, unset rules don't restrict signing
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct SigningPolicy {
+    /// Increased with every update so that an older policy can't be replayed
+    pub version: u64,
+    /// Amount of each asset that can be moved per UTC day
+    #[serde(default)]
+    pub daily_value_limits: BTreeMap<String, u128>,
+    #[serde(default)]
+    pub destination_allowlist: Option<Vec<String>>,
+    #[serde(default)]
+    pub allowed_message_kinds: Option<Vec<MessageKind>>,
+    #[serde(default)]
+    pub time_window: Option<TimeWindow>,
+    #[serde(default)]
+    pub velocity_limit: Option<VelocityLimit>,
+}
+
+/// Signing activity of a key counted by its policy
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+struct PolicyUsage {
+    day: Option<NaiveDate>,
+    spent: BTreeMap<String, u128>,
+    signed_at: Vec<DateTime<Utc>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PolicyDecision {
+    Allow(String),
+    Deny(String),
+}
+
+impl SigningPolicy {
+    pub fn load(key_id: &str, email: &str) -> Result<Option<Self>> {
+        match KeyMetadataStore::get(key_id, POLICY_METADATA, email) {
+            Ok(saved) => Ok(Some(serde_json::from_str(&saved)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn save(&self, key_id: &str, email: &str) -> Result<()> {
+        KeyMetadataStore::save(
+            &serde_json::to_string(self)?,
+            key_id,
+            POLICY_METADATA,
+            email,
+            &WriteOpts::Modify
+        )
+    }
+
+    fn evaluate(
+        &self,
+        intents: &[SigningIntent],
+        usage: &PolicyUsage,
+        now: DateTime<Utc>
+    ) -> PolicyDecision {
+        if let Some(kinds) = &self.allowed_message_kinds {
+            if let Some(intent) = intents.iter().find(|intent| !kinds.contains(&intent.kind)) {
+                return PolicyDecision::Deny(format!("{:?} messages are not allowed", intent.kind));
+            }
+        }
+
+        if let Some(window) = &self.time_window {
+            if !window.contains(now.hour()) {
+                return PolicyDecision::Deny(
+                    format!(
+                        "Signing is only allowed between {}:00 and {}:00 UTC",
+                        window.start_hour,
+                        window.end_hour
+                    )
+                );
+            }
+        }
+
+        if let Some(limit) = &self.velocity_limit {
+            let since = now - Duration::seconds(limit.window_secs);
+            let recent = usage.signed_at
+                .iter()
+                .filter(|signed_at| **signed_at > since)
+                .count();
+            if recent + intents.len() > limit.max_messages {
+                return PolicyDecision::Deny(
+                    format!(
+                        "Velocity limit of {} messages per {} seconds exceeded",
+                        limit.max_messages,
+                        limit.window_secs
+                    )
+                );
+            }
+        }
+
+        let restricts_value =
+            !self.daily_value_limits.is_empty() || self.destination_allowlist.is_some();
+        if restricts_value && intents.iter().any(|intent| !intent.is_decoded()) {
+            return PolicyDecision::Deny(
+                "Payload could not be decoded, value and destinations are unknown".to_string()
+            );
+        }
+
+        if let Some(allowlist) = &self.destination_allowlist {
+            let destinations = intents
+                .iter()
+                .flat_map(|intent| {
+                    intent.destinations
+                        .iter()
+                        .chain(intent.transfers.iter().map(|transfer| &transfer.destination))
+                });
+            for destination in destinations {
+                if !allowlist.iter().any(|allowed| allowed.eq_ignore_ascii_case(destination)) {
+                    return PolicyDecision::Deny(
+                        format!("Destination {} is not on the allowlist", destination)
+                    );
+                }
+            }
+        }
+
+        let spent = self.spent_after(intents, usage, now);
+        for (asset, limit) in &self.daily_value_limits {
+            let amount = spent.get(asset).copied().unwrap_or(0);
+            if amount > *limit {
+                return PolicyDecision::Deny(
+                    format!("Daily limit of {} {} would be exceeded: {}", limit, asset, amount)
+                );
+            }
+        }
+
+        PolicyDecision::Allow(
+            format!("{} message(s) satisfy policy version {}", intents.len(), self.version)
+        )
+    }
+
+    /// Value moved today once the intents are signed, saturating on overflow
+    fn spent_after(
+        &self,
+        intents: &[SigningIntent],
+        usage: &PolicyUsage,
+        now: DateTime<Utc>
+    ) -> BTreeMap<String, u128> {
+        let mut spent = if usage.day == Some(now.date_naive()) {
+            usage.spent.clone()
+        } else {
+            BTreeMap::new()
+        };
+        for transfer in intents.iter().flat_map(|intent| &intent.transfers) {
+            let amount = spent.entry(transfer.asset.clone()).or_insert(0);
+            *amount = amount.saturating_add(transfer.amount);
+        }
+        spent
+    }
+
+    fn record(
+        &self,
+        intents: &[SigningIntent],
+        usage: PolicyUsage,
+        now: DateTime<Utc>
+    ) -> PolicyUsage {
+        let spent = self.spent_after(intents, &usage, now);
+        // Only keep what the velocity limit still looks at
+        let since = now - Duration::seconds(
+            self.velocity_limit.as_ref().map_or(0, |limit| limit.window_secs)
+        );
+        let mut signed_at: Vec<_> = usage.signed_at
+            .into_iter()
+            .filter(|signed_at| *signed_at > since)
+            .collect();
+        signed_at.extend(intents.iter().map(|_| now));
+        PolicyUsage { day: Some(now.date_naive()), spent, signed_at }
+    }
+}
+
+/// Intents of a request whose messages are signed as opaque bytes: one per message of a batch,
+/// or the single message of the request
+pub fn raw_intents(batch_size: usize, is_transfer_tx: bool) -> Vec<SigningIntent> {
+    if is_transfer_tx {
+        return vec![SigningIntent::ownership_transfer()];
+    }
+    vec![SigningIntent::raw(); batch_size.max(1)]
+}
+
+/// Check the messages of a signing request against the policy of the key, recording them as
+/// signed when allowed. Keys without a policy are signed without restrictions.
+pub fn enforce_policy(key_id: &str, email: &str, intents: &[SigningIntent]) -> Result<()> {
+    let _guard = POLICY_LOCK.lock().map_err(|_| anyhow!("Policy lock is poisoned"))?;
+    let policy = match SigningPolicy::load(key_id, email)? {
+        Some(policy) => policy,
+        None => {
+            info!("Policy decision for key {}: allowed, no policy is set", key_id);
+            return Ok(());
+        }
+    };
+
+    let usage = match KeyMetadataStore::get(key_id, USAGE_METADATA, email) {
+        Ok(saved) => serde_json::from_str(&saved)?,
+        Err(_) => PolicyUsage::default(),
+    };
+    let now = Utc::now();
+    match policy.evaluate(intents, &usage, now) {
+        PolicyDecision::Allow(reason) => {
+            info!("Policy decision for key {}: allowed, {}", key_id, reason);
+            let usage = policy.record(intents, usage, now);
+            KeyMetadataStore::save(
+                &serde_json::to_string(&usage)?,
+                key_id,
+                USAGE_METADATA,
+                email,
+                &WriteOpts::Modify
+            )
+        }
+        PolicyDecision::Deny(reason) => {
+            warn!("Policy decision for key {}: denied, {}", key_id, reason);
+            bail!("Signing policy denied the request: {}", reason)
+        }
+    }
+}
+
+/// Policy administrator key configured as `ed25519:<base64>` or `p256:<base64>`
+fn policy_admin_key() -> Result<ClientIdentityKey> {
+    let configured = Config::policy_admin_key().ok_or_else(||
+        anyhow!("No policy administrator key is configured on this node")
+    )?;
+    let (kind, public_key) = configured
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Policy administrator key must be prefixed by its kind"))?;
+    let kind = match kind {
+        "ed25519" => IdentityKeyKind::Ed25519,
+        "p256" => IdentityKeyKind::P256,
+        _ => bail!("Unknown policy administrator key kind: {}", kind),
+    };
+    let key = ClientIdentityKey { kind, public_key: public_key.to_string() };
+    key.validate()?;
+    Ok(key)
+}
+
+/// Replace the signing policy of a key, signed by the policy administrator of the node
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateSigningPolicyCommand {
+    pub key_id: String,
+    /// Owner of the key, left out for keys stored without one
+    #[serde(default)]
+    pub email: Option<String>,
+    /// JSON encoded [SigningPolicy], kept as signed by the administrator
+    pub policy: String,
+    /// Base64 signature of the canonical command by the policy administrator key
+    pub admin_signature: String,
+}
+
+impl UpdateSigningPolicyCommand {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("signing_policy")
+            .str("key_id", &self.key_id)
+            .opt_str("email", self.email.as_deref())
+            .str("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl JsonCommand for UpdateSigningPolicyCommand {
+    type Response = ();
+
+    fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
+        policy_admin_key()?.verify(&self.canonical_encoding(), &self.admin_signature)?;
+        let policy: SigningPolicy = serde_json::from_str(&self.policy)?;
+        if let Some(window) = &policy.time_window {
+            if window.start_hour > 23 || window.end_hour > 24 {
+                bail!("Time window hours must be within a day");
+            }
+        }
+
+        let email = self.email.as_deref().unwrap_or_default();
+        let _guard = POLICY_LOCK.lock().map_err(|_| anyhow!("Policy lock is poisoned"))?;
+        if let Some(current) = SigningPolicy::load(&self.key_id, email)? {
+            if policy.version <= current.version {
+                bail!(
+                    "Policy version {} is not newer than the current version {}",
+                    policy.version,
+                    current.version
+                );
+            }
+        }
+        policy.save(&self.key_id, email)?;
+        info!("Updated signing policy of key {} to version {}", self.key_id, policy.version);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn transfer(destination: &str, amount: u128) -> SigningIntent {
+        SigningIntent {
+            kind: MessageKind::EvmTransaction,
+            destinations: Vec::new(),
+            transfers: vec![Transfer {
+                asset: NATIVE_ASSET.to_string(),
+                destination: destination.to_string(),
+                amount,
+            }],
+        }
+    }
+
+    #[test]
+    fn policy_limits_value_destinations_and_velocity() {
+        let policy = SigningPolicy {
+            version: 1,
+            daily_value_limits: [(NATIVE_ASSET.to_string(), 100)].into_iter().collect(),
+            destination_allowlist: Some(vec!["alice".to_string()]),
+            velocity_limit: Some(VelocityLimit { max_messages: 2, window_secs: 60 }),
+            ..Default::default()
+        };
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let usage = PolicyUsage::default();
+        let denied = |intents: &[SigningIntent], usage: &PolicyUsage, at| {
+            matches!(policy.evaluate(intents, usage, at), PolicyDecision::Deny(_))
+        };
+
+        assert!(denied(&[SigningIntent::raw()], &usage, now));
+        assert!(!denied(&[SigningIntent::ownership_transfer()], &usage, now));
+        assert!(denied(&[transfer("mallory", 1)], &usage, now));
+        assert!(denied(&[transfer("alice", 101)], &usage, now));
+
+        let intents = [transfer("alice", 60)];
+        assert!(!denied(&intents, &usage, now));
+        let usage = policy.record(&intents, usage, now);
+        assert!(denied(&intents, &usage, now));
+        // The daily limit resets on the next day
+        assert!(!denied(&intents, &usage, now + Duration::days(1)));
+        let usage = policy.record(&[transfer("alice", 1)], usage, now);
+        assert!(denied(&[transfer("alice", 1)], &usage, now + Duration::seconds(30)));
+        assert!(!denied(&[transfer("alice", 1)], &usage, now + Duration::seconds(61)));
+    }
+
+    #[test]
+    fn time_window_wraps_past_midnight() {
+        let window = TimeWindow { start_hour: 22, end_hour: 6 };
+        assert!(window.contains(23) && window.contains(0) && window.contains(5));
+        assert!(!window.contains(6) && !window.contains(12));
+    }
+}
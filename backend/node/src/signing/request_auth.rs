@@ -0,0 +1,370 @@
+//! Authentication of signing, decryption and recovery requests sent by clients.
+//!
+//! Users with a registered identity key sign a canonical encoding of every field of the request
+//! apart from the MAC and the signature. Other users send a base64 HMAC-SHA256 over the same
+//! encoding, keyed with their access key. Requests without an `auth_version` come from clients
+//! that only MAC `timestamp + email`; those are rejected unless the node explicitly allows
+//! legacy authentication.
+use crate::auth::e2e_decrypt;
+use crate::client_identity::ClientIdentityKey;
+use crate::config::{ Config, ConfigProvider };
+use crate::node::NodeIdentity;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use crate::storage::fs::WriteOpts;
+use anyhow::{ anyhow, bail, Result };
+use chrono::{ DateTime, Utc };
+use hmac::{ Hmac, Mac, NewMac };
+use serde::{ Deserialize, Serialize };
+use sha2::Sha256;
+use tracing::{ error, info, warn };
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// MAC over `timestamp + email` only
+pub const LEGACY_AUTH_VERSION: u8 = 1;
+/// MAC over the canonical encoding of the whole request
+pub const AUTH_VERSION: u8 = 2;
+
+const CANONICAL_ENCODING_TAG: &[u8] = b"gridlock/sign-request/v2";
+
+/// Client request whose fields are covered by the client MAC or signature
+pub trait AuthenticatedRequest {
+    fn canonical_encoding(&self) -> Vec<u8>;
+    fn credentials(&self) -> RequestCredentials<'_>;
+}
+
+/// Fields that clients add to a request to authorize it, flattened into the request
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ClientCredentials {
+    pub client_e2e_public_key: String,
+    /// Access key encrypted to this node, not needed once an identity key is registered
+    #[serde(default)]
+    pub encrypted_signing_key: String,
+    pub timestamp: Option<String>,
+    pub message_hmac: Option<String>,
+    pub email: Option<String>,
+    /// Version of the scheme `message_hmac` was computed with, left out by legacy clients
+    #[serde(default)]
+    pub auth_version: Option<u8>,
+    /// Signature by the identity key of the user, replacing `message_hmac` once one is registered
+    #[serde(default)]
+    pub client_signature: Option<String>,
+}
+
+impl ClientCredentials {
+    /// Credentials for a request on the given key
+    pub fn for_key<'a>(&'a self, key_id: &'a str) -> RequestCredentials<'a> {
+        RequestCredentials {
+            key_id,
+            email: self.email.as_deref(),
+            timestamp: self.timestamp.as_deref(),
+            message_hmac: self.message_hmac.as_deref(),
+            auth_version: self.auth_version,
+            client_signature: self.client_signature.as_deref(),
+            client_e2e_public_key: &self.client_e2e_public_key,
+            encrypted_signing_key: &self.encrypted_signing_key,
+        }
+    }
+}
+
+/// Fields of a request used to authorize it
+pub struct RequestCredentials<'a> {
+    pub key_id: &'a str,
+    pub email: Option<&'a str>,
+    pub timestamp: Option<&'a str>,
+    pub message_hmac: Option<&'a str>,
+    /// `None` for clients predating versioned authentication
+    pub auth_version: Option<u8>,
+    /// Base64 signature of the canonical request by the identity key of the user
+    pub client_signature: Option<&'a str>,
+    pub client_e2e_public_key: &'a str,
+    pub encrypted_signing_key: &'a str,
+}
+
+/// Canonical encoding of a request: the domain tag, the request kind and then every field as
+/// its name and value, each prefixed by its length as a big endian u64.
+/// Optional fields are prefixed by a presence byte and lists by their item count.
+pub struct CanonicalEncoder {
+    buf: Vec<u8>,
+}
+
+impl CanonicalEncoder {
+    pub fn new(kind: &str) -> Self {
+        let mut encoder = Self { buf: Vec::new() };
+        encoder.put(CANONICAL_ENCODING_TAG);
+        encoder.put(kind.as_bytes());
+        encoder
+    }
+
+    pub fn bytes(mut self, name: &str, value: &[u8]) -> Self {
+        self.put(name.as_bytes());
+        self.put(value);
+        self
+    }
+
+    pub fn str(self, name: &str, value: &str) -> Self {
+        self.bytes(name, value.as_bytes())
+    }
+
+    pub fn bool(self, name: &str, value: bool) -> Self {
+        self.bytes(name, &[value as u8])
+    }
+
+    pub fn list(mut self, name: &str, values: &[Vec<u8>]) -> Self {
+        self.put(name.as_bytes());
+        self.buf.extend_from_slice(&(values.len() as u64).to_be_bytes());
+        for value in values {
+            self.put(value);
+        }
+        self
+    }
+
+    pub fn opt_str(self, name: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) => self.bool(name, true).str(name, value),
+            None => self.bool(name, false),
+        }
+    }
+
+    pub fn opt_bool(self, name: &str, value: Option<bool>) -> Self {
+        match value {
+            Some(value) => self.bool(name, true).bool(name, value),
+            None => self.bool(name, false),
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn put(&mut self, value: &[u8]) {
+        self.buf.extend_from_slice(&(value.len() as u64).to_be_bytes());
+        self.buf.extend_from_slice(value);
+    }
+}
+
+/// Authorize a client signing request and check its timestamp, returning the email of the user
+pub(crate) fn authorize_request<R: AuthenticatedRequest>(request: &R) -> Result<String> {
+    authorize(request, ClientIdentityKey::load)
+}
+
+/// Authorize a request of a user in recovery, who lost the identity key registered for the key,
+/// by the identity key they recover with
+pub(crate) fn authorize_recovery_request<R: AuthenticatedRequest>(
+    request: &R,
+    identity: &ClientIdentityKey
+) -> Result<String> {
+    authorize(request, |_, _| Some(identity.clone()))
+}
+
+fn authorize<R, F>(request: &R, identity_key: F) -> Result<String>
+    where R: AuthenticatedRequest, F: FnOnce(&str, &str) -> Option<ClientIdentityKey>
+{
+    let credentials = request.credentials();
+    let (email, timestamp) = match (credentials.email, credentials.timestamp) {
+        (Some(email), Some(timestamp)) => (email, timestamp),
+        _ => bail!("Missing required security fields: timestamp or email"),
+    };
+
+    match identity_key(credentials.key_id, email) {
+        Some(identity) => {
+            if credentials.auth_version != Some(AUTH_VERSION) {
+                bail!("Signed requests must use authentication version {}", AUTH_VERSION);
+            }
+            let signature = credentials.client_signature.ok_or_else(||
+                anyhow!("Request is not signed with the registered identity key")
+            )?;
+            identity.verify(&request.canonical_encoding(), signature)?;
+            info!("Identity signature verified");
+        }
+        None => {
+            let message_hmac = credentials.message_hmac.ok_or_else(||
+                anyhow!("Missing required security field: message_hmac")
+            )?;
+            let node = NodeIdentity::load()?;
+            let decrypted_signing_key = e2e_decrypt(
+                credentials.encrypted_signing_key,
+                &node.e2e_private_key,
+                credentials.client_e2e_public_key
+            ).map_err(|err| anyhow!("Failed to decrypt signing key: {}", err))?;
+            let node_signing_key = String::from_utf8(decrypted_signing_key)?;
+
+            if !verify_request_hmac(request, message_hmac, timestamp, email, &node_signing_key) {
+                bail!("HMAC verification failed");
+            }
+            let saved_access_key = KeyMetadataStore::get(credentials.key_id, "access", email)
+                .map_err(|err| anyhow!("Failed to load saved access key: {}", err))?;
+            if node_signing_key != saved_access_key {
+                bail!("Access key mismatch: decrypted key does not match saved access key");
+            }
+        }
+    }
+
+    if !verify_timestamp(credentials.key_id, timestamp, email) {
+        bail!("Timestamp verification failed");
+    }
+    info!("Timestamp verified");
+    Ok(email.to_string())
+}
+
+/// Authorize a request received by a session handler, logging why it was rejected
+pub(crate) fn authorize_session_request<R: AuthenticatedRequest>(request: &R) -> Option<String> {
+    // Authorize by identity signature or access key, then check the timestamp
+    match authorize_request(request) {
+        Ok(email) => {
+            info!("Request authorized");
+            Some(email)
+        }
+        Err(err) => {
+            error!("Request authorization failed: {}", err);
+            None
+        }
+    }
+}
+
+/// Verify the client MAC of a signing request with the access key of the user
+fn verify_request_hmac<R: AuthenticatedRequest>(
+    request: &R,
+    provided_hmac: &str,
+    timestamp: &str,
+    email: &str,
+    signing_key: &str
+) -> bool {
+    match request.credentials().auth_version {
+        None | Some(LEGACY_AUTH_VERSION) => {
+            if !Config::allow_legacy_request_auth() {
+                error!("Request uses legacy authentication, which is not allowed on this node");
+                return false;
+            }
+            warn!("Accepting request with legacy authentication, its payload is not covered");
+            verify_hmac(provided_hmac, format!("{}{}", timestamp, email).as_bytes(), signing_key)
+        }
+        Some(AUTH_VERSION) => {
+            verify_hmac(provided_hmac, &request.canonical_encoding(), signing_key)
+        }
+        Some(version) => {
+            error!("Unsupported request authentication version: {}", version);
+            false
+        }
+    }
+}
+
+// HMAC-SHA256 verification with the signing key, base64 encoded to match the TypeScript client
+fn verify_hmac(provided_hmac: &str, input: &[u8], signing_key: &str) -> bool {
+    let provided_hmac = match base64::decode(provided_hmac) {
+        Ok(hmac) => hmac,
+        Err(err) => {
+            error!("Failed to decode HMAC: {}", err);
+            return false;
+        }
+    };
+
+    let mut mac = match HmacSha256::new_from_slice(signing_key.as_bytes()) {
+        Ok(m) => m,
+        Err(err) => {
+            error!("Failed to create HMAC instance: {}", err);
+            return false;
+        }
+    };
+    mac.update(input);
+
+    if mac.verify(&provided_hmac).is_err() {
+        error!("HMAC verification failed");
+        return false;
+    }
+    true
+}
+
+// Verify that the timestamp is newer than the last one we've seen
+fn verify_timestamp(key_id: &str, new_timestamp: &str, email: &str) -> bool {
+    let timestamp_key = "timestamp";
+    let new_dt = match DateTime::parse_from_rfc3339(new_timestamp) {
+        Ok(dt) => dt.with_timezone(&Utc),
+        Err(err) => {
+            error!("Failed to parse timestamp: {}", err);
+            return false;
+        }
+    };
+
+    // It's fine if this fails - it just means first tx
+    let previous_timestamp_result = KeyMetadataStore::get(key_id, timestamp_key, email);
+
+    match previous_timestamp_result {
+        Ok(prev_timestamp_str) => {
+            let prev_dt = match DateTime::parse_from_rfc3339(&prev_timestamp_str) {
+                Ok(dt) => dt.with_timezone(&Utc),
+                Err(err) => {
+                    error!("Failed to parse stored timestamp: {}", err);
+                    return false;
+                }
+            };
+
+            if new_dt <= prev_dt {
+                error!(
+                    "Timestamp validation failed: provided timestamp ({}) is not newer than stored timestamp ({})",
+                    new_timestamp,
+                    prev_timestamp_str
+                );
+                return false;
+            }
+        }
+        Err(err) => {
+            info!("No previous timestamp found, likely first transaction: {}", err);
+        }
+    }
+
+    match KeyMetadataStore::save(new_timestamp, key_id, timestamp_key, email, &WriteOpts::Modify) {
+        Ok(_) => true,
+        Err(err) => {
+            error!("Failed to save new timestamp: {}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Request {
+        message: Vec<u8>,
+        messages: Vec<Vec<u8>>,
+    }
+
+    impl AuthenticatedRequest for Request {
+        fn canonical_encoding(&self) -> Vec<u8> {
+            CanonicalEncoder::new("test")
+                .bytes("message", &self.message)
+                .list("messages", &self.messages)
+                .finish()
+        }
+
+        fn credentials(&self) -> RequestCredentials<'_> {
+            RequestCredentials {
+                key_id: "",
+                email: None,
+                timestamp: None,
+                message_hmac: None,
+                auth_version: Some(AUTH_VERSION),
+                client_signature: None,
+                client_e2e_public_key: "",
+                encrypted_signing_key: "",
+            }
+        }
+    }
+
+    #[test]
+    fn hmac_covers_request_payload() {
+        let request = Request { message: b"pay alice".to_vec(), messages: vec![] };
+        let mut mac = HmacSha256::new_from_slice(b"access key").unwrap();
+        mac.update(&request.canonical_encoding());
+        let hmac = base64::encode(mac.finalize().into_bytes());
+
+        assert!(verify_request_hmac(&request, &hmac, "", "", "access key"));
+        let swapped = Request { message: b"pay mallory".to_vec(), messages: vec![] };
+        assert!(!verify_request_hmac(&swapped, &hmac, "", "", "access key"));
+        // Moving bytes between fields changes the encoding
+        let shifted = Request { message: vec![], messages: vec![b"pay alice".to_vec()] };
+        assert!(!verify_request_hmac(&shifted, &hmac, "", "", "access key"));
+    }
+}
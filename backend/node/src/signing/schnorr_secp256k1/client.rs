@@ -0,0 +1,302 @@
+use crate::communication::nats::PeerMessenger;
+use crate::communication::protocol::{ AllRounds, KeySignSchnorrAllRounds };
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::signing::frost::{ self, Ciphersuite };
+use crate::signing::schnorr_secp256k1::{
+    challenge,
+    has_even_y,
+    parity_factor,
+    tagged_hash,
+    verify,
+    x_only,
+    SchnorrSignatureResult,
+    SigningKey,
+};
+use crate::storage::ECDSA;
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Point, Scalar, Secp256k1 };
+use curv::BigInt;
+use tracing::info;
+
+/// FROST with BIP340 nonces and challenge
+pub struct Secp256k1Bip340;
+
+pub type NonceCommitment = frost::NonceCommitment<Point<Secp256k1>>;
+pub type SignatureShare = frost::SignatureShare<Scalar<Secp256k1>>;
+
+pub struct SchnorrKeySignClient<C> {
+    pub peer_messenger: C,
+    pub all_party_indices: Vec<usize>,
+}
+
+impl<C> SchnorrKeySignClient<C> where C: PeerMessenger<KeySignSchnorrAllRounds> {
+    pub fn create_shared_sig(
+        &self,
+        message: &[u8],
+        keyshare: &ECDSA,
+        signing_key: &SigningKey
+    ) -> Result<SchnorrSignatureResult> {
+        let hiding_nonce = Scalar::<Secp256k1>::random();
+        let binding_nonce = Scalar::<Secp256k1>::random();
+        let commitment = NonceCommitment {
+            party_index: keyshare.party_index,
+            hiding: Point::generator() * &hiding_nonce,
+            binding: Point::generator() * &binding_nonce,
+        };
+
+        let commitments = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignSchnorrAllRounds as AllRounds>::BroadcastRound::NonceCommitments,
+            commitment
+        )?;
+        info!("Nonce commitments received - msg count: {}", commitments.len());
+
+        let package = SigningPackage::new(message, signing_key.clone(), commitments)?;
+        if package.indices() != self.all_party_indices {
+            bail!("Nonce commitments do not match the parties of the session");
+        }
+
+        let z_i = package.signature_share(
+            keyshare.party_index,
+            &hiding_nonce,
+            &binding_nonce,
+            &keyshare.x_i
+        )?;
+        let shares = self.peer_messenger.broadcast_and_collect_messages(
+            &<KeySignSchnorrAllRounds as AllRounds>::BroadcastRound::SignatureShare,
+            SignatureShare {
+                party_index: keyshare.party_index,
+                z_i,
+            }
+        )?;
+        info!("Signature shares received - msg count: {}", shares.len());
+
+        for share in shares.iter() {
+            let public_share = share.party_index
+                .checked_sub(1)
+                .and_then(|index| keyshare.public_key_vec.get(index))
+                .ok_or_else(|| anyhow!("Unknown party index {}", share.party_index))?;
+            package.verify_share(share, public_share)?;
+        }
+        info!("Verified all signature shares");
+
+        let signature = package.aggregate(&shares)?;
+        let public_key = x_only(&signing_key.public_key);
+        verify(&public_key, message, &signature)?;
+        info!("Full signature generated and verified");
+
+        Ok(SchnorrSignatureResult {
+            signature: hex::encode(signature),
+            public_key: hex::encode(public_key),
+        })
+    }
+
+    pub fn publish_result(&self, result: SchnorrSignatureResult) -> Result<()> {
+        self.peer_messenger.broadcast_message(
+            &<KeySignSchnorrAllRounds as AllRounds>::BroadcastRound::Result,
+            result
+        )
+    }
+}
+
+impl Ciphersuite for Secp256k1Bip340 {
+    type Point = Point<Secp256k1>;
+    type Scalar = Scalar<Secp256k1>;
+
+    fn identity() -> Self::Point {
+        Point::zero()
+    }
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point {
+        Point::generator() * scalar
+    }
+
+    fn scalar_zero() -> Self::Scalar {
+        Scalar::zero()
+    }
+
+    fn scalar_one() -> Self::Scalar {
+        Scalar::from(&BigInt::from(1u32))
+    }
+
+    fn encode_point(point: &Self::Point) -> Vec<u8> {
+        point.to_bytes(true).to_vec()
+    }
+
+    fn lagrange_coefficient(party_index: usize, indices: &[usize]) -> Result<Self::Scalar> {
+        RecoveryCalculator::<Secp256k1>::lagrange_coefficient_at_zero(party_index, indices)
+    }
+
+    fn binding_factor(
+        party_index: usize,
+        public_key: &Self::Point,
+        message: &[u8],
+        encoded_commitments: &[u8],
+        adaptor_point: Option<&Self::Point>
+    ) -> Self::Scalar {
+        let encoded_adaptor_point = adaptor_point
+            .map(|point| point.to_bytes(true).to_vec())
+            .unwrap_or_default();
+        let hash = tagged_hash(
+            "FROST/secp256k1/rho",
+            &[
+                &(party_index as u32).to_be_bytes(),
+                &x_only(public_key),
+                message,
+                encoded_commitments,
+                &encoded_adaptor_point,
+            ]
+        );
+        Scalar::<Secp256k1>::from_bigint(&BigInt::from_bytes(&hash))
+    }
+
+    fn nonce_factor(group_commitment: &Self::Point) -> Result<Self::Scalar> {
+        if group_commitment.is_zero() {
+            bail!("Group commitment is the point at infinity");
+        }
+        Ok(parity_factor(group_commitment))
+    }
+
+    fn challenge(
+        group_commitment: &Self::Point,
+        public_key: &Self::Point,
+        message: &[u8]
+    ) -> Self::Scalar {
+        challenge(group_commitment, public_key, message)
+    }
+}
+
+/// FROST package for a BIP340 signing key, whose parity and Taproot tweak the keyshares of
+/// the signers are adjusted for
+pub struct SigningPackage {
+    signing_key: SigningKey,
+    package: frost::SigningPackage<Secp256k1Bip340>,
+}
+
+impl SigningPackage {
+    pub fn new(
+        message: &[u8],
+        signing_key: SigningKey,
+        commitments: Vec<NonceCommitment>
+    ) -> Result<Self> {
+        Self::with_adaptor_point(message, signing_key, commitments, None)
+    }
+
+    /// Package of an adaptor pre-signature, whose group commitment includes the adaptor point
+    /// so that the aggregated signature only verifies once the adaptor secret is added to it
+    pub fn with_adaptor_point(
+        message: &[u8],
+        signing_key: SigningKey,
+        commitments: Vec<NonceCommitment>,
+        adaptor_point: Option<&Point<Secp256k1>>
+    ) -> Result<Self> {
+        let package = frost::SigningPackage::with_adaptor_point(
+            message,
+            &signing_key.public_key,
+            commitments,
+            adaptor_point
+        )?;
+        Ok(Self { signing_key, package })
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        self.package.indices()
+    }
+
+    pub fn signature_share(
+        &self,
+        party_index: usize,
+        hiding_nonce: &Scalar<Secp256k1>,
+        binding_nonce: &Scalar<Secp256k1>,
+        x_i: &Scalar<Secp256k1>
+    ) -> Result<Scalar<Secp256k1>> {
+        self.package.signature_share(
+            party_index,
+            hiding_nonce,
+            binding_nonce,
+            &(&self.signing_key.share_factor * x_i)
+        )
+    }
+
+    /// Checks a signature share against the public share of its sender, so that a party
+    /// which sends an invalid share is identified before the signature is put together
+    pub fn verify_share(
+        &self,
+        share: &SignatureShare,
+        public_share: &Point<Secp256k1>
+    ) -> Result<()> {
+        self.package.verify_share(share, &(public_share * &self.signing_key.share_factor))
+    }
+
+    pub fn aggregate(&self, shares: &[SignatureShare]) -> Result<[u8; 64]> {
+        let (group_commitment, s) = self.package.aggregate(shares)?;
+        let z = s + self.package.challenge() * &self.signing_key.tweak;
+
+        debug_assert!(has_even_y(&group_commitment));
+        let mut signature = [0; 64];
+        signature[..32].copy_from_slice(&x_only(&group_commitment));
+        signature[32..].copy_from_slice(&z.to_bytes());
+        Ok(signature)
+    }
+
+    /// Aggregates the shares of an adaptor pre-signature into the adapted nonce point, with the
+    /// y coordinate it had before its parity was normalized, and the pre-signature scalar
+    pub fn aggregate_pre_signature(&self, shares: &[SignatureShare]) -> Result<[u8; 65]> {
+        let (group_commitment, _) = self.package.aggregate(shares)?;
+        let signature = self.aggregate(shares)?;
+        let mut pre_signature = [0; 65];
+        pre_signature[..33].copy_from_slice(
+            &(group_commitment * self.package.nonce_factor()).to_bytes(true)
+        );
+        pre_signature[33..].copy_from_slice(&signature[32..]);
+        Ok(pre_signature)
+    }
+}
+
+#[test]
+fn threshold_signature_verifies_with_taproot_tweak() {
+    use crate::signing::schnorr_secp256k1::TaprootTweak;
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+
+    let secret = Scalar::<Secp256k1>::random();
+    let (vss, shares) = VerifiableSS::<Secp256k1>::share(1, 3, &secret);
+    let y_sum = Point::generator() * &secret;
+    let message = [7; 32];
+
+    for taproot in [None, Some(TaprootTweak::default())] {
+        let signing_key = SigningKey::new(&y_sum, taproot.as_ref()).unwrap();
+        let signers = [1, 3];
+        let nonces = signers
+            .iter()
+            .map(|_| (Scalar::<Secp256k1>::random(), Scalar::<Secp256k1>::random()))
+            .collect::<Vec<_>>();
+        let commitments = signers
+            .iter()
+            .zip(nonces.iter())
+            .map(|(&party_index, (hiding, binding))| NonceCommitment {
+                party_index,
+                hiding: Point::generator() * hiding,
+                binding: Point::generator() * binding,
+            })
+            .collect();
+
+        let package = SigningPackage::new(&message, signing_key.clone(), commitments).unwrap();
+        let signature_shares = signers
+            .iter()
+            .zip(nonces.iter())
+            .map(|(&party_index, (hiding, binding))| SignatureShare {
+                party_index,
+                z_i: package
+                    .signature_share(party_index, hiding, binding, &shares[party_index - 1])
+                    .unwrap(),
+            })
+            .collect::<Vec<_>>();
+        for share in signature_shares.iter() {
+            let public_share = vss.get_point_commitment(share.party_index as u16);
+            package.verify_share(share, &public_share).unwrap();
+        }
+
+        let signature = package.aggregate(&signature_shares).unwrap();
+        verify(&x_only(&signing_key.public_key), &message, &signature).unwrap();
+    }
+}
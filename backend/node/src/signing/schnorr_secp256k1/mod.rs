@@ -0,0 +1,171 @@
+pub mod client;
+pub mod orchestrate;
+pub mod session;
+
+use anyhow::{ anyhow, bail, Result };
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::{ Point, Scalar, Secp256k1 };
+use curv::BigInt;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+/// BIP341 tweak of the signing key, needed for Taproot key path spends
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct TaprootTweak {
+    /// Hex encoded root of the script tree, left out for outputs without script paths
+    #[serde(default)]
+    pub merkle_root: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SchnorrSignatureResult {
+    /// 64 byte BIP340 signature, hex encoded
+    pub signature: String,
+    /// X-only public key the signature is valid for, hex encoded
+    pub public_key: String,
+}
+
+/// Key that a BIP340 signature is created for, which is the shared public key with an even y
+/// coordinate and, for Taproot, the BIP341 tweak added to it
+#[derive(Clone)]
+pub struct SigningKey {
+    pub public_key: Point<Secp256k1>,
+    /// Factor of 1 or -1 that every keyshare is multiplied with
+    pub share_factor: Scalar<Secp256k1>,
+    /// Term that is added to the shared secret on top of the keyshares
+    pub tweak: Scalar<Secp256k1>,
+}
+
+impl SigningKey {
+    pub fn new(y_sum: &Point<Secp256k1>, taproot: Option<&TaprootTweak>) -> Result<Self> {
+        let key_factor = parity_factor(y_sum);
+        let even_key = y_sum * &key_factor;
+        let taproot = match taproot {
+            Some(taproot) => taproot,
+            None => {
+                return Ok(Self {
+                    public_key: even_key,
+                    share_factor: key_factor,
+                    tweak: Scalar::zero(),
+                });
+            }
+        };
+
+        let merkle_root = match &taproot.merkle_root {
+            Some(merkle_root) => {
+                let merkle_root = hex::decode(merkle_root)?;
+                if merkle_root.len() != 32 {
+                    bail!("Taproot merkle root has to be 32 bytes long");
+                }
+                merkle_root
+            }
+            None => Vec::new(),
+        };
+        let tweak_hash = tagged_hash("TapTweak", &[&x_only(&even_key), &merkle_root]);
+        let tweak_bn = BigInt::from_bytes(&tweak_hash);
+        if &tweak_bn >= Scalar::<Secp256k1>::group_order() {
+            bail!("Taproot tweak is out of range");
+        }
+        let tweak = Scalar::<Secp256k1>::from_bigint(&tweak_bn);
+
+        let tweaked_key = even_key + Point::generator() * &tweak;
+        if tweaked_key.is_zero() {
+            bail!("Taproot tweak results in an invalid public key");
+        }
+        let tweaked_factor = parity_factor(&tweaked_key);
+        Ok(Self {
+            public_key: tweaked_key * &tweaked_factor,
+            share_factor: key_factor * &tweaked_factor,
+            tweak: tweak * &tweaked_factor,
+        })
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(x_only(&self.public_key))
+    }
+}
+
+/// SHA256 of the data, prefixed with the hash of the tag twice as BIP340 defines it
+pub fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(&tag_hash);
+    hasher.update(&tag_hash);
+    for item in data {
+        hasher.update(item);
+    }
+    hasher.finalize().into()
+}
+
+pub fn x_only(point: &Point<Secp256k1>) -> [u8; 32] {
+    let mut x = [0; 32];
+    x.copy_from_slice(&point.to_bytes(true)[1..]);
+    x
+}
+
+pub fn has_even_y(point: &Point<Secp256k1>) -> bool {
+    point.to_bytes(true)[0] == 0x02
+}
+
+/// Factor that turns the point into the one with the same x coordinate and an even y coordinate
+pub fn parity_factor(point: &Point<Secp256k1>) -> Scalar<Secp256k1> {
+    let one = Scalar::<Secp256k1>::from(&BigInt::from(1u32));
+    if has_even_y(point) { one } else { -one }
+}
+
+/// The point with the given x coordinate and an even y coordinate
+pub fn lift_x(x: &[u8]) -> Result<Point<Secp256k1>> {
+    if x.len() != 32 {
+        bail!("X-only coordinates have to be 32 bytes long");
+    }
+    let mut compressed = vec![0x02];
+    compressed.extend_from_slice(x);
+    Point::<Secp256k1>::from_bytes(&compressed).map_err(|_| anyhow!("Point is not on the curve"))
+}
+
+pub fn challenge(
+    group_commitment: &Point<Secp256k1>,
+    public_key: &Point<Secp256k1>,
+    message: &[u8]
+) -> Scalar<Secp256k1> {
+    let hash = tagged_hash(
+        "BIP0340/challenge",
+        &[&x_only(group_commitment), &x_only(public_key), message]
+    );
+    Scalar::<Secp256k1>::from_bigint(&BigInt::from_bytes(&hash))
+}
+
+/// Verifies a 64 byte BIP340 signature against an x-only public key
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<()> {
+    if signature.len() != 64 {
+        bail!("BIP340 signatures have to be 64 bytes long");
+    }
+    let public_key = lift_x(public_key)?;
+    let group_commitment = lift_x(&signature[..32])?;
+    let s_bn = BigInt::from_bytes(&signature[32..]);
+    if &s_bn >= Scalar::<Secp256k1>::group_order() {
+        bail!("Signature scalar is out of range");
+    }
+    let s = Scalar::<Secp256k1>::from_bigint(&s_bn);
+
+    let c = challenge(&group_commitment, &public_key, message);
+    if Point::generator() * s != group_commitment + public_key * c {
+        bail!("Signature did not pass verification");
+    }
+    Ok(())
+}
+
+#[test]
+fn verifies_bip340_test_vector_0() {
+    let public_key = hex
+        ::decode("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9")
+        .unwrap();
+    let signature = hex
+        ::decode(
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+             25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"
+        )
+        .unwrap();
+    verify(&public_key, &[0; 32], &signature).unwrap();
+    assert!(verify(&public_key, &[1; 32], &signature).is_err());
+}
@@ -0,0 +1,119 @@
+use crate::command::MsgContext;
+use crate::communication::nats::{ BroadcastMessage, JoinMessage, JoinResponse };
+use crate::signing::ecdsa::orchestrate::derive_child_key;
+use crate::signing::schnorr_secp256k1::SchnorrSignatureResult;
+use crate::signing::{ SigningCommand, SigningResponse };
+use crate::storage::KeyInfoStore;
+use anyhow::{ anyhow, bail, Context, Result };
+use shared::key_info::Key;
+use tracing::{ error, info, instrument };
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+
+    let party_nodes = cmd.party_nodes;
+    let key_id = cmd.key_id;
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", &key_id)
+    )?;
+    if !matches!(key_info.kind, Key::ECDSA { .. }) {
+        bail!("Schnorr signatures can only be created with secp256k1 keys");
+    }
+
+    let party_count = party_nodes.len();
+    if party_count <= key_info.threshold {
+        bail!("Not enough nodes in party");
+    }
+
+    if !cmd.msgs.is_empty() {
+        bail!("Batch signing is not supported for Schnorr signatures");
+    }
+    if cmd.presignature_id.is_some() {
+        bail!("Presignatures are only supported for ECDSA signatures");
+    }
+    if let Some(derivation_path) = &cmd.derivation_path {
+        let child = derive_child_key(&key_id, derivation_path)?;
+        info!("Signing with child key {} at {}", child.public_key_hex(), derivation_path);
+    }
+    if cmd.schnorr_requests.len() != party_count {
+        bail!("Expected a client request for each of the {} party nodes", party_count);
+    }
+    for (node, request) in party_nodes.iter().zip(cmd.schnorr_requests.iter()) {
+        if
+            request.key_id != key_id ||
+            request.session_id != session_id ||
+            request.message != cmd.msg ||
+            request.taproot != cmd.taproot ||
+            request.derivation_path != cmd.derivation_path
+        {
+            bail!("Client request for node {} doesn't match the signing command", node);
+        }
+    }
+
+    let join_key = format!("network.gridlock.nodes.KeySignSchnorrSecp256k1.{}.Join", &session_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!(
+        "network.gridlock.nodes.KeySignSchnorrSecp256k1.{}.Result",
+        &session_id
+    );
+    let result_sub = nc.subscribe(&result_key)?;
+
+    // Guardians authorize the signature by the client request relayed to them
+    for (node, request) in party_nodes.iter().zip(cmd.schnorr_requests.iter()) {
+        nc.publish(
+            &format!("network.gridlock.nodes.KeySignSchnorrSecp256k1.new.{}", node),
+            &serde_json::to_string(request)?
+        )?;
+    }
+
+    let mut join_msg_vec = Vec::new();
+    for _ in 0..party_count {
+        let next = join_sub.next().context("Get next join message")?;
+        join_msg_vec.push(next);
+    }
+
+    if join_msg_vec.len() < party_count {
+        let msg = format!("Not every party joined - party_joined_count: {}", join_msg_vec.len());
+        error!("{}", &msg);
+        bail!(msg);
+    }
+
+    let mut indices = Vec::new();
+    for m in join_msg_vec.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        indices.push(confirmation.party_index);
+    }
+    indices.sort();
+    let join_resp = JoinResponse {
+        party_count: indices.len(),
+        all_party_indices: indices,
+    };
+    for msg in join_msg_vec {
+        msg.respond(
+            &serde_json::to_string(&join_resp).context("Respond to join message for every party")?
+        )?;
+    }
+    nc.flush()?;
+
+    info!("Parties joined to Schnorr signing");
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub.next().context("Signature result received from every party")?;
+        results.push(
+            serde_json::from_slice::<BroadcastMessage<SchnorrSignatureResult>>(&res.data)?.message
+        );
+    }
+
+    if results.iter().any(|result| result != &results[0]) {
+        bail!("Parties do not agree on the Schnorr signature");
+    }
+    info!("Signature result received");
+
+    Ok(SigningResponse::Schnorr(results.remove(0)))
+}
@@ -0,0 +1,200 @@
+use crate::communication::nats::{
+    BaseMessenger,
+    NatsBaseMessenger,
+    NatsBaseSession,
+    NatsPeerMessenger,
+};
+use crate::communication::protocol::{ KeySignSchnorrAllRounds, Topic };
+use crate::node::NodeIdentity;
+use crate::signing::ecdsa::derivation::{ apply_tweak, DerivationPath, ExtendedPublicKey };
+use crate::signing::policy::{ enforce_policy, raw_intents };
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::signing::schnorr_secp256k1::client::SchnorrKeySignClient;
+use crate::signing::schnorr_secp256k1::{ SigningKey, TaprootTweak };
+use crate::storage::{ KeyshareAccessor, ECDSA };
+use crate::App;
+use anyhow::{ bail, Result };
+use serde::{ Deserialize, Serialize };
+use std::thread;
+use tracing::{ error, info, instrument };
+
+#[instrument(skip_all)]
+fn sign_session(conn: nats::Connection, session: NewSchnorrKeySignSession) -> Result<()> {
+    let session_id = session.session_id.clone();
+    match keysign_session_inner(conn, session) {
+        Ok(()) => info!("Signing completed successfully for session id: {}", session_id),
+        Err(err) => error!("Error in Schnorr signing: session id: {}, error: {}", session_id, err),
+    }
+    Ok(())
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewSchnorrKeySignMessage {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+    #[serde(default)]
+    pub taproot: Option<TaprootTweak>,
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+}
+
+impl AuthenticatedRequest for NewSchnorrKeySignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let merkle_root = self.taproot.as_ref().and_then(|tweak| tweak.merkle_root.as_deref());
+        CanonicalEncoder::new("schnorr")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .bytes("message", &self.message)
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .bool("taproot", self.taproot.is_some())
+            .opt_str("taproot_merkle_root", merkle_root)
+            .opt_str("derivation_path", self.derivation_path.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewSchnorrKeySignSession {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    pub email: Option<String>,
+    /// Tweak of the signing key for a Taproot key path spend
+    #[serde(default)]
+    pub taproot: Option<TaprootTweak>,
+    /// BIP32 path of the non-hardened child key to sign with
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+}
+
+/// Signs with the ECDSA keyshare of the key, as both protocols share the secp256k1 secret
+fn load_keyshare(session: &NewSchnorrKeySignSession) -> Result<ECDSA> {
+    let mut keyshare = (
+        if let Some(email) = &session.email {
+            KeyshareAccessor::<ECDSA>::read_only_with_email(&session.key_id, email)?
+        } else {
+            KeyshareAccessor::<ECDSA>::read_only(&session.key_id)?
+        }
+    ).key;
+
+    if let Some(derivation_path) = &session.derivation_path {
+        let path = derivation_path.parse::<DerivationPath>()?;
+        let (_, tweak) = ExtendedPublicKey::from_keyshare(&keyshare)?.derive(&path)?;
+        apply_tweak(&mut keyshare, &tweak)?;
+        info!("Derived child key for path {}", derivation_path);
+    }
+    Ok(keyshare)
+}
+
+fn keysign_session_inner(conn: nats::Connection, session: NewSchnorrKeySignSession) -> Result<()> {
+    info!("joining Schnorr keysign session key_id: {}", &session.key_id);
+    let keyshare = load_keyshare(&session)?;
+    info!("Retrieved keyshare");
+
+    let signing_key = SigningKey::new(&keyshare.y_sum, session.taproot.as_ref())?;
+
+    let node = NodeIdentity::load()?;
+    info!("Retrieved node identity");
+
+    let nats_session = NatsBaseSession {
+        session_id: session.session_id.clone(),
+        thread_index: 0, // Single keyshare per device
+        node_id: node.node_id.to_string(),
+        public_key: node.networking_public_key,
+        party_index: keyshare.party_index,
+    };
+
+    let sign_messenger = NatsBaseMessenger::<KeySignSchnorrAllRounds>::new(
+        Topic::KeySignSchnorrSecp256k1,
+        conn,
+        nats_session
+    )?;
+
+    let join_response = sign_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
+    info!("Got join response");
+
+    let mut all_party_indices = join_response.all_party_indices;
+    all_party_indices.sort();
+    if all_party_indices.len() <= keyshare.threshold {
+        bail!("Not enough parties joined to sign");
+    }
+
+    let sign_peer_messenger = NatsPeerMessenger::from(
+        sign_messenger,
+        join_response.party_count,
+        all_party_indices.clone()
+    )?;
+
+    let keysign_client = SchnorrKeySignClient {
+        peer_messenger: sign_peer_messenger,
+        all_party_indices,
+    };
+
+    let signature = keysign_client.create_shared_sig(&session.message, &keyshare, &signing_key)?;
+    keysign_client.publish_result(signature)?;
+    info!("Signature published successfully");
+
+    Ok(())
+}
+
+pub fn handle_new_session_message(app: &App, message: nats::Message) {
+    let parsed_message = match
+        serde_json::from_slice::<NewSchnorrKeySignMessage>(&message.data[..])
+    {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!("Failed to parse message: {}", err);
+            return;
+        }
+    };
+
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
+    };
+
+    // Enforce the signing policy of the key before joining the session
+    let intents = raw_intents(1, false);
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &intents) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
+    let session = NewSchnorrKeySignSession {
+        key_id: parsed_message.key_id,
+        session_id: parsed_message.session_id,
+        message: parsed_message.message,
+        email: Some(email),
+        taproot: parsed_message.taproot,
+        derivation_path: parsed_message.derivation_path,
+    };
+
+    info!("Spawning a thread to handle Schnorr signature generation");
+    let thread_name = format!("sign_session_{}", session.session_id);
+    let nc = app.nc.clone();
+    match
+        thread::Builder
+            ::new()
+            .name(thread_name)
+            .spawn(move || sign_session(nc, session))
+    {
+        Ok(_) => info!("Started Schnorr signing thread"),
+        Err(err) => error!("Failed to spawn thread for Schnorr signing: {}", err),
+    };
+}
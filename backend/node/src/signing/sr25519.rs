@@ -1,16 +1,69 @@
 use crate::command::{ JsonCommand, MsgContext };
+use crate::signing::policy::enforce_policy;
+use crate::signing::request_auth::{
+    authorize_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    RequestCredentials,
+};
+use crate::signing::{ substrate, PayloadType };
 use crate::storage::{ KeyshareAccessor, Sr25519 };
 use anyhow::{ bail, Context, Result };
 use schnorrkel::{ ExpansionMode, Keypair, MiniSecretKey, SecretKey };
 use serde::{ Deserialize, Serialize };
 use tracing::info;
 
+/// Keeps the credential fields of `ClientCredentials` inline, as flattening them would not work
+/// with `deny_unknown_fields`, which this command needs to be told apart from other commands
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct KeySignCommand {
     pub key_id: String,
     pub key_type: String,
     pub message: Vec<u8>,
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+    pub client_e2e_public_key: String,
+    /// Access key encrypted to this node, not needed once an identity key is registered
+    #[serde(default)]
+    pub encrypted_signing_key: String,
+    pub timestamp: Option<String>,
+    pub message_hmac: Option<String>,
+    pub email: Option<String>,
+    /// Version of the scheme `message_hmac` was computed with, left out by legacy clients
+    #[serde(default)]
+    pub auth_version: Option<u8>,
+    /// Signature by the identity key of the user, replacing `message_hmac` once one is registered
+    #[serde(default)]
+    pub client_signature: Option<String>,
+}
+
+impl AuthenticatedRequest for KeySignCommand {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("sr25519_device")
+            .str("key_id", &self.key_id)
+            .str("key_type", &self.key_type)
+            .bytes("message", &self.message)
+            .opt_str("payload_type", self.payload_type.map(|payload_type| payload_type.as_str()))
+            .str("client_e2e_public_key", &self.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.encrypted_signing_key)
+            .opt_str("timestamp", self.timestamp.as_deref())
+            .opt_str("email", self.email.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        RequestCredentials {
+            key_id: &self.key_id,
+            email: self.email.as_deref(),
+            timestamp: self.timestamp.as_deref(),
+            message_hmac: self.message_hmac.as_deref(),
+            auth_version: self.auth_version,
+            client_signature: self.client_signature.as_deref(),
+            client_e2e_public_key: &self.client_e2e_public_key,
+            encrypted_signing_key: &self.encrypted_signing_key,
+        }
+    }
 }
 
 impl JsonCommand for KeySignCommand {
@@ -18,7 +71,18 @@ impl JsonCommand for KeySignCommand {
 
     fn execute_message(self, _ctx: MsgContext) -> Result<Self::Response> where Self: Sized {
         match self.key_type.as_str() {
-            "sr25519" => sign_for_sr25519(self.key_id, self.message),
+            "sr25519" => {
+                let email = authorize_request(&self)?;
+                info!("Request authorized");
+                let (message, intent) = substrate::prepare_message(
+                    &self.key_id,
+                    &email,
+                    self.payload_type,
+                    self.message
+                )?;
+                enforce_policy(&self.key_id, &email, &[intent])?;
+                sign_for_sr25519(self.key_id, message)
+            }
             "sr25519_musig" | "eddsa" | "ecdsa" => {
                 bail!("Sign orchestration inside node is not yet implemented for {}", self.key_type)
             }
@@ -29,7 +93,7 @@ impl JsonCommand for KeySignCommand {
 
 // Signing context that polkadot-js using for tx signing
 // https://github.com/polkadot-js/wasm/blob/3a06871f829b316eb8c2b7763f1df18aa0e5fcb2/packages/wasm-crypto/src/rs/sr25519.rs#L18
-const CTX: &[u8] = b"substrate";
+pub(crate) const CTX: &[u8] = b"substrate";
 
 fn sign_for_sr25519(key_id: String, message: Vec<u8>) -> Result<String> {
     let ka = KeyshareAccessor::<Sr25519>::read_only(&key_id)?;
@@ -1,19 +1,54 @@
+//! Threshold Sr25519 signing with the keyshares of the distributed key generation.
+//!
+//! The signers run two round FROST over Ristretto and take the challenge from the schnorrkel
+//! signing transcript, so the result is a plain Sr25519 signature for the shared key.
+
+use crate::command::MsgContext;
 use crate::communication::nats::{
     BaseMessenger,
+    BroadcastMessage,
+    JoinMessage,
+    JoinResponse,
     NatsBaseMessenger,
     NatsBaseSession,
     NatsPeerMessenger,
     PeerMessenger,
 };
 use crate::communication::protocol::{ AllRounds, KeySignSr25519AllRounds, Topic };
+use crate::keygen::sr25519_dkg::{
+    decode_point,
+    public_key_from_shares,
+    to_ristretto_scalar,
+    RistrettoPublicShare,
+};
 use crate::node::NodeIdentity;
-use crate::storage::{ KeyshareAccessor, Sr25519 };
+use crate::recovery::calculator::RecoveryCalculator;
+use crate::signing::frost::{ self, Ciphersuite };
+use crate::signing::policy::enforce_policy;
+use crate::signing::request_auth::{
+    authorize_session_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
+use crate::signing::sr25519::CTX;
+use crate::signing::{ substrate, PayloadType, SigningCommand, SigningResponse };
+use crate::storage::{ KeyInfoStore, KeyshareAccessor, Sr25519 };
 use crate::App;
-use anyhow::{ anyhow, Context, Error, Result };
-use schnorrkel::{ signing_context, ExpansionMode, Keypair, MiniSecretKey, SecretKey };
+use anyhow::{ anyhow, bail, Context, Result };
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::{ Ed25519, Scalar };
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar as RistrettoScalar;
+use curve25519_dalek::traits::Identity;
+use schnorrkel::signing_context;
 use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha512 };
+use shared::key_info::Key;
 use std::thread;
-use tracing::{ error, info };
+use tracing::{ error, info, instrument };
 
 fn sign_session(conn: nats::Connection, session: NewSr25519KeySignSession) -> Result<()> {
     let session_id = session.session_id.clone();
@@ -25,287 +60,363 @@ fn sign_session(conn: nats::Connection, session: NewSr25519KeySignSession) -> Re
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct NewSr25519KeySignSession {
+pub struct NewSr25519KeySignMessage {
     pub key_id: String,
     pub session_id: String,
     pub message: Vec<u8>,
-    pub party_index: usize,
-}
-
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
-pub struct PublicKey(String);
-
-impl From<schnorrkel::PublicKey> for PublicKey {
-    fn from(f: schnorrkel::PublicKey) -> Self {
-        PublicKey(hex::encode(f.to_bytes()))
-    }
-}
-
-impl From<PublicKey> for schnorrkel::PublicKey {
-    fn from(f: PublicKey) -> Self {
-        let r_bytes = hex::decode(f.0).expect("Hex encoded PublicKey");
-        schnorrkel::PublicKey::from_bytes(&r_bytes).expect("Failed to create public key from bytes")
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Commitment(String);
-
-impl From<schnorrkel::musig::Commitment> for Commitment {
-    fn from(f: schnorrkel::musig::Commitment) -> Self {
-        Commitment(hex::encode(f.0))
-    }
-}
-
-impl From<Commitment> for schnorrkel::musig::Commitment {
-    fn from(f: Commitment) -> Self {
-        let r_bytes = hex::decode(f.0).expect("Hex encoded Commitment");
-        schnorrkel::musig::Commitment(
-            r_bytes.try_into().expect("Wrong length of encoded Commitment")
-        )
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Reveal(String);
-
-impl From<schnorrkel::musig::Reveal> for Reveal {
-    fn from(f: schnorrkel::musig::Reveal) -> Self {
-        Reveal(hex::encode(f.0))
-    }
-}
-
-impl From<Reveal> for schnorrkel::musig::Reveal {
-    fn from(f: Reveal) -> Self {
-        let r_bytes = hex::decode(f.0).expect("Hex encoded Reveal");
-        schnorrkel::musig::Reveal(r_bytes.try_into().expect("Wrong length of encoded Reveal"))
-    }
+    /// Format of `message` when the guardian has to decode it
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Cosign(String);
-
-impl From<schnorrkel::musig::Cosignature> for Cosign {
-    fn from(f: schnorrkel::musig::Cosignature) -> Self {
-        Cosign(hex::encode(f.0))
+impl AuthenticatedRequest for NewSr25519KeySignMessage {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("sr25519")
+            .str("key_id", &self.key_id)
+            .str("session_id", &self.session_id)
+            .bytes("message", &self.message)
+            .opt_str("payload_type", self.payload_type.map(|payload_type| payload_type.as_str()))
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .str("encrypted_signing_key", &self.credentials.encrypted_signing_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .finish()
     }
-}
 
-impl From<Cosign> for schnorrkel::musig::Cosignature {
-    fn from(f: Cosign) -> Self {
-        let r_bytes = hex::decode(f.0).expect("Hex encoded Cosignature");
-        schnorrkel::musig::Cosignature(
-            r_bytes.try_into().expect("Wrong length of encoded Cosignature")
-        )
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Signature(String);
-
-impl From<schnorrkel::Signature> for Signature {
-    fn from(f: schnorrkel::Signature) -> Self {
-        Signature(hex::encode(f.to_bytes()))
-    }
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct NewSr25519KeySignSession {
+    pub key_id: String,
+    pub session_id: String,
+    pub message: Vec<u8>,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Replaced by the bytes to sign once the node decoded `message`
+    #[serde(default)]
+    pub payload_type: Option<PayloadType>,
 }
 
-impl From<Signature> for schnorrkel::Signature {
-    fn from(f: Signature) -> Self {
-        let r_bytes = hex::decode(f.0).expect("Hex encoded Cosignature");
-        schnorrkel::Signature
-            ::from_bytes(&r_bytes)
-            .expect("Unable to recreate schnorrkel signature from bytes")
-    }
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Sr25519SignatureResult {
+    /// 64 byte Sr25519 signature, hex encoded
+    pub signature: String,
+    /// Public key the signature is valid for, hex encoded
+    pub pk: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CommitmentMsg {
-    pub public_key: PublicKey,
-    pub commitment: Commitment,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct RevealMsg {
-    pub public_key: PublicKey,
-    pub reveal: Reveal,
+    pub public_share: RistrettoPublicShare,
+    /// Commitments to the hiding and binding nonces, hex encoded compressed Ristretto points
+    pub hiding: String,
+    pub binding: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CosignMsg {
-    pub public_key: PublicKey,
-    pub cosign: Cosign,
+    pub party_index: usize,
+    /// Signature share, hex encoded little endian scalar
+    pub z_i: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct ResultMsg {
-    pub musig_public_key: PublicKey,
-    pub sig: Signature,
+fn load_keyshare(session: &NewSr25519KeySignSession) -> Result<Sr25519> {
+    let key = (
+        if let Some(email) = &session.email {
+            KeyshareAccessor::<Sr25519>::read_only_with_email(&session.key_id, email)?
+        } else {
+            KeyshareAccessor::<Sr25519>::read_only(&session.key_id)?
+        }
+    ).key;
+    // Keys from `keygen::sr25519` share the mini secret key rather than the signing scalar
+    if key.party_index == 0 || key.secret_key.is_some() {
+        bail!("Keyshares of dealer created Sr25519 keys can not sign jointly");
+    }
+    Ok(key)
 }
 
 fn keysign_session_inner(conn: nats::Connection, session: NewSr25519KeySignSession) -> Result<()> {
-    let key_id = session.key_id.clone();
-    let session_id = session.session_id.clone();
-    let message = session.message.clone();
-    info!("joining Sr25519 keysign session key_id: {}", &key_id);
-
-    let key = KeyshareAccessor::<Sr25519>::read_only(&key_id)?.key;
-    let keypair = retrieve_keypair(&key)?;
-    let _public_key: PublicKey = keypair.public.into();
-
-    let _threshold = key.threshold;
-    let party_index = session.party_index;
+    info!("joining Sr25519 keysign session key_id: {}", &session.key_id);
+    let key = load_keyshare(&session)?;
+    let party_index = key.party_index;
+    let x_i: Scalar<Ed25519> = key.x_i.clone().into();
+    let vss_scheme: VerifiableSS<Ed25519> = key.vss_scheme.clone().into();
+    info!("Retrieved keyshare");
 
     let node = NodeIdentity::load()?;
-    let node_id = node.node_id.to_string();
-    let public_key = node.networking_public_key;
     info!("Retrieved node identity");
 
-    // We are not currently signing with more than one keyshare per device
-    let thread_index = 0;
     let nats_session = NatsBaseSession {
-        session_id,
-        thread_index,
-        node_id,
-        public_key: public_key.clone(),
+        session_id: session.session_id.clone(),
+        thread_index: 0, // Single keyshare per device
+        node_id: node.node_id.to_string(),
+        public_key: node.networking_public_key,
         party_index,
     };
 
     let sign_messenger = NatsBaseMessenger::<KeySignSr25519AllRounds>::new(
         Topic::KeySignSr25519,
-        conn.clone(),
+        conn,
         nats_session
     )?;
 
     let join_response = sign_messenger.wait_for_confirmation(std::time::Duration::from_secs(10))?;
-
     info!("Got join response");
 
-    let party_count = join_response.party_count;
     let mut all_party_indices = join_response.all_party_indices;
     all_party_indices.sort();
+    if all_party_indices.len() <= key.threshold {
+        bail!("Not enough parties joined to sign");
+    }
 
     let sign_peer_messenger = NatsPeerMessenger::from(
         sign_messenger,
-        party_count,
+        join_response.party_count,
         all_party_indices.clone()
     )?;
 
-    let t = signing_context(b"gridlock").bytes(&message);
-
     // Commit stage
-    let mut commit = keypair.musig(t.clone());
+    let hiding_nonce = to_ristretto_scalar(&Scalar::random());
+    let binding_nonce = to_ristretto_scalar(&Scalar::random());
     let commit_msg = CommitmentMsg {
-        public_key: PublicKey(public_key.clone()),
-        commitment: commit.our_commitment().into(),
+        public_share: RistrettoPublicShare::new(party_index, &x_i),
+        hiding: encode_point(&(RISTRETTO_BASEPOINT_POINT * hiding_nonce)),
+        binding: encode_point(&(RISTRETTO_BASEPOINT_POINT * binding_nonce)),
     };
-    let other_commit_msgs = sign_peer_messenger.broadcast_and_collect_messages(
+    let commit_msgs = sign_peer_messenger.broadcast_and_collect_messages(
         &<KeySignSr25519AllRounds as AllRounds>::BroadcastRound::Commit,
         commit_msg
     )?;
-    info!("Other parties commits received - msg count: {}", other_commit_msgs.len());
-    for other_commit_msg in other_commit_msgs {
-        if other_commit_msg.public_key == PublicKey(public_key.clone()) {
-            continue;
-        }
-
-        commit
-            .add_their_commitment(
-                other_commit_msg.public_key.into(),
-                other_commit_msg.commitment.into()
-            )
-            .map_err(Error::msg)?;
+    info!("Commitments received - msg count: {}", commit_msgs.len());
+
+    let mut public_shares = Vec::new();
+    let mut nonce_commitments = Vec::new();
+    for commit_msg in commit_msgs.iter() {
+        let index = commit_msg.public_share.party_index;
+        let ed25519_public_share = vss_scheme.get_point_commitment(index as u16);
+        public_shares.push((index, commit_msg.public_share.verify(&ed25519_public_share)?));
+        nonce_commitments.push(NonceCommitment {
+            party_index: index,
+            hiding: decode_point(&commit_msg.hiding)?,
+            binding: decode_point(&commit_msg.binding)?,
+        });
     }
-    info!("Commit stage passed");
+    let public_key = public_key_from_shares(&public_shares)?;
 
-    // Reveal stage
-    let mut reveal = commit.reveal_stage();
-    let our_reveal = reveal.our_reveal().clone();
-    let reveal_msg = RevealMsg {
-        public_key: PublicKey(public_key.clone()),
-        reveal: our_reveal.into(),
-    };
-    let other_reveal_msgs = sign_peer_messenger.broadcast_and_collect_messages(
-        &<KeySignSr25519AllRounds as AllRounds>::BroadcastRound::Reveal,
-        reveal_msg.clone()
-    )?;
-    info!("Other parties reveals received - msg count: {}", other_reveal_msgs.len());
-    for other_reveal_msg in other_reveal_msgs {
-        if other_reveal_msg.public_key.0 == public_key {
-            continue;
-        }
-
-        reveal
-            .add_their_reveal(other_reveal_msg.public_key.into(), other_reveal_msg.reveal.into())
-            .map_err(Error::msg)?;
+    let package = SigningPackage::new(&session.message, &public_key, nonce_commitments)?;
+    if package.indices() != all_party_indices {
+        bail!("Commitments do not match the parties of the session");
     }
-    let musig_public_key = reveal.public_key();
-    info!("Reveal stage passed");
+    info!("Commit stage passed");
 
     // Cosign stage
-    let mut cosign = reveal.cosign_stage();
-    let cosign_msg = CosignMsg {
-        public_key: PublicKey(public_key.clone()),
-        cosign: cosign.our_cosignature().into(),
-    };
-    let other_cosign_msgs = sign_peer_messenger.broadcast_and_collect_messages(
-        &<KeySignSr25519AllRounds as AllRounds>::BroadcastRound::Cosign,
-        cosign_msg
+    let z_i = package.signature_share(
+        party_index,
+        &hiding_nonce,
+        &binding_nonce,
+        &to_ristretto_scalar(&x_i)
     )?;
-    info!("Other parties cosignatures received - msg count: {}", other_cosign_msgs.len());
-    for other_cosign_msg in other_cosign_msgs {
-        if other_cosign_msg.public_key.0 == public_key {
-            continue;
+    let cosign_msgs = sign_peer_messenger.broadcast_and_collect_messages(
+        &<KeySignSr25519AllRounds as AllRounds>::BroadcastRound::Cosign,
+        CosignMsg {
+            party_index,
+            z_i: hex::encode(z_i.as_bytes()),
         }
-
-        debug_assert_eq!(musig_public_key, cosign.public_key());
-        cosign
-            .add_their_cosignature(
-                other_cosign_msg.public_key.into(),
-                other_cosign_msg.cosign.into()
-            )
-            .map_err(Error::msg)?;
-        debug_assert_eq!(musig_public_key, cosign.public_key());
+    )?;
+    info!("Cosignatures received - msg count: {}", cosign_msgs.len());
+
+    let mut shares = Vec::new();
+    for cosign_msg in cosign_msgs.iter() {
+        let share = SignatureShare {
+            party_index: cosign_msg.party_index,
+            z_i: decode_scalar(&cosign_msg.z_i)?,
+        };
+        let public_share = public_shares
+            .iter()
+            .find(|(index, _)| *index == share.party_index)
+            .map(|(_, public_share)| public_share)
+            .ok_or_else(|| anyhow!("Unknown party index {}", share.party_index))?;
+        package.verify_share(&share, public_share)?;
+        shares.push(share);
     }
     info!("Cosign stage passed");
 
-    let _signature = cosign.sign().context(anyhow!("Unbalte to git signature from cosignature"))?;
+    let (group_commitment, s) = package.aggregate(&shares)?;
+    let signature = encode_signature(&group_commitment, &s);
+    let public_key = public_key.compress().to_bytes();
+    verify(&public_key, &session.message, &signature)?;
 
     // Result stage
-    let result_msg = ResultMsg {
-        musig_public_key: musig_public_key.into(),
-        sig: cosign.sign().expect("Signature generated from cosign").into(),
-    };
-
     sign_peer_messenger.broadcast_message(
         &<KeySignSr25519AllRounds as AllRounds>::BroadcastRound::Result,
-        result_msg
+        Sr25519SignatureResult {
+            signature: hex::encode(signature),
+            pk: hex::encode(public_key),
+        }
     )?;
 
-    info!("Cosignature result published successfully");
+    info!("Signature result published successfully");
     Ok(())
 }
 
-fn retrieve_keypair(key: &Sr25519) -> Result<Keypair, Error> {
-    let secret = key.secret_key
-        .clone()
-        .context("Can not sign. This user is not the owner of this key")?;
-    let mini_secret: MiniSecretKey = secret.into();
-    info!("Retrieved keyshare");
+/// FROST over Ristretto with the challenge of the schnorrkel signing transcript
+pub struct Sr25519Ristretto;
 
-    let public = mini_secret.expand_to_public(ExpansionMode::Ed25519);
-    let secret: SecretKey = mini_secret.expand(ExpansionMode::Ed25519);
-    Ok(Keypair { public, secret })
+pub type NonceCommitment = frost::NonceCommitment<RistrettoPoint>;
+pub type SignatureShare = frost::SignatureShare<RistrettoScalar>;
+pub type SigningPackage = frost::SigningPackage<Sr25519Ristretto>;
+
+impl Ciphersuite for Sr25519Ristretto {
+    type Point = RistrettoPoint;
+    type Scalar = RistrettoScalar;
+
+    fn identity() -> Self::Point {
+        RistrettoPoint::identity()
+    }
+
+    fn base_mul(scalar: &Self::Scalar) -> Self::Point {
+        RISTRETTO_BASEPOINT_POINT * scalar
+    }
+
+    fn scalar_zero() -> Self::Scalar {
+        RistrettoScalar::zero()
+    }
+
+    fn scalar_one() -> Self::Scalar {
+        RistrettoScalar::one()
+    }
+
+    fn encode_point(point: &Self::Point) -> Vec<u8> {
+        point.compress().as_bytes().to_vec()
+    }
+
+    fn lagrange_coefficient(party_index: usize, indices: &[usize]) -> Result<Self::Scalar> {
+        let lambda = RecoveryCalculator::<Ed25519>::lagrange_coefficient_at_zero(
+            party_index,
+            indices
+        )?;
+        Ok(to_ristretto_scalar(&lambda))
+    }
+
+    fn binding_factor(
+        party_index: usize,
+        public_key: &Self::Point,
+        message: &[u8],
+        encoded_commitments: &[u8],
+        adaptor_point: Option<&Self::Point>
+    ) -> Self::Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(b"gridlock/sr25519/rho");
+        hasher.update((party_index as u32).to_be_bytes());
+        hasher.update(public_key.compress().as_bytes());
+        hasher.update(message);
+        hasher.update(encoded_commitments);
+        if let Some(adaptor_point) = adaptor_point {
+            hasher.update(adaptor_point.compress().as_bytes());
+        }
+        let mut hash = [0; 64];
+        hash.copy_from_slice(&hasher.finalize());
+        RistrettoScalar::from_bytes_mod_order_wide(&hash)
+    }
+
+    /// Computed the way schnorrkel does from the signing transcript
+    fn challenge(
+        group_commitment: &Self::Point,
+        public_key: &Self::Point,
+        message: &[u8]
+    ) -> Self::Scalar {
+        let mut transcript = signing_context(CTX).bytes(message);
+        transcript.append_message(b"proto-name", b"Schnorr-sig");
+        transcript.append_message(b"sign:pk", public_key.compress().as_bytes());
+        transcript.append_message(b"sign:R", group_commitment.compress().as_bytes());
+        let mut challenge = [0; 64];
+        transcript.challenge_bytes(b"sign:c", &mut challenge);
+        RistrettoScalar::from_bytes_mod_order_wide(&challenge)
+    }
+}
+
+/// Puts the signature together in the schnorrkel encoding, with the marker bit set
+pub fn encode_signature(group_commitment: &RistrettoPoint, s: &RistrettoScalar) -> [u8; 64] {
+    let mut signature = [0; 64];
+    signature[..32].copy_from_slice(group_commitment.compress().as_bytes());
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature[63] |= 128;
+    signature
+}
+
+/// Verifies the signature with schnorrkel, the way any Substrate chain would
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<()> {
+    let signature = schnorrkel::Signature
+        ::from_bytes(signature)
+        .map_err(|err| anyhow!("Invalid signature encoding: {}", err))?;
+    schnorrkel::PublicKey
+        ::from_bytes(public_key)
+        .map_err(|err| anyhow!("Invalid public key: {}", err))?
+        .verify_simple(CTX, message, &signature)
+        .map_err(|_| anyhow!("Signature did not pass verification"))
+}
+
+fn encode_point(point: &RistrettoPoint) -> String {
+    hex::encode(point.compress().as_bytes())
+}
+
+fn decode_scalar(encoded: &str) -> Result<RistrettoScalar> {
+    let bytes: [u8; 32] = hex
+        ::decode(encoded)?
+        .try_into()
+        .map_err(|_| anyhow!("Scalars have to be 32 bytes long"))?;
+    RistrettoScalar::from_canonical_bytes(bytes).ok_or_else(|| anyhow!("Scalar is out of range"))
 }
 
 pub fn handle_new_session_message(app: &App, message: nats::Message) {
-    let session = match serde_json::from_slice::<NewSr25519KeySignSession>(&message.data[..]) {
-        Ok(s) => s,
+    let parsed_message = match
+        serde_json::from_slice::<NewSr25519KeySignMessage>(&message.data[..])
+    {
+        Ok(parsed) => parsed,
         Err(e) => {
             error!("Incorrect key sign message format: {}", e);
             return;
         }
     };
 
+    let email = match authorize_session_request(&parsed_message) {
+        Some(email) => email,
+        None => return,
+    };
+
+    let prepared = substrate::prepare_message(
+        &parsed_message.key_id,
+        &email,
+        parsed_message.payload_type,
+        parsed_message.message
+    );
+    let (message, intent) = match prepared {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            error!("Failed to decode signing payload: {}", err);
+            return;
+        }
+    };
+
+    // Enforce the signing policy of the key before joining the session
+    if let Err(err) = enforce_policy(&parsed_message.key_id, &email, &[intent]) {
+        error!("Signing request rejected: {}", err);
+        return;
+    }
+
+    let session = NewSr25519KeySignSession {
+        key_id: parsed_message.key_id,
+        session_id: parsed_message.session_id,
+        message,
+        email: Some(email),
+        payload_type: None,
+    };
+
     let nc = app.nc.clone();
     let session_id = session.session_id.clone();
 
@@ -319,3 +430,143 @@ pub fn handle_new_session_message(app: &App, message: nats::Message) {
         Err(_) => error!("Failed to spawn thread for keysign session {}", &session_id),
     };
 }
+
+#[instrument(skip_all)]
+pub fn orchestrate(cmd: SigningCommand, ctx: MsgContext) -> Result<SigningResponse> {
+    let app = ctx.get_app()?;
+    let nc = app.nc;
+    let session_id = cmd.session_id.clone();
+
+    let party_nodes = cmd.party_nodes;
+    let key_id = cmd.key_id;
+
+    let key_info = KeyInfoStore::get_key_info(&key_id).map_err(|_|
+        anyhow!("Key info is not found - key_id: {}", &key_id)
+    )?;
+    let pk = match key_info.kind {
+        Key::Sr25519 { pk } => pk,
+        _ => bail!("Key {} is not an Sr25519 key", &key_id),
+    };
+
+    let party_count = party_nodes.len();
+    if party_count <= key_info.threshold {
+        bail!("Not enough nodes in party");
+    }
+
+    if !cmd.msgs.is_empty() {
+        bail!("Batch signing is not supported for Sr25519 signatures");
+    }
+    if cmd.presignature_id.is_some() {
+        bail!("Presignatures are only supported for ECDSA signatures");
+    }
+    if cmd.derivation_path.is_some() {
+        bail!("Child key derivation is only supported for secp256k1 keys");
+    }
+    if cmd.taproot.is_some() {
+        bail!("Taproot tweaks are only supported for BIP340 Schnorr signatures");
+    }
+
+    let join_key = format!("network.gridlock.nodes.KeySignSr25519.{}.Join", &session_id);
+    let join_sub = nc.subscribe(&join_key)?;
+
+    let result_key = format!("network.gridlock.nodes.KeySignSr25519.{}.Result", &session_id);
+    let result_sub = nc.subscribe(&result_key)?;
+
+    let key_sign_new_data = serde_json::to_string(
+        &(NewSr25519KeySignSession {
+            key_id: key_id.to_owned(),
+            session_id: session_id.to_owned(),
+            message: cmd.msg.clone(),
+            email: None,
+            payload_type: cmd.payload_type,
+        })
+    )?;
+    for node in party_nodes.iter() {
+        let sign_new_key = format!("network.gridlock.nodes.KeySignSr25519.new.{}", node);
+        nc.publish(&sign_new_key, &key_sign_new_data)?;
+    }
+
+    let mut join_msg_vec = Vec::new();
+    for _ in 0..party_count {
+        let next = join_sub.next().context("Get next join message")?;
+        join_msg_vec.push(next);
+    }
+
+    let mut indices = Vec::new();
+    for m in join_msg_vec.iter() {
+        let confirmation = serde_json::from_slice::<JoinMessage>(&m.data)?;
+        indices.push(confirmation.party_index);
+    }
+    indices.sort();
+    let join_resp = JoinResponse {
+        party_count: indices.len(),
+        all_party_indices: indices,
+    };
+    for msg in join_msg_vec {
+        msg.respond(
+            &serde_json::to_string(&join_resp).context("Respond to join message for every party")?
+        )?;
+    }
+    nc.flush()?;
+
+    info!("Parties joined to Sr25519 signing");
+
+    let mut results = Vec::new();
+    for _ in 0..party_count {
+        let res = result_sub.next().context("Signature result received from every party")?;
+        results.push(
+            serde_json::from_slice::<BroadcastMessage<Sr25519SignatureResult>>(&res.data)?.message
+        );
+    }
+
+    if results.iter().any(|result| result != &results[0]) {
+        bail!("Parties do not agree on the Sr25519 signature");
+    }
+    if results[0].pk != pk {
+        bail!("Signature was created for a different public key than the one of key {}", key_id);
+    }
+    info!("Signature result received");
+
+    Ok(SigningResponse::Sr25519(results.remove(0)))
+}
+
+#[test]
+fn threshold_signature_verifies_with_schnorrkel() {
+    let secret = Scalar::<Ed25519>::random();
+    let (_, shares) = VerifiableSS::<Ed25519>::share(1, 3, &secret);
+    let public_key = RISTRETTO_BASEPOINT_POINT * to_ristretto_scalar(&secret);
+    let message = b"polkadot extrinsic";
+
+    let signers = [1, 2];
+    let nonces = signers
+        .iter()
+        .map(|_| (to_ristretto_scalar(&Scalar::random()), to_ristretto_scalar(&Scalar::random())))
+        .collect::<Vec<_>>();
+    let commitments = signers
+        .iter()
+        .zip(nonces.iter())
+        .map(|(&party_index, (hiding, binding))| NonceCommitment {
+            party_index,
+            hiding: RISTRETTO_BASEPOINT_POINT * hiding,
+            binding: RISTRETTO_BASEPOINT_POINT * binding,
+        })
+        .collect();
+
+    let package = SigningPackage::new(message, &public_key, commitments).unwrap();
+    let mut signature_shares = Vec::new();
+    for (&party_index, (hiding, binding)) in signers.iter().zip(nonces.iter()) {
+        let x_i = to_ristretto_scalar(&shares[party_index - 1]);
+        let share = SignatureShare {
+            party_index,
+            z_i: package.signature_share(party_index, hiding, binding, &x_i).unwrap(),
+        };
+        package.verify_share(&share, &(RISTRETTO_BASEPOINT_POINT * x_i)).unwrap();
+        signature_shares.push(share);
+    }
+
+    let (group_commitment, s) = package.aggregate(&signature_shares).unwrap();
+    let signature = encode_signature(&group_commitment, &s);
+    let public_key = public_key.compress().to_bytes();
+    verify(&public_key, message, &signature).unwrap();
+    assert!(verify(&public_key, b"another extrinsic", &signature).is_err());
+}
@@ -0,0 +1,474 @@
+//! Substrate signing payloads for Sr25519 keys.
+//!
+//! The SCALE encoded payload starts with the call, whose length is only known with the runtime
+//! metadata, so clients send the fields of the payload the way polkadot-js hands them to signers.
+//! Guardians decode the call index and the era, check that the era starts at the checkpoint block
+//! and has not ended yet, and SCALE encode the payload themselves in the order of its signed
+//! extensions. Extensions whose data isn't modeled here are refused. Encoded payloads longer than
+//! 256 bytes are signed over their Blake2-256 hash, as Substrate runtimes expect.
+use crate::signing::policy::{ MessageKind, SigningIntent };
+use crate::signing::PayloadType;
+use crate::storage::fs::WriteOpts;
+use crate::storage::key_metadata_store::KeyMetadataStore;
+use anyhow::{ anyhow, bail, Result };
+use serde::{ Deserialize, Deserializer, Serialize };
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::info;
+
+const MAX_UNHASHED_PAYLOAD: usize = 256;
+
+/// Signed extensions of payloads that don't list theirs, as on Polkadot before metadata hashes
+const DEFAULT_SIGNED_EXTENSIONS: &[&str] = &[
+    "CheckSpecVersion",
+    "CheckTxVersion",
+    "CheckGenesis",
+    "CheckMortality",
+    "CheckNonce",
+    "CheckWeight",
+    "ChargeTransactionPayment",
+];
+
+/// Metadata entry holding the highest checkpoint block of each chain seen in payloads of a key
+const CHAIN_HEIGHTS: &str = "substrate_chain_heights";
+
+/// Fields of a `SignerPayloadJSON` from polkadot-js, numbers either as JSON numbers or as hex.
+/// Unknown fields are refused, as they may carry signed extension data that isn't encoded here.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SignerPayload {
+    /// SCALE encoded call, hex
+    pub method: String,
+    /// SCALE encoded era, hex
+    pub era: String,
+    #[serde(deserialize_with = "number")]
+    pub nonce: u128,
+    #[serde(deserialize_with = "number")]
+    pub tip: u128,
+    #[serde(deserialize_with = "number")]
+    pub spec_version: u128,
+    #[serde(deserialize_with = "number")]
+    pub transaction_version: u128,
+    pub genesis_hash: String,
+    /// Hash of the checkpoint block the era starts from
+    pub block_hash: String,
+    #[serde(deserialize_with = "number")]
+    pub block_number: u128,
+    /// Latest block known to the requester, payloads whose era ended before it are refused
+    #[serde(default, deserialize_with = "optional_number")]
+    pub current_block_number: Option<u128>,
+    /// Signed extensions of the runtime in the order they are encoded
+    #[serde(default)]
+    pub signed_extensions: Vec<String>,
+    /// `CheckMetadataHash` mode, 1 when the metadata hash is checked
+    #[serde(default, deserialize_with = "optional_number")]
+    pub mode: Option<u128>,
+    /// Hash of the runtime metadata checked by `CheckMetadataHash`, hex
+    #[serde(default)]
+    pub metadata_hash: Option<String>,
+    /// Signer address, not part of the signed payload
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Extrinsic version, not part of the signed payload
+    #[serde(default)]
+    pub version: Option<Value>,
+    #[serde(default)]
+    pub with_signed_transaction: Option<bool>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Era {
+    Immortal,
+    Mortal {
+        period: u64,
+        phase: u64,
+    },
+}
+
+impl Era {
+    fn decode(encoded: &[u8]) -> Result<Self> {
+        match encoded {
+            [0] => Ok(Era::Immortal),
+            [first, second] => {
+                let encoded = u16::from_le_bytes([*first, *second]) as u64;
+                let period = 2u64 << (encoded % (1 << 4));
+                let quantize_factor = (period >> 12).max(1);
+                let phase = (encoded >> 4) * quantize_factor;
+                if period < 4 || phase >= period {
+                    bail!("Invalid mortal era");
+                }
+                Ok(Era::Mortal { period, phase })
+            }
+            _ => bail!("Eras are encoded in one or two bytes"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct SubstratePayload {
+    pub pallet_index: u8,
+    pub call_index: u8,
+    pub era: Era,
+    pub nonce: u32,
+    pub tip: u128,
+    pub spec_version: u32,
+    pub transaction_version: u32,
+    /// Hex
+    pub genesis_hash: String,
+    /// Checkpoint block of the era
+    pub block_number: u64,
+    /// First block the transaction is no longer valid at, `None` for immortal transactions
+    pub era_end: Option<u64>,
+    /// SCALE encoded payload, hashed when longer than 256 bytes
+    #[serde(skip)]
+    signing_bytes: Vec<u8>,
+}
+
+impl SubstratePayload {
+    /// Bytes signed for the payload
+    pub fn signing_bytes(&self) -> &[u8] {
+        &self.signing_bytes
+    }
+
+    /// Calls are only known with the runtime metadata, so the policy can't see value moved
+    pub fn intent(&self) -> SigningIntent {
+        SigningIntent {
+            kind: MessageKind::SubstrateExtrinsic,
+            destinations: Vec::new(),
+            transfers: Vec::new(),
+        }
+    }
+}
+
+/// Bytes an Sr25519 key signs for a request and what they do, decoding typed payloads
+pub fn prepare_message(
+    key_id: &str,
+    email: &str,
+    payload_type: Option<PayloadType>,
+    message: Vec<u8>
+) -> Result<(Vec<u8>, SigningIntent)> {
+    match payload_type {
+        None => Ok((message, SigningIntent::raw())),
+        Some(PayloadType::SubstratePayload) => {
+            let decoded = decode_payload(&message)?;
+            info!("Decoded Substrate payload: {:?}", decoded);
+            check_chain_height(key_id, email, &decoded)?;
+            Ok((decoded.signing_bytes().to_vec(), decoded.intent()))
+        }
+        Some(other) => bail!("Sr25519 keys can't sign {} payloads", other.as_str()),
+    }
+}
+
+/// Decode and check a JSON encoded [SignerPayload], then SCALE encode it for signing
+pub fn decode_payload(payload: &[u8]) -> Result<SubstratePayload> {
+    let payload: SignerPayload = serde_json::from_slice(payload)?;
+    let method = decode_hex(&payload.method)?;
+    let (pallet_index, call_index) = match method[..] {
+        [pallet_index, call_index, ..] => (pallet_index, call_index),
+        _ => bail!("Call is too short to have an index"),
+    };
+    let era_bytes = decode_hex(&payload.era)?;
+    let era = Era::decode(&era_bytes)?;
+    let genesis_hash = decode_hash(&payload.genesis_hash)?;
+    let block_hash = decode_hash(&payload.block_hash)?;
+    let nonce = u32
+        ::try_from(payload.nonce)
+        .map_err(|_| anyhow!("Nonce {} is out of range", payload.nonce))?;
+    let spec_version = u32
+        ::try_from(payload.spec_version)
+        .map_err(|_| anyhow!("Spec version {} is out of range", payload.spec_version))?;
+    let transaction_version = u32::try_from(payload.transaction_version).map_err(|_|
+        anyhow!("Transaction version {} is out of range", payload.transaction_version)
+    )?;
+    let block_number = u64
+        ::try_from(payload.block_number)
+        .map_err(|_| anyhow!("Block number {} is out of range", payload.block_number))?;
+
+    let era_end = match era {
+        Era::Immortal if block_hash != genesis_hash => {
+            bail!("Immortal transactions have to be checkpointed at the genesis block");
+        }
+        Era::Immortal => None,
+        Era::Mortal { period, phase } => {
+            // Runtimes look up the hash of the era's birth block, which has to be the checkpoint
+            let quantize_factor = (period >> 12).max(1);
+            let birth = ((block_number.max(phase) - phase) / period) * period + phase;
+            if block_number - birth >= quantize_factor {
+                bail!("Era born at block {} doesn't start at checkpoint {}", birth, block_number);
+            }
+            if let Some(current) = payload.current_block_number {
+                if current >= ((birth + period) as u128) {
+                    bail!("Mortal era of blocks {} to {} has expired", birth, birth + period);
+                }
+            }
+            Some(birth + period)
+        }
+    };
+
+    let extensions: Vec<&str> = if payload.signed_extensions.is_empty() {
+        DEFAULT_SIGNED_EXTENSIONS.to_vec()
+    } else {
+        payload.signed_extensions.iter().map(String::as_str).collect()
+    };
+    if !extensions.contains(&"CheckGenesis") {
+        bail!("Payloads have to be signed with the CheckGenesis extension");
+    }
+    if !extensions.contains(&"CheckMortality") && !extensions.contains(&"CheckEra") {
+        bail!("Payloads have to be signed with the CheckMortality extension");
+    }
+
+    // Each extension appends its extrinsic data, then its implicit data follows the extra data of
+    // every extension
+    let mut extra = Vec::new();
+    let mut implicit = Vec::new();
+    let mut metadata_hash_signed = false;
+    for extension in extensions {
+        match extension {
+            "CheckNonZeroSender" | "CheckWeight" | "PrevalidateAttests" => {}
+            "CheckSpecVersion" => implicit.extend_from_slice(&spec_version.to_le_bytes()),
+            "CheckTxVersion" => implicit.extend_from_slice(&transaction_version.to_le_bytes()),
+            "CheckGenesis" => implicit.extend_from_slice(&genesis_hash),
+            "CheckMortality" | "CheckEra" => {
+                extra.extend_from_slice(&era_bytes);
+                implicit.extend_from_slice(&block_hash);
+            }
+            "CheckNonce" => encode_compact(nonce as u128, &mut extra),
+            "ChargeTransactionPayment" => encode_compact(payload.tip, &mut extra),
+            "CheckMetadataHash" => {
+                let metadata_hash = payload.metadata_hash.as_deref().map(decode_hash).transpose()?;
+                match (payload.mode.unwrap_or(0), metadata_hash) {
+                    (0, None) => {
+                        extra.push(0);
+                        implicit.push(0);
+                    }
+                    (1, Some(metadata_hash)) => {
+                        extra.push(1);
+                        implicit.push(1);
+                        implicit.extend_from_slice(&metadata_hash);
+                    }
+                    (mode, _) => {
+                        bail!("Metadata hash mode {} doesn't match the metadata hash", mode);
+                    }
+                }
+                metadata_hash_signed = true;
+            }
+            other => bail!("Signed extension {} is not supported", other),
+        }
+    }
+    let metadata_hash_sent = payload.mode.unwrap_or(0) != 0 || payload.metadata_hash.is_some();
+    if metadata_hash_sent && !metadata_hash_signed {
+        bail!("Metadata hashes are only signed with the CheckMetadataHash extension");
+    }
+
+    let mut encoded = method;
+    encoded.extend(extra);
+    encoded.extend(implicit);
+    let signing_bytes = if encoded.len() > MAX_UNHASHED_PAYLOAD {
+        blake2_256(&encoded).to_vec()
+    } else {
+        encoded
+    };
+
+    Ok(SubstratePayload {
+        pallet_index,
+        call_index,
+        era,
+        nonce,
+        tip: payload.tip,
+        spec_version,
+        transaction_version,
+        genesis_hash: hex::encode(genesis_hash),
+        block_number,
+        era_end,
+        signing_bytes,
+    })
+}
+
+/// Refuse a payload whose era ended before the highest checkpoint of its chain that this node saw
+/// in earlier payloads of the key, so that expiry doesn't only rest on the requester's block number
+fn check_chain_height(key_id: &str, email: &str, payload: &SubstratePayload) -> Result<()> {
+    let stored = KeyMetadataStore::get(key_id, CHAIN_HEIGHTS, email);
+    let mut heights: HashMap<String, u64> = match stored {
+        Ok(heights) => serde_json::from_str(&heights)?,
+        Err(_) => HashMap::new(),
+    };
+    let known = heights.get(&payload.genesis_hash).copied().unwrap_or(0);
+    if let Some(era_end) = payload.era_end {
+        if known >= era_end {
+            bail!("Mortal era ending at block {} has expired, block {} was seen", era_end, known);
+        }
+    }
+    if payload.block_number > known {
+        heights.insert(payload.genesis_hash.clone(), payload.block_number);
+        let heights = serde_json::to_string(&heights)?;
+        KeyMetadataStore::save(&heights, key_id, CHAIN_HEIGHTS, email, &WriteOpts::Modify)?;
+    }
+    Ok(())
+}
+
+/// SCALE compact encoding of an unsigned integer
+fn encode_compact(value: u128, out: &mut Vec<u8>) {
+    match value {
+        0..=0x3f => out.push((value as u8) << 2),
+        0x40..=0x3fff => out.extend_from_slice(&(((value as u16) << 2) | 0b01).to_le_bytes()),
+        0x4000..=0x3fff_ffff => {
+            out.extend_from_slice(&(((value as u32) << 2) | 0b10).to_le_bytes());
+        }
+        _ => {
+            let bytes = value.to_le_bytes();
+            let len = 16 - (value.leading_zeros() as usize) / 8;
+            out.push((((len - 4) as u8) << 2) | 0b11);
+            out.extend_from_slice(&bytes[..len]);
+        }
+    }
+}
+
+fn blake2_256(data: &[u8]) -> [u8; 32] {
+    let hash = blake2b_simd::Params::new().hash_length(32).hash(data);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(hash.as_bytes());
+    out
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(value.strip_prefix("0x").unwrap_or(value))?)
+}
+
+fn decode_hash(value: &str) -> Result<[u8; 32]> {
+    decode_hex(value)?
+        .try_into()
+        .map_err(|_| anyhow!("Hashes must be 32 bytes long"))
+}
+
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    parse_number(Value::deserialize(deserializer)?)
+}
+
+fn optional_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u128>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => parse_number(value).map(Some),
+    }
+}
+
+fn parse_number<E: serde::de::Error>(value: Value) -> Result<u128, E> {
+    let parsed = match value {
+        Value::Number(number) => number.as_u64().map(|number| number as u128),
+        Value::String(s) =>
+            match s.strip_prefix("0x") {
+                Some(hex) => u128::from_str_radix(hex, 16).ok(),
+                None => s.parse().ok(),
+            }
+        _ => None,
+    };
+    parsed.ok_or_else(|| E::custom("expected an unsigned number or hex string"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(method: &str, era: &str, block_number: u64) -> Vec<u8> {
+        serde_json::json!({
+            "method": method,
+            "era": era,
+            "nonce": "0x00000003",
+            "tip": "0x00000000000000000000000000000000",
+            "specVersion": 9430,
+            "transactionVersion": 24,
+            "genesisHash": format!("0x{}", "91".repeat(32)),
+            "blockHash": format!("0x{}", "ab".repeat(32)),
+            "blockNumber": block_number,
+        })
+            .to_string()
+            .into_bytes()
+    }
+
+    #[test]
+    fn payload_is_scale_encoded_and_hashed_when_long() {
+        // Era of period 64 and phase 0x27, born at block 0x27 + 64 * k
+        let decoded = decode_payload(&payload("0x0503", "0x7502", 103)).unwrap();
+        assert_eq!((decoded.pallet_index, decoded.call_index), (5, 3));
+        assert_eq!(decoded.era, Era::Mortal { period: 64, phase: 39 });
+        let mut expected = vec![0x05, 0x03, 0x75, 0x02, 3 << 2, 0];
+        expected.extend_from_slice(&9430u32.to_le_bytes());
+        expected.extend_from_slice(&24u32.to_le_bytes());
+        expected.extend_from_slice(&[0x91; 32]);
+        expected.extend_from_slice(&[0xab; 32]);
+        assert_eq!(decoded.signing_bytes(), &expected[..]);
+
+        let long_call = format!("0x0503{}", "00".repeat(200));
+        let decoded = decode_payload(&payload(&long_call, "0x7502", 103)).unwrap();
+        assert_eq!(decoded.signing_bytes().len(), 32);
+
+        // Immortal payloads are checkpointed at genesis
+        assert!(decode_payload(&payload("0x0503", "0x00", 103)).is_err());
+        // The era of phase 39 isn't born at block 104
+        assert!(decode_payload(&payload("0x0503", "0x7502", 104)).is_err());
+
+        let mut expired: Value = serde_json::from_slice(&payload("0x0503", "0x7502", 103)).unwrap();
+        expired["currentBlockNumber"] = 166.into();
+        assert!(decode_payload(expired.to_string().as_bytes()).is_ok());
+        expired["currentBlockNumber"] = 167.into();
+        assert!(decode_payload(expired.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn signed_extensions_are_encoded_in_order_or_refused() {
+        let mut payload: Value = serde_json::from_slice(&payload("0x0503", "0x7502", 103)).unwrap();
+        payload["signedExtensions"] = serde_json::json!([
+            "CheckNonZeroSender",
+            "CheckSpecVersion",
+            "CheckTxVersion",
+            "CheckGenesis",
+            "CheckMortality",
+            "CheckNonce",
+            "CheckWeight",
+            "ChargeTransactionPayment",
+            "CheckMetadataHash",
+        ]);
+        payload["mode"] = 1.into();
+        payload["metadataHash"] = format!("0x{}", "cd".repeat(32)).into();
+        let decoded = decode_payload(payload.to_string().as_bytes()).unwrap();
+        let mut expected = vec![0x05, 0x03, 0x75, 0x02, 3 << 2, 0, 1];
+        expected.extend_from_slice(&9430u32.to_le_bytes());
+        expected.extend_from_slice(&24u32.to_le_bytes());
+        expected.extend_from_slice(&[0x91; 32]);
+        expected.extend_from_slice(&[0xab; 32]);
+        expected.push(1);
+        expected.extend_from_slice(&[0xcd; 32]);
+        assert_eq!(decoded.signing_bytes(), &expected[..]);
+        assert_eq!(decoded.era_end, Some(167));
+
+        // A metadata hash the mode doesn't check
+        let mut mismatched = payload.clone();
+        mismatched["mode"] = 0.into();
+        assert!(decode_payload(mismatched.to_string().as_bytes()).is_err());
+
+        // A metadata hash without the extension that signs it
+        let mut unsigned = payload.clone();
+        unsigned["signedExtensions"] = serde_json::json!(["CheckGenesis", "CheckMortality"]);
+        assert!(decode_payload(unsigned.to_string().as_bytes()).is_err());
+
+        let mut asset: Value = payload.clone();
+        asset["signedExtensions"][7] = "ChargeAssetTxPayment".into();
+        assert!(decode_payload(asset.to_string().as_bytes()).is_err());
+        let mut asset_id = payload.clone();
+        asset_id["assetId"] = 1984.into();
+        assert!(decode_payload(asset_id.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn compact_encoding() {
+        let encode = |value| {
+            let mut out = Vec::new();
+            encode_compact(value, &mut out);
+            out
+        };
+        assert_eq!(encode(1), vec![0x04]);
+        assert_eq!(encode(64), vec![0x01, 0x01]);
+        assert_eq!(encode(16384), vec![0x02, 0x00, 0x01, 0x00]);
+        assert_eq!(encode(1 << 30), vec![0x03, 0x00, 0x00, 0x00, 0x40]);
+    }
+}
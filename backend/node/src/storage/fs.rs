@@ -2,8 +2,10 @@ use crate::config::{ Config, ConfigProvider };
 use anyhow::{ anyhow, bail, Result };
 use glob::glob;
 use regex::Regex;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{ Path, PathBuf };
 use std::fs;
+use uuid::Uuid;
 
 pub struct FileSystem;
 
@@ -74,6 +76,57 @@ impl FileSystem {
         Ok(())
     }
 
+    pub fn remove_key_info_file(key_id: &str) -> Result<()> {
+        let filepath = Config::get_key_info_storage_path(key_id);
+        if filepath.exists() {
+            fs::remove_file(filepath)?;
+        }
+        Ok(())
+    }
+
+    /// Overwrite and remove the keyshares and metadata of a key, both in the account directory
+    /// and in the legacy location, so the keyshare cannot be read back from the disk
+    pub fn shred_key_files(key_id: &str, email: &str) -> Result<()> {
+        let mut key_dir = Config::get_gridlock_directory();
+        key_dir.push("accounts");
+        key_dir.push(email);
+        key_dir.push("keys");
+        key_dir.push(key_id);
+
+        if key_dir.exists() {
+            for entry in fs::read_dir(&key_dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    Self::shred_file(&path)?;
+                }
+            }
+            fs::remove_dir_all(&key_dir)?;
+        }
+
+        let legacy_keyfile = Config::get_key_storage_path(key_id, 0);
+        if legacy_keyfile.exists() {
+            Self::shred_file(&legacy_keyfile)?;
+        }
+        let search_term = Config::get_gridlock_directory()
+            .to_str()
+            .map(|s| format!("{}/keys--{}--*.json", s, key_id))
+            .ok_or(anyhow!("Could not create search"))?;
+        for path in glob(&search_term)?.filter_map(Result::ok) {
+            Self::shred_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn shred_file(path: &Path) -> Result<()> {
+        let len = fs::metadata(path)?.len();
+        let mut file = fs::OpenOptions::new().write(true).open(path)?;
+        file.write_all(&vec![0; len as usize])?;
+        file.sync_all()?;
+        drop(file);
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
     pub fn read_keyfile(key_id: &str, index: usize) -> Result<String> {
         let filename = Config::get_key_storage_path(key_id, index);
         let kf = fs::read_to_string(filename)?;
@@ -251,6 +304,25 @@ impl FileSystem {
         Ok(())
     }
 
+    /// Read key metadata and remove it. The file is renamed away before it is read, so when several
+    /// callers take it at once only one of them succeeds.
+    pub fn take_key_metadata_file(
+        key_id: &str,
+        metadata_type: &str,
+        email: &str
+    ) -> Result<String> {
+        let filepath = Self::get_key_metadata_file_path(key_id, metadata_type, email);
+        let mut taken_filepath = filepath.clone().into_os_string();
+        taken_filepath.push(format!(".taken-{}", Uuid::new_v4()));
+
+        fs::rename(&filepath, &taken_filepath).map_err(|_| {
+            anyhow!("Key metadata of type `{}` for id `{}` is not available", metadata_type, key_id)
+        })?;
+        let content = fs::read_to_string(&taken_filepath);
+        fs::remove_file(&taken_filepath)?;
+        Ok(content?)
+    }
+
     pub fn get_gridlock_directory() -> Result<PathBuf> {
         Ok(Config::get_gridlock_directory())
     }
@@ -326,4 +398,38 @@ mod tests {
             Some(String::from("1b2359cf-e7d1-44e9-a8c2-daebdce9a89f"))
         )
     }
+
+    #[test]
+    fn presignature_can_only_be_taken_once() {
+        let key_id = Uuid::new_v4().to_string();
+        let email = "presign-test@example.com";
+        FileSystem::add_key_metadata_file(
+            &key_id,
+            "presignature_1",
+            "{}",
+            email,
+            &WriteOpts::CreateNewOnly
+        ).unwrap();
+
+        let takers = (0..8)
+            .map(|_| {
+                let key_id = key_id.clone();
+                std::thread::spawn(move || {
+                    FileSystem::take_key_metadata_file(&key_id, "presignature_1", email)
+                })
+            })
+            .collect::<Vec<_>>();
+        let taken = takers
+            .into_iter()
+            .map(|taker| taker.join().unwrap())
+            .filter(|result| result.is_ok())
+            .count();
+        assert_eq!(taken, 1);
+        assert!(FileSystem::take_key_metadata_file(&key_id, "presignature_1", email).is_err());
+
+        let mut key_dir = Config::get_gridlock_directory();
+        key_dir.push("accounts");
+        key_dir.push(email);
+        fs::remove_dir_all(key_dir).unwrap();
+    }
 }
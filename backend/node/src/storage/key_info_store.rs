@@ -14,4 +14,8 @@ impl KeyInfoStore {
         let data = FileSystem::read_key_info_file(key_id)?;
         serde_json::from_str(&data).context("Deserialize key info")
     }
+
+    pub fn remove_key_info(key_id: &str) -> Result<()> {
+        FileSystem::remove_key_info_file(key_id)
+    }
 }
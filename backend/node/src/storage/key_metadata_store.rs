@@ -22,6 +22,11 @@ impl KeyMetadataStore {
         FileSystem::read_key_metadata_file(key_id, metadata_type, email)
     }
 
+    /// Read key-specific metadata and remove it, only one of concurrent callers gets it
+    pub fn take(key_id: &str, metadata_type: &str, email: &str) -> Result<String> {
+        FileSystem::take_key_metadata_file(key_id, metadata_type, email)
+    }
+
     /// Remove key-specific metadata
     pub fn remove(key_id: &str, metadata_type: &str, email: &str) -> Result<()> {
         FileSystem::remove_key_metadata_file(key_id, metadata_type, email)
@@ -2,7 +2,7 @@ use super::fs::{ FileSystem, WriteOpts };
 use crate::recovery::RecoveryCalculator;
 use anyhow::Result;
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::elliptic::curves::{ Ed25519, Point, Scalar, Secp256k1 };
+use curv::elliptic::curves::{ Bls12_381_1, Ed25519, Point, Scalar, Secp256k1 };
 use curv::BigInt;
 use itertools::Itertools;
 use paillier::{ DecryptionKey, EncryptionKey };
@@ -33,6 +33,7 @@ pub trait CurrentKeyshareFormat: Serialize + DeserializeOwned + TryFrom<Keyshare
 impl CurrentKeyshareFormat for ECDSA_V4 {}
 impl CurrentKeyshareFormat for EdDSA_V3 {}
 impl CurrentKeyshareFormat for Sr25519 {}
+impl CurrentKeyshareFormat for Bls12381 {}
 
 impl TryFrom<KeyshareFormat> for ECDSA_V4 {
     type Error = &'static str;
@@ -53,6 +54,7 @@ impl TryFrom<KeyshareFormat> for ECDSA_V4 {
                     y_sum: ecdsa_v1v2.y_sum.into(),
                     public_key_vec: ecdsa_v1v2.public_key_vec.into_iter().map_into().collect(),
                     paillier_dk: ecdsa_v1v2.party_keys.dk,
+                    chain_code: None,
                 }),
             KeyshareFormat::ECDSA_V3(ecdsa_v3) =>
                 Ok(Self {
@@ -65,12 +67,14 @@ impl TryFrom<KeyshareFormat> for ECDSA_V4 {
                     paillier_key_vec: ecdsa_v3.paillier_key_vec.into_iter().map_into().collect(),
                     h1_h2_N_tilde_vec: ecdsa_v3.h1_h2_N_tilde_vec.into_iter().map_into().collect(),
                     paillier_dk: ecdsa_v3.paillier_dk,
+                    chain_code: None,
                 }),
             KeyshareFormat::ECDSA_V4(ecdsa_v4) => Ok(ecdsa_v4),
             | KeyshareFormat::EdDSA_V1(_)
             | KeyshareFormat::EdDSA_V2(_)
             | KeyshareFormat::EdDSA_V3(_)
-            | KeyshareFormat::Sr25519(_) => {
+            | KeyshareFormat::Sr25519(_)
+            | KeyshareFormat::Bls12381(_) => {
                 Err("The key file contained a different key type, expecting ECDSA")
             }
         }
@@ -86,7 +90,8 @@ impl TryFrom<KeyshareFormat> for EdDSA_V3 {
             | KeyshareFormat::EdDSA_V2(_)
             | KeyshareFormat::ECDSA_V1V2(_)
             | KeyshareFormat::ECDSA_V3(_)
-            | KeyshareFormat::ECDSA_V4(_) => {
+            | KeyshareFormat::ECDSA_V4(_)
+            | KeyshareFormat::Bls12381(_) => {
                 Err("The key file contained a different key type, expecting EdDSA")
             }
             KeyshareFormat::EdDSA_V3(eddsa_v2) => Ok(eddsa_v2),
@@ -122,6 +127,17 @@ impl TryFrom<KeyshareFormat> for Sr25519 {
     }
 }
 
+impl TryFrom<KeyshareFormat> for Bls12381 {
+    type Error = &'static str;
+
+    fn try_from(kf: KeyshareFormat) -> Result<Self, Self::Error> {
+        match kf {
+            KeyshareFormat::Bls12381(bls12381) => Ok(bls12381),
+            _ => Err("The key file contained a different key type, expecting BLS12-381"),
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[allow(non_snake_case)]
 #[derive(Clone, Serialize, Deserialize)]
@@ -135,6 +151,9 @@ pub struct ECDSA_V4 {
     pub paillier_key_vec: Vec<EncryptionKey>,
     pub h1_h2_N_tilde_vec: Vec<DLogStatement>,
     pub paillier_dk: DecryptionKey,
+    /// BIP32 chain code, hex encoded, generated jointly at keygen
+    #[serde(default)]
+    pub chain_code: Option<String>,
 }
 
 #[allow(non_camel_case_types)]
@@ -206,6 +225,7 @@ pub enum KeyshareFormat {
     EdDSA_V2(EdDSA_V2),
     EdDSA_V3(EdDSA_V3),
     Sr25519(Sr25519),
+    Bls12381(Bls12381),
 }
 
 pub struct Keystore;
@@ -323,6 +343,18 @@ pub struct Sr25519 {
     pub vss_scheme: WVerifiableSS<Ed25519>,
 }
 
+/// Keyshare of a BLS12-381 key, whose public key and shares are on G1
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bls12381 {
+    pub threshold: usize,
+    pub party_index: usize,
+    pub x_i: Scalar<Bls12_381_1>,
+    pub public_key: Point<Bls12_381_1>,
+    /// Commitments to the sum of the polynomials dealt at key generation
+    pub vss_scheme: VerifiableSS<Bls12_381_1>,
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct ECKeysV1V2 {
     u_i: WScalar<Secp256k1>,
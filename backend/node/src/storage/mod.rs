@@ -11,6 +11,7 @@ pub use key_store::CurrentKeyshareFormat;
 pub use key_store::EdDSA_V3 as EDDSA;
 pub use key_store::ECDSA_V4 as ECDSA;
 pub use key_store::Sr25519;
+pub use key_store::Bls12381;
 pub use key_store::Keystore;
 pub use keyshare_access::{ KeyshareAccessor, KeyshareSaver };
 pub use wrappers::SchnorrkelSecretKey;
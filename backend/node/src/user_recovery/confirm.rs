@@ -1,25 +1,73 @@
 use crate::auth::e2e_decrypt;
+use crate::client_identity::ClientIdentityKey;
 use crate::node::NodeIdentity;
+use crate::signing::request_auth::{
+    authorize_recovery_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
 use crate::storage::fs::WriteOpts;
 use crate::storage::key_metadata_store::KeyMetadataStore;
-use anyhow::Result;
+use anyhow::{ bail, Result };
 use nats::Message;
 use serde::{ Deserialize, Serialize };
 use std::thread;
-use tracing::{ error, info };
+use crate::user_recovery::session::RECOVERY_IDENTITY_KEY;
+use tracing::{ error, info, warn };
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ConfirmRecoverySession {
     pub key_id: String,
-    pub client_e2e_public_key: String,
     pub encrypted_recovery_confirmation: String,
-    pub email: Option<String>,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 struct RecoveryConfirmationData {
     recovery_challenge: String,
     client_identity_public_key: String,
+    /// New identity key of the user, replacing the one registered before recovery
+    #[serde(default)]
+    client_identity_key: Option<ClientIdentityKey>,
+    /// Signature of the confirmation by the new identity key, proving possession of it
+    #[serde(default)]
+    client_signature: Option<String>,
+    /// Signature of the replacement by the identity key registered before recovery, if the user
+    /// still holds it. The recovery challenge alone authorizes the replacement.
+    #[serde(default)]
+    identity_key_authorization: Option<String>,
+}
+
+/// Recovery confirmation together with its decrypted data, signed by the new identity key
+struct RecoveryConfirmationRequest<'a> {
+    confirmation: &'a ConfirmRecoverySession,
+    data: &'a RecoveryConfirmationData,
+}
+
+impl AuthenticatedRequest for RecoveryConfirmationRequest<'_> {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        let credentials = &self.confirmation.credentials;
+        CanonicalEncoder::new("user_recovery_confirm")
+            .str("key_id", &self.confirmation.key_id)
+            .str("client_e2e_public_key", &credentials.client_e2e_public_key)
+            .opt_str("email", credentials.email.as_deref())
+            .opt_str("timestamp", credentials.timestamp.as_deref())
+            .str("recovery_challenge", &self.data.recovery_challenge)
+            .str("client_identity_public_key", &self.data.client_identity_public_key)
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        let mut credentials = self.confirmation.credentials.for_key(&self.confirmation.key_id);
+        // The signature may travel encrypted with the rest of the confirmation
+        if let Some(signature) = &self.data.client_signature {
+            credentials.client_signature = Some(signature);
+        }
+        credentials
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -63,7 +111,7 @@ fn confirm_recovery_session(
     };
 
     // Validate and extract the email address for recovery
-    let recovery_email = if let Some(email) = &confirmation.email {
+    let recovery_email = if let Some(email) = &confirmation.credentials.email {
         info!("Processing recovery confirmation for email: {}", email);
         email.clone()
     } else {
@@ -79,7 +127,7 @@ fn confirm_recovery_session(
     let decrypted_confirmation = e2e_decrypt(
         &confirmation.encrypted_recovery_confirmation,
         &node.e2e_private_key,
-        &confirmation.client_e2e_public_key
+        &confirmation.credentials.client_e2e_public_key
     )?;
 
     let recovery_data: RecoveryConfirmationData = match
@@ -106,10 +154,43 @@ fn confirm_recovery_session(
         return Ok(());
     }
 
+    // The confirmation must come with the identity key the recovery was started with
+    let pending_identity_key = KeyMetadataStore::get_user_level(
+        RECOVERY_IDENTITY_KEY,
+        &recovery_email
+    )
+        .ok()
+        .map(|key| serde_json::from_str::<ClientIdentityKey>(&key))
+        .transpose()?;
+    match (&pending_identity_key, &recovery_data.client_identity_key) {
+        (Some(pending), Some(identity_key)) if pending != identity_key => {
+            bail!("Recovery confirmed with another identity key than it was started with");
+        }
+        (Some(_), None) => bail!("Recovery confirmation is missing the new identity key"),
+        (None, None) => warn!("Recovery confirmation is not signed by an identity key"),
+        _ => {}
+    }
+
+    // Replace the identity key once the client proves it holds the new one
+    if let Some(identity_key) = &recovery_data.client_identity_key {
+        let request = RecoveryConfirmationRequest {
+            confirmation: &confirmation,
+            data: &recovery_data,
+        };
+        authorize_recovery_request(&request, identity_key)?;
+        identity_key.replace(
+            &confirmation.key_id,
+            &recovery_email,
+            &recovery_data.recovery_challenge,
+            recovery_data.identity_key_authorization.as_deref()
+        )?;
+        info!("Client identity key replaced for email: {}", recovery_email);
+    }
+
     // Store client's E2E public key for future communication
     if
         let Err(e) = KeyMetadataStore::save_user_level(
-            &confirmation.client_e2e_public_key,
+            &confirmation.credentials.client_e2e_public_key,
             "e2e_key",
             &recovery_email,
             &WriteOpts::Modify
@@ -177,6 +258,12 @@ fn confirm_recovery_session(
     if let Err(err) = KeyMetadataStore::remove_user_level("recovery", &recovery_email) {
         error!("Failed to remove recovery key file: {}", err);
     }
+    if pending_identity_key.is_some() {
+        let removed = KeyMetadataStore::remove_user_level(RECOVERY_IDENTITY_KEY, &recovery_email);
+        if let Err(err) = removed {
+            error!("Failed to remove recovery identity key file: {}", err);
+        }
+    }
 
     Ok(())
 }
@@ -1,20 +1,54 @@
 use crate::auth::{ e2e_decrypt, e2e_encrypt };
+use crate::client_identity::ClientIdentityKey;
 use crate::node::NodeIdentity;
+use crate::signing::request_auth::{
+    authorize_recovery_request,
+    AuthenticatedRequest,
+    CanonicalEncoder,
+    ClientCredentials,
+    RequestCredentials,
+};
 use crate::storage::fs::WriteOpts;
 use crate::storage::key_metadata_store::KeyMetadataStore;
 use crate::App;
 use nats::Message;
 use serde::{ Deserialize, Serialize };
 use std::thread;
-use tracing::{ error, info };
+use tracing::{ error, info, warn };
 use uuid::Uuid;
 
+/// Metadata entry holding the identity key a user started a recovery with
+pub(crate) const RECOVERY_IDENTITY_KEY: &str = "recovery_identity_key";
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct NewUserRecoverySession {
     pub key_id: String,
-    pub client_e2e_public_key: String,
     pub encrypted_recovery_key: String,
-    pub email: Option<String>,
+    /// Identity key the user recovers with, which signs this request and the confirmation
+    #[serde(default)]
+    pub client_identity_key: Option<ClientIdentityKey>,
+    #[serde(flatten)]
+    pub credentials: ClientCredentials,
+}
+
+impl AuthenticatedRequest for NewUserRecoverySession {
+    fn canonical_encoding(&self) -> Vec<u8> {
+        CanonicalEncoder::new("user_recovery")
+            .str("key_id", &self.key_id)
+            .str("encrypted_recovery_key", &self.encrypted_recovery_key)
+            .opt_str(
+                "client_identity_key",
+                self.client_identity_key.as_ref().map(|key| key.public_key.as_str())
+            )
+            .str("client_e2e_public_key", &self.credentials.client_e2e_public_key)
+            .opt_str("timestamp", self.credentials.timestamp.as_deref())
+            .opt_str("email", self.credentials.email.as_deref())
+            .finish()
+    }
+
+    fn credentials(&self) -> RequestCredentials<'_> {
+        self.credentials.for_key(&self.key_id)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -57,7 +91,7 @@ fn recovery_session(
     };
 
     // Validate and extract the email address for recovery
-    let recovery_email = if let Some(email) = &session.email {
+    let recovery_email = if let Some(email) = &session.credentials.email {
         info!("Processing recovery request for email: {}", email);
         email.clone()
     } else {
@@ -69,11 +103,28 @@ fn recovery_session(
         return Err(err.into());
     };
 
+    // The identity key the user recovers with signs the request, and has to sign the
+    // confirmation as well
+    match &session.client_identity_key {
+        Some(identity_key) => {
+            identity_key.validate()?;
+            authorize_recovery_request(&session, identity_key)?;
+            KeyMetadataStore::save_user_level(
+                &serde_json::to_string(identity_key)?,
+                RECOVERY_IDENTITY_KEY,
+                &recovery_email,
+                &WriteOpts::Modify
+            )?;
+            info!("Recovery request signed by the new identity key");
+        }
+        None => warn!("Recovery request is not signed by an identity key"),
+    }
+
     // Decrypt and store the recovery key
     let decrypted_recovery_key = e2e_decrypt(
         &session.encrypted_recovery_key,
         &node.e2e_private_key,
-        &session.client_e2e_public_key
+        &session.credentials.client_e2e_public_key
     )?;
 
     let recovery_key_str = String::from_utf8(decrypted_recovery_key)?;
@@ -81,7 +132,7 @@ fn recovery_session(
     // Store client's E2E public key for future communication
     if
         let Err(e) = KeyMetadataStore::save_user_level(
-            &session.client_e2e_public_key,
+            &session.credentials.client_e2e_public_key,
             "e2e_key",
             &recovery_email,
             &WriteOpts::Modify
@@ -115,7 +166,7 @@ fn recovery_session(
     let encrypted_bundle = match
         e2e_encrypt(
             challenge_bundle.to_string().as_bytes(),
-            &session.client_e2e_public_key,
+            &session.credentials.client_e2e_public_key,
             &node.e2e_private_key
         )
     {
@@ -17,6 +17,14 @@ pub struct KeyInfo {
     #[serde(flatten)]
     pub kind: Key,
     pub node_pool: Vec<NodeInfo>,
+    /// Threshold as stored in keyshares, threshold + 1 parties are needed to sign
+    #[serde(default = "legacy_threshold")]
+    pub threshold: usize,
+}
+
+/// Keys created before the threshold was configurable are all 3-of-5
+fn legacy_threshold() -> usize {
+    2
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -53,6 +61,9 @@ impl NodeId {
 pub enum Key {
     ECDSA {
         y_sum: Sum,
+        /// BIP32 chain code, hex encoded, missing for keys created before derivation support
+        #[serde(default)]
+        chain_code: Option<String>,
     },
     EDDSA {
         y_sum: String,
@@ -60,6 +71,10 @@ pub enum Key {
     Sr25519 {
         pk: String,
     },
+    Bls12381 {
+        /// Compressed G1 public key, hex encoded
+        pk: String,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
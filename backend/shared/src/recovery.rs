@@ -17,6 +17,8 @@ pub struct ReceiveRecoveryPackages {
 pub struct UpdatePaillierKeysCommand {
     pub key_id: String,
     pub new_eks: Vec<EncryptionKey>,
+    /// JSON encoded proofs that the new keys are sound, one for each key in `new_eks`
+    pub key_proofs: Vec<String>,
 }
 
 impl Debug for UpdatePaillierKeysCommand {
@@ -31,6 +33,8 @@ pub struct UpdateSinglePaillierKeyCommand {
     pub key_id: String,
     pub new_ek: EncryptionKey,
     pub index: usize,
+    /// JSON encoded proof that the new key is sound
+    pub key_proof: String,
 }
 
 impl Debug for UpdateSinglePaillierKeyCommand {
@@ -47,6 +51,7 @@ pub enum Key {
     ECDSA,
     EDDSA,
     Sr25519,
+    Bls12381,
 }
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]